@@ -1,6 +1,19 @@
+/// Set up the `cfg` aliases, and let the compiler know about each of them.
+macro_rules! aliases {
+    ($($alias:ident: $cfg:tt,)*) => {
+        cfg_aliases::cfg_aliases! {
+            $($alias: $cfg,)*
+        }
+
+        $(
+            println!("cargo:rustc-check-cfg=cfg({})", stringify!($alias));
+        )*
+    };
+}
+
 fn main() {
     // Setup alias to reduce `cfg` boilerplate.
-    cfg_aliases::cfg_aliases! {
+    aliases! {
         // Systems.
         android: { target_os = "android" },
         wasm: { target_arch = "wasm32" },
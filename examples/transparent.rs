@@ -0,0 +1,125 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later OR MPL-2.0
+// This file is a part of `theo`.
+//
+// `theo` is free software: you can redistribute it and/or modify it under the
+// terms of either:
+//
+// * GNU Lesser General Public License as published by the Free Software Foundation, either
+//   version 3 of the License, or (at your option) any later version.
+// * Mozilla Public License as published by the Mozilla Foundation, version 2.
+//
+// `theo` is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR
+// PURPOSE. See the GNU Lesser General Public License or the Mozilla Public License for more
+// details.
+//
+// You should have received a copy of the GNU Lesser General Public License and the Mozilla
+// Public License along with `theo`. If not, see <https://www.gnu.org/licenses/>.
+
+//! Drawing a circle on a transparent window.
+
+use piet::kurbo::Circle;
+use piet::{Color, RenderContext as _};
+use theo::{Display, RenderContext};
+
+use winit::dpi::LogicalSize;
+use winit::event::{Event, WindowEvent};
+use winit::event_loop::EventLoop;
+use winit::window::WindowBuilder;
+
+fn main() {
+    env_logger::init();
+
+    let event_loop = EventLoop::new();
+
+    // Ask for a display that supports transparency.
+    let mut display = {
+        #[allow(unused_mut)]
+        let mut display = Display::builder().transparent(true);
+
+        #[cfg(x11_platform)]
+        {
+            display = display.glx_error_hook(winit::platform::x11::register_xlib_error_hook);
+        }
+
+        unsafe {
+            display
+                .build(&*event_loop)
+                .expect("Failed to create display")
+        }
+    };
+
+    if !display.supports_transparency() {
+        println!("This display does not support transparency, the background will be black");
+    }
+
+    let mut state = None;
+
+    event_loop.run(move |event, elwt, control_flow| {
+        control_flow.set_wait();
+
+        match event {
+            Event::Resumed => {
+                #[allow(unused_mut)]
+                let mut window_builder = WindowBuilder::new()
+                    .with_title("theo transparency example")
+                    .with_inner_size(LogicalSize::new(400.0, 400.0))
+                    .with_transparent(display.supports_transparency());
+
+                #[cfg(x11_platform)]
+                {
+                    use winit::platform::x11::WindowBuilderExtX11;
+
+                    if let Some(visual) = display.x11_visual() {
+                        window_builder = window_builder.with_x11_visual(visual.as_ptr());
+                    }
+                }
+
+                let window = window_builder.build(elwt).expect("Failed to create window");
+                let size = window.inner_size();
+                let surface =
+                    unsafe { display.make_surface_blocking(&window, size.width, size.height) }
+                        .expect("Failed to create surface");
+
+                state = Some((window, surface));
+            }
+
+            Event::Suspended => {
+                if let Some((_, surface)) = state.take() {
+                    display.destroy_surface(surface).unwrap();
+                }
+            }
+
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                ..
+            } => control_flow.set_exit(),
+
+            Event::RedrawRequested(_) => {
+                if let Some((window, surface)) = &mut state {
+                    let size = window.inner_size();
+                    let mut ctx =
+                        RenderContext::new(&mut display, surface, size.width, size.height)
+                            .expect("Failed to create render context");
+
+                    // Clearing the whole surface to a translucent color lets the desktop show
+                    // through.
+                    ctx.clear(None, Color::TRANSPARENT);
+
+                    let center = (size.width as f64 / 2.0, size.height as f64 / 2.0);
+                    let radius = center.0.min(center.1) * 0.75;
+                    ctx.fill(
+                        Circle::new(center, radius),
+                        &Color::rgba8(0xff, 0x00, 0x00, 0xc0),
+                    );
+
+                    ctx.finish().expect("Failed to draw");
+                    drop(ctx);
+                    theo::block_on(display.present());
+                }
+            }
+
+            _ => {}
+        }
+    });
+}
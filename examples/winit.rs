@@ -343,10 +343,9 @@ fn main() -> ! {
 
                 // Create a new theo surface.
                 let size = window.inner_size();
-                let surface = futures_lite::future::block_on(unsafe {
-                    display.make_surface(&window, size.width, size.height)
-                })
-                .expect("Failed to create surface");
+                let surface =
+                    unsafe { display.make_surface_blocking(&window, size.width, size.height) }
+                        .expect("Failed to create surface");
 
                 // Save the state.
                 state = Some((window, surface));
@@ -354,7 +353,9 @@ fn main() -> ! {
 
             Event::Suspended => {
                 // On Android, this means that we have to destroy the surface.
-                state.take();
+                if let Some((_, surface)) = state.take() {
+                    display.destroy_surface(surface).unwrap();
+                }
             }
 
             Event::WindowEvent {
@@ -379,7 +380,7 @@ fn main() -> ! {
 
                     // As this is our only window, we can just swap buffers now.
                     drop(render_context);
-                    futures_lite::future::block_on(display.present());
+                    theo::block_on(display.present());
                 }
 
                 next_frame += framerate;
@@ -0,0 +1,115 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later OR MPL-2.0
+// This file is a part of `theo`.
+//
+// `theo` is free software: you can redistribute it and/or modify it under the terms of
+// either:
+//
+// * GNU Lesser General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+// * Mozilla Public License as published by the Mozilla Foundation, version 2.
+//
+// `theo` is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+// See the GNU Lesser General Public License or the Mozilla Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License and the Mozilla
+// Public License along with `theo`. If not, see <https://www.gnu.org/licenses/>.
+
+//! A cache for the images of blurred rectangles.
+
+use crate::Image;
+
+use piet::kurbo::{Rect, Size};
+use piet::Color;
+
+/// The number of blurred rectangles to keep around.
+const CAPACITY: usize = 16;
+
+/// A least-recently-used cache of the images of blurred rectangles.
+pub(super) struct BlurCache {
+    /// The cached images, from least to most recently used.
+    entries: Vec<(Key, Blur)>,
+}
+
+/// The parameters that a blurred rectangle is drawn with.
+#[derive(PartialEq, Eq)]
+struct Key {
+    /// The context that the image was created by.
+    context: u64,
+
+    /// The width and height of the rectangle, as bits.
+    size: (u64, u64),
+
+    /// The blur radius, as bits.
+    radius: u64,
+
+    /// The color of the rectangle.
+    color: u32,
+}
+
+/// The image of a blurred rectangle.
+#[derive(Clone)]
+pub(super) struct Blur {
+    /// The image.
+    pub(super) image: Image,
+
+    /// Where to draw the image, relative to the top-left corner of the rectangle.
+    pub(super) rect: Rect,
+}
+
+impl BlurCache {
+    /// Create a new, empty cache.
+    pub(super) fn new() -> Self {
+        Self {
+            entries: Vec::with_capacity(CAPACITY),
+        }
+    }
+
+    /// Get the image of a blurred rectangle, if it is in the cache.
+    ///
+    /// `context` identifies the context that created the image, since images can't be used
+    /// with other contexts.
+    pub(super) fn get(
+        &mut self,
+        context: u64,
+        size: Size,
+        radius: f64,
+        color: Color,
+    ) -> Option<Blur> {
+        let key = Key::new(context, size, radius, color);
+
+        // Shadows are usually drawn for the same few rectangles every frame.
+        let position = self.entries.iter().rposition(|(entry, _)| *entry == key)?;
+        let entry = self.entries.remove(position);
+        let blur = entry.1.clone();
+        self.entries.push(entry);
+        Some(blur)
+    }
+
+    /// Add the image of a blurred rectangle to the cache.
+    pub(super) fn insert(
+        &mut self,
+        context: u64,
+        size: Size,
+        radius: f64,
+        color: Color,
+        blur: Blur,
+    ) {
+        if self.entries.len() >= CAPACITY {
+            self.entries.remove(0);
+        }
+        self.entries
+            .push((Key::new(context, size, radius, color), blur));
+    }
+}
+
+impl Key {
+    fn new(context: u64, size: Size, radius: f64, color: Color) -> Self {
+        Self {
+            context,
+            size: (size.width.to_bits(), size.height.to_bits()),
+            radius: radius.to_bits(),
+            color: color.as_rgba_u32(),
+        }
+    }
+}
@@ -20,16 +20,22 @@
 //! We use `piet-glow` as the main rendering backend, and `glutin` to set up the `glow`
 //! context.
 
-use super::text::{TextInner, TextLayoutInner};
-use super::{DisplayBuilder, Error, ResultExt, SwitchToSwrast, Text, TextLayout};
+use super::gl_info::{query_max_texture_size, renderer_info, FALLBACK_MAX_TEXTURE_SIZE};
+use super::text::{BundledFonts, TextInner, TextLayoutInner};
+use super::{
+    BlendMode, ColorSpace, DisplayBuilder, Error, FormatInfo, GlApi, RendererInfo, ResultExt,
+    SwitchToSwrast, Text, TextLayout, X11VisualInfo,
+};
 
-use glutin::config::{Config, ConfigTemplateBuilder};
+use glutin::config::{ColorBufferType, Config, ConfigTemplateBuilder};
 use glutin::context::{
     ContextApi, ContextAttributesBuilder, NotCurrentContext, PossiblyCurrentContext, Version,
 };
 use glutin::display::{Display as GlutinDisplay, DisplayApiPreference};
 use glutin::prelude::*;
-use glutin::surface::{Surface as GlutinSurface, SurfaceAttributesBuilder, WindowSurface};
+use glutin::surface::{
+    Surface as GlutinSurface, SurfaceAttributesBuilder, SwapInterval, WindowSurface,
+};
 
 use glow::Context;
 use piet::kurbo::{Point, Rect, Shape};
@@ -39,6 +45,10 @@ use raw_window_handle::{RawDisplayHandle, RawWindowHandle};
 
 use std::num::NonZeroU32;
 use std::ptr::NonNull;
+use std::rc::{Rc, Weak};
+
+/// The swap interval that surfaces start out with on every platform.
+const DEFAULT_SWAP_INTERVAL: SwapInterval = SwapInterval::Wait(NonZeroU32::MIN);
 
 /// The display for the GL backend.
 pub(super) struct Display {
@@ -48,19 +58,110 @@ pub(super) struct Display {
     /// The `GlConfig` that we are using.
     config: Config,
 
-    /// The GL context, but not current.
+    /// The GL context.
     ///
-    /// This is taken by the `RenderContext` to be made current.
-    context: Option<NotCurrentContext>,
+    /// This is taken by the `RenderContext` to be made current. It is only left current
+    /// between `begin_frame` and `end_frame`, so that it doesn't have to be released and made
+    /// current again for every surface drawn in the frame.
+    context: Option<ContextSlot>,
 
     /// The cached OpenGL context.
     renderer: Option<GlContext<Context>>,
+
+    /// The fonts to load into the text backends.
+    fonts: BundledFonts,
+
+    /// Whether `fonts` were loaded into the text backend of `renderer` yet.
+    renderer_fonts_loaded: bool,
+
+    /// The GL functions, for operations that the renderer doesn't expose.
+    ///
+    /// This is loaded along with the renderer. It is boxed since the function table is large.
+    gl: Option<Box<Context>>,
+
+    /// Information about the renderer, queried when the renderer is created.
+    info: Option<RendererInfo>,
+
+    /// The largest width or height of a texture, queried when the renderer is created.
+    max_texture_size: Option<u32>,
+
+    /// The text backend shared by layouts built outside of a frame.
+    text: Option<Text>,
+
+    /// The X11 visual of `config`, if it has one.
+    x11_visual_info: Option<X11VisualInfo>,
+
+    /// The surfaces waiting to be swapped at the end of the frame.
+    ///
+    /// This is `None` if we aren't between `begin_frame` and `end_frame`.
+    deferred: Option<Vec<DeferredSwap>>,
+
+    /// Copies of the last frame finished on each surface, for `Surface::snapshot`.
+    frames: Vec<FrameCopy>,
+
+    /// The resources used to composite layers, created when the first layer is pushed.
+    compositor: Option<Compositor>,
+}
+
+/// A surface waiting to be swapped at the end of the frame.
+struct DeferredSwap {
+    /// The surface to swap.
+    surface: Rc<GlutinSurface<WindowSurface>>,
+
+    /// The height of the surface, used to flip the damaged regions.
+    height: u32,
+
+    /// The damaged regions of the surface, or `None` if all of it should be presented.
+    damage: Option<Vec<Rect>>,
+}
+
+/// A copy of the last frame finished on a surface.
+///
+/// The contents of the back buffer are undefined after a swap, so each frame is copied here
+/// before the buffers are swapped.
+struct FrameCopy {
+    /// The surface that the frame was drawn to.
+    surface: Weak<GlutinSurface<WindowSurface>>,
+
+    /// The framebuffer holding the copy.
+    framebuffer: glow::Framebuffer,
+
+    /// The color buffer of `framebuffer`.
+    renderbuffer: glow::Renderbuffer,
+
+    /// The size of `renderbuffer`.
+    size: (u32, u32),
+}
+
+/// The resources used to composite layers.
+///
+/// When a layer is pushed, the surface is copied into a texture to keep the backdrop. When it
+/// is popped, the surface is copied again and the two are blended with a shader of our own,
+/// since the renderer only draws with source-over blending.
+struct Compositor {
+    /// The program that blends the layer onto the backdrop.
+    program: glow::Program,
+
+    /// The empty vertex array bound while drawing, since the vertices come from their indices.
+    vertex_array: glow::VertexArray,
+
+    /// The framebuffer that textures are attached to when copying the surface into them.
+    framebuffer: glow::Framebuffer,
+
+    /// The location of the opacity of the layer in `program`.
+    alpha: Option<glow::UniformLocation>,
+
+    /// The location of the blend mode in `program`.
+    mode: Option<glow::UniformLocation>,
+
+    /// Textures that aren't in use, along with their sizes.
+    textures: Vec<(glow::Texture, (u32, u32))>,
 }
 
 /// The surface for the GL backend.
 pub(super) struct Surface {
     /// The `glutin` window.
-    surface: GlutinSurface<WindowSurface>,
+    surface: Rc<GlutinSurface<WindowSurface>>,
 }
 
 /// The rendering context for the GL backend.
@@ -77,6 +178,27 @@ pub(super) struct RenderContext<'dsp, 'surf> {
     /// The surface.
     surface: &'surf mut Surface,
 
+    /// The surfaces waiting to be swapped, if we are deferring swaps.
+    deferred: Option<&'dsp mut Vec<DeferredSwap>>,
+
+    /// The copies of the last frame of each surface.
+    frames: &'dsp mut Vec<FrameCopy>,
+
+    /// The resources used to composite layers.
+    compositor: &'dsp mut Option<Compositor>,
+
+    /// The copies of the backdrops of the layers that are currently pushed.
+    layers: Vec<glow::Texture>,
+
+    /// The GL functions, for operations that the renderer doesn't expose.
+    gl: &'dsp Context,
+
+    /// The size of the surface.
+    size: (u32, u32),
+
+    /// The largest width or height of a texture.
+    max_texture_size: u32,
+
     /// The text renderer.
     text: Text,
 
@@ -134,7 +256,7 @@ impl Display {
         // Create a template for the config.
         let mut template_chooser = ConfigTemplateBuilder::new()
             .with_alpha_size(8)
-            .with_transparency(cfg!(target_vendor = "apple") || builder.transparent);
+            .with_transparency(builder.transparent);
 
         if let Some(window) = builder.window {
             template_chooser = template_chooser.compatible_with_native_window(window);
@@ -145,13 +267,40 @@ impl Display {
         // Get the list of configs for the display.
         let config_list = display.find_configs(template).piet_err()?;
 
-        // Get the config that matches our transparency support and has the most samples.
+        // Tell if a config has a better sample count than another.
+        //
+        // If the user requested a sample count, prefer the largest count that doesn't go over it.
+        // Otherwise, use the most samples we can get.
+        let msaa = builder.msaa;
+        let better_samples = |config: &Config, accum: &Config| match msaa {
+            None => config.num_samples() > accum.num_samples(),
+            Some(samples) => {
+                let fits = |config: &Config| u32::from(config.num_samples()) <= samples;
+
+                match (fits(config), fits(accum)) {
+                    (true, true) => config.num_samples() > accum.num_samples(),
+                    (true, false) => true,
+                    (false, true) => false,
+                    (false, false) => config.num_samples() < accum.num_samples(),
+                }
+            }
+        };
+
+        // Tell if a config matches the requested transparency.
+        //
+        // Not asking for transparency doesn't stop the platform from returning configs that
+        // support it, so opaque configs have to be preferred here.
+        let transparent = builder.transparent;
+        let matches_transparency =
+            |config: &Config| config.supports_transparency().unwrap_or(false) == transparent;
+
+        // Get the config that matches our transparency support and has the best sample count.
         let config = config_list
             .reduce(|accum, config| {
-                let transparency_check = config.supports_transparency().unwrap_or(false)
-                    & !accum.supports_transparency().unwrap_or(false);
+                let transparency_check =
+                    matches_transparency(&config) & !matches_transparency(&accum);
 
-                if transparency_check || config.num_samples() > accum.num_samples() {
+                if transparency_check || better_samples(&config, &accum) {
                     config
                 } else {
                     accum
@@ -172,7 +321,21 @@ impl Display {
             .with_context_api(ContextApi::OpenGl(Some(Version::new(3, 3))))
             .build(builder.window);
 
-        let contexts = [modern_context, gles_context, old_context];
+        // Only try the requested context if there is one.
+        let contexts = match builder.gl_version {
+            Some((api, major, minor)) => {
+                let version = Some(Version::new(major, minor));
+                let api = match api {
+                    GlApi::OpenGl => ContextApi::OpenGl(version),
+                    GlApi::Gles => ContextApi::Gles(version),
+                };
+
+                vec![ContextAttributesBuilder::new()
+                    .with_context_api(api)
+                    .build(builder.window)]
+            }
+            None => vec![modern_context, gles_context, old_context],
+        };
 
         // Try contexts until one works.
         let context = (|| {
@@ -189,14 +352,42 @@ impl Display {
         })()
         .piet_err()?;
 
+        let x11_visual_info = query_x11_visual_info(&config);
+
         Ok(Self {
             display,
             config,
-            context: Some(context),
+            context: Some(ContextSlot::NotCurrent(context)),
             renderer: None,
+            fonts: BundledFonts::new(&builder.fonts),
+            renderer_fonts_loaded: false,
+            gl: None,
+            info: None,
+            max_texture_size: None,
+            text: None,
+            x11_visual_info,
+            deferred: None,
+            frames: Vec::new(),
+            compositor: None,
         })
     }
 
+    pub(super) fn text(&mut self) -> Text {
+        self.text
+            .get_or_insert_with(|| self.fonts.cosmic_text())
+            .clone()
+    }
+
+    pub(super) fn renderer_info(&self) -> Option<RendererInfo> {
+        // The context is only current while drawing, so this is queried when it is first used.
+        self.info.clone()
+    }
+
+    pub(super) fn max_texture_size(&self) -> Option<u32> {
+        // This is queried along with the renderer info.
+        self.max_texture_size
+    }
+
     pub(super) fn supports_transparency(&self) -> bool {
         self.config.supports_transparency().unwrap_or(false)
     }
@@ -216,32 +407,202 @@ impl Display {
         None
     }
 
+    pub(super) fn x11_visual_info(&self) -> Option<X11VisualInfo> {
+        self.x11_visual_info
+    }
+
     pub(super) async unsafe fn make_surface(
         &mut self,
         raw: RawWindowHandle,
         width: u32,
         height: u32,
     ) -> Result<Surface, Error> {
-        let attrs = SurfaceAttributesBuilder::<WindowSurface>::new().build(
-            raw,
-            NonZeroU32::new(width).unwrap(),
-            NonZeroU32::new(height).unwrap(),
-        );
+        // piet colors are already in sRGB space, so don't let the driver convert them again.
+        let attrs = SurfaceAttributesBuilder::<WindowSurface>::new()
+            .with_srgb(Some(false))
+            .build(
+                raw,
+                NonZeroU32::new(width).unwrap(),
+                NonZeroU32::new(height).unwrap(),
+            );
 
         let surface = self
             .display
             .create_window_surface(&self.config, &attrs)
             .piet_err()?;
 
-        Ok(Surface { surface })
+        Ok(Surface {
+            surface: Rc::new(surface),
+        })
+    }
+
+    pub(super) fn destroy_surface(&mut self, surface: Surface) -> Result<(), Error> {
+        // Don't try to swap the surface later.
+        if let Some(deferred) = &mut self.deferred {
+            deferred.retain(|deferred| !Rc::ptr_eq(&deferred.surface, &surface.surface));
+        }
+
+        // The surface's resources are freed when it is dropped.
+        drop(surface);
+        Ok(())
     }
 
     pub(super) async fn present(&mut self) {
         // no-op
     }
+
+    pub(super) fn poll(&mut self) {
+        // no-op
+    }
+
+    pub(super) fn device_lost(&self) -> bool {
+        // Context resets aren't detected.
+        false
+    }
+
+    pub(super) fn begin_frame(&mut self) {
+        self.deferred.get_or_insert_with(Vec::new);
+    }
+
+    pub(super) async fn end_frame(&mut self) -> Result<(), Error> {
+        let deferred = match self.deferred.take() {
+            Some(deferred) => deferred,
+            None => return Ok(()),
+        };
+
+        // The scope puts the context back when it is dropped, even if one of the swaps fails.
+        let possibly_current = self.context.take().unwrap().into_possibly_current();
+        let scope = ContextScope {
+            slot: &mut self.context,
+            context: Some(possibly_current),
+            keep_current: false,
+        };
+        let last = deferred.len().saturating_sub(1);
+        let mut result = Ok(());
+
+        // Swap every surface, even if one of them fails.
+        for (i, swap) in deferred.iter().enumerate() {
+            let current = scope.context();
+            let surface = &*swap.surface;
+            if let Err(err) = current.make_current(surface) {
+                result = result.and(Err(err).piet_err());
+                continue;
+            }
+
+            // Only wait for vblank on the last surface, so the swaps don't stall one another.
+            let wait = i == last;
+            if !wait {
+                if let Err(err) = surface.set_swap_interval(current, SwapInterval::DontWait) {
+                    tracing::debug!("Failed to set swap interval: {}", err);
+                }
+            }
+
+            let damage = swap.damage.as_deref().unwrap_or(&[]);
+            result = result.and(swap_surface(surface, current, swap.height, damage));
+
+            // Go back to the default interval, so that swaps outside of a frame wait again.
+            if !wait {
+                if let Err(err) = surface.set_swap_interval(current, DEFAULT_SWAP_INTERVAL) {
+                    tracing::debug!("Failed to restore swap interval: {}", err);
+                }
+            }
+        }
+
+        result
+    }
+}
+
+impl Surface {
+    pub(super) fn snapshot(&mut self, display: &mut Display) -> Result<(u32, u32, Vec<u8>), Error> {
+        use glow::HasContext;
+
+        // The renderer is created along with the first render context.
+        let gl = display.gl.as_deref().ok_or(Error::NotSupported)?;
+        if gl.version().major < 3 {
+            // The frames were never copied.
+            return Err(Error::NotSupported);
+        }
+        let frame = display
+            .frames
+            .iter()
+            .find(|frame| frame.surface.as_ptr() == Rc::as_ptr(&self.surface))
+            .ok_or_else(|| Error::BackendError("No frame was finished on the surface".into()))?;
+
+        // The scope puts the context back when it is dropped.
+        let possibly_current = display.context.take().unwrap().into_possibly_current();
+        let scope = ContextScope {
+            slot: &mut display.context,
+            context: Some(possibly_current),
+            keep_current: display.deferred.is_some(),
+        };
+        scope.context().make_current(&*self.surface).piet_err()?;
+
+        let (width, height) = frame.size;
+        let stride = width as usize * 4;
+        let mut pixels = vec![0u8; stride * height as usize];
+
+        // SAFETY: The context is current and the buffer is large enough for the copy.
+        unsafe {
+            gl.bind_framebuffer(glow::READ_FRAMEBUFFER, Some(frame.framebuffer));
+            gl.pixel_store_i32(glow::PACK_ALIGNMENT, 1);
+            gl.read_pixels(
+                0,
+                0,
+                width as i32,
+                height as i32,
+                glow::RGBA,
+                glow::UNSIGNED_BYTE,
+                glow::PixelPackData::Slice(&mut pixels),
+            );
+            gl.bind_framebuffer(glow::READ_FRAMEBUFFER, None);
+        }
+        drop(scope);
+
+        // OpenGL starts at the bottom-left corner, so flip the rows.
+        let pixels = pixels
+            .chunks_exact(stride)
+            .rev()
+            .flatten()
+            .copied()
+            .collect::<Vec<_>>();
+
+        Ok((width, height, super::demultiply(&pixels)))
+    }
+
+    pub(super) fn is_renderable(&self, _display: &Display) -> bool {
+        // Ask the platform for the current size of the drawable.
+        let (width, height) = (self.surface.width(), self.surface.height());
+        width != Some(0) && height != Some(0)
+    }
+
+    pub(super) fn format_info(&self, display: &Display) -> Result<FormatInfo, Error> {
+        let (red_bits, green_bits, blue_bits) = match display.config.color_buffer_type() {
+            Some(ColorBufferType::Rgb {
+                r_size,
+                g_size,
+                b_size,
+            }) => (r_size, g_size, b_size),
+            Some(ColorBufferType::Luminance(size)) => (size, size, size),
+            None => (0, 0, 0),
+        };
+
+        Ok(FormatInfo {
+            red_bits,
+            green_bits,
+            blue_bits,
+            alpha_bits: display.config.alpha_size(),
+            // Window surfaces are always created without sRGB conversion.
+            color_space: ColorSpace::Linear,
+            #[cfg(feature = "wgpu")]
+            wgpu_format: None,
+        })
+    }
 }
 
 impl<'dsp, 'surf> RenderContext<'dsp, 'surf> {
+    /// Only one OpenGL context can be current on a thread at a time.
+    pub(super) const EXCLUSIVE: bool = true;
+
     pub(super) unsafe fn new(
         display: &'dsp mut Display,
         surface: &'surf mut Surface,
@@ -260,6 +621,11 @@ impl<'dsp, 'surf> RenderContext<'dsp, 'surf> {
         Self::new_impl(display, surface, width, height, false)
     }
 
+    /// Every surface is drawn with the display's renderer, so brushes can be shared.
+    pub(super) fn cache_key(&self) -> u64 {
+        0
+    }
+
     unsafe fn new_impl(
         display: &'dsp mut Display,
         surface: &'surf mut Surface,
@@ -270,21 +636,28 @@ impl<'dsp, 'surf> RenderContext<'dsp, 'surf> {
         let Display {
             context,
             renderer,
+            fonts,
+            renderer_fonts_loaded,
+            gl,
             display,
+            deferred,
+            info,
+            max_texture_size,
+            frames,
+            compositor,
             ..
         } = display;
+        let display: &'dsp GlutinDisplay = display;
 
-        // Make the context current.
-        let not_current_context = context.take().unwrap();
-
-        // TODO: Restore not_current_context if this call fails.
-        let current_context = not_current_context
-            .make_current(&surface.surface)
-            .piet_err()?;
+        // Make the context current. If this fails, dropping the scope puts the context back.
+        // During a frame, the context stays current for the next surface.
+        let possibly_current = context.take().unwrap().into_possibly_current();
         let scope = ContextScope {
             slot: context,
-            context: Some(current_context),
+            context: Some(possibly_current),
+            keep_current: deferred.is_some(),
         };
+        scope.context().make_current(&surface.surface).piet_err()?;
 
         // Resize the surface.
         surface.surface.resize(
@@ -300,24 +673,52 @@ impl<'dsp, 'surf> RenderContext<'dsp, 'surf> {
                 // Create the new GlContext.
                 // SAFETY: The context is current.
                 slot.insert(unsafe {
-                    let context = glow::Context::from_loader_function_cstr(|s| {
-                        display.get_proc_address(s) as *const _
-                    });
+                    let load = || {
+                        glow::Context::from_loader_function_cstr(|s| {
+                            display.get_proc_address(s) as *const _
+                        })
+                    };
+                    let context = load();
+                    *info = Some(renderer_info(&context, "OpenGL"));
+                    *max_texture_size = Some(query_max_texture_size(&context));
+
+                    // The renderer's context is borrowed while drawing, so keep another one.
+                    *gl = Some(Box::new(load()));
 
                     GlContext::new(context).piet_err()?
                 })
             }
         };
+        let gl: &'dsp Context = gl.as_ref().unwrap();
+
+        // Free the copies of frames drawn to surfaces that have been dropped since.
+        frames.retain(|frame| {
+            let alive = frame.surface.strong_count() > 0;
+            if !alive {
+                // SAFETY: The context is current.
+                unsafe { frame.delete(gl) };
+            }
+            alive
+        });
 
         // Create a draw context on top of that.
         // SAFETY: The context is current.
         let mut draw_context = unsafe { renderer.render_context(width, height) };
+        let mut text = Text::new(TextInner::Glow(draw_context.text().clone()));
+        fonts.load_into(&mut text, renderer_fonts_loaded);
 
         Ok(Self {
             scope,
-            text: Text(TextInner::Glow(draw_context.text().clone())),
+            text,
             inner: draw_context,
             surface,
+            deferred: deferred.as_mut(),
+            frames,
+            compositor,
+            layers: Vec::new(),
+            gl,
+            size: (width, height),
+            max_texture_size: max_texture_size.unwrap_or(FALLBACK_MAX_TEXTURE_SIZE),
             check_current,
             current_mismatch: Ok(()),
         })
@@ -349,6 +750,10 @@ impl<'dsp, 'surf> RenderContext<'dsp, 'surf> {
         status.and(mismatch)
     }
 
+    pub(super) fn max_texture_size(&self) -> u32 {
+        self.max_texture_size
+    }
+
     pub(super) fn solid_brush(&mut self, color: piet::Color) -> Brush {
         // SAFETY: This doesn't involve any GL for the time being, and probably won't ever.
         self.inner.solid_brush(color)
@@ -364,6 +769,22 @@ impl<'dsp, 'surf> RenderContext<'dsp, 'surf> {
             return;
         }
 
+        // The renderer always clears to an opaque color, which breaks transparent windows.
+        let (r, g, b, a) = color.as_rgba();
+        if region.is_none() && a < 1.0 {
+            // SAFETY: The context is current.
+            unsafe {
+                use glow::HasContext;
+
+                self.gl.disable(glow::SCISSOR_TEST);
+                self.gl
+                    .clear_color((r * a) as f32, (g * a) as f32, (b * a) as f32, a as f32);
+                self.gl.clear(glow::COLOR_BUFFER_BIT);
+            }
+
+            return;
+        }
+
         self.inner.clear(region, color)
     }
 
@@ -419,8 +840,21 @@ impl<'dsp, 'surf> RenderContext<'dsp, 'surf> {
         }
         let layout = match layout.0 {
             TextLayoutInner::Glow(ref layout) => layout,
-            _ => {
-                panic!("TextLayout was not created by this backend")
+
+            // Layouts from `Display::text` need to be rebuilt with our text backend.
+            TextLayoutInner::Cosmic(_) => {
+                match layout.rebuild(&mut self.text) {
+                    Ok(layout) => self.draw_text(&layout, pos),
+                    Err(err) => self.current_mismatch = Err(err),
+                }
+                return;
+            }
+
+            // Layouts from other renderers can't be drawn by this one.
+            #[cfg(feature = "wgpu")]
+            TextLayoutInner::Wgpu(_) => {
+                self.current_mismatch = Err(Error::InvalidInput);
+                return;
             }
         };
         self.inner.draw_text(layout, pos)
@@ -439,13 +873,223 @@ impl<'dsp, 'surf> RenderContext<'dsp, 'surf> {
     pub(super) fn finish(&mut self) -> Result<(), Error> {
         self.check_current()?;
         self.inner.finish()?;
+        self.swap_buffers()
+    }
+
+    pub(super) fn finish_with_damage(&mut self, regions: &[Rect]) -> Result<(), Error> {
+        self.check_current()?;
+        self.inner.finish()?;
+        self.swap_buffers_with_damage(regions)
+    }
+
+    pub(super) fn flush(&mut self) -> Result<(), Error> {
+        // piet-glow's `finish` only flushes the GL command stream.
+        self.check_current()?;
+        self.inner.finish()
+    }
+
+    pub(super) fn push_layer(&mut self, blend: BlendMode) -> Result<(), Error> {
+        use glow::HasContext;
+
+        // Copying the surface and the shader need OpenGL 3.0 or OpenGL ES 3.0.
+        self.check_current()?;
+        let gl = self.gl;
+        if gl.version().major < 3 {
+            return Err(Error::NotSupported);
+        }
+
+        // Draw everything before the layer, so that it ends up in the backdrop.
+        self.inner.finish()?;
+
+        let compositor = match &mut *self.compositor {
+            Some(compositor) => compositor,
+            // SAFETY: The context is current.
+            slot @ None => slot.insert(unsafe { Compositor::new(gl)? }),
+        };
 
-        // Swap the buffers.
         // SAFETY: The context is current.
-        self.surface
-            .surface
-            .swap_buffers(self.scope.context())
-            .piet_err()?;
+        unsafe {
+            let backdrop = compositor.texture(gl, self.size)?;
+            compositor.copy_surface(gl, backdrop, self.size);
+            self.layers.push(backdrop);
+
+            // For blend modes other than source-over, the layer needs to be drawn on its own.
+            if blend != BlendMode::SourceOver {
+                let scissor = gl.is_enabled(glow::SCISSOR_TEST);
+                gl.disable(glow::SCISSOR_TEST);
+                gl.clear_color(0.0, 0.0, 0.0, 0.0);
+                gl.clear(glow::COLOR_BUFFER_BIT);
+                if scissor {
+                    gl.enable(glow::SCISSOR_TEST);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(super) fn pop_layer(&mut self, alpha: f64, blend: BlendMode) {
+        if self.not_current() {
+            return;
+        }
+        let (Some(backdrop), Some(compositor)) = (self.layers.pop(), self.compositor.as_mut())
+        else {
+            return;
+        };
+
+        // Draw the rest of the layer before copying it.
+        let gl = self.gl;
+        let result = self.inner.finish().and_then(|()| {
+            // SAFETY: The context is current.
+            unsafe {
+                let layer = compositor.texture(gl, self.size)?;
+                compositor.copy_surface(gl, layer, self.size);
+                compositor.composite(gl, backdrop, layer, alpha, blend, self.size);
+                compositor.textures.push((layer, self.size));
+            }
+            Ok(())
+        });
+        compositor.textures.push((backdrop, self.size));
+
+        if let Err(err) = result {
+            self.current_mismatch = Err(err);
+        }
+    }
+
+    pub(super) fn finish_to_buffer(&mut self) -> Result<Vec<u8>, Error> {
+        self.check_current()?;
+        self.inner.finish()?;
+
+        let gl = self.gl;
+        let (width, height) = self.size;
+        let stride = width as usize * 4;
+        let mut pixels = vec![0u8; stride * height as usize];
+
+        // SAFETY: The context is current and the buffer is large enough for the surface.
+        unsafe {
+            use glow::HasContext;
+
+            gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+            gl.pixel_store_i32(glow::PACK_ALIGNMENT, 1);
+            gl.read_pixels(
+                0,
+                0,
+                width as i32,
+                height as i32,
+                glow::RGBA,
+                glow::UNSIGNED_BYTE,
+                glow::PixelPackData::Slice(&mut pixels),
+            );
+        }
+
+        // OpenGL starts at the bottom-left corner, so flip the rows.
+        let pixels = pixels
+            .chunks_exact(stride)
+            .rev()
+            .flatten()
+            .copied()
+            .collect();
+
+        self.swap_buffers()?;
+        Ok(pixels)
+    }
+
+    fn swap_buffers(&mut self) -> Result<(), Error> {
+        self.swap_buffers_with_damage(&[])
+    }
+
+    fn swap_buffers_with_damage(&mut self, regions: &[Rect]) -> Result<(), Error> {
+        let height = self.size.1;
+        self.copy_frame()?;
+
+        // If we're in a frame, swap the buffers along with the other surfaces in `end_frame`.
+        if let Some(deferred) = &mut self.deferred {
+            let damage = (!regions.is_empty()).then(|| regions.to_vec());
+            match deferred
+                .iter_mut()
+                .find(|deferred| Rc::ptr_eq(&deferred.surface, &self.surface.surface))
+            {
+                Some(swap) => {
+                    // Present everything that was damaged by any of the drawing this frame.
+                    swap.height = height;
+                    match (&mut swap.damage, damage) {
+                        (Some(old), Some(new)) => old.extend(new),
+                        (old, _) => *old = None,
+                    }
+                }
+                None => deferred.push(DeferredSwap {
+                    surface: self.surface.surface.clone(),
+                    height,
+                    damage,
+                }),
+            }
+
+            return Ok(());
+        }
+
+        swap_surface(&self.surface.surface, self.scope.context(), height, regions)
+    }
+
+    /// Copy the finished frame, so that `Surface::snapshot` can read it after the swap.
+    fn copy_frame(&mut self) -> Result<(), Error> {
+        use glow::HasContext;
+
+        // Blitting between framebuffers needs OpenGL 3.0 or OpenGL ES 3.0.
+        let gl = self.gl;
+        if gl.version().major < 3 {
+            return Ok(());
+        }
+
+        let surface = &self.surface.surface;
+        let index = self
+            .frames
+            .iter()
+            .position(|frame| frame.surface.as_ptr() == Rc::as_ptr(surface));
+        let frame = match index {
+            Some(index) => &mut self.frames[index],
+            None => {
+                // SAFETY: The context is current.
+                let frame = unsafe { FrameCopy::new(gl, surface)? };
+                self.frames.push(frame);
+                self.frames.last_mut().unwrap()
+            }
+        };
+
+        let (width, height) = (self.size.0 as i32, self.size.1 as i32);
+
+        // SAFETY: The context is current.
+        unsafe {
+            if frame.size != self.size {
+                gl.bind_renderbuffer(glow::RENDERBUFFER, Some(frame.renderbuffer));
+                gl.renderbuffer_storage(glow::RENDERBUFFER, glow::RGBA8, width, height);
+                gl.bind_renderbuffer(glow::RENDERBUFFER, None);
+                frame.size = self.size;
+            }
+
+            // The scissor test applies to blits, and the renderer may have left it enabled.
+            let scissor = gl.is_enabled(glow::SCISSOR_TEST);
+            gl.disable(glow::SCISSOR_TEST);
+
+            gl.bind_framebuffer(glow::READ_FRAMEBUFFER, None);
+            gl.bind_framebuffer(glow::DRAW_FRAMEBUFFER, Some(frame.framebuffer));
+            gl.blit_framebuffer(
+                0,
+                0,
+                width,
+                height,
+                0,
+                0,
+                width,
+                height,
+                glow::COLOR_BUFFER_BIT,
+                glow::NEAREST,
+            );
+            gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+
+            if scissor {
+                gl.enable(glow::SCISSOR_TEST);
+            }
+        }
 
         Ok(())
     }
@@ -463,7 +1107,8 @@ impl<'dsp, 'surf> RenderContext<'dsp, 'surf> {
         format: piet::ImageFormat,
     ) -> Result<Image, Error> {
         self.check_current()?;
-        self.inner.make_image(width, height, buf, format)
+        let (buf, format) = crate::straight_alpha(buf, format);
+        self.inner.make_image(width, height, &buf, format)
     }
 
     pub(super) fn draw_image(
@@ -493,8 +1138,63 @@ impl<'dsp, 'surf> RenderContext<'dsp, 'surf> {
     }
 
     pub(super) fn capture_image_area(&mut self, src_rect: Rect) -> Result<Image, Error> {
+        use glow::HasContext;
+
         self.check_current()?;
-        self.inner.capture_image_area(src_rect)
+
+        // Read the pixels back here, so that the rectangle is in device pixels from the top-left
+        // corner of the surface, like on the other backends.
+        let (width, height) = self.size;
+        if src_rect.x1 > f64::from(width) || src_rect.y1 > f64::from(height) {
+            return Err(Error::InvalidInput);
+        }
+
+        // Draw everything before the capture, so that it ends up in the image.
+        self.inner.finish()?;
+
+        let gl = self.gl;
+        let (x, y) = (src_rect.x0 as i32, src_rect.y0 as i32);
+        let (columns, rows) = (src_rect.width() as usize, src_rect.height() as usize);
+        let stride = columns * 4;
+        let mut pixels = vec![0u8; stride * rows];
+
+        // SAFETY: The context is current and the buffer is large enough for the rectangle.
+        unsafe {
+            let framebuffer = gl.get_parameter_i32(glow::FRAMEBUFFER_BINDING);
+
+            gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+            gl.pixel_store_i32(glow::PACK_ALIGNMENT, 1);
+            gl.read_pixels(
+                x,
+                height as i32 - y - rows as i32,
+                columns as i32,
+                rows as i32,
+                glow::RGBA,
+                glow::UNSIGNED_BYTE,
+                glow::PixelPackData::Slice(&mut pixels),
+            );
+
+            gl.bind_framebuffer(
+                glow::FRAMEBUFFER,
+                native(framebuffer).map(glow::NativeFramebuffer),
+            );
+        }
+
+        // OpenGL starts at the bottom-left corner, so flip the rows.
+        let pixels = pixels
+            .chunks_exact(stride)
+            .rev()
+            .flatten()
+            .copied()
+            .collect::<Vec<_>>();
+
+        self.inner
+            .make_image(columns, rows, &pixels, piet::ImageFormat::RgbaSeparate)
+    }
+
+    pub(super) fn read_image(&mut self, _image: &Image) -> Result<(u32, u32, Vec<u8>), Error> {
+        // The renderer can't read textures back.
+        Err(Error::NotSupported)
     }
 
     pub(super) fn blurred_rect(&mut self, rect: Rect, blur_radius: f64, brush: &Brush) {
@@ -510,12 +1210,477 @@ impl<'dsp, 'surf> RenderContext<'dsp, 'surf> {
     }
 }
 
+impl FrameCopy {
+    /// Create an empty copy for `surface`.
+    ///
+    /// # Safety
+    ///
+    /// The context must be current.
+    unsafe fn new(gl: &Context, surface: &Rc<GlutinSurface<WindowSurface>>) -> Result<Self, Error> {
+        use glow::HasContext;
+
+        let framebuffer = gl
+            .create_framebuffer()
+            .map_err(|err| Error::BackendError(err.into()))?;
+        let renderbuffer = match gl.create_renderbuffer() {
+            Ok(renderbuffer) => renderbuffer,
+            Err(err) => {
+                gl.delete_framebuffer(framebuffer);
+                return Err(Error::BackendError(err.into()));
+            }
+        };
+
+        gl.bind_framebuffer(glow::FRAMEBUFFER, Some(framebuffer));
+        gl.framebuffer_renderbuffer(
+            glow::FRAMEBUFFER,
+            glow::COLOR_ATTACHMENT0,
+            glow::RENDERBUFFER,
+            Some(renderbuffer),
+        );
+        gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+
+        Ok(Self {
+            surface: Rc::downgrade(surface),
+            framebuffer,
+            renderbuffer,
+            size: (0, 0),
+        })
+    }
+
+    /// Free the GL objects of the copy.
+    ///
+    /// # Safety
+    ///
+    /// The context must be current.
+    unsafe fn delete(&self, gl: &Context) {
+        use glow::HasContext;
+
+        gl.delete_framebuffer(self.framebuffer);
+        gl.delete_renderbuffer(self.renderbuffer);
+    }
+}
+
+impl Compositor {
+    /// Compile the shader and create the objects used to composite layers.
+    ///
+    /// # Safety
+    ///
+    /// The context must be current, and support OpenGL 3.0 or OpenGL ES 3.0.
+    unsafe fn new(gl: &Context) -> Result<Self, Error> {
+        use glow::HasContext;
+
+        let version = gl.version();
+        let header = match (version.is_embedded, version.major, version.minor) {
+            (true, _, _) => "#version 300 es",
+            (false, 3, 0) => "#version 130",
+            (false, 3, 1) => "#version 140",
+            (false, 3, 2) => "#version 150",
+            (false, _, _) => "#version 330 core",
+        };
+
+        let program = gl
+            .create_program()
+            .map_err(|err| Error::BackendError(err.into()))?;
+        let mut shaders = Vec::new();
+        let mut result = Ok(());
+        for (kind, source) in [
+            (glow::VERTEX_SHADER, COMPOSITE_VERTEX_SHADER),
+            (glow::FRAGMENT_SHADER, COMPOSITE_FRAGMENT_SHADER),
+        ] {
+            let shader = match gl.create_shader(kind) {
+                Ok(shader) => shader,
+                Err(err) => {
+                    result = Err(err);
+                    break;
+                }
+            };
+            shaders.push(shader);
+
+            gl.shader_source(shader, &format!("{header}\n{source}"));
+            gl.compile_shader(shader);
+            if !gl.get_shader_compile_status(shader) {
+                result = Err(gl.get_shader_info_log(shader));
+                break;
+            }
+            gl.attach_shader(program, shader);
+        }
+
+        if result.is_ok() {
+            gl.link_program(program);
+            if !gl.get_program_link_status(program) {
+                result = Err(gl.get_program_info_log(program));
+            }
+        }
+
+        // The shaders aren't needed once the program is linked.
+        for shader in shaders {
+            gl.delete_shader(shader);
+        }
+        if let Err(err) = result {
+            gl.delete_program(program);
+            return Err(Error::BackendError(
+                format!("failed to build the layer shader: {err}").into(),
+            ));
+        }
+
+        let vertex_array = match gl.create_vertex_array() {
+            Ok(vertex_array) => vertex_array,
+            Err(err) => {
+                gl.delete_program(program);
+                return Err(Error::BackendError(err.into()));
+            }
+        };
+        let framebuffer = match gl.create_framebuffer() {
+            Ok(framebuffer) => framebuffer,
+            Err(err) => {
+                gl.delete_program(program);
+                gl.delete_vertex_array(vertex_array);
+                return Err(Error::BackendError(err.into()));
+            }
+        };
+
+        // The samplers always read from the first two texture units.
+        let previous = current_program(gl);
+        gl.use_program(Some(program));
+        let backdrop = gl.get_uniform_location(program, "backdrop");
+        let layer = gl.get_uniform_location(program, "layer");
+        gl.uniform_1_i32(backdrop.as_ref(), 0);
+        gl.uniform_1_i32(layer.as_ref(), 1);
+        gl.use_program(previous);
+
+        Ok(Self {
+            alpha: gl.get_uniform_location(program, "alpha"),
+            mode: gl.get_uniform_location(program, "mode"),
+            program,
+            vertex_array,
+            framebuffer,
+            textures: Vec::new(),
+        })
+    }
+
+    /// Get a texture the size of the surface that isn't in use.
+    ///
+    /// # Safety
+    ///
+    /// The context must be current.
+    unsafe fn texture(&mut self, gl: &Context, size: (u32, u32)) -> Result<glow::Texture, Error> {
+        use glow::HasContext;
+
+        if let Some(index) = self.textures.iter().position(|&(_, other)| other == size) {
+            return Ok(self.textures.swap_remove(index).0);
+        }
+
+        // Resize a texture of another size, or make a new one.
+        let texture = match self.textures.pop() {
+            Some((texture, _)) => texture,
+            None => gl
+                .create_texture()
+                .map_err(|err| Error::BackendError(err.into()))?,
+        };
+
+        let previous = gl.get_parameter_i32(glow::TEXTURE_BINDING_2D);
+        gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+        gl.tex_image_2d(
+            glow::TEXTURE_2D,
+            0,
+            glow::RGBA8 as i32,
+            size.0 as i32,
+            size.1 as i32,
+            0,
+            glow::RGBA,
+            glow::UNSIGNED_BYTE,
+            None,
+        );
+
+        // The texture has no mipmaps, so it can't be sampled with the default filter.
+        gl.tex_parameter_i32(
+            glow::TEXTURE_2D,
+            glow::TEXTURE_MIN_FILTER,
+            glow::NEAREST as i32,
+        );
+        gl.tex_parameter_i32(
+            glow::TEXTURE_2D,
+            glow::TEXTURE_MAG_FILTER,
+            glow::NEAREST as i32,
+        );
+        gl.bind_texture(glow::TEXTURE_2D, native(previous).map(glow::NativeTexture));
+
+        Ok(texture)
+    }
+
+    /// Copy the surface into `texture`.
+    ///
+    /// # Safety
+    ///
+    /// The context must be current, and `texture` must be the size of the surface.
+    unsafe fn copy_surface(&self, gl: &Context, texture: glow::Texture, size: (u32, u32)) {
+        use glow::HasContext;
+
+        let (width, height) = (size.0 as i32, size.1 as i32);
+        let draw = gl.get_parameter_i32(glow::DRAW_FRAMEBUFFER_BINDING);
+        let read = gl.get_parameter_i32(glow::READ_FRAMEBUFFER_BINDING);
+
+        // The scissor test applies to blits, and the renderer may have left it enabled.
+        let scissor = gl.is_enabled(glow::SCISSOR_TEST);
+        gl.disable(glow::SCISSOR_TEST);
+
+        gl.bind_framebuffer(glow::DRAW_FRAMEBUFFER, Some(self.framebuffer));
+        gl.framebuffer_texture_2d(
+            glow::DRAW_FRAMEBUFFER,
+            glow::COLOR_ATTACHMENT0,
+            glow::TEXTURE_2D,
+            Some(texture),
+            0,
+        );
+        gl.bind_framebuffer(glow::READ_FRAMEBUFFER, None);
+        gl.blit_framebuffer(
+            0,
+            0,
+            width,
+            height,
+            0,
+            0,
+            width,
+            height,
+            glow::COLOR_BUFFER_BIT,
+            glow::NEAREST,
+        );
+
+        gl.bind_framebuffer(
+            glow::DRAW_FRAMEBUFFER,
+            native(draw).map(glow::NativeFramebuffer),
+        );
+        gl.bind_framebuffer(
+            glow::READ_FRAMEBUFFER,
+            native(read).map(glow::NativeFramebuffer),
+        );
+        if scissor {
+            gl.enable(glow::SCISSOR_TEST);
+        }
+    }
+
+    /// Blend `layer` onto `backdrop`, and draw the result to the surface.
+    ///
+    /// # Safety
+    ///
+    /// The context must be current, and both textures must be the size of the surface.
+    unsafe fn composite(
+        &self,
+        gl: &Context,
+        backdrop: glow::Texture,
+        layer: glow::Texture,
+        alpha: f64,
+        blend: BlendMode,
+        size: (u32, u32),
+    ) {
+        use glow::HasContext;
+
+        // Save the state that the renderer expects to be left alone.
+        let mut viewport = [0; 4];
+        gl.get_parameter_i32_slice(glow::VIEWPORT, &mut viewport);
+        let program = current_program(gl);
+        let vertex_array = gl.get_parameter_i32(glow::VERTEX_ARRAY_BINDING);
+        let active_texture = gl.get_parameter_i32(glow::ACTIVE_TEXTURE) as u32;
+        let blend_enabled = gl.is_enabled(glow::BLEND);
+        let scissor = gl.is_enabled(glow::SCISSOR_TEST);
+
+        // Sampler objects override the filters of the textures, but need OpenGL 3.3.
+        let version = gl.version();
+        let samplers = version.is_embedded || (version.major, version.minor) >= (3, 3);
+
+        let units = [(0, backdrop), (1, layer)].map(|(unit, texture)| {
+            gl.active_texture(glow::TEXTURE0 + unit);
+            let previous = gl.get_parameter_i32(glow::TEXTURE_BINDING_2D);
+            gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+            let sampler = if samplers {
+                let sampler = gl.get_parameter_i32(glow::SAMPLER_BINDING);
+                gl.bind_sampler(unit, None);
+                sampler
+            } else {
+                0
+            };
+            (unit, previous, sampler)
+        });
+
+        let mode = match blend {
+            BlendMode::SourceOver => 0,
+            BlendMode::Multiply => 1,
+            BlendMode::Screen => 2,
+            BlendMode::Add => 3,
+        };
+
+        gl.viewport(0, 0, size.0 as i32, size.1 as i32);
+        gl.disable(glow::BLEND);
+        gl.disable(glow::SCISSOR_TEST);
+        gl.use_program(Some(self.program));
+        gl.uniform_1_f32(self.alpha.as_ref(), alpha as f32);
+        gl.uniform_1_i32(self.mode.as_ref(), mode);
+        gl.bind_vertex_array(Some(self.vertex_array));
+        gl.draw_arrays(glow::TRIANGLE_STRIP, 0, 4);
+
+        // Put everything back.
+        gl.bind_vertex_array(native(vertex_array).map(glow::NativeVertexArray));
+        gl.use_program(program);
+        for (unit, texture, sampler) in units {
+            gl.active_texture(glow::TEXTURE0 + unit);
+            gl.bind_texture(glow::TEXTURE_2D, native(texture).map(glow::NativeTexture));
+            if samplers {
+                gl.bind_sampler(unit, native(sampler).map(glow::NativeSampler));
+            }
+        }
+        gl.active_texture(active_texture);
+        if scissor {
+            gl.enable(glow::SCISSOR_TEST);
+        }
+        if blend_enabled {
+            gl.enable(glow::BLEND);
+        }
+        let [x, y, width, height] = viewport;
+        gl.viewport(x, y, width, height);
+    }
+}
+
+/// The vertex shader used to composite layers, without its version header.
+///
+/// It covers the viewport with a strip of two triangles.
+const COMPOSITE_VERTEX_SHADER: &str = r#"
+void main() {
+    vec2 corner = vec2(float(gl_VertexID & 1), float(gl_VertexID >> 1));
+    gl_Position = vec4(corner * 2.0 - 1.0, 0.0, 1.0);
+}
+"#;
+
+/// The fragment shader used to composite layers, without its version header.
+///
+/// The colors are premultiplied, and the blend modes match the software rasterizer.
+const COMPOSITE_FRAGMENT_SHADER: &str = r#"
+precision highp float;
+
+uniform sampler2D backdrop;
+uniform sampler2D layer;
+uniform float alpha;
+uniform int mode;
+
+out vec4 color;
+
+void main() {
+    ivec2 pos = ivec2(gl_FragCoord.xy);
+    vec4 d = texelFetch(backdrop, pos, 0);
+    vec4 s = texelFetch(layer, pos, 0);
+
+    if (mode == 0) {
+        // The layer was drawn over the backdrop, so fade between the two.
+        color = mix(d, s, alpha);
+        return;
+    }
+
+    s *= alpha;
+    if (mode == 1) {
+        vec3 rgb = s.rgb * d.rgb + s.rgb * (1.0 - d.a) + d.rgb * (1.0 - s.a);
+        color = vec4(rgb, s.a + d.a - s.a * d.a);
+    } else if (mode == 2) {
+        color = s + d - s * d;
+    } else {
+        color = min(s + d, 1.0);
+    }
+}
+"#;
+
+/// Get the program that is currently in use.
+///
+/// # Safety
+///
+/// The context must be current.
+unsafe fn current_program(gl: &Context) -> Option<glow::Program> {
+    use glow::HasContext;
+
+    native(gl.get_parameter_i32(glow::CURRENT_PROGRAM)).map(glow::NativeProgram)
+}
+
+/// Convert the name of a GL object queried with `glGetIntegerv` to the ID used by `glow`.
+fn native(name: i32) -> Option<NonZeroU32> {
+    NonZeroU32::new(name as u32)
+}
+
+/// The GL context held by the display.
+enum ContextSlot {
+    /// The context isn't current.
+    NotCurrent(NotCurrentContext),
+
+    /// The context was left current during a frame.
+    ///
+    /// Another context may have been made current since then, so it is made current again
+    /// before it is used.
+    Current(PossiblyCurrentContext),
+}
+
+impl ContextSlot {
+    fn into_possibly_current(self) -> PossiblyCurrentContext {
+        match self {
+            Self::NotCurrent(context) => context.treat_as_possibly_current(),
+            Self::Current(context) => context,
+        }
+    }
+}
+
+/// Swap the buffers of a surface, only presenting the damaged `regions` if there are any.
+///
+/// The context must be current with the surface. `height` is the height of the surface.
+fn swap_surface(
+    surface: &GlutinSurface<WindowSurface>,
+    context: &PossiblyCurrentContext,
+    height: u32,
+    regions: &[Rect],
+) -> Result<(), Error> {
+    // Only EGL can tell the compositor which regions were damaged.
+    #[cfg(egl_backend)]
+    #[allow(irrefutable_let_patterns)]
+    if let (false, GlutinSurface::Egl(surface), PossiblyCurrentContext::Egl(context)) =
+        (regions.is_empty(), surface, context)
+    {
+        // EGL puts the origin in the bottom-left corner.
+        let height = height as f64;
+        let rects = regions
+            .iter()
+            .map(|region| region.abs().expand())
+            .filter(|region| region.area() > 0.0)
+            .map(|region| {
+                glutin::surface::Rect::new(
+                    region.x0 as i32,
+                    (height - region.y1) as i32,
+                    region.width() as i32,
+                    region.height() as i32,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        match surface.swap_buffers_with_damage(context, &rects) {
+            Ok(()) => return Ok(()),
+
+            // Present the whole surface instead.
+            Err(err) if err.not_supported() => {
+                tracing::debug!("Failed to swap buffers with damage: {}", err);
+            }
+
+            Err(err) => return Err(err).piet_err(),
+        }
+    }
+
+    #[cfg(not(egl_backend))]
+    let _ = (height, regions);
+
+    surface.swap_buffers(context).piet_err()
+}
+
 struct ContextScope<'a> {
     /// The display we're borrowing from.
-    slot: &'a mut Option<NotCurrentContext>,
+    slot: &'a mut Option<ContextSlot>,
 
     /// The context we're borrowing.
     context: Option<PossiblyCurrentContext>,
+
+    /// Whether to leave the context current when it is put back.
+    keep_current: bool,
 }
 
 impl ContextScope<'_> {
@@ -528,10 +1693,56 @@ impl Drop for ContextScope<'_> {
     fn drop(&mut self) {
         let context = self.context.take().unwrap();
 
-        *self.slot = Some(
-            context
-                .make_not_current()
-                .expect("Failed to make context not current"),
-        );
+        *self.slot = Some(if self.keep_current {
+            ContextSlot::Current(context)
+        } else {
+            ContextSlot::NotCurrent(
+                context
+                    .make_not_current()
+                    .expect("Failed to make context not current"),
+            )
+        });
+    }
+}
+
+/// Get the X11 visual of a config.
+#[cfg_attr(not(x11_platform), allow(unused_variables))]
+fn query_x11_visual_info(config: &Config) -> Option<X11VisualInfo> {
+    #[cfg(x11_platform)]
+    {
+        use glutin::platform::x11::X11GlConfigExt;
+        use std::os::raw::{c_int, c_ulong};
+
+        /// The start of Xlib's `XVisualInfo`.
+        #[repr(C)]
+        struct XVisualInfo {
+            visual: *mut (),
+            visualid: c_ulong,
+            screen: c_int,
+            depth: c_int,
+        }
+
+        let visual = config.x11_visual()?;
+        let supports_transparency = visual.supports_transparency();
+
+        // Freeing the visual info needs Xlib, which we don't link to, so it is leaked. This is
+        // only done once per display.
+        // SAFETY: glutin returns a valid `XVisualInfo`.
+        let raw = unsafe { &*(visual.into_raw() as *const XVisualInfo) };
+
+        // `c_ulong` is only 32 bits wide on some platforms.
+        #[allow(clippy::useless_conversion)]
+        let visual_id = u64::from(raw.visualid);
+
+        return Some(X11VisualInfo {
+            visual: NonNull::new(raw.visual)?,
+            visual_id,
+            depth: raw.depth as u8,
+            screen: raw.screen,
+            supports_transparency,
+        });
     }
+
+    #[allow(unreachable_code)]
+    None
 }
@@ -0,0 +1,391 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later OR MPL-2.0
+// This file is a part of `theo`.
+//
+// `theo` is free software: you can redistribute it and/or modify it under the terms of
+// either:
+//
+// * GNU Lesser General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+// * Mozilla Public License as published by the Mozilla Foundation, version 2.
+//
+// `theo` is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+// See the GNU Lesser General Public License or the Mozilla Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License and the Mozilla
+// Public License along with `theo`. If not, see <https://www.gnu.org/licenses/>.
+
+//! The backend for OpenGL contexts created outside of `theo`.
+//!
+//! This uses `piet-glow` on top of a `glow` context provided by the user, skipping all of the
+//! `glutin` setup. The user is responsible for making the context current and presenting.
+
+use crate::gl_info::{query_max_texture_size, renderer_info};
+use crate::text::{BundledFonts, Text, TextInner, TextLayout, TextLayoutInner};
+use crate::{BlendMode, DisplayBuilder, Error, FormatInfo, RendererInfo};
+
+use glow::Context;
+use piet::kurbo::{Affine, Point, Rect, Shape};
+use piet::{RenderContext as _, StrokeStyle};
+use piet_glow::GlContext;
+use raw_window_handle::RawWindowHandle;
+
+use std::marker::PhantomData;
+use std::ptr::NonNull;
+
+/// The display for an external OpenGL context.
+pub(super) struct Display {
+    /// The `piet-glow` renderer.
+    renderer: GlContext<Context>,
+
+    /// The fonts to load into the text backends.
+    fonts: BundledFonts,
+
+    /// Whether `fonts` were loaded into the text backend of `renderer` yet.
+    renderer_fonts_loaded: bool,
+
+    /// Allow the use of transparency.
+    transparency: bool,
+
+    /// Information about the renderer, queried when the display is created.
+    info: RendererInfo,
+
+    /// The largest width or height of a texture, queried when the display is created.
+    max_texture_size: u32,
+
+    /// The text backend shared by layouts built outside of a frame.
+    text: Option<Text>,
+}
+
+/// The surface for an external OpenGL context.
+///
+/// This draws to whatever framebuffer is bound when the context is created.
+pub(super) struct Surface {
+    _private: (),
+}
+
+/// The rendering context for an external OpenGL context.
+pub(super) struct RenderContext<'dsp, 'surf> {
+    /// The piet-glow render context.
+    inner: piet_glow::RenderContext<'dsp, Context>,
+
+    /// The text renderer.
+    text: Text,
+
+    /// Error from mismatched type usages.
+    mismatch_err: Result<(), Error>,
+
+    /// The largest width or height of a texture.
+    max_texture_size: u32,
+
+    /// Eat the surface lifetime.
+    _surface: PhantomData<&'surf mut Surface>,
+}
+
+type Brush = piet_glow::Brush<Context>;
+type Image = piet_glow::Image<Context>;
+
+impl Display {
+    /// Create a new display from an existing context.
+    ///
+    /// # Safety
+    ///
+    /// The context must be current.
+    pub(super) unsafe fn from_context(
+        builder: &mut DisplayBuilder,
+        context: Context,
+    ) -> Result<Self, Error> {
+        let info = unsafe { renderer_info(&context, "OpenGL") };
+        let max_texture_size = unsafe { query_max_texture_size(&context) };
+
+        Ok(Self {
+            renderer: unsafe { GlContext::new(context)? },
+            fonts: BundledFonts::new(&builder.fonts),
+            renderer_fonts_loaded: false,
+            info,
+            max_texture_size,
+            transparency: builder.transparent,
+            text: None,
+        })
+    }
+
+    pub(super) fn text(&mut self) -> Text {
+        self.text
+            .get_or_insert_with(|| self.fonts.cosmic_text())
+            .clone()
+    }
+
+    pub(super) fn renderer_info(&self) -> Option<RendererInfo> {
+        Some(self.info.clone())
+    }
+
+    pub(super) fn max_texture_size(&self) -> Option<u32> {
+        Some(self.max_texture_size)
+    }
+
+    pub(super) fn supports_transparency(&self) -> bool {
+        self.transparency
+    }
+
+    pub(super) fn x11_visual(&self) -> Option<NonNull<()>> {
+        None
+    }
+
+    pub(super) fn x11_visual_info(&self) -> Option<crate::X11VisualInfo> {
+        None
+    }
+
+    pub(super) async unsafe fn make_surface(
+        &mut self,
+        _raw: RawWindowHandle,
+        _width: u32,
+        _height: u32,
+    ) -> Result<Surface, Error> {
+        // The user is in charge of the window, so there's nothing to set up.
+        Ok(Surface { _private: () })
+    }
+
+    pub(super) fn destroy_surface(&mut self, _surface: Surface) -> Result<(), Error> {
+        // There are no resources associated with the surface.
+        Ok(())
+    }
+
+    pub(super) async fn present(&mut self) {
+        // no-op
+    }
+
+    pub(super) fn poll(&mut self) {
+        // no-op
+    }
+
+    pub(super) fn device_lost(&self) -> bool {
+        // The application owns the context, so it is up to it to detect resets.
+        false
+    }
+
+    pub(super) fn begin_frame(&mut self) {
+        // no-op
+    }
+
+    pub(super) async fn end_frame(&mut self) -> Result<(), Error> {
+        // no-op
+        Ok(())
+    }
+}
+
+impl Surface {
+    pub(super) fn snapshot(
+        &mut self,
+        _display: &mut Display,
+    ) -> Result<(u32, u32, Vec<u8>), Error> {
+        // The framebuffer belongs to the application.
+        Err(Error::NotSupported)
+    }
+
+    pub(super) fn is_renderable(&self, _display: &Display) -> bool {
+        // The application knows better than we do.
+        true
+    }
+
+    pub(super) fn format_info(&self, _display: &Display) -> Result<FormatInfo, Error> {
+        // The application chose the framebuffer's format.
+        Err(Error::NotSupported)
+    }
+}
+
+impl<'dsp, 'surf> RenderContext<'dsp, 'surf> {
+    /// The user is in charge of keeping the context current.
+    pub(super) const EXCLUSIVE: bool = false;
+
+    pub(super) unsafe fn new(
+        display: &'dsp mut Display,
+        _surface: &'surf mut Surface,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, Error> {
+        // SAFETY: The user guarantees that the context is current.
+        let mut inner = unsafe { display.renderer.render_context(width, height) };
+        let mut text = Text::new(TextInner::Glow(inner.text().clone()));
+        display
+            .fonts
+            .load_into(&mut text, &mut display.renderer_fonts_loaded);
+
+        Ok(Self {
+            text,
+            inner,
+            mismatch_err: Ok(()),
+            max_texture_size: display.max_texture_size,
+            _surface: PhantomData,
+        })
+    }
+
+    pub(super) unsafe fn new_unchecked(
+        display: &'dsp mut Display,
+        surface: &'surf mut Surface,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, Error> {
+        Self::new(display, surface, width, height)
+    }
+
+    /// Every surface is drawn with the display's renderer, so brushes can be shared.
+    pub(super) fn cache_key(&self) -> u64 {
+        0
+    }
+
+    pub(super) fn status(&mut self) -> Result<(), Error> {
+        let status = self.inner.status();
+        let mismatch = std::mem::replace(&mut self.mismatch_err, Ok(()));
+        status.and(mismatch)
+    }
+
+    pub(super) fn max_texture_size(&self) -> u32 {
+        self.max_texture_size
+    }
+
+    pub(super) fn solid_brush(&mut self, color: piet::Color) -> Brush {
+        self.inner.solid_brush(color)
+    }
+
+    pub(super) fn gradient(&mut self, gradient: piet::FixedGradient) -> Result<Brush, Error> {
+        self.inner.gradient(gradient)
+    }
+
+    pub(super) fn clear(&mut self, region: Option<Rect>, color: piet::Color) {
+        self.inner.clear(region, color)
+    }
+
+    pub(super) fn stroke(&mut self, shape: impl Shape, brush: &Brush, width: f64) {
+        self.inner.stroke(shape, brush, width)
+    }
+
+    pub(super) fn stroke_styled(
+        &mut self,
+        shape: impl Shape,
+        brush: &Brush,
+        width: f64,
+        style: &StrokeStyle,
+    ) {
+        self.inner.stroke_styled(shape, brush, width, style)
+    }
+
+    pub(super) fn fill(&mut self, shape: impl Shape, brush: &Brush) {
+        self.inner.fill(shape, brush)
+    }
+
+    pub(super) fn fill_even_odd(&mut self, shape: impl Shape, brush: &Brush) {
+        self.inner.fill_even_odd(shape, brush)
+    }
+
+    pub(super) fn clip(&mut self, shape: impl Shape) {
+        self.inner.clip(shape)
+    }
+
+    pub(super) fn text(&mut self) -> &mut Text {
+        &mut self.text
+    }
+
+    pub(super) fn draw_text(&mut self, layout: &TextLayout, pos: Point) {
+        match layout.0 {
+            TextLayoutInner::Glow(ref layout) => self.inner.draw_text(layout, pos),
+
+            // Layouts from `Display::text` need to be rebuilt with our text backend.
+            TextLayoutInner::Cosmic(_) => match layout.rebuild(&mut self.text) {
+                Ok(layout) => self.draw_text(&layout, pos),
+                Err(err) => self.mismatch_err = Err(err),
+            },
+
+            // Layouts from other renderers can't be drawn by this one.
+            #[cfg(feature = "wgpu")]
+            TextLayoutInner::Wgpu(_) => self.mismatch_err = Err(Error::InvalidInput),
+        }
+    }
+
+    pub(super) fn save(&mut self) -> Result<(), Error> {
+        self.inner.save()
+    }
+
+    pub(super) fn restore(&mut self) -> Result<(), Error> {
+        self.inner.restore()
+    }
+
+    pub(super) fn finish(&mut self) -> Result<(), Error> {
+        // The user is in charge of presenting.
+        self.inner.finish()
+    }
+
+    pub(super) fn finish_with_damage(&mut self, _regions: &[Rect]) -> Result<(), Error> {
+        // The user is in charge of presenting.
+        self.finish()
+    }
+
+    pub(super) fn flush(&mut self) -> Result<(), Error> {
+        // piet-glow's `finish` only flushes the GL command stream.
+        self.inner.finish()
+    }
+
+    pub(super) fn push_layer(&mut self, _blend: BlendMode) -> Result<(), Error> {
+        // The GL functions needed to copy the surface aren't available.
+        Err(Error::NotSupported)
+    }
+
+    pub(super) fn pop_layer(&mut self, _alpha: f64, _blend: BlendMode) {
+        // Layers are never pushed.
+    }
+
+    pub(super) fn finish_to_buffer(&mut self) -> Result<Vec<u8>, Error> {
+        Err(Error::NotSupported)
+    }
+
+    pub(super) fn transform(&mut self, transform: Affine) {
+        self.inner.transform(transform)
+    }
+
+    pub(super) fn make_image(
+        &mut self,
+        width: usize,
+        height: usize,
+        buf: &[u8],
+        format: piet::ImageFormat,
+    ) -> Result<Image, Error> {
+        let (buf, format) = crate::straight_alpha(buf, format);
+        self.inner.make_image(width, height, &buf, format)
+    }
+
+    pub(super) fn draw_image(
+        &mut self,
+        image: &Image,
+        dst_rect: Rect,
+        interp: piet::InterpolationMode,
+    ) {
+        self.inner.draw_image(image, dst_rect, interp)
+    }
+
+    pub(super) fn draw_image_area(
+        &mut self,
+        image: &Image,
+        src_rect: Rect,
+        dst_rect: Rect,
+        interp: piet::InterpolationMode,
+    ) {
+        self.inner
+            .draw_image_area(image, src_rect, dst_rect, interp)
+    }
+
+    pub(super) fn capture_image_area(&mut self, src_rect: Rect) -> Result<Image, Error> {
+        self.inner.capture_image_area(src_rect)
+    }
+
+    pub(super) fn read_image(&mut self, _image: &Image) -> Result<(u32, u32, Vec<u8>), Error> {
+        // The renderer can't read textures back.
+        Err(Error::NotSupported)
+    }
+
+    pub(super) fn blurred_rect(&mut self, rect: Rect, blur_radius: f64, brush: &Brush) {
+        self.inner.blurred_rect(rect, blur_radius, brush)
+    }
+
+    pub(super) fn current_transform(&self) -> Affine {
+        self.inner.current_transform()
+    }
+}
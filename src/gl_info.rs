@@ -0,0 +1,63 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later OR MPL-2.0
+// This file is a part of `theo`.
+//
+// `theo` is free software: you can redistribute it and/or modify it under the terms of
+// either:
+//
+// * GNU Lesser General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+// * Mozilla Public License as published by the Mozilla Foundation, version 2.
+//
+// `theo` is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+// See the GNU Lesser General Public License or the Mozilla Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License and the Mozilla
+// Public License along with `theo`. If not, see <https://www.gnu.org/licenses/>.
+
+//! Information queried from OpenGL contexts, shared between the OpenGL backends.
+
+use crate::RendererInfo;
+
+use glow::HasContext;
+
+/// The texture size to assume when the context doesn't report one.
+///
+/// Every OpenGL ES 3.0 and WebGL 2 implementation supports textures at least this large.
+pub(super) const FALLBACK_MAX_TEXTURE_SIZE: u32 = 2048;
+
+/// Query the renderer information for a GL context.
+///
+/// `backend` is the name of the API that the context implements.
+///
+/// # Safety
+///
+/// The context must be current.
+pub(super) unsafe fn renderer_info(gl: &glow::Context, backend: &str) -> RendererInfo {
+    unsafe {
+        RendererInfo {
+            backend: backend.into(),
+            name: gl.get_parameter_string(glow::RENDERER),
+            vendor: gl.get_parameter_string(glow::VENDOR),
+            driver: gl.get_parameter_string(glow::VERSION),
+        }
+    }
+}
+
+/// Query the largest width or height of a texture.
+///
+/// # Safety
+///
+/// The context must be current.
+pub(super) unsafe fn query_max_texture_size(gl: &glow::Context) -> u32 {
+    let size = unsafe { gl.get_parameter_i32(glow::MAX_TEXTURE_SIZE) };
+    match u32::try_from(size) {
+        Ok(size) if size > 0 => size,
+        _ => {
+            tracing::warn!(
+                "invalid maximum texture size {size}, assuming {FALLBACK_MAX_TEXTURE_SIZE}"
+            );
+            FALLBACK_MAX_TEXTURE_SIZE
+        }
+    }
+}
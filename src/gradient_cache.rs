@@ -0,0 +1,234 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later OR MPL-2.0
+// This file is a part of `theo`.
+//
+// `theo` is free software: you can redistribute it and/or modify it under the terms of
+// either:
+//
+// * GNU Lesser General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+// * Mozilla Public License as published by the Mozilla Foundation, version 2.
+//
+// `theo` is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+// See the GNU Lesser General Public License or the Mozilla Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License and the Mozilla
+// Public License along with `theo`. If not, see <https://www.gnu.org/licenses/>.
+
+//! A cache for gradient brushes.
+
+use crate::Brush;
+
+use piet::{FixedGradient, GradientStop};
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::rc::{Rc, Weak};
+
+/// The default number of gradient brushes to keep around.
+pub(super) const DEFAULT_CAPACITY: usize = 32;
+
+/// A least-recently-used cache of gradient brushes.
+pub(super) struct GradientCache {
+    /// The maximum number of brushes to keep.
+    capacity: usize,
+
+    /// The cached brushes, from least to most recently used.
+    entries: Vec<Entry>,
+
+    /// The cached faded copies of brushes, from least to most recently used.
+    faded: Vec<FadedEntry>,
+}
+
+/// A gradient brush in the cache.
+struct Entry {
+    /// The context that the brush was created by.
+    key: u64,
+
+    /// The hash of the gradient.
+    hash: u64,
+
+    /// The gradient used to create the brush.
+    gradient: FixedGradient,
+
+    /// The brush.
+    brush: Brush,
+}
+
+/// A faded copy of a gradient brush in the cache.
+struct FadedEntry {
+    /// The context that the brush was created by.
+    key: u64,
+
+    /// The gradient of the brush that was faded.
+    ///
+    /// This keeps the allocation alive, so the pointer can't be reused by other gradients
+    /// while the entry exists.
+    source: Weak<FixedGradient>,
+
+    /// The bits of the alpha that the colors of the gradient were multiplied by.
+    alpha: u64,
+
+    /// The faded brush.
+    brush: Brush,
+}
+
+impl GradientCache {
+    /// Create a new, empty cache.
+    pub(super) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Vec::new(),
+            faded: Vec::new(),
+        }
+    }
+
+    /// Get the brush for a gradient, or create it if it isn't in the cache.
+    ///
+    /// `key` identifies the context that creates the brush, since brushes can't be used with
+    /// other contexts.
+    pub(super) fn get_or_insert<E>(
+        &mut self,
+        key: u64,
+        gradient: FixedGradient,
+        create: impl FnOnce(FixedGradient) -> Result<Brush, E>,
+    ) -> Result<Brush, E> {
+        if self.capacity == 0 {
+            return create(gradient);
+        }
+
+        let hash = hash_gradient(&gradient);
+        let position = self.entries.iter().position(|entry| {
+            entry.key == key && entry.hash == hash && gradients_equal(&entry.gradient, &gradient)
+        });
+
+        if let Some(position) = position {
+            // Move the entry to the back, since it was just used.
+            let entry = self.entries.remove(position);
+            let brush = entry.brush.clone();
+            self.entries.push(entry);
+            return Ok(brush);
+        }
+
+        let brush = create(gradient.clone())?;
+        if self.entries.len() >= self.capacity {
+            self.entries.remove(0);
+        }
+        self.entries.push(Entry {
+            key,
+            hash,
+            gradient,
+            brush: brush.clone(),
+        });
+
+        Ok(brush)
+    }
+
+    /// Get the copy of a gradient brush with its colors multiplied by `alpha`, if it is in the
+    /// cache.
+    ///
+    /// `source` is the gradient of the brush that was faded.
+    pub(super) fn get_faded(
+        &mut self,
+        key: u64,
+        source: &Rc<FixedGradient>,
+        alpha: f64,
+    ) -> Option<Brush> {
+        let source = Rc::downgrade(source);
+        let position = self.faded.iter().position(|entry| {
+            entry.key == key
+                && entry.alpha == alpha.to_bits()
+                && Weak::ptr_eq(&entry.source, &source)
+        })?;
+
+        // Move the entry to the back, since it was just used.
+        let entry = self.faded.remove(position);
+        let brush = entry.brush.clone();
+        self.faded.push(entry);
+        Some(brush)
+    }
+
+    /// Add the copy of a gradient brush with its colors multiplied by `alpha` to the cache.
+    pub(super) fn insert_faded(
+        &mut self,
+        key: u64,
+        source: &Rc<FixedGradient>,
+        alpha: f64,
+        brush: Brush,
+    ) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        // Brushes faded from gradients that were dropped can never be used again.
+        self.faded.retain(|entry| entry.source.strong_count() > 0);
+        if self.faded.len() >= self.capacity {
+            self.faded.remove(0);
+        }
+
+        self.faded.push(FadedEntry {
+            key,
+            source: Rc::downgrade(source),
+            alpha: alpha.to_bits(),
+            brush,
+        });
+    }
+}
+
+/// Hash the parameters of a gradient.
+fn hash_gradient(gradient: &FixedGradient) -> u64 {
+    let mut hasher = DefaultHasher::new();
+
+    let stops = match gradient {
+        FixedGradient::Linear(linear) => {
+            0u8.hash(&mut hasher);
+            for value in [linear.start.x, linear.start.y, linear.end.x, linear.end.y] {
+                value.to_bits().hash(&mut hasher);
+            }
+            &linear.stops
+        }
+        FixedGradient::Radial(radial) => {
+            1u8.hash(&mut hasher);
+            for value in [
+                radial.center.x,
+                radial.center.y,
+                radial.origin_offset.x,
+                radial.origin_offset.y,
+                radial.radius,
+            ] {
+                value.to_bits().hash(&mut hasher);
+            }
+            &radial.stops
+        }
+    };
+
+    for stop in stops {
+        stop.pos.to_bits().hash(&mut hasher);
+        stop.color.as_rgba_u32().hash(&mut hasher);
+    }
+
+    hasher.finish()
+}
+
+/// Tell whether two gradients are the same.
+fn gradients_equal(a: &FixedGradient, b: &FixedGradient) -> bool {
+    fn stops_equal(a: &[GradientStop], b: &[GradientStop]) -> bool {
+        a.len() == b.len()
+            && a.iter().zip(b).all(|(a, b)| {
+                a.pos.to_bits() == b.pos.to_bits() && a.color.as_rgba_u32() == b.color.as_rgba_u32()
+            })
+    }
+
+    match (a, b) {
+        (FixedGradient::Linear(a), FixedGradient::Linear(b)) => {
+            a.start == b.start && a.end == b.end && stops_equal(&a.stops, &b.stops)
+        }
+        (FixedGradient::Radial(a), FixedGradient::Radial(b)) => {
+            a.center == b.center
+                && a.origin_offset == b.origin_offset
+                && a.radius.to_bits() == b.radius.to_bits()
+                && stops_equal(&a.stops, &b.stops)
+        }
+        _ => false,
+    }
+}
@@ -0,0 +1,115 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later OR MPL-2.0
+// This file is a part of `theo`.
+//
+// `theo` is free software: you can redistribute it and/or modify it under the terms of
+// either:
+//
+// * GNU Lesser General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+// * Mozilla Public License as published by the Mozilla Foundation, version 2.
+//
+// `theo` is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+// See the GNU Lesser General Public License or the Mozilla Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License and the Mozilla
+// Public License along with `theo`. If not, see <https://www.gnu.org/licenses/>.
+
+//! A cache for images made from the pixels of other images.
+
+use crate::{ExtendMode, Image, ImagePixels};
+
+use std::rc::{Rc, Weak};
+
+/// The number of images to keep around.
+const CAPACITY: usize = 16;
+
+/// How a cached image is made from the pixels of another image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct Derivation {
+    /// The alpha that the pixels are multiplied by.
+    pub(super) alpha: u8,
+
+    /// How the pixels are tiled and how many times they are repeated across and down, or
+    /// `None` if they aren't tiled.
+    pub(super) tiling: Option<(ExtendMode, u32, u32)>,
+}
+
+/// A least-recently-used cache of images made from the pixels of other images.
+pub(super) struct ImageCache {
+    /// The cached images, from least to most recently used.
+    entries: Vec<Entry>,
+}
+
+/// An image in the cache.
+struct Entry {
+    /// The context that the image was created by.
+    key: u64,
+
+    /// The pixels that the image was made from.
+    ///
+    /// This keeps the allocation alive, so the pointer can't be reused by other pixels while
+    /// the entry exists.
+    source: Weak<ImagePixels>,
+
+    /// How the image was made from the pixels.
+    derivation: Derivation,
+
+    /// The image.
+    image: Image,
+}
+
+impl ImageCache {
+    /// Create a new, empty cache.
+    pub(super) fn new() -> Self {
+        Self {
+            entries: Vec::with_capacity(CAPACITY),
+        }
+    }
+
+    /// Get the image made from `source`, if it is in the cache.
+    ///
+    /// `key` identifies the context that created the image, since images can't be used with
+    /// other contexts.
+    pub(super) fn get(
+        &mut self,
+        key: u64,
+        source: &Rc<ImagePixels>,
+        derivation: Derivation,
+    ) -> Option<Image> {
+        let source = Rc::downgrade(source);
+        let position = self.entries.iter().position(|entry| {
+            entry.key == key
+                && entry.derivation == derivation
+                && Weak::ptr_eq(&entry.source, &source)
+        })?;
+
+        // Move the entry to the back, since it was just used.
+        let entry = self.entries.remove(position);
+        let image = entry.image.clone();
+        self.entries.push(entry);
+        Some(image)
+    }
+
+    /// Add an image made from `source` to the cache.
+    pub(super) fn insert(
+        &mut self,
+        key: u64,
+        source: &Rc<ImagePixels>,
+        derivation: Derivation,
+        image: Image,
+    ) {
+        // Images made from pixels that were dropped can never be used again.
+        self.entries.retain(|entry| entry.source.strong_count() > 0);
+        if self.entries.len() >= CAPACITY {
+            self.entries.remove(0);
+        }
+
+        self.entries.push(Entry {
+            key,
+            source: Rc::downgrade(source),
+            derivation,
+            image,
+        });
+    }
+}
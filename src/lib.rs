@@ -66,7 +66,7 @@
 //! # let my_display = MyDisplay;
 //! # let window = Window;
 //!
-//! # futures_lite::future::block_on(async move {
+//! # theo::block_on(async move {
 //! // Create a display using a display handle from your windowing framework.
 //! // It must implement `raw_window_handle::HasRawDisplayHandle`.
 //! let mut display = unsafe {
@@ -123,14 +123,24 @@
 //!
 //! - [`wgpu`] backend (enabled with the `wgpu` feature), which uses the [`piet-wgpu`] crate to render
 //!   to the window. This backend supports all of the graphics APIs that `wgpu` supports, including
-//!   Vulkan, Metal, and DirectX 11/12.
+//!   Vulkan, Metal, and DirectX 11/12. The `wgpu` types that appear in `theo`'s API, for sharing a
+//!   device with other rendering code, are re-exported in the [`theo::wgpu`](crate::wgpu) module.
 //! - [`glow`] backend (enabled with the `gl` feature), which uses the [`piet-glow`] crate to render to
 //!   the window. [`glutin`] is used on desktop platforms to create the OpenGL context, and [`glow`] is
-//!   used to interact with the OpenGL API. This backend supports OpenGL 3.2 and above.
+//!   used to interact with the OpenGL API. This backend supports OpenGL 3.2 and above. An existing
+//!   OpenGL context can also be used through [`Display::from_glow_context`].
 //! - A software rasterization backend. [`tiny-skia`] is used to render to a bitmap, and then
 //!   [`softbuffer`] is used to copy the bitmap to the window. This backend is enabled by default and is
 //!   used when no other backend is available.
 //!
+//! # Color Space
+//!
+//! [`piet`] colors are specified in the sRGB color space. On every backend, `theo` writes these
+//! values to the framebuffer as-is, without any further gamma conversion. This means that the
+//! framebuffer holds sRGB-encoded colors, and that the same color will produce the same pixels
+//! regardless of which backend is in use. Where the only available surface formats are sRGB
+//! formats, `theo` renders into a non-sRGB view of the surface to avoid double conversion.
+//!
 //! # Performance
 //!
 //! As `theo` implements most of its own rendering logic, this can lead to serious performance
@@ -140,9 +150,12 @@
 //! Mode.
 //!
 //! In addition, gradient brushes are optimized in such a way that the actual gradient needs to be
-//! computed only once. However, this means that, if you re-instantiate the brush every time, the
-//! gradient will be re-computed every time. This can lead to serious performance degradations even on
-//! hardware-accelerated backends. The solution is to cache the brushes that you use. For instance,
+//! computed only once. The [`Display`] keeps the most recently used gradient brushes in a cache,
+//! so re-creating the same gradient every frame only hashes its parameters. However, if a program
+//! uses more gradients than the cache can hold (see
+//! [`DisplayBuilder::gradient_cache_capacity`]), the gradients will be re-computed every time. This
+//! can lead to serious performance degradations even on hardware-accelerated backends. In this case,
+//! either raise the capacity of the cache or cache the brushes that you use yourself. For instance,
 //! instead of doing this:
 //!
 //! ```no_compile
@@ -167,6 +180,10 @@
 //! })
 //! ```
 //!
+//! Solid color brushes are cheaper to create, but the [`Display`] also keeps the last 16 colors
+//! that were used, so calling `solid_brush` with the same color over and over returns the same
+//! brush instead of creating a new one each time.
+//!
 //! `theo` explicitly opts into a thread-unsafe model. Not only is thread-unsafe code more performant,
 //! but these API types are usually thread-unsafe anyways.
 //!
@@ -183,11 +200,34 @@
 //! [`glow`]: https://crates.io/crates/glow
 //! [`theo`]: https://crates.io/crates/theo
 
+/// The [`wgpu`] types used in `theo`'s API.
+///
+/// This module is only available with the `wgpu` feature. It contains the types needed to
+/// share a device with `theo`, such as in [`DisplayBuilder::wgpu_device`] and
+/// [`Display::wgpu_device`]. The rest of [`wgpu`] should be used through a direct dependency
+/// on the same version of the crate.
+///
+/// [`wgpu`]: https://crates.io/crates/wgpu
 #[cfg(feature = "wgpu")]
-extern crate wgpu0 as wgpu;
+pub mod wgpu {
+    pub use wgpu0::{
+        Adapter, Backends, Device, Features, Instance, Limits, Queue, Texture, TextureFormat,
+    };
+}
+
+pub extern crate softbuffer;
+pub extern crate tiny_skia;
 
+mod blur_cache;
 #[cfg(all(feature = "gl", not(target_arch = "wasm32")))]
 mod desktop_gl;
+#[cfg(all(feature = "gl", not(target_arch = "wasm32")))]
+mod external_gl;
+#[cfg(feature = "gl")]
+mod gl_info;
+mod gradient_cache;
+mod image_cache;
+mod solid_cache;
 mod swrast;
 mod text;
 #[cfg(all(feature = "gl", target_arch = "wasm32"))]
@@ -195,27 +235,65 @@ mod web_gl;
 #[cfg(feature = "wgpu")]
 #[path = "wgpu.rs"]
 mod wgpu_backend;
+#[cfg(feature = "winit")]
+pub mod winit;
 
-use piet::kurbo::{Affine, Point, Shape, Size};
+use piet::kurbo::{Affine, BezPath, PathEl, Point, Shape, Size, Vec2};
 use piet::{kurbo::Rect, Error};
-use piet::{FixedGradient, ImageFormat, InterpolationMode, IntoBrush, StrokeStyle};
+use piet::{
+    FixedGradient, GradientStop, ImageFormat, InterpolationMode, IntoBrush, LineCap, LineJoin,
+    StrokeStyle,
+};
 
 use raw_window_handle::{
     HasRawDisplayHandle, HasRawWindowHandle, RawDisplayHandle, RawWindowHandle,
 };
 
+use blur_cache::{Blur, BlurCache};
+use gradient_cache::GradientCache;
+use image_cache::{Derivation, ImageCache};
+use solid_cache::SolidCache;
+use text::BundledFonts;
+
 use std::borrow::Cow;
-use std::cell::Cell;
+use std::cell::{Cell, OnceCell};
 use std::ffi::c_void;
 use std::fmt;
+use std::future::Future;
 use std::marker::PhantomData;
+use std::mem;
+use std::ops::Range;
+use std::pin::pin;
 use std::rc::Rc;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread;
+use std::time::Duration;
 
-pub use text::{Text, TextLayout, TextLayoutBuilder};
+pub use text::{MissingGlyphPolicy, Text, TextLayout, TextLayoutBuilder};
 
 std::thread_local! {
     // Make sure that we don't try to multiple contexts per thread.
-    static HAS_CONTEXT: Cell<bool> = Cell::new(false);
+    static HAS_CONTEXT: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Mark that a context that needs exclusive access to this thread is active.
+fn lock_thread() -> Result<(), Error> {
+    let prev = HAS_CONTEXT
+        .try_with(|has_context| has_context.replace(true))
+        .piet_err()?;
+    if prev {
+        return Err(Error::BackendError(Box::new(ContextInUse { _private: () })));
+    }
+
+    Ok(())
+}
+
+/// Mark that the context that was using this thread is gone.
+fn unlock_thread() {
+    HAS_CONTEXT
+        .try_with(|has_context| has_context.set(false))
+        .ok();
 }
 
 /// An error handler for GLX.
@@ -283,6 +361,66 @@ pub struct DisplayBuilder {
     /// Force software rendering.
     force_swrast: bool,
 
+    /// The number of threads the software rasterizer uses to convert its output, or zero to
+    /// use every available core.
+    swrast_threads: usize,
+
+    /// An existing `softbuffer` context for the software rasterizer to present with.
+    softbuffer_context: Option<softbuffer::Context>,
+
+    /// The number of samples to use for multisample anti-aliasing.
+    msaa: Option<u32>,
+
+    /// How surfaces should encode the colors written to them.
+    #[cfg_attr(not(feature = "wgpu"), allow(dead_code))]
+    color_space: Option<ColorSpace>,
+
+    /// How long to wait for a GPU adapter.
+    #[cfg_attr(not(feature = "wgpu"), allow(dead_code))]
+    adapter_timeout: Option<Duration>,
+
+    /// The function to call when the GPU device is lost.
+    device_lost_callback: Option<Rc<dyn Fn()>>,
+
+    /// The number of gradient brushes to cache.
+    gradient_cache_capacity: usize,
+
+    /// What to draw for glyphs that are missing from every font.
+    missing_glyph: MissingGlyphPolicy,
+
+    /// The fonts that every text backend of the display loads.
+    fonts: Vec<Arc<[u8]>>,
+
+    /// Whether to make sure that only one OpenGL render context is active per thread.
+    single_context_check: bool,
+
+    /// The OpenGL version to create contexts with, instead of trying the default ones.
+    #[cfg_attr(
+        not(all(feature = "gl", not(target_arch = "wasm32"))),
+        allow(dead_code)
+    )]
+    gl_version: Option<(GlApi, u8, u8)>,
+
+    /// An existing `wgpu` device to render with.
+    #[cfg(feature = "wgpu")]
+    wgpu_device: Option<wgpu_backend::ExternalDevice>,
+
+    /// An existing `wgpu` instance to create adapters with.
+    #[cfg(feature = "wgpu")]
+    wgpu_instance: Option<Arc<wgpu::Instance>>,
+
+    /// The graphics APIs that `wgpu` may use.
+    #[cfg(feature = "wgpu")]
+    wgpu_backends: wgpu::Backends,
+
+    /// Extra features to request from `wgpu` devices.
+    #[cfg(feature = "wgpu")]
+    wgpu_features: wgpu::Features,
+
+    /// The limits to request from `wgpu` devices.
+    #[cfg(feature = "wgpu")]
+    wgpu_limits: Option<wgpu::Limits>,
+
     _thread_unsafe: PhantomData<*mut ()>,
 }
 
@@ -293,6 +431,27 @@ impl Default for DisplayBuilder {
             glx_error_hook: None,
             transparent: true,
             force_swrast: false,
+            swrast_threads: 1,
+            softbuffer_context: None,
+            msaa: None,
+            color_space: None,
+            adapter_timeout: None,
+            device_lost_callback: None,
+            gradient_cache_capacity: gradient_cache::DEFAULT_CAPACITY,
+            missing_glyph: MissingGlyphPolicy::default(),
+            fonts: Vec::new(),
+            single_context_check: true,
+            gl_version: None,
+            #[cfg(feature = "wgpu")]
+            wgpu_device: None,
+            #[cfg(feature = "wgpu")]
+            wgpu_instance: None,
+            #[cfg(feature = "wgpu")]
+            wgpu_backends: wgpu::Backends::all(),
+            #[cfg(feature = "wgpu")]
+            wgpu_features: wgpu::Features::empty(),
+            #[cfg(feature = "wgpu")]
+            wgpu_limits: None,
             _thread_unsafe: PhantomData,
         }
     }
@@ -414,6 +573,28 @@ impl DisplayBuilder {
     /// others, such as EGL, do. This method allows you to set whether or not we should support
     /// transparent backgrounds.
     ///
+    /// The software rasterizer is always opaque, even if this is set. [`softbuffer`] requires the
+    /// alpha channel of every pixel to be zero, so translucent pixels are shown as if they were
+    /// drawn over black. Use [`Display::supports_transparency`] to check whether the display
+    /// that was created can actually show transparent windows.
+    ///
+    /// This is respected on every platform, including macOS. When it is `false`, OpenGL
+    /// configurations that don't support transparent windows are preferred, so that the window
+    /// is composited as opaque and doesn't blend with the desktop behind it. Transparent
+    /// configurations are still used if they are the only ones available.
+    ///
+    /// To let the desktop show through a window, the window itself must be created as
+    /// transparent, and the surface should be cleared to a translucent color such as
+    /// [`Color::TRANSPARENT`] by calling [`clear`] with no region at the start of each frame.
+    /// Like any other clear, this replaces everything drawn earlier in the frame. Clearing a
+    /// region, or clearing through a display created with
+    /// [`Display::from_glow_context`], always produces an opaque color. See the
+    /// `transparent` example for a full program.
+    ///
+    /// [`softbuffer`]: https://crates.io/crates/softbuffer
+    /// [`Color::TRANSPARENT`]: piet::Color::TRANSPARENT
+    /// [`clear`]: piet::RenderContext::clear
+    ///
     /// # Examples
     ///
     /// ```
@@ -447,11 +628,439 @@ impl DisplayBuilder {
         self
     }
 
+    /// Set the number of threads the software rasterizer uses to present frames.
+    ///
+    /// Before presenting a frame to a window, the software rasterizer converts every pixel from
+    /// the format it draws in to the one the window expects. With more than one thread, the
+    /// framebuffer is split into bands of rows that are converted in parallel. This is the part
+    /// of a frame that grows with the size of the window regardless of what is drawn, so it helps
+    /// the most for large windows. Drawing itself still happens on the calling thread, as
+    /// [`tiny-skia`] rasterizes each operation as soon as it is issued.
+    ///
+    /// If `threads` is zero, the number of threads is chosen from
+    /// [`std::thread::available_parallelism`]. The default is one thread. Small frames are
+    /// always converted on the calling thread, since starting threads would cost more than it
+    /// saves. This has no effect on the hardware-accelerated backends.
+    ///
+    /// [`tiny-skia`]: https://crates.io/crates/tiny-skia
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use theo::DisplayBuilder;
+    ///
+    /// let mut builder = DisplayBuilder::new();
+    /// builder = builder.force_swrast(true).swrast_threads(0);
+    /// ```
+    pub fn swrast_threads(mut self, threads: usize) -> Self {
+        self.swrast_threads = threads;
+        self
+    }
+
+    /// Use an existing [`softbuffer`] context to present software-rendered frames with.
+    ///
+    /// By default, the software rasterizer creates its own context for the display. Applications
+    /// that already use [`softbuffer`] for other windows can pass their context in here, so that
+    /// there is only one of them, and create their surfaces from it with
+    /// [`Display::softbuffer_context`]. The context must have been created for the same display
+    /// that is passed to [`DisplayBuilder::build`]. This is ignored if the software rasterizer is
+    /// not used, so combine it with [`DisplayBuilder::force_swrast`] to make sure that the
+    /// context is used.
+    ///
+    /// [`softbuffer`]: https://crates.io/crates/softbuffer
+    pub fn softbuffer_context(mut self, context: softbuffer::Context) -> Self {
+        self.softbuffer_context = Some(context);
+        self
+    }
+
+    /// Set the number of samples to use for multisample anti-aliasing.
+    ///
+    /// For the [`wgpu`] backend, this creates a multisampled render target that is resolved into
+    /// the window. For the OpenGL backend, this selects a framebuffer configuration with this
+    /// number of samples. The software rasterizer always anti-aliases its output, so this has no
+    /// effect on it.
+    ///
+    /// If the requested sample count isn't supported, the nearest supported count below it is
+    /// used instead. By default, [`wgpu`] does not use multisampling, while OpenGL uses the
+    /// configuration with the most samples.
+    ///
+    /// [`wgpu`]: https://crates.io/crates/wgpu
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use theo::DisplayBuilder;
+    ///
+    /// let mut builder = DisplayBuilder::new();
+    /// builder = builder.msaa(4);
+    /// ```
+    pub fn msaa(mut self, samples: u32) -> Self {
+        self.msaa = Some(samples);
+        self
+    }
+
+    /// Set how surfaces should encode the colors written to them.
+    ///
+    /// By default, the [`wgpu`] backend prefers surface formats that store colors as they are
+    /// written, which is [`ColorSpace::Linear`]. Asking for [`ColorSpace::Srgb`] prefers the
+    /// sRGB variants of the same formats, which tells the compositor that the contents are
+    /// sRGB-encoded. Colors look the same either way, since `theo` draws through a view that
+    /// skips the conversion. If the requested encoding isn't available, the default is used.
+    /// [`Surface::format_info`] reports what was actually chosen.
+    ///
+    /// This only applies to the [`wgpu`] backend. The OpenGL backends and the software
+    /// rasterizer always use [`ColorSpace::Linear`].
+    ///
+    /// Wide-gamut output, like Display P3 or extended sRGB, isn't supported. [`piet`] colors
+    /// and images are 8-bit sRGB, so they can't describe colors outside of the sRGB gamut, and
+    /// the version of [`wgpu`] in use can't tag a surface with a color space.
+    ///
+    /// [`wgpu`]: https://crates.io/crates/wgpu
+    /// [`piet`]: https://crates.io/crates/piet
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use theo::{ColorSpace, DisplayBuilder};
+    ///
+    /// let mut builder = DisplayBuilder::new();
+    /// builder = builder.color_space(ColorSpace::Srgb);
+    /// ```
+    pub fn color_space(mut self, color_space: ColorSpace) -> Self {
+        self.color_space = Some(color_space);
+        self
+    }
+
+    /// Set how long to wait for a GPU adapter when creating a surface.
+    ///
+    /// Finding an adapter can hang indefinitely on broken drivers. With a timeout,
+    /// [`Display::make_surface`] returns an error instead once it runs out, and the application
+    /// can fall back to a [`Display`] built with [`DisplayBuilder::force_swrast`]. By default,
+    /// there is no timeout.
+    ///
+    /// This only applies to the [`wgpu`] backend, and is ignored on the web.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use theo::DisplayBuilder;
+    ///
+    /// let mut builder = DisplayBuilder::new();
+    /// builder = builder.adapter_timeout(Duration::from_secs(5));
+    /// ```
+    ///
+    /// [`wgpu`]: https://crates.io/crates/wgpu
+    pub fn adapter_timeout(mut self, timeout: Duration) -> Self {
+        self.adapter_timeout = Some(timeout);
+        self
+    }
+
+    /// Set a function to call when the GPU device is lost.
+    ///
+    /// A device can be lost when the driver crashes or resets the GPU, for example after a
+    /// timeout. Nothing can be drawn with a lost device, so the application should drop the
+    /// [`Display`], along with its surfaces and resources, and build a new one. The function is
+    /// called once, from [`Display::begin_frame`], [`Display::end_frame`], [`Display::present`]
+    /// or [`Display::poll`], whichever notices the lost device first. See
+    /// [`Display::device_lost`] for which backends can detect this.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::cell::Cell;
+    /// use std::rc::Rc;
+    /// use theo::DisplayBuilder;
+    ///
+    /// let lost = Rc::new(Cell::new(false));
+    /// let builder = DisplayBuilder::new().on_device_lost({
+    ///     let lost = lost.clone();
+    ///     move || lost.set(true)
+    /// });
+    /// ```
+    pub fn on_device_lost(mut self, callback: impl Fn() + 'static) -> Self {
+        self.device_lost_callback = Some(Rc::new(callback));
+        self
+    }
+
+    /// Set the number of gradient brushes that the [`Display`] keeps cached.
+    ///
+    /// Creating a gradient brush computes the gradient, which is expensive. To avoid this when
+    /// the same gradient is created every frame, the [`Display`] keeps the most recently used
+    /// gradient brushes around and reuses them. The default is 32. Setting this to zero disables
+    /// the cache.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use theo::DisplayBuilder;
+    ///
+    /// let mut builder = DisplayBuilder::new();
+    /// builder = builder.gradient_cache_capacity(64);
+    /// ```
+    pub fn gradient_cache_capacity(mut self, capacity: usize) -> Self {
+        self.gradient_cache_capacity = capacity;
+        self
+    }
+
+    /// Set what to draw for characters that none of the fonts have a glyph for.
+    ///
+    /// `cosmic-text` already falls back to other installed fonts when the requested one doesn't
+    /// have a glyph. When none of them do, the placeholder glyph of the font is drawn, which is
+    /// usually an empty box, or "tofu". This is the default, [`MissingGlyphPolicy::Tofu`].
+    ///
+    /// [`MissingGlyphPolicy::Blank`] hides those characters instead, and
+    /// [`MissingGlyphPolicy::FallbackFont`] lays them out again with a specific font family,
+    /// like a CJK font loaded with [`load_font`]. Finding the missing glyphs means laying the
+    /// text out a second time, so building layouts is slower with these policies.
+    ///
+    /// This applies to the text backends returned by [`Display::text`] and
+    /// [`RenderContext::text`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use theo::{DisplayBuilder, MissingGlyphPolicy};
+    ///
+    /// let mut builder = DisplayBuilder::new();
+    /// builder = builder.missing_glyph(MissingGlyphPolicy::Blank);
+    /// ```
+    ///
+    /// [`load_font`]: piet::Text::load_font
+    /// [`RenderContext::text`]: piet::RenderContext::text
+    pub fn missing_glyph(mut self, policy: MissingGlyphPolicy) -> Self {
+        self.missing_glyph = policy;
+        self
+    }
+
+    /// Add a font that every text backend of the display loads.
+    ///
+    /// Fonts loaded with [`load_font`] only apply to the text backend they were loaded into, so
+    /// they have to be loaded again on [`RenderContext::text`] and [`Display::text`]. Fonts added
+    /// here are loaded into all of them, before any text is laid out. This is meant for
+    /// applications that ship their own fonts, and for rendering on servers, where the same fonts
+    /// should be available everywhere. `data` is the contents of a TrueType or OpenType font
+    /// file. Fonts that fail to load are skipped with a warning.
+    ///
+    /// The system fonts are still loaded, as every renderer creates its own font database that
+    /// can't be replaced. For output that doesn't depend on the fonts of the system, lay text out
+    /// with the family names of the added fonts instead of the generic families, like
+    /// [`FontFamily::SANS_SERIF`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use piet::{FontFamily, Text as _, TextLayoutBuilder as _};
+    /// use theo::DisplayBuilder;
+    ///
+    /// let font = std::fs::read("assets/Inter-Regular.ttf").unwrap();
+    /// let mut display = DisplayBuilder::new().font(font).build_offscreen();
+    ///
+    /// let layout = display
+    ///     .text()
+    ///     .new_text_layout("Hello, world!")
+    ///     .font(FontFamily::new_unchecked("Inter"), 12.0)
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    ///
+    /// [`load_font`]: piet::Text::load_font
+    /// [`RenderContext::text`]: piet::RenderContext::text
+    /// [`FontFamily::SANS_SERIF`]: piet::FontFamily::SANS_SERIF
+    pub fn font(mut self, data: impl Into<Arc<[u8]>>) -> Self {
+        self.fonts.push(data.into());
+        self
+    }
+
+    /// Set whether [`RenderContext::new`] makes sure that only one context is active per thread.
+    ///
+    /// Only one OpenGL context can be current on a thread at a time, so by default,
+    /// [`RenderContext::new`] returns a [`ContextInUse`] error if another desktop OpenGL
+    /// [`RenderContext`] is already active on the thread. The other backends never check this.
+    ///
+    /// Turning the check off lets contexts from several [`Display`]s be created on the same
+    /// thread without [`RenderContext::new_unchecked`]. This is still safe: creating a desktop
+    /// OpenGL context makes its OpenGL context current, and drawing with a context whose OpenGL
+    /// context is no longer current does nothing and makes [`status`] return an error. The
+    /// application has to finish with one context before creating the next one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use theo::DisplayBuilder;
+    ///
+    /// let mut builder = DisplayBuilder::new();
+    /// builder = builder.single_context_check(false);
+    /// ```
+    ///
+    /// [`status`]: piet::RenderContext::status
+    pub fn single_context_check(mut self, check: bool) -> Self {
+        self.single_context_check = check;
+        self
+    }
+
+    /// Set the OpenGL version and API to create contexts with.
+    ///
+    /// By default, the desktop OpenGL backend tries the newest context that the driver offers,
+    /// then OpenGL ES, then OpenGL 3.3, and uses the first one that can be created. Some drivers
+    /// create contexts that don't work well with the renderer. This replaces that sequence with
+    /// a single context of the given API and version. If it can't be created, the next backend
+    /// is tried, usually ending with the software rasterizer.
+    ///
+    /// This has no effect on the other backends, including WebGL.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use theo::{DisplayBuilder, GlApi};
+    ///
+    /// let mut builder = DisplayBuilder::new();
+    /// builder = builder.gl_version(3, 0, GlApi::Gles);
+    /// ```
+    pub fn gl_version(mut self, major: u8, minor: u8, api: GlApi) -> Self {
+        self.gl_version = Some((api, major, minor));
+        self
+    }
+
+    /// Use an existing [`wgpu`] device to render with.
+    ///
+    /// This allows `theo` to share a device with other [`wgpu`] rendering code, so that
+    /// resources can be used by both. The `adapter` must have been created from `instance`, and
+    /// `device` and `queue` must have been created from `adapter`. Surfaces will be created using
+    /// `instance`, and will use this device if the adapter supports them.
+    ///
+    /// The device can be accessed again later through [`Display::wgpu_device`] and
+    /// [`Display::wgpu_queue`]. This is ignored if the [`wgpu`] backend is not used.
+    ///
+    /// [`wgpu`]: https://crates.io/crates/wgpu
+    #[cfg(feature = "wgpu")]
+    pub fn wgpu_device(
+        mut self,
+        instance: wgpu::Instance,
+        adapter: wgpu::Adapter,
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+    ) -> Self {
+        self.wgpu_device = Some(wgpu_backend::ExternalDevice {
+            instance,
+            adapter,
+            device,
+            queue,
+        });
+        self
+    }
+
+    /// Use an existing [`wgpu`] instance to find adapters and create surfaces with.
+    ///
+    /// Creating an instance is expensive, and having several of them can confuse validation
+    /// layers. Applications that already use [`wgpu`] for other rendering can pass their instance
+    /// in here, either on its own or in an [`Arc`] that they keep a clone of. `theo` still
+    /// creates its own devices. This is ignored if the [`wgpu`] backend is not used, or if a
+    /// device was provided through [`DisplayBuilder::wgpu_device`], which comes with its own
+    /// instance.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use theo::DisplayBuilder;
+    ///
+    /// let instance = Arc::new(theo::wgpu::Instance::default());
+    ///
+    /// let mut builder = DisplayBuilder::new();
+    /// builder = builder.wgpu_instance(instance.clone());
+    /// ```
+    ///
+    /// [`wgpu`]: https://crates.io/crates/wgpu
+    #[cfg(feature = "wgpu")]
+    pub fn wgpu_instance(mut self, instance: impl Into<Arc<wgpu::Instance>>) -> Self {
+        self.wgpu_instance = Some(instance.into());
+        self
+    }
+
+    /// Choose the graphics APIs that [`wgpu`] may use.
+    ///
+    /// By default, every API that [`wgpu`] supports on the platform is allowed, and it picks
+    /// between them. Restricting this can work around driver bugs in one of them, for instance by
+    /// using DX12 rather than Vulkan on Windows. If none of the APIs can present to a window,
+    /// creating a surface for it fails with [`piet::Error::NotSupported`]. This is ignored if the
+    /// [`wgpu`] backend is not used, or if an instance or device was provided through
+    /// [`DisplayBuilder::wgpu_instance`] or [`DisplayBuilder::wgpu_device`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use theo::DisplayBuilder;
+    ///
+    /// let mut builder = DisplayBuilder::new();
+    /// builder = builder.wgpu_backends(theo::wgpu::Backends::DX12 | theo::wgpu::Backends::METAL);
+    /// ```
+    ///
+    /// [`wgpu`]: https://crates.io/crates/wgpu
+    #[cfg(feature = "wgpu")]
+    pub fn wgpu_backends(mut self, backends: wgpu::Backends) -> Self {
+        self.wgpu_backends = backends;
+        self
+    }
+
+    /// Request additional features from the [`wgpu`] devices that `theo` creates.
+    ///
+    /// These are requested on top of the features that the renderer needs. Adapters that don't
+    /// support all of them are rejected. This is ignored if the [`wgpu`] backend is not used, or
+    /// if a device was provided through [`DisplayBuilder::wgpu_device`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use theo::DisplayBuilder;
+    ///
+    /// let mut builder = DisplayBuilder::new();
+    /// builder = builder.wgpu_features(theo::wgpu::Features::PUSH_CONSTANTS);
+    /// ```
+    ///
+    /// [`wgpu`]: https://crates.io/crates/wgpu
+    #[cfg(feature = "wgpu")]
+    pub fn wgpu_features(mut self, features: wgpu::Features) -> Self {
+        self.wgpu_features = features;
+        self
+    }
+
+    /// Set the limits to request from the [`wgpu`] devices that `theo` creates.
+    ///
+    /// By default, [`wgpu::Limits::default`] is used, or
+    /// [`wgpu::Limits::downlevel_webgl2_defaults`] for adapters that run on OpenGL or WebGL2.
+    /// Some mobile GPUs can't provide the default limits either. This is ignored if the [`wgpu`]
+    /// backend is not used, or if a device was provided through [`DisplayBuilder::wgpu_device`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use theo::DisplayBuilder;
+    ///
+    /// let mut builder = DisplayBuilder::new();
+    /// builder = builder.wgpu_limits(theo::wgpu::Limits::downlevel_defaults());
+    /// ```
+    ///
+    /// [`wgpu`]: https://crates.io/crates/wgpu
+    #[cfg(feature = "wgpu")]
+    pub fn wgpu_limits(mut self, limits: wgpu::Limits) -> Self {
+        self.wgpu_limits = Some(limits);
+        self
+    }
+
     /// Build a new [`Display`].
     ///
     /// Using the provided parameters, this method will attempt to build a new [`Display`]. If
     /// successful, it will return a new [`Display`]. Otherwise, it will return an error.
     ///
+    /// # Errors
+    ///
+    /// The backends that were compiled in are tried in order. If none of them support the
+    /// platform of `display`, this returns a [`NoBackendAvailable`] error wrapped in
+    /// [`Error::BackendError`]. This usually means that the features needed for the platform
+    /// weren't enabled. If a backend supports the platform but fails to start, the error from the
+    /// last backend that failed is returned instead.
+    ///
     /// # Safety
     ///
     /// - The `display` handle must be a valid `display` that isn't currently suspended.
@@ -469,6 +1078,26 @@ impl DisplayBuilder {
     pub unsafe fn build(self, display: impl HasRawDisplayHandle) -> Result<Display, Error> {
         self.build_from_raw(display.raw_display_handle())
     }
+
+    /// Build a new [`Display`] that isn't connected to any windowing system.
+    ///
+    /// This is like [`Display::offscreen`], but with the settings of this builder. Settings that
+    /// only apply to the hardware-accelerated backends are ignored.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use theo::{DisplayBuilder, MissingGlyphPolicy};
+    ///
+    /// let mut display = DisplayBuilder::new()
+    ///     .missing_glyph(MissingGlyphPolicy::Blank)
+    ///     .build_offscreen();
+    /// let surface = display.make_offscreen_surface(64, 64).unwrap();
+    /// ```
+    pub fn build_offscreen(mut self) -> Display {
+        let display = swrast::Display::offscreen(&mut self);
+        Display::from_dispatch(DisplayDispatch::SwRast(display), &self)
+    }
 }
 
 /// The display used to manage all surfaces.
@@ -528,7 +1157,7 @@ impl DisplayBuilder {
 /// # }
 /// # let my_display = MyDisplay;
 ///
-/// # futures_lite::future::block_on(async {
+/// # theo::block_on(async {
 /// // Create a new display.
 /// let mut display = unsafe { Display::new(&my_display) }.unwrap();
 ///
@@ -559,6 +1188,31 @@ impl DisplayBuilder {
 /// ```
 pub struct Display {
     dispatch: Box<DisplayDispatch>,
+
+    /// The most recently used gradient brushes.
+    gradients: GradientCache,
+
+    /// The most recently used solid color brushes.
+    solids: SolidCache,
+
+    /// The most recently used faded and tiled copies of images.
+    images: ImageCache,
+
+    /// The most recently used images of blurred rectangles.
+    blurs: BlurCache,
+
+    /// What to draw for glyphs that are missing from every font.
+    missing_glyph: MissingGlyphPolicy,
+
+    /// Whether to make sure that only one OpenGL render context is active per thread.
+    single_context_check: bool,
+
+    /// The function to call when the GPU device is lost.
+    device_lost_callback: Option<Rc<dyn Fn()>>,
+
+    /// Whether `device_lost_callback` has been called yet.
+    device_lost_reported: bool,
+
     _thread_unsafe: PhantomData<*mut ()>,
 }
 
@@ -568,13 +1222,35 @@ impl fmt::Debug for Display {
     }
 }
 
-impl From<DisplayDispatch> for Display {
-    fn from(dispatch: DisplayDispatch) -> Self {
+impl Display {
+    fn from_dispatch(dispatch: DisplayDispatch, builder: &DisplayBuilder) -> Self {
+        BundledFonts::new(&builder.fonts).register();
+
         Self {
             dispatch: Box::new(dispatch),
+            gradients: GradientCache::new(builder.gradient_cache_capacity),
+            solids: SolidCache::new(),
+            images: ImageCache::new(),
+            blurs: BlurCache::new(),
+            missing_glyph: builder.missing_glyph.clone(),
+            single_context_check: builder.single_context_check,
+            device_lost_callback: builder.device_lost_callback.clone(),
+            device_lost_reported: false,
             _thread_unsafe: PhantomData,
         }
     }
+
+    /// Call the device lost callback if the device was just lost.
+    fn report_device_lost(&mut self) {
+        if self.device_lost_reported || !self.device_lost() {
+            return;
+        }
+
+        self.device_lost_reported = true;
+        if let Some(callback) = &self.device_lost_callback {
+            callback();
+        }
+    }
 }
 
 impl Display {
@@ -607,6 +1283,281 @@ impl Display {
         Self::builder().build_from_raw(display.raw_display_handle())
     }
 
+    /// Get the [`wgpu`] device used by this display.
+    ///
+    /// Returns `None` if this display doesn't use the [`wgpu`] backend, or if no device has been
+    /// created yet. Devices are created along with the first [`Surface`] that needs them, unless
+    /// one was provided through [`DisplayBuilder::wgpu_device`]. If there is more than one
+    /// device, this returns the first one.
+    ///
+    /// [`wgpu`]: https://crates.io/crates/wgpu
+    #[cfg(feature = "wgpu")]
+    #[allow(unreachable_patterns)]
+    pub fn wgpu_device(&self) -> Option<&wgpu::Device> {
+        match &*self.dispatch {
+            DisplayDispatch::Wgpu(display) => display.device(),
+            _ => None,
+        }
+    }
+
+    /// Get the [`wgpu`] queue used by this display.
+    ///
+    /// This is the queue associated with the device returned by [`Display::wgpu_device`].
+    ///
+    /// [`wgpu`]: https://crates.io/crates/wgpu
+    #[cfg(feature = "wgpu")]
+    #[allow(unreachable_patterns)]
+    pub fn wgpu_queue(&self) -> Option<&wgpu::Queue> {
+        match &*self.dispatch {
+            DisplayDispatch::Wgpu(display) => display.queue(),
+            _ => None,
+        }
+    }
+
+    /// Create a new [`Display`] that isn't connected to any windowing system.
+    ///
+    /// This display uses the software rasterizer. It can't create surfaces from windows, but it
+    /// can create surfaces that draw into memory using [`Display::make_offscreen_surface`]. This
+    /// is useful for headless rendering, like taking screenshots in tests.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use theo::Display;
+    ///
+    /// let mut display = Display::offscreen();
+    /// let surface = display.make_offscreen_surface(64, 64).unwrap();
+    /// ```
+    pub fn offscreen() -> Self {
+        DisplayBuilder::new().build_offscreen()
+    }
+
+    /// Create a new [`Display`] on top of an existing OpenGL context.
+    ///
+    /// This is useful for applications that already create their own OpenGL context, through
+    /// [`glutin`], SDL or otherwise. `theo` will not create a context of its own, and will draw
+    /// using the provided one instead. The settings of `builder` are used for everything that
+    /// doesn't involve creating the context.
+    ///
+    /// Surfaces created from this display draw into the framebuffer that is bound when the
+    /// [`RenderContext`] is created, and the window handle passed to
+    /// [`Display::make_surface`] is ignored. Finishing a [`RenderContext`] does not swap the
+    /// buffers; that is left up to the application.
+    ///
+    /// # Safety
+    ///
+    /// The context must be current on this thread when this function is called, and whenever a
+    /// [`RenderContext`] created from this display is created, used or dropped.
+    ///
+    /// [`glutin`]: https://crates.io/crates/glutin
+    #[cfg(all(feature = "gl", not(target_arch = "wasm32")))]
+    pub unsafe fn from_glow_context(
+        mut builder: DisplayBuilder,
+        context: glow::Context,
+    ) -> Result<Self, Error> {
+        let display = unsafe { external_gl::Display::from_context(&mut builder, context)? };
+        Ok(Display::from_dispatch(
+            DisplayDispatch::ExternalGl(display),
+            &builder,
+        ))
+    }
+
+    /// Create a new [`Surface`] that draws into memory.
+    ///
+    /// The contents of the surface can be read using [`RenderContext::finish_to_buffer`].
+    /// Offscreen surfaces are only supported by the software rasterizer, so this returns
+    /// [`Error::NotSupported`] for other backends.
+    #[allow(unreachable_patterns)]
+    pub fn make_offscreen_surface(&mut self, width: u32, height: u32) -> Result<Surface, Error> {
+        match &mut *self.dispatch {
+            DisplayDispatch::SwRast(display) => {
+                let surface = display.make_offscreen_surface(width, height)?;
+                Ok(SurfaceDispatch::SwRast(surface).into())
+            }
+            _ => Err(Error::NotSupported),
+        }
+    }
+
+    /// Create a new [`Surface`] that draws into an existing [`tiny_skia::Pixmap`].
+    ///
+    /// This is like [`Display::make_offscreen_surface`], but the surface starts with the
+    /// contents of `pixmap`, and everything drawn to it ends up in the same pixmap, which can be
+    /// accessed with [`Surface::pixmap`] and [`Surface::pixmap_mut`]. This makes it easy to
+    /// combine `theo`'s output with a larger software-rendered scene. The pixels are
+    /// premultiplied RGBA, as usual for [`tiny-skia`].
+    ///
+    /// If the surface is drawn to at a different size than the pixmap, the pixmap is replaced
+    /// with a new, transparent one of the right size. Pixmaps are only supported by the software
+    /// rasterizer, like offscreen surfaces, so this returns [`Error::NotSupported`] for other
+    /// backends.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use piet::RenderContext as _;
+    /// use theo::tiny_skia::Pixmap;
+    /// use theo::{Display, RenderContext};
+    ///
+    /// let mut display = Display::offscreen();
+    /// let pixmap = Pixmap::new(2, 2).unwrap();
+    /// let mut surface = display.make_pixmap_surface(pixmap).unwrap();
+    ///
+    /// let mut ctx = RenderContext::new(&mut display, &mut surface, 2, 2).unwrap();
+    /// ctx.clear(None, piet::Color::RED);
+    /// ctx.finish().unwrap();
+    /// drop(ctx);
+    ///
+    /// // The drawing ended up in the pixmap.
+    /// let pixmap = surface.pixmap().unwrap();
+    /// assert_eq!(&pixmap.data()[..4], &[255, 0, 0, 255]);
+    /// ```
+    ///
+    /// [`tiny-skia`]: https://crates.io/crates/tiny-skia
+    #[allow(unreachable_patterns)]
+    pub fn make_pixmap_surface(&mut self, pixmap: tiny_skia::Pixmap) -> Result<Surface, Error> {
+        match &mut *self.dispatch {
+            DisplayDispatch::SwRast(display) => {
+                Ok(SurfaceDispatch::SwRast(display.make_pixmap_surface(pixmap)).into())
+            }
+            _ => Err(Error::NotSupported),
+        }
+    }
+
+    /// Get the [`softbuffer`] context that the software rasterizer presents with.
+    ///
+    /// This is either the context passed to [`DisplayBuilder::softbuffer_context`], or the one
+    /// that the display created. It can be used to create [`softbuffer`] surfaces for windows
+    /// that aren't drawn to by `theo`, or surfaces to pass to [`Display::make_softbuffer_surface`].
+    /// This returns `None` if the display doesn't use the software rasterizer, or if it is
+    /// offscreen.
+    ///
+    /// [`softbuffer`]: https://crates.io/crates/softbuffer
+    #[allow(unreachable_patterns)]
+    pub fn softbuffer_context(&self) -> Option<&softbuffer::Context> {
+        match &*self.dispatch {
+            DisplayDispatch::SwRast(display) => display.softbuffer_context(),
+            _ => None,
+        }
+    }
+
+    /// Create a new [`Surface`] that presents through an existing [`softbuffer`] surface.
+    ///
+    /// This is like [`Display::make_surface`], but the window's buffer stays owned by `surface`
+    /// rather than by a second [`softbuffer`] surface for the same window. `surface` should be
+    /// created from [`Display::softbuffer_context`], and is resized to `width` and `height`. It can
+    /// be accessed again with [`Surface::softbuffer_surface`]. [`softbuffer`] surfaces can only be
+    /// drawn to by the software rasterizer, so this returns [`Error::NotSupported`] for other
+    /// backends.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidInput`] if `width` or `height` is zero.
+    ///
+    /// [`softbuffer`]: https://crates.io/crates/softbuffer
+    #[allow(unreachable_patterns)]
+    pub fn make_softbuffer_surface(
+        &mut self,
+        surface: softbuffer::Surface,
+        width: u32,
+        height: u32,
+    ) -> Result<Surface, Error> {
+        match &mut *self.dispatch {
+            DisplayDispatch::SwRast(display) => {
+                let surface = display.make_softbuffer_surface(surface, width, height)?;
+                Ok(SurfaceDispatch::SwRast(surface).into())
+            }
+            _ => Err(Error::NotSupported),
+        }
+    }
+
+    /// Whether or not this display renders using the GPU.
+    ///
+    /// This is `false` when `theo` fell back to the software rasterizer, either because no GPU
+    /// backend could be created or because [`DisplayBuilder::force_swrast`] was set. Rendering
+    /// is much slower in that case, which applications may want to tell the user about.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use theo::Display;
+    ///
+    /// let display = Display::offscreen();
+    /// assert!(!display.is_hardware_accelerated());
+    /// ```
+    pub fn is_hardware_accelerated(&self) -> bool {
+        !matches!(&*self.dispatch, DisplayDispatch::SwRast(_))
+    }
+
+    /// Prepare this display and its surfaces to be moved to another thread.
+    ///
+    /// [`Display`] and [`Surface`] are not [`Send`], because the OpenGL backends are tied to the
+    /// thread that created them. The [`wgpu`] backend and the software rasterizer aren't, so a
+    /// display that uses one of them can be wrapped in a [`SendDisplay`] along with its surfaces,
+    /// sent to a rendering thread, and unwrapped there with [`SendDisplay::into_inner`]. Returns
+    /// the display and the surfaces back if they use OpenGL, or on the web, where none of the
+    /// backends can leave the main thread.
+    ///
+    /// # Safety
+    ///
+    /// The surfaces share reference-counted state with the display that created them, so they
+    /// have to move together. Every surface created by this display that is still alive must be
+    /// passed in `surfaces`, and `surfaces` may only contain surfaces created by this display.
+    ///
+    /// The brushes, images and text objects created from this display share reference-counted
+    /// state with it too. None of them may be used on this thread after the display is sent, and
+    /// they may not be sent along with it. The same goes for anything captured by the function
+    /// passed to [`DisplayBuilder::on_device_lost`]. The windowing system must also allow the
+    /// surfaces to be presented from the other thread. For example, the software rasterizer
+    /// presents to macOS windows through Core Animation, which has to be done on the main thread.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use piet::RenderContext as _;
+    /// use theo::{Display, RenderContext};
+    ///
+    /// let mut display = Display::offscreen();
+    /// let surface = display.make_offscreen_surface(1, 1).unwrap();
+    /// let display = unsafe { display.into_send(vec![surface]) }.unwrap();
+    ///
+    /// std::thread::spawn(move || {
+    ///     let (mut display, mut surfaces) = display.into_inner();
+    ///     let mut ctx = RenderContext::new(&mut display, &mut surfaces[0], 1, 1).unwrap();
+    ///     ctx.clear(None, piet::Color::RED);
+    ///     ctx.finish().unwrap();
+    /// })
+    /// .join()
+    /// .unwrap();
+    /// ```
+    ///
+    /// [`wgpu`]: https://crates.io/crates/wgpu
+    pub unsafe fn into_send(
+        self,
+        surfaces: Vec<Surface>,
+    ) -> Result<SendDisplay, (Self, Vec<Surface>)> {
+        let sendable = match &*self.dispatch {
+            #[cfg(all(feature = "wgpu", not(target_arch = "wasm32")))]
+            DisplayDispatch::Wgpu(_) => surfaces
+                .iter()
+                .all(|surface| matches!(&*surface.dispatch, SurfaceDispatch::Wgpu(_))),
+            #[cfg(not(target_arch = "wasm32"))]
+            DisplayDispatch::SwRast(_) => surfaces
+                .iter()
+                .all(|surface| matches!(&*surface.dispatch, SurfaceDispatch::SwRast(_))),
+            #[allow(unreachable_patterns)]
+            _ => false,
+        };
+
+        if sendable {
+            Ok(SendDisplay {
+                display: self,
+                surfaces,
+            })
+        } else {
+            Err((self, surfaces))
+        }
+    }
+
     /// Create a new [`Surface`] from a window.
     ///
     /// This function creates the state that `theo` associates with a window with the provided
@@ -624,6 +1575,17 @@ impl Display {
     /// `width` and `height` parameters aren't necessarily required to be correct, but
     /// it's recommended that they are in order to avoid visual bugs.
     ///
+    /// # Suspending and Resuming
+    ///
+    /// On Android, windows are destroyed when the application is suspended, and new ones are
+    /// created when it is resumed. Pass each [`Surface`] to [`Display::destroy_surface`] when
+    /// the application is suspended, so that nothing refers to the old window, and call this
+    /// function again with the new window when it is resumed. The [`Display`] stays alive the
+    /// whole time. The [`wgpu`] backend reuses the adapter and device that it already has for
+    /// the new surface, so images, brushes and text layouts created before suspending can still
+    /// be used afterwards. Surfaces that were dropped instead of destroyed are freed before the
+    /// new surface is created.
+    ///
     /// # Examples
     ///
     /// ```no_run
@@ -631,7 +1593,7 @@ impl Display {
     /// use winit::event_loop::EventLoop;
     /// use winit::window::Window;
     ///
-    /// # futures_lite::future::block_on(async {
+    /// # theo::block_on(async {
     /// let event_loop = EventLoop::new();
     /// let mut display = unsafe { Display::new(&event_loop) }.unwrap();
     ///
@@ -657,6 +1619,88 @@ impl Display {
         self.make_surface_from_raw(window.raw_window_handle(), width, height)
             .await
     }
+
+    /// Create a new [`Surface`] from a window, without `async`.
+    ///
+    /// This is equivalent to calling [`block_on`] on [`make_surface`]. Only the [`wgpu`] backend
+    /// ever waits while creating a surface, so this returns immediately for every other backend.
+    /// On the web, the main thread can't be blocked, so [`make_surface`] should be used there
+    /// when [`wgpu`] is enabled.
+    ///
+    /// # Safety
+    ///
+    /// The same as [`make_surface`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use theo::Display;
+    /// use winit::event_loop::EventLoop;
+    /// use winit::window::Window;
+    ///
+    /// let event_loop = EventLoop::new();
+    /// let mut display = unsafe { Display::new(&event_loop) }.unwrap();
+    ///
+    /// let window = Window::new(&event_loop).unwrap();
+    /// let size = window.inner_size();
+    ///
+    /// let surface = unsafe {
+    ///     display.make_surface_blocking(&window, size.width, size.height).unwrap()
+    /// };
+    /// ```
+    ///
+    /// [`make_surface`]: Display::make_surface
+    pub unsafe fn make_surface_blocking(
+        &mut self,
+        window: impl HasRawWindowHandle,
+        width: u32,
+        height: u32,
+    ) -> Result<Surface, Error> {
+        block_on(self.make_surface(window, width, height))
+    }
+}
+
+/// Drive a future to completion on the current thread.
+///
+/// Some of the methods in `theo`, like [`Display::make_surface`] and [`Display::present`], are
+/// asynchronous. This function is a minimal executor that polls the future, parking the current
+/// thread whenever it returns `Pending`. It allows these methods to be used without pulling in
+/// an async runtime.
+///
+/// This should be called on the thread that is used for rendering. It only polls the provided
+/// future and will not run any other tasks. On the web, where the main thread cannot be parked,
+/// futures should be spawned onto the browser's event loop instead.
+///
+/// # Examples
+///
+/// ```
+/// let value = theo::block_on(async { 1 + 2 });
+/// assert_eq!(value, 3);
+/// ```
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    /// Wakes up the thread that is blocking on the future.
+    struct ThreadWaker(thread::Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+
+        fn wake_by_ref(self: &Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let mut future = pin!(future);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
 }
 
 /// The surface used to draw to.
@@ -701,7 +1745,7 @@ impl Display {
 /// # }
 /// # let my_display = MyDisplay;
 ///
-/// # futures_lite::future::block_on(async {
+/// # theo::block_on(async {
 /// // Create a new display.
 /// let mut display = unsafe { Display::new(&my_display) }.unwrap();
 ///
@@ -740,9 +1784,41 @@ impl Display {
 /// ```
 pub struct Surface {
     dispatch: Box<SurfaceDispatch>,
+
+    /// The color that the surface is cleared to, set by `set_background`.
+    background: Option<piet::Color>,
+
+    /// Whether to clear the surface to its background when a render context is created.
+    auto_clear: bool,
+
+    /// The scale that render contexts for this surface start with.
+    scale_factor: f64,
+
     _thread_unsafe: PhantomData<*mut ()>,
 }
 
+/// A [`Display`] and its surfaces, ready to be sent to another thread.
+///
+/// This is created by [`Display::into_send`].
+#[derive(Debug)]
+pub struct SendDisplay {
+    display: Display,
+    surfaces: Vec<Surface>,
+}
+
+// SAFETY: `Display::into_send` only creates this for backends that aren't tied to a thread, and
+// its caller promises that nothing sharing state with the display stays behind.
+unsafe impl Send for SendDisplay {}
+
+impl SendDisplay {
+    /// Get the [`Display`] and its surfaces back, on the thread that they were sent to.
+    ///
+    /// The surfaces are in the same order as they were passed to [`Display::into_send`].
+    pub fn into_inner(self) -> (Display, Vec<Surface>) {
+        (self.display, self.surfaces)
+    }
+}
+
 impl fmt::Debug for Surface {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Surface").finish_non_exhaustive()
@@ -753,11 +1829,171 @@ impl From<SurfaceDispatch> for Surface {
     fn from(dispatch: SurfaceDispatch) -> Self {
         Self {
             dispatch: Box::new(dispatch),
+            background: None,
+            auto_clear: true,
+            scale_factor: 1.0,
             _thread_unsafe: PhantomData,
         }
     }
 }
 
+impl Surface {
+    /// Get the pixmap that this surface draws into.
+    ///
+    /// This returns `None` unless the surface was created with
+    /// [`Display::make_offscreen_surface`] or [`Display::make_pixmap_surface`]. The pixmap holds
+    /// premultiplied RGBA pixels, as of the last call to [`finish`].
+    ///
+    /// [`finish`]: piet::RenderContext::finish
+    #[allow(unreachable_patterns)]
+    pub fn pixmap(&self) -> Option<&tiny_skia::Pixmap> {
+        match &*self.dispatch {
+            SurfaceDispatch::SwRast(surface) => surface.pixmap(),
+            _ => None,
+        }
+    }
+
+    /// Get mutable access to the pixmap that this surface draws into.
+    ///
+    /// See [`Surface::pixmap`] for more information. The pixmap can be drawn to directly
+    /// between frames, or swapped out for another one of the same size with [`mem::swap`].
+    ///
+    /// [`mem::swap`]: std::mem::swap
+    #[allow(unreachable_patterns)]
+    pub fn pixmap_mut(&mut self) -> Option<&mut tiny_skia::Pixmap> {
+        match &mut *self.dispatch {
+            SurfaceDispatch::SwRast(surface) => surface.pixmap_mut(),
+            _ => None,
+        }
+    }
+
+    /// Get the [`softbuffer`] surface that this surface presents through.
+    ///
+    /// This returns `None` unless the surface draws to a window using the software rasterizer,
+    /// either because it was created with [`Display::make_softbuffer_surface`] or because the
+    /// display fell back to software rendering.
+    ///
+    /// [`softbuffer`]: https://crates.io/crates/softbuffer
+    #[allow(unreachable_patterns)]
+    pub fn softbuffer_surface(&self) -> Option<&softbuffer::Surface> {
+        match &*self.dispatch {
+            SurfaceDispatch::SwRast(surface) => surface.softbuffer_surface(),
+            _ => None,
+        }
+    }
+
+    /// Get mutable access to the [`softbuffer`] surface that this surface presents through.
+    ///
+    /// See [`Surface::softbuffer_surface`] for more information. Anything written to the
+    /// surface's buffer between frames is overwritten by the next frame that `theo` draws.
+    ///
+    /// [`softbuffer`]: https://crates.io/crates/softbuffer
+    #[allow(unreachable_patterns)]
+    pub fn softbuffer_surface_mut(&mut self) -> Option<&mut softbuffer::Surface> {
+        match &mut *self.dispatch {
+            SurfaceDispatch::SwRast(surface) => surface.softbuffer_surface_mut(),
+            _ => None,
+        }
+    }
+
+    /// Set the background color of this surface.
+    ///
+    /// Once a background is set, every [`RenderContext`] created for this surface starts by
+    /// clearing the whole surface to it, unless that is turned off with [`set_auto_clear`].
+    /// The background can also be used to clear only parts of the surface with
+    /// [`RenderContext::clear_background`], which is useful when only damaged regions are
+    /// redrawn. Pass `None` to remove the background.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use piet::Color;
+    /// use theo::Display;
+    ///
+    /// let mut display = Display::offscreen();
+    /// let mut surface = display.make_offscreen_surface(1, 1).unwrap();
+    /// surface.set_background(Color::BLUE);
+    /// assert_eq!(surface.background(), Some(Color::BLUE));
+    /// ```
+    ///
+    /// [`set_auto_clear`]: Self::set_auto_clear
+    pub fn set_background(&mut self, color: impl Into<Option<piet::Color>>) {
+        self.background = color.into();
+    }
+
+    /// Get the background color of this surface, if one was set.
+    ///
+    /// See [`set_background`] for more information.
+    ///
+    /// [`set_background`]: Self::set_background
+    pub fn background(&self) -> Option<piet::Color> {
+        self.background
+    }
+
+    /// Set whether new render contexts clear the surface to its background.
+    ///
+    /// This is on by default, and does nothing if there is no background. Turn it off to keep
+    /// the previous contents of the surface and only clear the damaged regions with
+    /// [`RenderContext::clear_background`]. Keep in mind that some platforms don't preserve the
+    /// contents of a window between frames.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use piet::kurbo::Rect;
+    /// use piet::Color;
+    /// use theo::{Display, RenderContext};
+    ///
+    /// let mut display = Display::offscreen();
+    /// let mut surface = display.make_offscreen_surface(2, 1).unwrap();
+    /// surface.set_background(Color::WHITE);
+    /// surface.set_auto_clear(false);
+    ///
+    /// // Only the damaged area is cleared to the background.
+    /// let mut ctx = RenderContext::new(&mut display, &mut surface, 2, 1).unwrap();
+    /// ctx.clear_background(Rect::new(1.0, 0.0, 2.0, 1.0));
+    /// ```
+    pub fn set_auto_clear(&mut self, auto_clear: bool) {
+        self.auto_clear = auto_clear;
+    }
+
+    /// Set the scale factor of the window that this surface draws to.
+    ///
+    /// Every [`RenderContext`] created for this surface afterwards starts with its transform
+    /// scaled by `scale`, so that shapes can be drawn in logical coordinates while the width and
+    /// height passed to [`RenderContext::new`] stay in physical pixels. Call this when the
+    /// window moves to a monitor with a different scale factor, like on winit's
+    /// `ScaleFactorChanged` event, instead of recreating the surface. A `scale` that is not a
+    /// positive, finite number is treated as `1.0`, which is also the default.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use theo::Display;
+    ///
+    /// let mut display = Display::offscreen();
+    /// let mut surface = display.make_offscreen_surface(2, 2).unwrap();
+    /// surface.set_scale_factor(2.0);
+    /// assert_eq!(surface.scale_factor(), 2.0);
+    /// ```
+    pub fn set_scale_factor(&mut self, scale: f64) {
+        self.scale_factor = if scale > 0.0 && scale.is_finite() {
+            scale
+        } else {
+            1.0
+        };
+    }
+
+    /// Get the scale factor of this surface.
+    ///
+    /// See [`set_scale_factor`] for more information.
+    ///
+    /// [`set_scale_factor`]: Self::set_scale_factor
+    pub fn scale_factor(&self) -> f64 {
+        self.scale_factor
+    }
+}
+
 /// The context used to draw to a [`Surface`].
 ///
 /// This is the whole point of this crate, and is the aperture used to actually draw with vector
@@ -766,7 +2002,85 @@ impl From<SurfaceDispatch> for Surface {
 ///
 /// See the [`RenderContext`] documentation for more information.
 ///
+/// # Stroking
+///
+/// Dash patterns are handled the same way on every backend. The lengths alternate between drawn
+/// and skipped segments, starting with a drawn one, and a pattern with an odd number of lengths
+/// is repeated to make it even. The dash offset moves the start of the pattern forward along
+/// the path. Patterns with negative lengths are drawn as a solid line.
+///
+/// The renderers disagree on how to dash a path, and the OpenGL renderer ignores the dash offset,
+/// so the path is split into its dashes before it reaches them. Each dash is then stroked as a
+/// solid line, with the caps of the style on both of its ends.
+///
+/// The dash offset is wrapped around to the length of the pattern before it reaches the
+/// renderers, which only keep it with single precision. This means that the offset can be
+/// increased every frame to animate the dashes, like a "marching ants" selection, without them
+/// starting to jitter after a while. No geometry is kept between calls, so a dashed path is still
+/// split and tessellated again every time it is stroked.
+///
+/// Miter limits follow SVG on every backend: the limit is the longest allowed ratio between the
+/// length of the miter and the stroke width, and joins that exceed it are drawn as bevels. A
+/// limit below one, which no join can meet, always produces bevels. The renderers don't agree on
+/// joins and caps, so the outline of every stroke is made on the CPU the same way as the software
+/// rasterizer makes it, and the backends fill that outline. Round joins and caps are curves that
+/// are flattened with a small tolerance on the GPU backends, so their edges can still differ from
+/// the software rasterizer by a fraction of a pixel.
+///
+/// # Filling
+///
+/// [`fill`] uses the nonzero winding rule and [`fill_even_odd`] uses the even-odd rule on every
+/// backend. They only differ for paths that overlap themselves, like a star drawn as a single
+/// self-intersecting line: the nonzero rule fills its center, while the even-odd rule leaves a
+/// hole there. This also holds for image brushes and conic gradients, which are drawn by `theo`
+/// itself inside of a clip that covers what the fill rule covers.
+///
+/// # Drawing Images
+///
+/// [`InterpolationMode::NearestNeighbor`] samples the closest pixel of the image on every
+/// backend, using nearest filtering in the GPU renderers' samplers, like
+/// [`wgpu::FilterMode::Nearest`] on [`wgpu`], and in the software rasterizer. The mode is passed
+/// to the renderer separately for every draw, so the same image can be drawn both ways in one
+/// frame, and `theo` only swaps the image for a smaller mipmap of it in bilinear draws. This
+/// keeps pixel art crisp when it is scaled up, as long as the destination rectangle lines up
+/// with whole pixels.
+///
+/// # Clipping
+///
+/// Shapes passed to [`clip`] are transformed by the current transform, like everything else
+/// that is drawn. Rectangles, including rounded rectangles without any rounding, stay
+/// rectangles as long as the transform doesn't rotate or skew them. The GPU backends clip to the
+/// first such rectangle with a scissor rectangle, which is much cheaper than the mask used for
+/// other shapes. Clipping again inside of a clip still uses a mask, even if both clips are
+/// rectangles. Other shapes are clipped with the nonzero rule on every backend, like [`fill`];
+/// use [`clip_even_odd`] for the even-odd rule.
+///
+/// # Blurred Rectangles
+///
+/// [`blurred_rect`] approximates a Gaussian blur with a standard deviation of `blur_radius`,
+/// which spreads the rectangle out by two and a half times the radius on every side. For solid
+/// colors, the blur is computed on the CPU and drawn as an image, so every backend draws the same
+/// shadow. The images are cached by the [`Display`], so a shadow is only blurred once for each
+/// size, radius and color, no matter where it is drawn. Gradient brushes are blurred by the
+/// backends, which use the same approximation. A radius of zero or less draws the rectangle
+/// without blurring it.
+///
+/// # Capturing Images
+///
+/// The rectangle passed to [`capture_image_area`] is always in device pixels, with the origin at
+/// the top-left corner of the surface. The current transform is not applied to it. The rectangle
+/// is rounded to whole pixels, and must lie within the surface. The desktop OpenGL backend reads
+/// the pixels back itself to make sure of this, since OpenGL starts at the bottom-left corner.
+///
 /// [`RenderContext`]: https://docs.rs/piet/0.6.2/piet/trait.RenderContext.html
+/// [`blurred_rect`]: piet::RenderContext::blurred_rect
+/// [`capture_image_area`]: piet::RenderContext::capture_image_area
+/// [`clip`]: piet::RenderContext::clip
+/// [`clip_even_odd`]: RenderContext::clip_even_odd
+/// [`fill`]: piet::RenderContext::fill
+/// [`fill_even_odd`]: piet::RenderContext::fill_even_odd
+/// [`wgpu`]: https://crates.io/crates/wgpu
+/// [`wgpu::FilterMode::Nearest`]: https://docs.rs/wgpu/0.17/wgpu/enum.FilterMode.html#variant.Nearest
 pub struct RenderContext<'dsp, 'surf> {
     /// The dispatch used to draw to the surface.
     dispatch: Box<ContextDispatch<'dsp, 'surf>>,
@@ -774,354 +2088,4652 @@ pub struct RenderContext<'dsp, 'surf> {
     /// The mismatch error.
     mismatch: Result<(), Error>,
 
-    /// Whether we check for an existing context.
-    check_context: bool,
+    /// The gradient brushes cached by the display.
+    gradients: &'dsp mut GradientCache,
 
-    /// Ensure that the context is not sent to another thread.
-    _thread_unsafe: PhantomData<*mut ()>,
-}
+    /// The solid color brushes cached by the display.
+    solids: &'dsp mut SolidCache,
 
-impl<'dsp, 'surf> RenderContext<'dsp, 'surf> {
-    fn from_dispatch(dispatch: ContextDispatch<'dsp, 'surf>, check_context: bool) -> Self {
-        Self {
+    /// The faded and tiled copies of images cached by the display.
+    images: &'dsp mut ImageCache,
+
+    /// The images of blurred rectangles cached by the display.
+    blurs: &'dsp mut BlurCache,
+
+    /// Whether this context has exclusive access to the thread, and needs to unlock it.
+    check_context: bool,
+
+    /// The stack of opacities pushed by `push_opacity`.
+    opacity: Vec<Opacity>,
+
+    /// The stack of layers pushed by `push_layer`.
+    layers: Vec<Layer>,
+
+    /// The number of states currently saved with `save`.
+    save_depth: usize,
+
+    /// The bounds of the current clip in device space, or `None` if nothing is clipped.
+    clip_bounds: Option<Rect>,
+
+    /// Whether shapes with solid colors are drawn with antialiasing.
+    antialias: bool,
+
+    /// The alpha that every shape is multiplied by, set with `set_global_alpha`.
+    global_alpha: f64,
+
+    /// The parts of the states saved with `save` that are tracked here rather than by the
+    /// backends.
+    saved_states: Vec<SavedState>,
+
+    /// The color space that gradients are interpolated in.
+    gradient_interpolation: GradientInterpolation,
+
+    /// The background color of the surface.
+    background: Option<piet::Color>,
+
+    /// When the current frame started.
+    frame_start: Timestamp,
+
+    /// The number of drawing operations issued to the backend during the current frame.
+    draw_calls: u32,
+
+    /// Ensure that the context is not sent to another thread.
+    _thread_unsafe: PhantomData<*mut ()>,
+}
+
+/// The state saved by `save`, in addition to the state saved by the backends.
+struct SavedState {
+    /// The bounds of the clip.
+    clip_bounds: Option<Rect>,
+
+    /// Whether shapes with solid colors are drawn with antialiasing.
+    antialias: bool,
+
+    /// The alpha that every shape is multiplied by.
+    global_alpha: f64,
+}
+
+/// A layer pushed by `push_layer`.
+struct Layer {
+    /// The opacity to composite the layer with.
+    alpha: f64,
+
+    /// The blend mode to composite the layer with.
+    blend: BlendMode,
+
+    /// The save depth, including the state saved by the layer itself.
+    save_depth: usize,
+
+    /// The number of opacities pushed by `push_opacity` when the layer was pushed.
+    opacity_depth: usize,
+}
+
+/// An opacity pushed by `push_opacity`.
+struct Opacity {
+    /// The opacity that brushes are multiplied by, including the opacities below this one.
+    faded: f64,
+
+    /// The opacity to composite the offscreen layer with, if the content is drawn into one.
+    layer: Option<f64>,
+
+    /// The number of layers pushed by `push_layer` when the opacity was pushed.
+    layer_depth: usize,
+}
+
+impl<'dsp, 'surf> RenderContext<'dsp, 'surf> {
+    fn from_dispatch(
+        dispatch: ContextDispatch<'dsp, 'surf>,
+        gradients: &'dsp mut GradientCache,
+        solids: &'dsp mut SolidCache,
+        images: &'dsp mut ImageCache,
+        blurs: &'dsp mut BlurCache,
+        check_context: bool,
+        background: Option<piet::Color>,
+    ) -> Self {
+        Self {
             dispatch: Box::new(dispatch),
             mismatch: Ok(()),
+            gradients,
+            solids,
+            images,
+            blurs,
             check_context,
+            opacity: Vec::new(),
+            layers: Vec::new(),
+            save_depth: 0,
+            clip_bounds: None,
+            antialias: true,
+            global_alpha: 1.0,
+            saved_states: Vec::new(),
+            gradient_interpolation: GradientInterpolation::default(),
+            background,
+            frame_start: Timestamp::now(),
+            draw_calls: 0,
             _thread_unsafe: PhantomData,
         }
     }
-}
 
-impl fmt::Debug for RenderContext<'_, '_> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("RenderContext").finish_non_exhaustive()
+    /// Draw the following operations with the given opacity.
+    ///
+    /// Until the matching call to [`pop_opacity`], everything that is drawn is faded by
+    /// `alpha`. Calls can be nested, in which case the opacities are multiplied together.
+    /// `alpha` is clamped to the range `0.0..=1.0`.
+    ///
+    /// # Layers and the Fast Path
+    ///
+    /// There are two ways to fade the content. It can be drawn into an offscreen layer that is
+    /// composited with `alpha` when the opacity is popped, like [`push_layer`] does, or the
+    /// alpha of each brush, image and text can be multiplied by `alpha` as it is drawn. The
+    /// second way is much cheaper, but it is only exact when the content doesn't overlap itself:
+    /// where translucent shapes overlap, they appear more opaque than they would if the group
+    /// were faded as a whole.
+    ///
+    /// `theo` can't know what will be drawn before the opacity is popped, so it picks the way
+    /// when the opacity is pushed:
+    ///
+    /// - An `alpha` of `1.0` or `0.0` takes the cheap way, since it is exact for any content.
+    /// - Otherwise, the content is drawn into a layer on the backends that can render one. These
+    ///   are the software rasterizer and desktop OpenGL 3.0 or OpenGL ES 3.0, as described for
+    ///   [`push_layer`].
+    /// - The other backends, including `wgpu`, take the cheap way. Overlapping content has to be
+    ///   avoided there, or merged into a single shape before it is drawn.
+    ///
+    /// On the cheap way, faded gradient brushes are cached by the [`Display`], like the
+    /// gradients themselves. Images created with [`make_image`] are drawn from a faded copy of
+    /// their pixels, which is cached as well, so animating the opacity makes a new copy for
+    /// each step. Text, and images without a copy of their pixels, are drawn at full opacity.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use piet::kurbo::Rect;
+    /// use piet::RenderContext as _;
+    /// # let mut display = theo::Display::offscreen();
+    /// # let mut surface = display.make_offscreen_surface(4, 4).unwrap();
+    /// # let mut ctx = theo::RenderContext::new(&mut display, &mut surface, 4, 4).unwrap();
+    ///
+    /// ctx.push_opacity(0.5);
+    /// ctx.fill(Rect::new(0.0, 0.0, 4.0, 4.0), &piet::Color::WHITE);
+    /// ctx.pop_opacity().unwrap();
+    /// ctx.finish().unwrap();
+    /// ```
+    ///
+    /// [`pop_opacity`]: Self::pop_opacity
+    /// [`push_layer`]: Self::push_layer
+    /// [`make_image`]: piet::RenderContext::make_image
+    pub fn push_opacity(&mut self, alpha: f64) {
+        let alpha = if alpha.is_nan() {
+            1.0
+        } else {
+            alpha.clamp(0.0, 1.0)
+        };
+
+        let layer = if alpha > 0.0 && alpha < 1.0 {
+            match self.push_backend_layer(BlendMode::SourceOver) {
+                Ok(()) => Some(alpha),
+                Err(Error::NotSupported) => None,
+                Err(err) => {
+                    self.mismatch = Err(err);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        let current = self.current_opacity();
+        self.opacity.push(Opacity {
+            faded: if layer.is_some() {
+                current
+            } else {
+                current * alpha
+            },
+            layer,
+            layer_depth: self.layers.len(),
+        });
     }
-}
 
-impl Drop for RenderContext<'_, '_> {
-    fn drop(&mut self) {
-        if self.check_context {
-            // Unlock the thread.
-            HAS_CONTEXT
-                .try_with(|has_context| has_context.set(false))
-                .ok();
+    /// Stop drawing with the opacity pushed by the last call to [`push_opacity`].
+    ///
+    /// Returns an error if there is no opacity to pop, or if a layer pushed after the opacity
+    /// hasn't been popped yet.
+    ///
+    /// [`push_opacity`]: Self::push_opacity
+    pub fn pop_opacity(&mut self) -> Result<(), Error> {
+        match self.opacity.last() {
+            Some(opacity) if opacity.layer_depth == self.layers.len() => {}
+            _ => return Err(Error::StackUnbalance),
+        }
+
+        if let Some(alpha) = self.opacity.pop().unwrap().layer {
+            self.pop_backend_layer(alpha, BlendMode::SourceOver);
         }
+        Ok(())
     }
-}
 
-/// The brushes used to draw to a [`Surface`].
-///
-/// See the documentation for [`Brush`] for more information.
-///
-/// [`Brush`]: https://docs.rs/piet/0.6.2/piet/trait.RenderContext.html#associatedtype.Brush
-#[derive(Clone)]
-pub struct Brush {
-    dispatch: Rc<BrushDispatch>,
-    _thread_unsafe: PhantomData<*mut ()>,
-}
+    /// Get the opacity that brushes are currently multiplied by.
+    fn current_opacity(&self) -> f64 {
+        self.opacity.last().map_or(1.0, |opacity| opacity.faded)
+    }
 
-impl From<BrushDispatch> for Brush {
-    fn from(dispatch: BrushDispatch) -> Self {
-        Self {
-            dispatch: Rc::new(dispatch),
-            _thread_unsafe: PhantomData,
-        }
+    /// Set the color space that gradients created afterwards are interpolated in.
+    ///
+    /// The default, [`GradientInterpolation::Srgb`], matches the other [`piet`] backends. See
+    /// [`GradientInterpolation`] for the difference between the options. This doesn't affect
+    /// brushes that were already created.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use piet::kurbo::Rect;
+    /// use piet::{Color, FixedLinearGradient, GradientStop, RenderContext as _};
+    /// use theo::GradientInterpolation;
+    /// # let mut display = theo::Display::offscreen();
+    /// # let mut surface = display.make_offscreen_surface(64, 1).unwrap();
+    /// # let mut ctx = theo::RenderContext::new(&mut display, &mut surface, 64, 1).unwrap();
+    ///
+    /// ctx.set_gradient_interpolation(GradientInterpolation::LinearRgb);
+    /// let gradient = FixedLinearGradient {
+    ///     start: (0.0, 0.0).into(),
+    ///     end: (64.0, 0.0).into(),
+    ///     stops: vec![
+    ///         GradientStop { pos: 0.0, color: Color::RED },
+    ///         GradientStop { pos: 1.0, color: Color::GREEN },
+    ///     ],
+    /// };
+    /// let brush = ctx.gradient(gradient).unwrap();
+    /// ctx.fill(Rect::new(0.0, 0.0, 64.0, 1.0), &brush);
+    /// ```
+    ///
+    /// [`piet`]: https://crates.io/crates/piet
+    pub fn set_gradient_interpolation(&mut self, interpolation: GradientInterpolation) {
+        self.gradient_interpolation = interpolation;
     }
-}
 
-impl fmt::Debug for Brush {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("Brush").finish_non_exhaustive()
+    /// Set whether the following shapes are drawn with antialiased edges.
+    ///
+    /// Shapes are antialiased by default. Turning it off draws crisp edges that cover whole
+    /// pixels, which suits pixel grids, QR codes and pixel art drawn inside an otherwise smooth
+    /// scene. Like the transform and the clip, this is part of the state saved by [`save`] and
+    /// restored by [`restore`], so it can be turned off for a few shapes at a time.
+    ///
+    /// This applies to fills and strokes with solid colors. Rectangles that are aligned with the
+    /// pixel grid after the current transform are snapped to whole pixels and drawn as usual.
+    /// Other shapes are rasterized on the CPU without antialiasing, and drawn as an image. A pixel
+    /// is filled if its center is inside of the shape. Shapes drawn with gradients and images,
+    /// text, images and clips are still antialiased.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use piet::kurbo::Rect;
+    /// use piet::{Color, RenderContext as _};
+    /// # let mut display = theo::Display::offscreen();
+    /// # let mut surface = display.make_offscreen_surface(20, 20).unwrap();
+    /// # let mut ctx = theo::RenderContext::new(&mut display, &mut surface, 20, 20).unwrap();
+    ///
+    /// // A crisp square, with edges that snap to whole pixels.
+    /// ctx.save().unwrap();
+    /// ctx.set_antialias(false);
+    /// ctx.fill(Rect::new(0.4, 0.4, 9.6, 9.6), &Color::WHITE);
+    /// ctx.restore().unwrap();
+    /// ```
+    ///
+    /// [`save`]: piet::RenderContext::save
+    /// [`restore`]: piet::RenderContext::restore
+    pub fn set_antialias(&mut self, antialias: bool) {
+        self.antialias = antialias;
     }
-}
 
-/// The images used to draw to a [`Surface`].
-///
-/// See the documentation for [`Image`] for more information.
-///
-/// [`Image`]: https://docs.rs/piet/0.6.2/piet/trait.RenderContext.html#associatedtype.Image
-#[derive(Clone)]
-pub struct Image {
-    dispatch: Rc<ImageDispatch>,
-    _thread_unsafe: PhantomData<*mut ()>,
-}
+    /// Set the alpha that the following shapes are drawn with.
+    ///
+    /// Every shape drawn afterwards has the alpha of its brush multiplied by `alpha`, on top of
+    /// the alpha of the brush itself and any opacity pushed with [`push_opacity`]. This is useful
+    /// for fading a whole frame in or out. Like the transform and the clip, this is part of the
+    /// state saved by [`save`] and restored by [`restore`]. `alpha` is clamped to the range
+    /// `0.0..=1.0`, and the default is `1.0`.
+    ///
+    /// This applies to everything but [`clear`]: fills, strokes and blurred rectangles with any
+    /// brush, images and text. Images are faded from a copy of their pixels, like by
+    /// [`push_opacity`] on the backends that can't render offscreen. Text is drawn into an
+    /// offscreen layer on the backends that can render one, and at full opacity on the others.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use piet::kurbo::Rect;
+    /// use piet::{Color, RenderContext as _};
+    /// # let mut display = theo::Display::offscreen();
+    /// # let mut surface = display.make_offscreen_surface(2, 1).unwrap();
+    /// # let mut ctx = theo::RenderContext::new(&mut display, &mut surface, 2, 1).unwrap();
+    ///
+    /// ctx.save().unwrap();
+    /// ctx.set_global_alpha(0.5);
+    /// ctx.fill(Rect::new(0.0, 0.0, 1.0, 1.0), &Color::RED);
+    /// ctx.restore().unwrap();
+    /// ```
+    ///
+    /// [`push_opacity`]: Self::push_opacity
+    /// [`save`]: piet::RenderContext::save
+    /// [`restore`]: piet::RenderContext::restore
+    /// [`clear`]: piet::RenderContext::clear
+    pub fn set_global_alpha(&mut self, alpha: f64) {
+        self.global_alpha = if alpha.is_nan() {
+            1.0
+        } else {
+            alpha.clamp(0.0, 1.0)
+        };
+    }
 
-impl From<ImageDispatch> for Image {
-    fn from(dispatch: ImageDispatch) -> Self {
-        Self {
-            dispatch: Rc::new(dispatch),
-            _thread_unsafe: PhantomData,
+    /// Get the alpha that the following shapes are drawn with.
+    ///
+    /// See [`RenderContext::set_global_alpha`] for more information.
+    pub fn global_alpha(&self) -> f64 {
+        self.global_alpha
+    }
+
+    /// Multiply the alpha of a color by the global alpha.
+    fn fade_global(&self, color: piet::Color) -> piet::Color {
+        let (r, g, b, a) = color.as_rgba();
+        piet::Color::rgba(r, g, b, a * self.global_alpha)
+    }
+
+    /// Apply the global alpha to a brush that is about to be passed to the backend.
+    fn apply_global_alpha<'a>(&mut self, brush: Cow<'a, Brush>) -> Cow<'a, Brush> {
+        if self.global_alpha < 1.0 {
+            if let Some(brush) = brush.with_opacity(self, self.global_alpha) {
+                return Cow::Owned(brush);
+            }
         }
+
+        brush
     }
-}
 
-impl fmt::Debug for Image {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("Image").finish_non_exhaustive()
+    /// Clear a region to the background color of the surface.
+    ///
+    /// This clears `region`, or the whole surface if it is `None`, to the color set with
+    /// [`Surface::set_background`]. It does nothing if the surface has no background. Like
+    /// [`clear`], this ignores the current transform and clip.
+    ///
+    /// [`clear`]: piet::RenderContext::clear
+    pub fn clear_background(&mut self, region: impl Into<Option<Rect>>) {
+        use piet::RenderContext as _;
+
+        if let Some(color) = self.background {
+            self.clear(region, color);
+        }
     }
-}
 
-macro_rules! make_dispatch {
-    ($($(#[$meta:meta])* $name:ident (
-        $display:ty,
-        $surface:ty,
-        $ctx:ty,
-        $brush:ty,
-        $image:ty
-    )),* $(,)?) => {
-        enum DisplayDispatch {
-            $(
-                $(#[$meta])*
-                $name($display),
-            )*
+    /// Run `f` with drawing clipped to a shape.
+    ///
+    /// This saves the current state, applies [`clip`], runs `f` and then restores the state, so
+    /// the clip can't leak into anything drawn afterwards. Like [`with_save`], the state is
+    /// restored even if `f` returns an error, and the result of `f` is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use piet::kurbo::Rect;
+    /// use piet::{Color, RenderContext as _};
+    /// # let mut display = theo::Display::offscreen();
+    /// # let mut surface = display.make_offscreen_surface(4, 1).unwrap();
+    /// # let mut ctx = theo::RenderContext::new(&mut display, &mut surface, 4, 1).unwrap();
+    ///
+    /// ctx.with_clip(Rect::new(0.0, 0.0, 2.0, 1.0), |ctx| {
+    ///     ctx.fill(Rect::new(0.0, 0.0, 4.0, 1.0), &Color::RED);
+    ///     Ok(())
+    /// })
+    /// .unwrap();
+    /// ```
+    ///
+    /// [`clip`]: piet::RenderContext::clip
+    /// [`with_save`]: piet::RenderContext::with_save
+    pub fn with_clip<R>(
+        &mut self,
+        shape: impl Shape,
+        f: impl FnOnce(&mut Self) -> Result<R, Error>,
+    ) -> Result<R, Error> {
+        use piet::RenderContext as _;
+
+        self.save()?;
+        self.clip(shape);
+        let result = f(self);
+        self.restore()?;
+        result
+    }
+
+    /// Get the bounds of the current clip, in device space.
+    ///
+    /// This is the intersection of the bounding boxes of every shape passed to [`clip`] that
+    /// hasn't been undone by [`restore`], each transformed by the transform that was active when
+    /// it was clipped. Returns `None` if nothing is clipped. A clip that doesn't
+    /// overlap the previous ones leaves empty bounds.
+    ///
+    /// The backends don't expose their clip masks, so the bounds are tracked as the clips are
+    /// applied. They are conservative: for clips that aren't axis-aligned rectangles, like
+    /// circles or rotated rectangles, the bounds contain pixels that are clipped out, but they
+    /// never leave out pixels that can be drawn. This makes them suitable for skipping shapes
+    /// that are entirely outside of the clip.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use piet::kurbo::{Affine, Rect};
+    /// use piet::RenderContext as _;
+    /// # let mut display = theo::Display::offscreen();
+    /// # let mut surface = display.make_offscreen_surface(100, 100).unwrap();
+    /// # let mut ctx = theo::RenderContext::new(&mut display, &mut surface, 100, 100).unwrap();
+    /// assert_eq!(ctx.current_clip_bounds(), None);
+    ///
+    /// ctx.save().unwrap();
+    /// ctx.clip(Rect::new(10.0, 10.0, 60.0, 60.0));
+    /// ctx.transform(Affine::translate((20.0, 20.0)));
+    /// ctx.clip(Rect::new(0.0, 0.0, 80.0, 80.0));
+    /// assert_eq!(ctx.current_clip_bounds(), Some(Rect::new(20.0, 20.0, 60.0, 60.0)));
+    ///
+    /// ctx.restore().unwrap();
+    /// assert_eq!(ctx.current_clip_bounds(), None);
+    /// ```
+    ///
+    /// [`clip`]: piet::RenderContext::clip
+    /// [`restore`]: piet::RenderContext::restore
+    pub fn current_clip_bounds(&self) -> Option<Rect> {
+        self.clip_bounds
+    }
+
+    /// Check whether an error is pending, without clearing it.
+    ///
+    /// [`status`] reports the first error raised by the drawing operations since it was last
+    /// called, and clears it. This returns the same error, but keeps it, so that the next call
+    /// to [`status`] still reports it. This is useful for diagnostics, like a debug overlay,
+    /// that shouldn't swallow errors meant for the rest of the application.
+    ///
+    /// This needs mutable access because the backends only report their errors by clearing
+    /// them, so the error is moved into the context to be reported again later.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use piet::kurbo::Line;
+    /// use piet::{Color, RenderContext as _};
+    /// # let mut display = theo::Display::offscreen();
+    /// # let mut surface = display.make_offscreen_surface(1, 1).unwrap();
+    /// # let mut ctx = theo::RenderContext::new(&mut display, &mut surface, 1, 1).unwrap();
+    /// assert!(ctx.peek_status().is_ok());
+    ///
+    /// // Conic gradients can't be used for strokes.
+    /// let brush = ctx.conic_gradient((0.5, 0.5), 0.0, (Color::RED, Color::BLUE));
+    /// ctx.stroke(Line::new((0.0, 0.0), (1.0, 1.0)), &brush, 1.0);
+    ///
+    /// assert!(matches!(ctx.peek_status(), Err(piet::Error::NotSupported)));
+    /// assert!(matches!(ctx.peek_status(), Err(piet::Error::NotSupported)));
+    /// assert!(matches!(ctx.status(), Err(piet::Error::NotSupported)));
+    /// assert!(ctx.status().is_ok());
+    /// ```
+    ///
+    /// [`status`]: piet::RenderContext::status
+    pub fn peek_status(&mut self) -> Result<(), &Error> {
+        // The next call to `status` returns whatever is left in `mismatch`.
+        self.mismatch = piet::RenderContext::status(self);
+        self.mismatch.as_ref().map(|&()| ())
+    }
+
+    /// Save the current drawing state, and return a token that restores it.
+    ///
+    /// This works like [`save`], but the state is restored by passing the returned
+    /// [`SavePoint`] to [`restore_to`], which also restores any states saved after it. This
+    /// lets drawing helpers return to a known state even if the code they call leaves extra
+    /// states on the stack.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use piet::kurbo::Rect;
+    /// use piet::RenderContext as _;
+    /// # let mut display = theo::Display::offscreen();
+    /// # let mut surface = display.make_offscreen_surface(100, 100).unwrap();
+    /// # let mut ctx = theo::RenderContext::new(&mut display, &mut surface, 100, 100).unwrap();
+    ///
+    /// let point = ctx.save_point().unwrap();
+    /// ctx.clip(Rect::new(0.0, 0.0, 50.0, 50.0));
+    ///
+    /// // A mismatched `save` in some other code.
+    /// ctx.save().unwrap();
+    /// ctx.clip(Rect::new(0.0, 0.0, 10.0, 10.0));
+    ///
+    /// ctx.restore_to(point).unwrap();
+    /// assert_eq!(ctx.current_clip_bounds(), None);
+    /// assert!(matches!(ctx.restore(), Err(piet::Error::StackUnbalance)));
+    /// ```
+    ///
+    /// [`save`]: piet::RenderContext::save
+    /// [`restore_to`]: RenderContext::restore_to
+    pub fn save_point(&mut self) -> Result<SavePoint, Error> {
+        let depth = self.save_depth;
+        piet::RenderContext::save(self)?;
+        Ok(SavePoint { depth })
+    }
+
+    /// Restore the drawing state saved by [`save_point`].
+    ///
+    /// Every state saved since the [`SavePoint`] was created is restored as well. Returns
+    /// [`Error::StackUnbalance`] if the state was already restored, either by [`restore`] or by
+    /// an earlier save point.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use piet::RenderContext as _;
+    /// # let mut display = theo::Display::offscreen();
+    /// # let mut surface = display.make_offscreen_surface(1, 1).unwrap();
+    /// # let mut ctx = theo::RenderContext::new(&mut display, &mut surface, 1, 1).unwrap();
+    ///
+    /// let point = ctx.save_point().unwrap();
+    /// ctx.restore().unwrap();
+    /// assert!(matches!(ctx.restore_to(point), Err(piet::Error::StackUnbalance)));
+    /// ```
+    ///
+    /// [`save_point`]: RenderContext::save_point
+    /// [`restore`]: piet::RenderContext::restore
+    pub fn restore_to(&mut self, point: SavePoint) -> Result<(), Error> {
+        if self.save_depth <= point.depth {
+            return Err(Error::StackUnbalance);
         }
 
-        enum SurfaceDispatch {
-            $(
-                $(#[$meta])*
-                $name($surface),
-            )*
+        while self.save_depth > point.depth {
+            piet::RenderContext::restore(self)?;
+        }
+
+        Ok(())
+    }
+
+    /// Finish drawing, and return statistics about the frame.
+    ///
+    /// This does the same thing as [`finish`], but also measures how long the frame took and how
+    /// much was drawn in it, which helps to tell whether the time is spent in `theo` or in the
+    /// application. The frame starts when the context is created, and again after every call to
+    /// this method. See [`FrameStats`] for what is measured.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use piet::kurbo::Rect;
+    /// use piet::RenderContext as _;
+    /// # let mut display = theo::Display::offscreen();
+    /// # let mut surface = display.make_offscreen_surface(4, 4).unwrap();
+    /// # let mut ctx = theo::RenderContext::new(&mut display, &mut surface, 4, 4).unwrap();
+    ///
+    /// ctx.clear(None, piet::Color::BLACK);
+    /// ctx.fill(Rect::new(0.0, 0.0, 2.0, 2.0), &piet::Color::RED);
+    ///
+    /// let stats = ctx.finish_with_stats().unwrap();
+    /// assert_eq!(stats.draw_calls, 2);
+    /// assert!(stats.finish_time <= stats.frame_time);
+    /// ```
+    ///
+    /// [`finish`]: piet::RenderContext::finish
+    pub fn finish_with_stats(&mut self) -> Result<FrameStats, Error> {
+        let finish_start = Timestamp::now();
+        let result = piet::RenderContext::finish(self);
+
+        let finish_time = finish_start.elapsed();
+        let stats = FrameStats {
+            frame_time: self.frame_start.elapsed(),
+            finish_time,
+            draw_calls: self.draw_calls,
+        };
+        self.frame_start = Timestamp::now();
+        self.draw_calls = 0;
+
+        result.map(|()| stats)
+    }
+
+    /// Fill a shape, and return the error if it failed.
+    ///
+    /// [`fill`] reports its errors later, through [`status`], which makes it hard to tell which
+    /// of the drawing operations since the last call failed. This draws in the same way, but
+    /// returns the error right away. Errors from earlier operations are left for [`status`] to
+    /// report, and aren't returned here. The backends are asked for their errors after every
+    /// call, so this is meant for debugging and for operations that are likely to fail, rather
+    /// than for every shape in a frame.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use piet::kurbo::Rect;
+    /// use piet::{Color, RenderContext as _};
+    /// # let mut display = theo::Display::offscreen();
+    /// # let mut surface = display.make_offscreen_surface(1, 1).unwrap();
+    /// # let mut ctx = theo::RenderContext::new(&mut display, &mut surface, 1, 1).unwrap();
+    ///
+    /// let brush = ctx.conic_gradient((0.5, 0.5), 0.0, (Color::RED, Color::BLUE));
+    /// let rect = Rect::new(0.0, 0.0, 1.0, 1.0);
+    ///
+    /// // Conic gradients can fill shapes, but not stroke them.
+    /// assert!(ctx.try_fill(rect, &brush).is_ok());
+    /// let err = ctx.try_stroke(rect, &brush, 1.0).unwrap_err();
+    /// assert!(matches!(err, piet::Error::NotSupported));
+    ///
+    /// // The error was already returned, so it isn't reported again.
+    /// assert!(ctx.status().is_ok());
+    /// ```
+    ///
+    /// [`fill`]: piet::RenderContext::fill
+    /// [`status`]: piet::RenderContext::status
+    pub fn try_fill(
+        &mut self,
+        shape: impl Shape,
+        brush: &impl IntoBrush<Self>,
+    ) -> Result<(), Error> {
+        self.try_draw(|ctx| piet::RenderContext::fill(ctx, shape, brush))
+    }
+
+    /// Fill a shape using the even-odd rule, and return the error if it failed.
+    ///
+    /// See [`try_fill`] for more information.
+    ///
+    /// [`try_fill`]: RenderContext::try_fill
+    pub fn try_fill_even_odd(
+        &mut self,
+        shape: impl Shape,
+        brush: &impl IntoBrush<Self>,
+    ) -> Result<(), Error> {
+        self.try_draw(|ctx| piet::RenderContext::fill_even_odd(ctx, shape, brush))
+    }
+
+    /// Stroke a shape, and return the error if it failed.
+    ///
+    /// See [`try_fill`] for more information.
+    ///
+    /// [`try_fill`]: RenderContext::try_fill
+    pub fn try_stroke(
+        &mut self,
+        shape: impl Shape,
+        brush: &impl IntoBrush<Self>,
+        width: f64,
+    ) -> Result<(), Error> {
+        self.try_draw(|ctx| piet::RenderContext::stroke(ctx, shape, brush, width))
+    }
+
+    /// Stroke a shape with a style, and return the error if it failed.
+    ///
+    /// See [`try_fill`] for more information.
+    ///
+    /// [`try_fill`]: RenderContext::try_fill
+    pub fn try_stroke_styled(
+        &mut self,
+        shape: impl Shape,
+        brush: &impl IntoBrush<Self>,
+        width: f64,
+        style: &StrokeStyle,
+    ) -> Result<(), Error> {
+        self.try_draw(|ctx| piet::RenderContext::stroke_styled(ctx, shape, brush, width, style))
+    }
+
+    /// Clear an area, or the whole surface, and return the error if it failed.
+    ///
+    /// See [`try_fill`] for more information.
+    ///
+    /// [`try_fill`]: RenderContext::try_fill
+    pub fn try_clear(
+        &mut self,
+        region: impl Into<Option<Rect>>,
+        color: piet::Color,
+    ) -> Result<(), Error> {
+        self.try_draw(|ctx| piet::RenderContext::clear(ctx, region, color))
+    }
+
+    /// Draw a text layout, and return the error if it failed.
+    ///
+    /// See [`try_fill`] for more information.
+    ///
+    /// [`try_fill`]: RenderContext::try_fill
+    pub fn try_draw_text(
+        &mut self,
+        layout: &TextLayout,
+        pos: impl Into<Point>,
+    ) -> Result<(), Error> {
+        self.try_draw(|ctx| piet::RenderContext::draw_text(ctx, layout, pos))
+    }
+
+    /// Draw an image, and return the error if it failed.
+    ///
+    /// See [`try_fill`] for more information.
+    ///
+    /// [`try_fill`]: RenderContext::try_fill
+    pub fn try_draw_image(
+        &mut self,
+        image: &Image,
+        dst_rect: impl Into<Rect>,
+        interp: InterpolationMode,
+    ) -> Result<(), Error> {
+        self.try_draw(|ctx| piet::RenderContext::draw_image(ctx, image, dst_rect, interp))
+    }
+
+    /// Run a drawing operation, and return only the error that it raised.
+    fn try_draw(&mut self, f: impl FnOnce(&mut Self)) -> Result<(), Error> {
+        // Keep the errors from earlier operations for `status`.
+        let pending = piet::RenderContext::status(self);
+        f(self);
+        let result = piet::RenderContext::status(self);
+        self.mismatch = pending;
+        result
+    }
+
+    /// Clip the following operations to a shape, using the even-odd rule.
+    ///
+    /// This works like [`clip`], except that where the shape overlaps itself, only the areas
+    /// covered an odd number of times are kept. This is the `clip-rule="evenodd"` of SVG, while
+    /// [`clip`] uses the nonzero rule like [`fill`] does. Like [`clip`], the clip is intersected
+    /// with the current one and lasts until the matching [`restore`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use piet::kurbo::{Circle, Rect, Shape as _};
+    /// use piet::RenderContext as _;
+    /// # let mut display = theo::Display::offscreen();
+    /// # let mut surface = display.make_offscreen_surface(40, 40).unwrap();
+    /// # let mut ctx = theo::RenderContext::new(&mut display, &mut surface, 40, 40).unwrap();
+    ///
+    /// // A ring: the inner circle cuts a hole into the outer one.
+    /// let mut ring = Circle::new((20.0, 20.0), 18.0).to_path(0.1);
+    /// ring.extend(Circle::new((20.0, 20.0), 10.0).path_elements(0.1));
+    ///
+    /// ctx.save().unwrap();
+    /// ctx.clip_even_odd(&ring);
+    /// ctx.fill(Rect::new(0.0, 0.0, 40.0, 40.0), &piet::Color::WHITE);
+    /// ctx.restore().unwrap();
+    /// ctx.finish().unwrap();
+    /// ```
+    ///
+    /// [`clip`]: piet::RenderContext::clip
+    /// [`fill`]: piet::RenderContext::fill
+    /// [`restore`]: piet::RenderContext::restore
+    pub fn clip_even_odd(&mut self, shape: impl Shape) {
+        self.clip_with_rule(shape, FillRule::EvenOdd)
+    }
+
+    /// Fill a path directly from an iterator over its elements.
+    ///
+    /// This is the same as calling [`fill`] with a [`BezPath`] made from `elements`, but the
+    /// elements are passed straight to the renderer instead of being collected into a path first.
+    /// For very large paths, like polylines with hundreds of thousands of segments, this avoids
+    /// keeping a second copy of the path in memory while it is drawn.
+    ///
+    /// Some brushes need the bounding box of the path before it can be drawn. This includes
+    /// image brushes, conic gradients and gradients made with [`extended_gradient`]. The path is
+    /// also needed in full when antialiasing is turned off. In these cases, the elements are
+    /// collected into a [`BezPath`] anyway. Otherwise, each element is pulled from the iterator
+    /// when the renderer reaches it and isn't stored by `theo`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use piet::kurbo::{PathEl, Point};
+    /// # let mut display = theo::Display::offscreen();
+    /// # let mut surface = display.make_offscreen_surface(40, 40).unwrap();
+    /// # let mut ctx = theo::RenderContext::new(&mut display, &mut surface, 40, 40).unwrap();
+    ///
+    /// // A circle made of ten thousand line segments, generated as it is drawn.
+    /// let point = |i: usize| {
+    ///     let angle = std::f64::consts::TAU * i as f64 / 10_000.0;
+    ///     Point::new(20.0 + 15.0 * angle.cos(), 20.0 + 15.0 * angle.sin())
+    /// };
+    /// let elements = std::iter::once(PathEl::MoveTo(point(0)))
+    ///     .chain((1..10_000).map(|i| PathEl::LineTo(point(i))))
+    ///     .chain(std::iter::once(PathEl::ClosePath));
+    /// ctx.fill_path_elements(elements, &piet::Color::WHITE);
+    /// ```
+    ///
+    /// [`fill`]: piet::RenderContext::fill
+    /// [`extended_gradient`]: Self::extended_gradient
+    pub fn fill_path_elements(
+        &mut self,
+        elements: impl IntoIterator<Item = PathEl>,
+        brush: &impl IntoBrush<Self>,
+    ) {
+        let path = StreamedPath::new(elements.into_iter());
+        self.fill_streamed(&path, brush);
+    }
+
+    /// Fill a [`StreamedPath`], collecting it first for the brushes that need more than one pass.
+    fn fill_streamed<I: Iterator<Item = PathEl>>(
+        &mut self,
+        path: &StreamedPath<I>,
+        brush: &impl IntoBrush<Self>,
+    ) {
+        let multi_pass = {
+            let brush = brush.make_brush(self, || path.bounding_box());
+            !self.antialias
+                || matches!(
+                    brush.source,
+                    BrushSource::Image(..) | BrushSource::Conic(..)
+                )
+        };
+        if multi_pass {
+            path.path();
+        }
+
+        piet::RenderContext::fill(self, path, brush);
+
+        // The streamed elements are gone, so a backend that needs them again drew the wrong path.
+        if path.reused.get() {
+            self.mismatch = Err(Error::NotSupported);
+        }
+    }
+
+    /// Fill a compound path, with a fill rule for each of its subpaths.
+    ///
+    /// `rules[i]` is the rule for the `i`th subpath of `shape`. If there are more subpaths than
+    /// rules, the last rule is used for the rest, and if `rules` is empty, every subpath uses
+    /// [`FillRule::NonZero`]. This is meant for drawing vector formats that mix winding rules
+    /// within a single path.
+    ///
+    /// The shape is filled in a single pass, so there are no antialiasing seams where subpaths
+    /// touch, just like with a single call to [`fill`]. Subpaths that share a rule can cut holes
+    /// into each other, and the areas covered by the two rules are combined. If both rules are
+    /// used, each group of subpaths is cut into trapezoids that cover the same area first, so
+    /// that both can be filled with the nonzero rule.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use piet::kurbo::{Rect, Shape};
+    /// use theo::FillRule;
+    /// # let mut display = theo::Display::offscreen();
+    /// # let mut surface = display.make_offscreen_surface(40, 40).unwrap();
+    /// # let mut ctx = theo::RenderContext::new(&mut display, &mut surface, 40, 40).unwrap();
+    ///
+    /// // A square with a hole in the middle, next to a plain square.
+    /// let mut path = Rect::new(0.0, 0.0, 20.0, 20.0).to_path(0.1);
+    /// path.extend(Rect::new(5.0, 5.0, 15.0, 15.0).path_elements(0.1));
+    /// path.extend(Rect::new(20.0, 0.0, 40.0, 20.0).path_elements(0.1));
+    ///
+    /// let rules = [FillRule::EvenOdd, FillRule::EvenOdd, FillRule::NonZero];
+    /// ctx.fill_with_rules(&path, &piet::Color::WHITE, &rules);
+    /// ```
+    ///
+    /// [`fill`]: piet::RenderContext::fill
+    pub fn fill_with_rules(
+        &mut self,
+        shape: impl Shape,
+        brush: &impl IntoBrush<Self>,
+        rules: &[FillRule],
+    ) {
+        use piet::RenderContext as _;
+
+        let mut nonzero = BezPath::new();
+        let mut even_odd = BezPath::new();
+        let mut rule = rules.first().copied().unwrap_or_default();
+        let mut subpaths = 0;
+
+        let tolerance = device_tolerance(self.current_transform());
+        for el in shape.path_elements(tolerance) {
+            if let PathEl::MoveTo(_) = el {
+                rule = rules
+                    .get(subpaths)
+                    .or(rules.last())
+                    .copied()
+                    .unwrap_or_default();
+                subpaths += 1;
+            }
+
+            match rule {
+                FillRule::NonZero => nonzero.push(el),
+                FillRule::EvenOdd => even_odd.push(el),
+            }
+        }
+
+        match (
+            nonzero.elements().is_empty(),
+            even_odd.elements().is_empty(),
+        ) {
+            (true, true) => {}
+            (false, true) => self.fill(nonzero, brush),
+            (true, false) => self.fill_even_odd(even_odd, brush),
+            (false, false) => {
+                // The trapezoids all wind the same way, so neither group cuts into the other.
+                let mut path = to_trapezoids(&nonzero, FillRule::NonZero, tolerance);
+                path.extend(to_trapezoids(&even_odd, FillRule::EvenOdd, tolerance));
+                self.fill(path, brush);
+            }
+        }
+    }
+
+    /// Stroke a shape with a line that is one device pixel wide.
+    ///
+    /// Line widths passed to [`stroke`] are in user space, so they are scaled along with the
+    /// shape by the current transform. This draws a hairline that stays one pixel wide at any
+    /// scale, which is useful for grids, guides and selection outlines.
+    ///
+    /// For solid color brushes, the shape is transformed into device space and stroked there,
+    /// so the line is one pixel wide even if the transform scales each axis differently. Other
+    /// brushes are defined in user space, so for them the width is divided by the scale of the
+    /// current transform instead. This is exact for transforms that scale both axes equally.
+    /// Nothing is drawn if the current transform collapses shapes onto a line or a point.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use piet::kurbo::{Affine, Line};
+    /// use piet::{Color, RenderContext as _};
+    /// # let mut display = theo::Display::offscreen();
+    /// # let mut surface = display.make_offscreen_surface(20, 20).unwrap();
+    /// # let mut ctx = theo::RenderContext::new(&mut display, &mut surface, 20, 20).unwrap();
+    ///
+    /// // The line stays one pixel wide, even though the context is scaled down.
+    /// ctx.transform(Affine::scale(0.1));
+    /// ctx.stroke_hairline(Line::new((0.0, 105.0), (200.0, 105.0)), &Color::WHITE);
+    /// ```
+    ///
+    /// [`stroke`]: piet::RenderContext::stroke
+    pub fn stroke_hairline(&mut self, shape: impl Shape, brush: &impl IntoBrush<Self>) {
+        use piet::RenderContext as _;
+
+        let transform = self.current_transform();
+        let det = transform.determinant();
+        if det == 0.0 || !det.is_finite() {
+            return;
+        }
+
+        let brush = brush.make_brush(self, || shape.bounding_box());
+        if !matches!(brush.source, BrushSource::Solid(_)) {
+            self.stroke(shape, &*brush, det.abs().sqrt().recip());
+            return;
+        }
+
+        let mut path = shape.into_path(device_tolerance(transform));
+        path.apply_affine(transform);
+
+        if let Err(err) = self.save() {
+            self.mismatch = Err(err);
+            return;
+        }
+
+        self.transform(transform.inverse());
+        self.stroke(path, &*brush, 1.0);
+
+        if let Err(err) = self.restore() {
+            self.mismatch = Err(err);
+        }
+    }
+
+    /// Create an image from the contents of a [`wgpu`] texture.
+    ///
+    /// This is intended for textures that are written to by other `wgpu` code, like video frames
+    /// decoded on the GPU. The texture must have been created with the [`wgpu::Device`] returned
+    /// by [`Display::wgpu_device`], and must have the `COPY_SRC` usage. Its format must be one of
+    /// the 8-bit RGBA or BGRA formats, and its contents are treated as non-premultiplied colors.
+    ///
+    /// The contents of the texture are copied when this is called, so the image doesn't borrow
+    /// the texture. The texture can be written to or dropped as soon as this returns, without
+    /// affecting the image. piet-wgpu can only create images from pixel data, so the copy
+    /// currently goes through memory that the CPU can read, and this waits for the GPU to finish
+    /// its queued work.
+    ///
+    /// Returns [`Error::NotSupported`] on the other backends, for unsupported formats, and on the
+    /// web, where waiting for the GPU isn't possible. Returns [`Error::InvalidInput`] if the
+    /// texture can't be copied from.
+    ///
+    /// [`wgpu`]: https://crates.io/crates/wgpu
+    #[cfg(feature = "wgpu")]
+    #[allow(unreachable_patterns)]
+    pub fn image_from_texture(&mut self, texture: &wgpu::Texture) -> Result<Image, Error> {
+        match &mut *self.dispatch {
+            ContextDispatch::Wgpu(ctx) => {
+                check_image_size(
+                    texture.width() as usize,
+                    texture.height() as usize,
+                    ctx.max_texture_size(),
+                )?;
+                let img = ctx.image_from_texture(texture)?;
+                Ok(Image::new(
+                    ImageDispatch::Wgpu(img),
+                    ImageFormat::RgbaSeparate,
+                ))
+            }
+            _ => Err(Error::NotSupported),
+        }
+    }
+
+    /// Create an image with downsampled copies for drawing it at smaller sizes.
+    ///
+    /// This takes the same arguments as [`make_image`], and also stores copies of the image at
+    /// half, a quarter, and so on, down to a single pixel. When the image is drawn smaller than
+    /// half of its size with [`InterpolationMode::Bilinear`], the smallest copy that is still at
+    /// least as large as the destination is drawn instead. This avoids the aliasing and
+    /// shimmering of large images drawn as thumbnails or icons, which bilinear filtering alone
+    /// can't remove.
+    ///
+    /// The copies are computed by averaging blocks of pixels on the CPU, and are uploaded as
+    /// separate images, since the backends don't expose texture mipmaps. This takes a third more
+    /// memory than the image itself. Filtering between two copies isn't supported, so an
+    /// animated scale may visibly switch from one copy to the next.
+    /// [`InterpolationMode::NearestNeighbor`] always draws the full image.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use piet::kurbo::Rect;
+    /// use piet::{ImageFormat, InterpolationMode, RenderContext as _};
+    /// # let mut display = theo::Display::offscreen();
+    /// # let mut surface = display.make_offscreen_surface(16, 16).unwrap();
+    /// # let mut ctx = theo::RenderContext::new(&mut display, &mut surface, 16, 16).unwrap();
+    ///
+    /// let pixels = vec![128; 256 * 256];
+    /// let image = ctx
+    ///     .make_image_mipmapped(256, 256, &pixels, ImageFormat::Grayscale)
+    ///     .unwrap();
+    ///
+    /// // Draw the image as a thumbnail.
+    /// let rect = Rect::new(0.0, 0.0, 16.0, 16.0);
+    /// ctx.draw_image(&image, rect, InterpolationMode::Bilinear);
+    /// ```
+    ///
+    /// [`make_image`]: piet::RenderContext::make_image
+    pub fn make_image_mipmapped(
+        &mut self,
+        width: usize,
+        height: usize,
+        buf: &[u8],
+        format: ImageFormat,
+    ) -> Result<Image, Error> {
+        use piet::RenderContext as _;
+
+        let mut image = self.make_image(width, height, buf, format)?;
+
+        // Average premultiplied colors, so that transparent pixels don't darken their neighbors.
+        let mut pixels = premultiplied_rgba(buf, format)?;
+        let (mut width, mut height) = (width, height);
+        let mut mipmaps = Vec::new();
+
+        while width > 1 || height > 1 {
+            (pixels, width, height) = downsample(&pixels, width, height);
+            mipmaps.push(self.make_image(width, height, &pixels, ImageFormat::RgbaPremul)?);
+        }
+
+        image.mipmaps = mipmaps.into();
+        Ok(image)
+    }
+
+    /// Draw one of each kind of primitive, so the renderer is ready for the first frame.
+    ///
+    /// The GPU backends create their pipelines when a surface or the first [`RenderContext`] is
+    /// created, but drivers often wait until something is drawn to finish compiling them. The
+    /// text renderers also wait for the system fonts to load and create their glyph atlas the
+    /// first time text is drawn. All of this can stall the first frame of an animation.
+    ///
+    /// This draws a fill, a stroke, a gradient, an image, a blurred rectangle and some text, all
+    /// of them fully transparent, so that this work happens now instead. Call it while showing
+    /// a loading screen, then [`finish`] the frame as usual. Errors from the drawing operations
+    /// are reported by [`status`], like for any other drawing.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use piet::RenderContext as _;
+    /// # let mut display = theo::Display::offscreen();
+    /// # let mut surface = display.make_offscreen_surface(4, 4).unwrap();
+    /// # let mut ctx = theo::RenderContext::new(&mut display, &mut surface, 4, 4).unwrap();
+    ///
+    /// ctx.warm_up().unwrap();
+    /// ctx.finish().unwrap();
+    /// ```
+    ///
+    /// [`finish`]: piet::RenderContext::finish
+    /// [`status`]: piet::RenderContext::status
+    pub fn warm_up(&mut self) -> Result<(), Error> {
+        use piet::kurbo::Line;
+        use piet::{GradientStops as _, RenderContext as _, Text as _, TextLayoutBuilder as _};
+
+        let transparent = piet::Color::TRANSPARENT;
+        let rect = Rect::new(0.0, 0.0, 1.0, 1.0);
+
+        self.fill(rect, &transparent);
+        self.stroke(Line::new((0.0, 0.0), (1.0, 1.0)), &transparent, 1.0);
+        self.blurred_rect(rect, 1.0, &transparent);
+
+        let gradient = self.gradient(piet::FixedLinearGradient {
+            start: Point::ZERO,
+            end: Point::new(1.0, 0.0),
+            stops: (transparent, transparent).to_vec(),
+        })?;
+        self.fill(rect, &gradient);
+
+        let image = self.make_image(1, 1, &[0; 4], ImageFormat::RgbaSeparate)?;
+        self.draw_image(&image, rect, InterpolationMode::Bilinear);
+
+        let layout = self
+            .text()
+            .new_text_layout("Aa")
+            .text_color(transparent)
+            .build()?;
+        self.draw_text(&layout, Point::ZERO);
+
+        Ok(())
+    }
+
+    /// Create a brush that fills shapes with an image.
+    ///
+    /// The image is placed at its natural size with its top-left corner at the origin of the
+    /// current coordinate space, and `extend` decides what is drawn outside of it.
+    ///
+    /// Image brushes can fill shapes with either fill rule and stroke them. Using them with
+    /// [`blurred_rect`] draws nothing and makes [`status`] return [`Error::NotSupported`]. Like
+    /// other brushes, they can be created once and reused across frames.
+    ///
+    /// Images created with [`make_image`] keep a copy of their pixels, which is used to fade
+    /// them by [`push_opacity`] and [`set_global_alpha`], and to draw repeated tiles that blend
+    /// into each other without seams. The faded and tiled copies are cached by the [`Display`].
+    /// Images captured with [`capture_image_area`] have no such copy, so they are faded as a
+    /// layer like [`push_layer`] on the backends that support it and drawn at full opacity on the
+    /// others, and are only repeated up to a fixed number of times.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use piet::kurbo::Rect;
+    /// use piet::{ImageFormat, RenderContext as _};
+    /// use theo::ExtendMode;
+    /// # let mut display = theo::Display::offscreen();
+    /// # let mut surface = display.make_offscreen_surface(4, 4).unwrap();
+    /// # let mut ctx = theo::RenderContext::new(&mut display, &mut surface, 4, 4).unwrap();
+    ///
+    /// // A checkerboard tile, repeated across the surface.
+    /// let tile = ctx
+    ///     .make_image(2, 2, &[0, 255, 255, 0], ImageFormat::Grayscale)
+    ///     .unwrap();
+    /// let brush = ctx.image_brush(&tile, ExtendMode::Repeat);
+    /// ctx.fill(Rect::new(0.0, 0.0, 4.0, 4.0), &brush);
+    /// ```
+    ///
+    /// [`blurred_rect`]: piet::RenderContext::blurred_rect
+    /// [`status`]: piet::RenderContext::status
+    /// [`make_image`]: piet::RenderContext::make_image
+    /// [`push_opacity`]: Self::push_opacity
+    /// [`set_global_alpha`]: Self::set_global_alpha
+    /// [`capture_image_area`]: piet::RenderContext::capture_image_area
+    /// [`push_layer`]: Self::push_layer
+    pub fn image_brush(&mut self, image: &Image, extend: ExtendMode) -> Brush {
+        use piet::RenderContext as _;
+
+        let transparent = self.solid_brush(piet::Color::TRANSPARENT);
+        Brush {
+            source: BrushSource::Image(image.clone(), extend, 1.0),
+            ..transparent
+        }
+    }
+
+    /// Create a brush that sweeps a gradient around a point.
+    ///
+    /// The gradient starts at `start_angle`, in radians, and goes clockwise around `center`
+    /// for one full turn. A stop at position `0.0` is at `start_angle` and a stop at `1.0` is
+    /// back at `start_angle` again, so the first and last stops should usually match to avoid
+    /// a hard edge. Colors are interpolated in sRGB.
+    ///
+    /// piet's gradients are only linear or radial, so none of the backends can draw this
+    /// natively. Instead, shapes filled with this brush are covered with a fan of wedges,
+    /// each with a solid color from the gradient. Like [`image_brush`], the brush can only be
+    /// used with [`fill`] and [`fill_even_odd`]. Gradients with translucent stops may show faint
+    /// seams between the wedges near the center.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use piet::kurbo::Rect;
+    /// use piet::{Color, GradientStop, RenderContext as _};
+    /// # let mut display = theo::Display::offscreen();
+    /// # let mut surface = display.make_offscreen_surface(4, 4).unwrap();
+    /// # let mut ctx = theo::RenderContext::new(&mut display, &mut surface, 4, 4).unwrap();
+    ///
+    /// // A color wheel around the center of the surface.
+    /// let stops = vec![
+    ///     GradientStop { pos: 0.0, color: Color::RED },
+    ///     GradientStop { pos: 0.5, color: Color::BLUE },
+    ///     GradientStop { pos: 1.0, color: Color::RED },
+    /// ];
+    /// let brush = ctx.conic_gradient((2.0, 2.0), 0.0, stops);
+    /// ctx.fill(Rect::new(0.0, 0.0, 4.0, 4.0), &brush);
+    /// ```
+    ///
+    /// [`image_brush`]: RenderContext::image_brush
+    /// [`fill`]: piet::RenderContext::fill
+    /// [`fill_even_odd`]: piet::RenderContext::fill_even_odd
+    pub fn conic_gradient(
+        &mut self,
+        center: impl Into<Point>,
+        start_angle: f64,
+        stops: impl piet::GradientStops,
+    ) -> Brush {
+        use piet::RenderContext as _;
+
+        let mut stops = stops.to_vec();
+        stops.sort_by(|a, b| a.pos.total_cmp(&b.pos));
+
+        let transparent = self.solid_brush(piet::Color::TRANSPARENT);
+        Brush {
+            source: BrushSource::Conic(Rc::new(ConicGradient {
+                center: center.into(),
+                start_angle,
+                stops,
+            })),
+            ..transparent
+        }
+    }
+
+    /// Create a gradient brush that repeats past the ends of the gradient.
+    ///
+    /// [`gradient`] extends the colors at the ends of a gradient across the rest of the shape,
+    /// which is the same as [`ExtendMode::Clamp`] here. [`ExtendMode::Repeat`] starts the
+    /// gradient over again past each end, and [`ExtendMode::Reflect`] runs it backwards every
+    /// other time, which is useful for stripes and bands. Linear gradients repeat along the line
+    /// from their start to their end, and radial gradients repeat outwards from their center.
+    ///
+    /// None of the backends can repeat gradients natively. Instead, each shape drawn with this
+    /// brush is drawn with a longer gradient that covers it with as many repetitions as it needs,
+    /// up to 256 on each side of the original gradient. Past that, the colors at the ends are
+    /// extended as usual. Radial gradients whose origin offset is outside of their circle are
+    /// always clamped.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use piet::kurbo::Rect;
+    /// use piet::{Color, FixedLinearGradient, GradientStop, RenderContext as _};
+    /// use theo::ExtendMode;
+    /// # let mut display = theo::Display::offscreen();
+    /// # let mut surface = display.make_offscreen_surface(8, 1).unwrap();
+    /// # let mut ctx = theo::RenderContext::new(&mut display, &mut surface, 8, 1).unwrap();
+    ///
+    /// // A gradient from red to blue, repeated every two pixels.
+    /// let gradient = FixedLinearGradient {
+    ///     start: (0.0, 0.0).into(),
+    ///     end: (2.0, 0.0).into(),
+    ///     stops: vec![
+    ///         GradientStop { pos: 0.0, color: Color::RED },
+    ///         GradientStop { pos: 1.0, color: Color::BLUE },
+    ///     ],
+    /// };
+    /// let brush = ctx.extended_gradient(gradient, ExtendMode::Repeat).unwrap();
+    /// ctx.fill(Rect::new(0.0, 0.0, 8.0, 1.0), &brush);
+    /// ```
+    ///
+    /// [`gradient`]: piet::RenderContext::gradient
+    pub fn extended_gradient(
+        &mut self,
+        gradient: impl Into<FixedGradient>,
+        extend: ExtendMode,
+    ) -> Result<Brush, Error> {
+        use piet::RenderContext as _;
+
+        let brush = self.gradient(gradient)?;
+        let gradient = match (&brush.source, extend) {
+            (_, ExtendMode::Clamp) => return Ok(brush),
+            (BrushSource::Gradient(gradient), _) => gradient.clone(),
+            _ => return Ok(brush),
+        };
+
+        Ok(Brush {
+            source: BrushSource::ExtendedGradient(gradient, extend),
+            ..brush
+        })
+    }
+
+    /// Draw part of an image with a transform that only applies to the image.
+    ///
+    /// `src_rect` is the area of the image to draw, in pixels. It is placed with its top-left
+    /// corner at the origin and its natural size, and then `transform` is applied on top of the
+    /// current transform. This makes it easy to draw rotated or skewed sprites, without changing
+    /// the transform of anything drawn afterwards.
+    ///
+    /// The current clip is kept as it is, since clips are fixed in place when they are set.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use piet::kurbo::{Affine, Rect};
+    /// use piet::{ImageFormat, InterpolationMode, RenderContext as _};
+    /// # let mut display = theo::Display::offscreen();
+    /// # let mut surface = display.make_offscreen_surface(1, 2).unwrap();
+    /// # let mut ctx = theo::RenderContext::new(&mut display, &mut surface, 1, 2).unwrap();
+    ///
+    /// let pixels = [255, 0, 0, 255, 0, 0, 255, 255];
+    /// let image = ctx.make_image(2, 1, &pixels, ImageFormat::RgbaSeparate).unwrap();
+    ///
+    /// // Stand the image up by rotating it a quarter turn clockwise.
+    /// let transform = Affine::translate((1.0, 0.0)) * Affine::rotate(std::f64::consts::FRAC_PI_2);
+    /// let src = Rect::new(0.0, 0.0, 2.0, 1.0);
+    /// ctx.draw_image_transformed(&image, src, transform, InterpolationMode::NearestNeighbor);
+    /// ```
+    pub fn draw_image_transformed(
+        &mut self,
+        image: &Image,
+        src_rect: impl Into<Rect>,
+        transform: Affine,
+        interp: InterpolationMode,
+    ) {
+        use piet::RenderContext as _;
+
+        let src_rect = src_rect.into();
+        if let Err(err) = self.save() {
+            self.mismatch = Err(err);
+            return;
+        }
+
+        self.transform(transform);
+        self.draw_image_area(
+            image,
+            src_rect,
+            Rect::from_origin_size(Point::ZERO, src_rect.size()),
+            interp,
+        );
+
+        if let Err(err) = self.restore() {
+            self.mismatch = Err(err);
+        }
+    }
+
+    /// Draw a text layout with a transform that only applies to the text.
+    ///
+    /// The layout is drawn with its origin at the origin, and then `transform` is applied on top
+    /// of the current transform. This is how rotated labels, like the title of a vertical axis,
+    /// or scaled text can be drawn without changing the transform of anything drawn afterwards.
+    ///
+    /// The current clip is kept as it is, since clips are fixed in place when they are set.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use piet::kurbo::Affine;
+    /// use piet::{RenderContext as _, Text as _, TextLayout as _, TextLayoutBuilder as _};
+    /// # let mut display = theo::Display::offscreen();
+    /// # let mut surface = display.make_offscreen_surface(100, 100).unwrap();
+    /// # let mut ctx = theo::RenderContext::new(&mut display, &mut surface, 100, 100).unwrap();
+    ///
+    /// let layout = ctx.text().new_text_layout("Axis").build().unwrap();
+    ///
+    /// // Draw the text bottom to top along the left edge.
+    /// let transform = Affine::translate((0.0, 90.0)) * Affine::rotate(-std::f64::consts::FRAC_PI_2);
+    /// ctx.draw_text_transformed(&layout, transform);
+    ///
+    /// assert_eq!(ctx.current_transform(), Affine::IDENTITY);
+    /// ctx.finish().unwrap();
+    /// ```
+    pub fn draw_text_transformed(&mut self, layout: &TextLayout, transform: Affine) {
+        use piet::RenderContext as _;
+
+        if let Err(err) = self.save() {
+            self.mismatch = Err(err);
+            return;
+        }
+
+        self.transform(transform);
+        self.draw_text(layout, Point::ZERO);
+
+        if let Err(err) = self.restore() {
+            self.mismatch = Err(err);
+        }
+    }
+
+    /// Fill a shape with an image brush.
+    ///
+    /// The shape is filled with `rule`, and the image is drawn with its pixels multiplied by
+    /// `alpha` and the global alpha.
+    fn fill_with_image(
+        &mut self,
+        shape: impl Shape,
+        rule: FillRule,
+        image: &Image,
+        extend: ExtendMode,
+        alpha: f64,
+    ) {
+        use piet::{Image as _, RenderContext as _};
+
+        let size = image.size();
+        if size.is_empty() {
+            return;
+        }
+
+        let alpha = alpha * self.global_alpha;
+        if alpha <= 0.0 {
+            return;
+        }
+
+        // Images without a copy of their pixels can only be faded as a layer.
+        if alpha < 1.0 && image.pixels.is_none() {
+            let global_alpha = std::mem::replace(&mut self.global_alpha, 1.0);
+            self.draw_with_alpha(alpha, |ctx| {
+                ctx.fill_with_image(shape, rule, image, extend, 1.0)
+            });
+            self.global_alpha = global_alpha;
+            return;
+        }
+
+        let area = self.visible_area(shape.bounding_box());
+        if let Err(err) = self.save() {
+            self.mismatch = Err(err);
+            return;
+        }
+
+        self.clip_with_rule(shape, rule);
+
+        match extend {
+            ExtendMode::Repeat | ExtendMode::Reflect => {
+                self.draw_image_tiles(area, image, extend, alpha)
+            }
+
+            ExtendMode::Clamp => {
+                let faded = match self.derived_image(image, alpha, None) {
+                    Some(Ok(faded)) => Cow::Owned(faded),
+                    Some(Err(err)) => {
+                        self.mismatch = Err(err);
+                        Cow::Borrowed(image)
+                    }
+                    None => Cow::Borrowed(image),
+                };
+
+                // Split the area into a grid of nine cells around the image. The cells on the
+                // sides stretch the pixels on the edges of the image.
+                let (width, height) = (size.width, size.height);
+                let columns = [
+                    (area.x0.min(0.0), 0.0, 0.0, 1.0),
+                    (0.0, width, 0.0, width),
+                    (width, area.x1.max(width), width - 1.0, width),
+                ];
+                let rows = [
+                    (area.y0.min(0.0), 0.0, 0.0, 1.0),
+                    (0.0, height, 0.0, height),
+                    (height, area.y1.max(height), height - 1.0, height),
+                ];
+
+                for (i, &(y0, y1, src_y0, src_y1)) in rows.iter().enumerate() {
+                    for (j, &(x0, x1, src_x0, src_x1)) in columns.iter().enumerate() {
+                        let dst = Rect::new(x0, y0, x1, y1);
+                        if dst.area() <= 0.0 {
+                            continue;
+                        }
+
+                        // Don't blend the edges into their neighbors when stretching them.
+                        let interp = if i == 1 && j == 1 {
+                            InterpolationMode::Bilinear
+                        } else {
+                            InterpolationMode::NearestNeighbor
+                        };
+                        let src = Rect::new(src_x0, src_y0, src_x1, src_y1);
+                        self.draw_image_unfaded(&faded, Some(src), dst, interp);
+                    }
+                }
+            }
+        }
+
+        if let Err(err) = self.restore() {
+            self.mismatch = Err(err);
+        }
+    }
+
+    /// Stroke a shape with an image brush.
+    ///
+    /// The outline of the stroke is computed here and filled with the image.
+    fn stroke_with_image(
+        &mut self,
+        shape: impl Shape,
+        width: f64,
+        style: &StrokeStyle,
+        image: &Image,
+        extend: ExtendMode,
+        alpha: f64,
+    ) {
+        use piet::RenderContext as _;
+
+        let transform = self.current_transform();
+        if let Some(outline) = stroke_outline(shape, width, style, transform) {
+            self.fill_with_image(outline, FillRule::NonZero, image, extend, alpha);
+        }
+    }
+
+    /// Cover `area` with repetitions of an image.
+    ///
+    /// Each tile is drawn from a copy of the image with a border of the pixels that wrap around
+    /// from the opposite edges, so that bilinear filtering blends the tiles into each other
+    /// instead of into transparency. If the image is small compared to `area`, each tile holds
+    /// several repetitions to keep the number of draws down.
+    fn draw_image_tiles(&mut self, area: Rect, image: &Image, extend: ExtendMode, alpha: f64) {
+        use piet::{Image as _, RenderContext as _};
+
+        // Reflected images repeat every two copies.
+        let size = image.size();
+        let period = match extend {
+            ExtendMode::Reflect => size * 2.0,
+            _ => size,
+        };
+        let columns =
+            (area.x0 / period.width).floor() as i64..(area.x1 / period.width).ceil() as i64;
+        let rows =
+            (area.y0 / period.height).floor() as i64..(area.y1 / period.height).ceil() as i64;
+        if columns.is_empty() || rows.is_empty() {
+            return;
+        }
+
+        let repeat = |range: &Range<i64>| {
+            let count = range.end.saturating_sub(range.start);
+            count.saturating_add(MAX_IMAGE_TILES - 1) / MAX_IMAGE_TILES
+        };
+        let (across, down) = (repeat(&columns), repeat(&rows));
+
+        let tiling = (extend, across as u32, down as u32);
+        let tile = match self.derived_image(image, alpha, Some(tiling)) {
+            Some(Ok(tile)) => tile,
+
+            // The image has no copy of its pixels, or the tiles are too large for the backend.
+            _ => {
+                self.draw_image_tiles_unpadded(columns, rows, image, extend);
+                return;
+            }
+        };
+
+        // Tiles that line up with the pixels are snapped to them, so that no seams show
+        // between them.
+        let [a, b, c, d, e, f] = self.current_transform().as_coeffs();
+        let aligned = b == 0.0 && c == 0.0 && a != 0.0 && d != 0.0;
+        let snap = |value: f64, scale: f64, offset: f64| {
+            if aligned {
+                ((value * scale + offset).round() - offset) / scale
+            } else {
+                value
+            }
+        };
+
+        let step = Size::new(period.width * across as f64, period.height * down as f64);
+        let src = Rect::from_origin_size((1.0, 1.0), step);
+        for row in rows.step_by(down as usize) {
+            let y = row as f64 * period.height;
+            let (y0, y1) = (snap(y, d, f), snap(y + step.height, d, f));
+            for column in columns.clone().step_by(across as usize) {
+                let x = column as f64 * period.width;
+                let (x0, x1) = (snap(x, a, e), snap(x + step.width, a, e));
+                let dst = Rect::new(x0, y0, x1, y1);
+                self.draw_image_unfaded(&tile, Some(src), dst, InterpolationMode::Bilinear);
+            }
+        }
+    }
+
+    /// Cover a grid of periods with repetitions of an image, without making a tiled copy of it.
+    ///
+    /// Each copy of the image is drawn separately at full opacity, so this is limited to a fixed
+    /// number of copies.
+    fn draw_image_tiles_unpadded(
+        &mut self,
+        columns: Range<i64>,
+        rows: Range<i64>,
+        image: &Image,
+        extend: ExtendMode,
+    ) {
+        use piet::{Image as _, RenderContext as _};
+
+        let size = image.size();
+        let reflect = extend == ExtendMode::Reflect;
+        let (columns, rows) = if reflect {
+            let double =
+                |range: Range<i64>| range.start.saturating_mul(2)..range.end.saturating_mul(2);
+            (double(columns), double(rows))
+        } else {
+            (columns, rows)
+        };
+
+        let count = columns
+            .end
+            .saturating_sub(columns.start)
+            .saturating_mul(rows.end.saturating_sub(rows.start));
+        if count > MAX_IMAGE_TILES * MAX_IMAGE_TILES {
+            self.mismatch = Err(Error::NotSupported);
+            return;
+        }
+
+        for row in rows {
+            for column in columns.clone() {
+                let origin = Point::new(column as f64 * size.width, row as f64 * size.height);
+                let dst = Rect::from_origin_size(origin, size);
+                let flip_x = reflect && column.rem_euclid(2) == 1;
+                let flip_y = reflect && row.rem_euclid(2) == 1;
+                if !flip_x && !flip_y {
+                    self.draw_image_unfaded(image, None, dst, InterpolationMode::Bilinear);
+                    continue;
+                }
+
+                // Mirror the tile around its center.
+                let center = dst.center().to_vec2();
+                let scale = Affine::scale_non_uniform(
+                    if flip_x { -1.0 } else { 1.0 },
+                    if flip_y { -1.0 } else { 1.0 },
+                );
+                if let Err(err) = self.save() {
+                    self.mismatch = Err(err);
+                    continue;
+                }
+                self.transform(Affine::translate(center) * scale * Affine::translate(-center));
+                self.draw_image_unfaded(image, None, dst, InterpolationMode::Bilinear);
+                if let Err(err) = self.restore() {
+                    self.mismatch = Err(err);
+                }
+            }
+        }
+    }
+
+    /// Draw an image, or the `src_rect` part of it, with the current opacity and global alpha.
+    fn draw_image_faded(
+        &mut self,
+        image: &Image,
+        src_rect: Option<Rect>,
+        dst_rect: Rect,
+        interp: InterpolationMode,
+    ) {
+        let alpha = self.current_opacity() * self.global_alpha;
+        if alpha <= 0.0 {
+            return;
+        }
+
+        match self.derived_image(image, alpha, None) {
+            Some(Ok(faded)) => self.draw_image_unfaded(&faded, src_rect, dst_rect, interp),
+            Some(Err(err)) => self.mismatch = Err(err),
+            None => self.draw_with_alpha(alpha, |ctx| {
+                ctx.draw_image_unfaded(image, src_rect, dst_rect, interp)
+            }),
+        }
+    }
+
+    /// Run `draw`, which can't fade what it draws by itself, with the current opacity and
+    /// global alpha.
+    fn draw_faded(&mut self, draw: impl FnOnce(&mut Self)) {
+        let alpha = self.current_opacity() * self.global_alpha;
+        self.draw_with_alpha(alpha, draw);
+    }
+
+    /// Run `draw`, which can't fade what it draws by itself, with its drawing faded by `alpha`.
+    ///
+    /// If the backend can render offscreen, `draw` is run in a layer that is composited with
+    /// `alpha`. Otherwise, what it draws is left at full opacity.
+    fn draw_with_alpha(&mut self, alpha: f64, draw: impl FnOnce(&mut Self)) {
+        if alpha >= 1.0 {
+            draw(self);
+            return;
+        }
+        if alpha <= 0.0 {
+            return;
+        }
+
+        match self.push_backend_layer(BlendMode::SourceOver) {
+            Ok(()) => {
+                draw(self);
+                self.pop_backend_layer(alpha, BlendMode::SourceOver);
+            }
+            Err(Error::NotSupported) => draw(self),
+            Err(err) => self.mismatch = Err(err),
+        }
+    }
+
+    /// Get a copy of an image with its pixels multiplied by `alpha`, and tiled if `tiling` is
+    /// set.
+    ///
+    /// Tiled copies repeat the image the given number of times across and down, and have a
+    /// border of one pixel that wraps around to the opposite edge.
+    ///
+    /// Returns `None` if the image has no copy of its pixels. If nothing needs to change, the
+    /// image itself is returned.
+    fn derived_image(
+        &mut self,
+        image: &Image,
+        alpha: f64,
+        tiling: Option<(ExtendMode, u32, u32)>,
+    ) -> Option<Result<Image, Error>> {
+        use piet::RenderContext as _;
+
+        let alpha = (alpha.clamp(0.0, 1.0) * 255.0).round() as u8;
+        if alpha == 255 && tiling.is_none() {
+            return Some(Ok(image.clone()));
+        }
+
+        let pixels = image.pixels.clone()?;
+        let derivation = Derivation { alpha, tiling };
+        let key = self.cache_key();
+
+        if let Some(image) = self.images.get(key, &pixels, derivation) {
+            return Some(Ok(image));
+        }
+
+        let (width, height, data) = derive_pixels(&pixels, derivation);
+        let result = self.make_image(width, height, &data, ImageFormat::RgbaPremul);
+        Some(result.map(|mut image| {
+            // The copy is never derived from itself, so it doesn't need its own pixels.
+            image.pixels = None;
+            self.images.insert(key, &pixels, derivation, image.clone());
+            image
+        }))
+    }
+
+    /// Get the part of `rect` that isn't clipped, in user space.
+    fn visible_area(&self, rect: Rect) -> Rect {
+        use piet::RenderContext as _;
+
+        let transform = self.current_transform();
+        let det = transform.determinant();
+        match self.clip_bounds {
+            Some(clip) if det != 0.0 && det.is_finite() => {
+                rect.intersect(transform.inverse().transform_rect_bbox(clip))
+            }
+            _ => rect,
+        }
+    }
+
+    /// Fill a shape with a conic gradient brush.
+    fn fill_with_conic(&mut self, shape: impl Shape, rule: FillRule, conic: &ConicGradient) {
+        use piet::RenderContext as _;
+        use std::f64::consts::TAU;
+
+        /// The number of wedges to split a full turn into.
+        const WEDGES: usize = 256;
+
+        let bbox = shape.bounding_box();
+        let center = conic.center;
+        let radius = [
+            Point::new(bbox.x0, bbox.y0),
+            Point::new(bbox.x1, bbox.y0),
+            Point::new(bbox.x0, bbox.y1),
+            Point::new(bbox.x1, bbox.y1),
+        ]
+        .iter()
+        .map(|corner| corner.distance(center))
+        .fold(0.0, f64::max);
+
+        if radius <= 0.0 {
+            return;
+        }
+
+        let step = TAU / WEDGES as f64;
+
+        if let Err(err) = self.save() {
+            self.mismatch = Err(err);
+            return;
+        }
+        self.clip_with_rule(shape, rule);
+
+        // Thin wedges only partially cover the pixels near the center, which leaves gaps once
+        // they are antialiased. If the gradient is opaque, each wedge reaches a quarter turn
+        // ahead and is painted over by the ones after it, so only its leading edge is blended.
+        // Translucent wedges can't be painted over, so they only cover their own slice.
+        let opaque = conic.stops.iter().all(|stop| stop.color.as_rgba().3 >= 1.0);
+
+        for i in 0..WEDGES {
+            let color = conic.color_at((i as f64 + 0.5) / WEDGES as f64);
+            let start = i as f64 * step;
+
+            // The last wedges go a little past the end to cover the edge of the first one.
+            let span = if opaque {
+                (TAU + step / 2.0 - start).min(TAU / 4.0)
+            } else {
+                step
+            };
+
+            // Push the outer edge out far enough that the flat sides cover the circle.
+            let pieces = (span / (TAU / 16.0)).ceil();
+            let outer = radius / (span / pieces / 2.0).cos() + 1.0;
+
+            let mut wedge = BezPath::new();
+            wedge.move_to(center);
+            for j in 0..=pieces as usize {
+                let angle = conic.start_angle + start + span * j as f64 / pieces;
+                wedge.line_to(center + Vec2::from_angle(angle) * outer);
+            }
+            wedge.close_path();
+
+            self.fill(wedge, &color);
+        }
+
+        if let Err(err) = self.restore() {
+            self.mismatch = Err(err);
+        }
+    }
+
+    /// Clip to the area that filling a shape with `rule` would cover.
+    fn clip_with_rule(&mut self, shape: impl Shape, rule: FillRule) {
+        use piet::RenderContext as _;
+
+        // The backends clip in device space, so the transform is applied here.
+        let transform = self.current_transform();
+        let [_, b, c, _, _, _] = transform.as_coeffs();
+        let rect = shape.as_rect().or_else(|| {
+            shape
+                .as_rounded_rect()
+                .filter(|rect| rect.radii().as_single_radius() == Some(0.0))
+                .map(|rect| rect.rect())
+        });
+
+        let bounds = match rect {
+            // Keep axis-aligned rectangles as rectangles, so the GPU backends can use a scissor
+            // rectangle instead of a mask.
+            Some(rect) if b == 0.0 && c == 0.0 => {
+                let rect = transform.transform_rect_bbox(rect);
+                self.clip_device(rect);
+                rect
+            }
+
+            // The backends' clip masks don't agree on a fill rule, so the path is cut into
+            // trapezoids that cover the same area with either of them.
+            _ => {
+                let tolerance = device_tolerance(transform);
+                let mut path = to_trapezoids(&shape.into_path(tolerance), rule, tolerance);
+                path.apply_affine(transform);
+                let bounds = path.bounding_box();
+                self.clip_device(path);
+                bounds
+            }
+        };
+
+        self.clip_bounds = Some(match self.clip_bounds {
+            Some(clip) => clip.intersect(bounds),
+            None => bounds,
+        });
+    }
+
+    /// Fill or stroke a shape with a solid color, without antialiasing.
+    fn draw_aliased(&mut self, shape: impl Shape, color: piet::Color, draw: AliasedDraw<'_>) {
+        use piet::RenderContext as _;
+
+        let transform = self.current_transform();
+        let det = transform.determinant();
+        if det == 0.0 || !det.is_finite() {
+            return;
+        }
+
+        // Rectangles that stay aligned with the pixels are drawn by the backend at whole pixels.
+        let [_, b, c, _, _, _] = transform.as_coeffs();
+        if let (AliasedDraw::Fill(_), Some(rect), true) =
+            (&draw, shape.as_rect(), b == 0.0 && c == 0.0)
+        {
+            let rect = transform.transform_rect_bbox(rect).round();
+            self.draw_device_space(|ctx| {
+                if rect.area() > 0.0 {
+                    ctx.fill(rect, &color);
+                }
+            });
+            return;
+        }
+
+        // Rasterize the shape in device space with tiny-skia, which can turn antialiasing off.
+        let Some(path) = skia_path(&shape.into_path(device_tolerance(transform))) else {
+            return;
+        };
+        let (path, rule) = match draw {
+            AliasedDraw::Fill(FillRule::NonZero) => (Some(path), tiny_skia::FillRule::Winding),
+            AliasedDraw::Fill(FillRule::EvenOdd) => (Some(path), tiny_skia::FillRule::EvenOdd),
+            AliasedDraw::Stroke(width, style) => {
+                let stroke = skia_stroke(width, style);
+                (
+                    path.stroke(&stroke, det.abs().sqrt() as f32),
+                    tiny_skia::FillRule::Winding,
+                )
+            }
+        };
+        let Some(path) = path.and_then(|path| path.transform(skia_transform(transform))) else {
+            return;
+        };
+
+        let bounds = path.bounds();
+        let mut rect = Rect::new(
+            bounds.left().into(),
+            bounds.top().into(),
+            bounds.right().into(),
+            bounds.bottom().into(),
+        );
+        if let Some(clip) = self.clip_bounds {
+            rect = rect.intersect(clip);
+        }
+        let rect = rect.expand();
+        let (width, height) = (rect.width() as u32, rect.height() as u32);
+        let Some(mut pixmap) = tiny_skia::Pixmap::new(width, height) else {
+            return;
+        };
+
+        let (r, g, b, a) = color.as_rgba();
+        let paint = tiny_skia::Paint {
+            shader: tiny_skia::Shader::SolidColor(
+                tiny_skia::Color::from_rgba(r as f32, g as f32, b as f32, a as f32)
+                    .unwrap_or(tiny_skia::Color::TRANSPARENT),
+            ),
+            anti_alias: false,
+            ..Default::default()
+        };
+        let offset = tiny_skia::Transform::from_translate(-rect.x0 as f32, -rect.y0 as f32);
+        pixmap.fill_path(&path, &paint, rule, offset, None);
+
+        let image = self.make_image(
+            width as usize,
+            height as usize,
+            pixmap.data(),
+            ImageFormat::RgbaPremul,
+        );
+        match image {
+            Ok(image) => self.draw_device_space(|ctx| {
+                ctx.draw_image_unfaded(&image, None, rect, InterpolationMode::NearestNeighbor)
+            }),
+            Err(err) => self.mismatch = Err(err),
+        }
+    }
+
+    /// Draw with the identity transform and antialiasing, then go back to the current state.
+    ///
+    /// The opacity and the global alpha are already applied to the colors drawn here, so they
+    /// are left out as well.
+    fn draw_device_space(&mut self, f: impl FnOnce(&mut Self)) {
+        use piet::RenderContext as _;
+
+        let transform = self.current_transform();
+        if let Err(err) = self.save() {
+            self.mismatch = Err(err);
+            return;
+        }
+
+        self.transform(transform.inverse());
+        self.antialias = true;
+        self.global_alpha = 1.0;
+        let opacity = mem::take(&mut self.opacity);
+        f(self);
+        self.opacity = opacity;
+
+        if let Err(err) = self.restore() {
+            self.mismatch = Err(err);
+        }
+    }
+
+    /// Draw a blurred rectangle with a solid color.
+    ///
+    /// The blur is computed here and drawn as an image, so that every backend draws the same
+    /// pixels. The images are cached by their size, radius and color, so shadows that are drawn
+    /// every frame are only blurred once.
+    fn blurred_rect_solid(&mut self, rect: Rect, blur_radius: f64, color: piet::Color) {
+        use piet::RenderContext as _;
+
+        let rect = rect.abs();
+        if blur_radius.is_nan() || blur_radius <= 0.0 {
+            self.fill(rect, &color);
+            return;
+        }
+
+        let color = self.fade_global(color);
+        let key = self.cache_key();
+        let size = rect.size();
+        let blur = match self.blurs.get(key, size, blur_radius, color) {
+            Some(blur) => blur,
+            None => {
+                // The blur is sampled at the top-left corner of each pixel, so move the
+                // rectangle by half a pixel to sample it at the centers instead.
+                let local = Rect::from_origin_size((-0.5, -0.5), size);
+
+                let mask_size = piet::util::size_for_blurred_rect(local, blur_radius);
+                let (width, height) = (mask_size.width as usize, mask_size.height as usize);
+                if width == 0 || height == 0 {
+                    return;
+                }
+
+                let mut mask = vec![0; width * height];
+                let rect_exp =
+                    piet::util::compute_blurred_rect(local, blur_radius, width, &mut mask);
+
+                let (r, g, b, a) = color.as_rgba8();
+                let pixels = mask
+                    .iter()
+                    .flat_map(|&coverage| {
+                        let alpha = (u16::from(coverage) * u16::from(a) + 127) / 255;
+                        [r, g, b, alpha as u8]
+                    })
+                    .collect::<Vec<_>>();
+
+                let mut image =
+                    match self.make_image(width, height, &pixels, ImageFormat::RgbaSeparate) {
+                        Ok(image) => image,
+                        Err(err) => {
+                            self.mismatch = Err(err);
+                            return;
+                        }
+                    };
+                image.pixels = None;
+
+                let blur = Blur {
+                    image,
+                    rect: rect_exp,
+                };
+                self.blurs
+                    .insert(key, size, blur_radius, color, blur.clone());
+                blur
+            }
+        };
+
+        // Rectangles that aren't on whole pixels reuse the same image, moved by a fraction of
+        // a pixel.
+        let rect = blur.rect + rect.origin().to_vec2();
+        self.draw_image_unfaded(&blur.image, None, rect, InterpolationMode::Bilinear);
+    }
+}
+
+impl fmt::Debug for RenderContext<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RenderContext").finish_non_exhaustive()
+    }
+}
+
+impl Drop for RenderContext<'_, '_> {
+    fn drop(&mut self) {
+        if self.check_context {
+            unlock_thread();
+        }
+    }
+}
+
+/// Convert pixels of any format to premultiplied RGBA pixels.
+fn premultiplied_rgba(data: &[u8], format: ImageFormat) -> Result<Vec<u8>, Error> {
+    let premultiply =
+        |channel: u8, alpha: u8| ((u16::from(channel) * u16::from(alpha) + 127) / 255) as u8;
+
+    let pixels = match format {
+        ImageFormat::Grayscale => data.iter().flat_map(|&l| [l, l, l, 255]).collect(),
+        ImageFormat::Rgb => data
+            .chunks_exact(3)
+            .flat_map(|pixel| [pixel[0], pixel[1], pixel[2], 255])
+            .collect(),
+        ImageFormat::RgbaSeparate => data
+            .chunks_exact(4)
+            .flat_map(|pixel| {
+                let alpha = pixel[3];
+                [
+                    premultiply(pixel[0], alpha),
+                    premultiply(pixel[1], alpha),
+                    premultiply(pixel[2], alpha),
+                    alpha,
+                ]
+            })
+            .collect(),
+        ImageFormat::RgbaPremul => data.to_vec(),
+        _ => return Err(Error::NotSupported),
+    };
+
+    Ok(pixels)
+}
+
+/// Make the pixels of an image derived from `pixels`.
+///
+/// Returns the width and height of the new image, followed by its premultiplied RGBA pixels.
+fn derive_pixels(pixels: &ImagePixels, derivation: Derivation) -> (usize, usize, Vec<u8>) {
+    let (width, height) = (pixels.width, pixels.height);
+    let alpha = u16::from(derivation.alpha);
+    let fade = |channel: u8| ((u16::from(channel) * alpha + 127) / 255) as u8;
+
+    let Some((extend, across, down)) = derivation.tiling else {
+        let data = pixels.data.iter().map(|&channel| fade(channel)).collect();
+        return (width, height, data);
+    };
+
+    // Map a position in the tiled image to the pixel that it shows.
+    let reflect = extend == ExtendMode::Reflect;
+    let source = |pos: usize, len: usize| {
+        let period = if reflect { len * 2 } else { len };
+        let pos = (pos as i64 - 1).rem_euclid(period as i64) as usize;
+        if pos >= len {
+            period - 1 - pos
+        } else {
+            pos
+        }
+    };
+
+    let period = |len: usize| if reflect { len * 2 } else { len };
+    let new_width = period(width) * across as usize + 2;
+    let new_height = period(height) * down as usize + 2;
+    let mut data = Vec::with_capacity(new_width * new_height * 4);
+    for y in 0..new_height {
+        let row = source(y, height) * width;
+        for x in 0..new_width {
+            let start = (row + source(x, width)) * 4;
+            data.extend(
+                pixels.data[start..start + 4]
+                    .iter()
+                    .map(|&channel| fade(channel)),
+            );
+        }
+    }
+
+    (new_width, new_height, data)
+}
+
+/// Halve the size of an image of RGBA pixels by averaging blocks of pixels.
+///
+/// Odd rows and columns are folded into the last block.
+fn downsample(data: &[u8], width: usize, height: usize) -> (Vec<u8>, usize, usize) {
+    let (new_width, new_height) = ((width / 2).max(1), (height / 2).max(1));
+    let mut pixels = Vec::with_capacity(new_width * new_height * 4);
+
+    let span = |i: usize, len: usize, new_len: usize| {
+        let start = i * len / new_len;
+        start..(i + 1) * len / new_len
+    };
+
+    for y in 0..new_height {
+        let rows = span(y, height, new_height);
+        for x in 0..new_width {
+            let columns = span(x, width, new_width);
+            let count = (rows.len() * columns.len()) as u32;
+
+            let mut sum = [0u32; 4];
+            for row in rows.clone() {
+                for column in columns.clone() {
+                    let offset = (row * width + column) * 4;
+                    for (total, &channel) in sum.iter_mut().zip(&data[offset..offset + 4]) {
+                        *total += u32::from(channel);
+                    }
+                }
+            }
+
+            pixels.extend(sum.map(|total| ((total + count / 2) / count) as u8));
+        }
+    }
+
+    (pixels, new_width, new_height)
+}
+
+/// Convert premultiplied RGBA pixels to straight RGBA pixels.
+fn demultiply(data: &[u8]) -> Vec<u8> {
+    data.chunks_exact(4)
+        .flat_map(|pixel| {
+            let alpha = u32::from(pixel[3]);
+            let unmultiply = |channel: u8| match alpha {
+                0 => 0,
+                _ => ((u32::from(channel) * 255 + alpha / 2) / alpha).min(255) as u8,
+            };
+
+            [
+                unmultiply(pixel[0]),
+                unmultiply(pixel[1]),
+                unmultiply(pixel[2]),
+                pixel[3],
+            ]
+        })
+        .collect()
+}
+
+/// A shape made from an iterator over path elements.
+///
+/// The elements are streamed the first time the path is iterated, without being stored. If
+/// anything else about the shape is needed first, like its bounding box, the elements are
+/// collected into a path instead. Streamed elements are gone, so using the shape again after it
+/// has been iterated gives an empty path and sets `reused`.
+struct StreamedPath<I> {
+    /// The elements that haven't been streamed or collected yet.
+    elements: Cell<Option<I>>,
+
+    /// The elements, collected into a path.
+    path: OnceCell<BezPath>,
+
+    /// Whether the shape was used again after its elements were streamed.
+    reused: Cell<bool>,
+}
+
+impl<I: Iterator<Item = PathEl>> StreamedPath<I> {
+    fn new(elements: I) -> Self {
+        Self {
+            elements: Cell::new(Some(elements)),
+            path: OnceCell::new(),
+            reused: Cell::new(false),
+        }
+    }
+
+    /// Get the elements collected into a path.
+    fn path(&self) -> &BezPath {
+        self.path.get_or_init(|| match self.elements.take() {
+            Some(elements) => elements.collect(),
+            None => {
+                self.reused.set(true);
+                BezPath::new()
+            }
+        })
+    }
+}
+
+impl<I: Iterator<Item = PathEl>> Shape for StreamedPath<I> {
+    type PathElementsIter<'iter>
+        = StreamedElements<'iter, I>
+    where
+        I: 'iter;
+
+    fn path_elements(&self, _tolerance: f64) -> Self::PathElementsIter<'_> {
+        if self.path.get().is_none() {
+            if let Some(elements) = self.elements.take() {
+                return StreamedElements::Streamed(elements);
+            }
+        }
+
+        StreamedElements::Collected(self.path().elements().iter())
+    }
+
+    fn area(&self) -> f64 {
+        self.path().area()
+    }
+
+    fn perimeter(&self, accuracy: f64) -> f64 {
+        self.path().perimeter(accuracy)
+    }
+
+    fn winding(&self, pt: Point) -> i32 {
+        self.path().winding(pt)
+    }
+
+    fn bounding_box(&self) -> Rect {
+        self.path().bounding_box()
+    }
+}
+
+/// The iterator over the elements of a [`StreamedPath`].
+enum StreamedElements<'a, I> {
+    /// The elements are coming from the original iterator.
+    Streamed(I),
+
+    /// The elements were collected into a path.
+    Collected(std::slice::Iter<'a, PathEl>),
+}
+
+impl<I: Iterator<Item = PathEl>> Iterator for StreamedElements<'_, I> {
+    type Item = PathEl;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Self::Streamed(elements) => elements.next(),
+            Self::Collected(elements) => elements.next().copied(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self {
+            Self::Streamed(elements) => elements.size_hint(),
+            Self::Collected(elements) => elements.size_hint(),
+        }
+    }
+}
+
+/// How to draw a shape without antialiasing.
+enum AliasedDraw<'a> {
+    /// Fill the shape with a fill rule.
+    Fill(FillRule),
+
+    /// Stroke the shape with a width and a style.
+    Stroke(f64, &'a StrokeStyle),
+}
+
+/// Convert a path to a `tiny-skia` path.
+fn skia_path(path: &BezPath) -> Option<tiny_skia::Path> {
+    let mut builder = tiny_skia::PathBuilder::new();
+    for element in path.elements() {
+        match *element {
+            PathEl::MoveTo(p) => builder.move_to(p.x as f32, p.y as f32),
+            PathEl::LineTo(p) => builder.line_to(p.x as f32, p.y as f32),
+            PathEl::QuadTo(p1, p2) => {
+                builder.quad_to(p1.x as f32, p1.y as f32, p2.x as f32, p2.y as f32)
+            }
+            PathEl::CurveTo(p1, p2, p3) => builder.cubic_to(
+                p1.x as f32,
+                p1.y as f32,
+                p2.x as f32,
+                p2.y as f32,
+                p3.x as f32,
+                p3.y as f32,
+            ),
+            PathEl::ClosePath => builder.close(),
+        }
+    }
+
+    builder.finish()
+}
+
+/// Convert a stroke width and style to a `tiny-skia` stroke.
+fn skia_stroke(width: f64, style: &StrokeStyle) -> tiny_skia::Stroke {
+    let (line_join, miter_limit) = match style.line_join {
+        LineJoin::Miter { limit } => (tiny_skia::LineJoin::Miter, limit as f32),
+        LineJoin::Round => (tiny_skia::LineJoin::Round, 4.0),
+        LineJoin::Bevel => (tiny_skia::LineJoin::Bevel, 4.0),
+    };
+    let line_cap = match style.line_cap {
+        LineCap::Butt => tiny_skia::LineCap::Butt,
+        LineCap::Round => tiny_skia::LineCap::Round,
+        LineCap::Square => tiny_skia::LineCap::Square,
+    };
+    let dash = tiny_skia::StrokeDash::new(
+        style.dash_pattern.iter().map(|&dash| dash as f32).collect(),
+        style.dash_offset as f32,
+    );
+
+    tiny_skia::Stroke {
+        width: width as f32,
+        miter_limit,
+        line_cap,
+        line_join,
+        dash,
+    }
+}
+
+/// Convert an affine transform to a `tiny-skia` transform.
+fn skia_transform(transform: Affine) -> tiny_skia::Transform {
+    let [a, b, c, d, e, f] = transform.as_coeffs();
+    tiny_skia::Transform::from_row(a as f32, b as f32, c as f32, d as f32, e as f32, f as f32)
+}
+
+/// Convert a `tiny-skia` path to a path.
+fn bez_path(path: &tiny_skia::Path) -> BezPath {
+    let point = |p: tiny_skia::Point| Point::new(p.x.into(), p.y.into());
+
+    let mut bez_path = BezPath::new();
+    for segment in path.segments() {
+        match segment {
+            tiny_skia::PathSegment::MoveTo(p) => bez_path.move_to(point(p)),
+            tiny_skia::PathSegment::LineTo(p) => bez_path.line_to(point(p)),
+            tiny_skia::PathSegment::QuadTo(p1, p2) => bez_path.quad_to(point(p1), point(p2)),
+            tiny_skia::PathSegment::CubicTo(p1, p2, p3) => {
+                bez_path.curve_to(point(p1), point(p2), point(p3))
+            }
+            tiny_skia::PathSegment::Close => bez_path.close_path(),
+        }
+    }
+
+    bez_path
+}
+
+/// Convert a path filled with `rule` to trapezoids that cover the same area.
+///
+/// The path is flattened and cut into horizontal slabs at every vertex and crossing, so no
+/// edges cross inside of a slab. The edges in a slab then bound trapezoids that are inside of
+/// the path. The trapezoids don't overlap and all wind the same way, so the result covers the
+/// same area with either rule.
+fn to_trapezoids(path: &BezPath, rule: FillRule, tolerance: f64) -> BezPath {
+    // Collect the edges, pointing down, along with the direction that they wind in.
+    let mut edges = Vec::new();
+    let mut add_edge = |from: Point, to: Point| {
+        if from.y < to.y {
+            edges.push((from, to, 1));
+        } else if from.y > to.y {
+            edges.push((to, from, -1));
+        }
+    };
+    let (mut start, mut last) = (Point::ZERO, Point::ZERO);
+    path.flatten(tolerance, |el| match el {
+        PathEl::MoveTo(p) => {
+            add_edge(last, start);
+            (start, last) = (p, p);
+        }
+        PathEl::LineTo(p) => {
+            add_edge(last, p);
+            last = p;
+        }
+        PathEl::ClosePath => {
+            add_edge(last, start);
+            last = start;
+        }
+        _ => {}
+    });
+    add_edge(last, start);
+
+    let x_at = |(top, bottom, _): (Point, Point, i32), y: f64| {
+        top.x + (bottom.x - top.x) * (y - top.y) / (bottom.y - top.y)
+    };
+    let inside = |winding: i32| match rule {
+        FillRule::NonZero => winding != 0,
+        FillRule::EvenOdd => winding % 2 != 0,
+    };
+
+    // Cut the slabs at the ends of every edge and wherever two edges cross.
+    let mut cuts = edges
+        .iter()
+        .flat_map(|(top, bottom, _)| [top.y, bottom.y])
+        .collect::<Vec<_>>();
+    for (i, &(a0, a1, _)) in edges.iter().enumerate() {
+        for &(b0, b1, _) in &edges[i + 1..] {
+            let (d1, d2) = (a1 - a0, b1 - b0);
+            let denom = d1.cross(d2);
+            if denom == 0.0 {
+                continue;
+            }
+
+            let t = (b0 - a0).cross(d2) / denom;
+            let u = (b0 - a0).cross(d1) / denom;
+            if (0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u) {
+                cuts.push(a0.y + d1.y * t);
+            }
+        }
+    }
+    cuts.sort_by(f64::total_cmp);
+    cuts.dedup();
+
+    let mut result = BezPath::new();
+    let mut active = Vec::new();
+    for slab in cuts.windows(2) {
+        let (y0, y1) = (slab[0], slab[1]);
+        let middle = (y0 + y1) / 2.0;
+
+        active.clear();
+        active.extend(
+            edges
+                .iter()
+                .filter(|(top, bottom, _)| top.y <= y0 && bottom.y >= y1)
+                .copied(),
+        );
+        active.sort_by(|&a, &b| x_at(a, middle).total_cmp(&x_at(b, middle)));
+
+        // Each trapezoid goes from an edge that enters the path to the next edge that leaves it.
+        let (mut winding, mut left) = (0, None);
+        for &edge in &active {
+            let was_inside = inside(winding);
+            winding += edge.2;
+            match (was_inside, inside(winding), left) {
+                (false, true, _) => left = Some(edge),
+                (true, false, Some(left)) => {
+                    result.move_to((x_at(left, y0), y0));
+                    result.line_to((x_at(edge, y0), y0));
+                    result.line_to((x_at(edge, y1), y1));
+                    result.line_to((x_at(left, y1), y1));
+                    result.close_path();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    result
+}
+
+/// Get the tolerance for converting a shape to a path before it is transformed into device space.
+///
+/// Curves are flattened finely enough for the largest scale of the transform.
+fn device_tolerance(transform: Affine) -> f64 {
+    let [a, b, c, d, _, _] = transform.as_coeffs();
+    let scale = a.hypot(b).max(c.hypot(d));
+    if scale > 0.0 && scale.is_finite() {
+        0.1 / scale
+    } else {
+        0.1
+    }
+}
+
+/// Make the stroke style behave the same way on every backend.
+///
+/// The tessellators disagree on miter limits that can never be reached, so those are drawn as
+/// bevels everywhere. They also convert the dash offset to single precision, so it is wrapped
+/// around to the length of the pattern first.
+fn normalize_stroke_style(style: &StrokeStyle) -> Cow<'_, StrokeStyle> {
+    let mut style = Cow::Borrowed(style);
+
+    if let LineJoin::Miter { limit } = style.line_join {
+        if limit < 1.0 || limit.is_nan() {
+            style.to_mut().line_join = LineJoin::Bevel;
+        }
+    }
+
+    // Odd patterns are repeated to make them even.
+    let pattern = &style.dash_pattern;
+    let mut period = pattern.iter().sum::<f64>();
+    if pattern.len() % 2 == 1 {
+        period *= 2.0;
+    }
+
+    let offset = style.dash_offset;
+    if pattern.iter().all(|&length| length >= 0.0)
+        && period.is_finite()
+        && period > 0.0
+        && !(0.0..period).contains(&offset)
+    {
+        style.to_mut().dash_offset = offset.rem_euclid(period);
+    }
+
+    style
+}
+
+/// Get the outline of a stroke, as a path to fill with the nonzero rule.
+///
+/// Returns `None` if the stroke has no outline, like when `transform` collapses it.
+fn stroke_outline(
+    shape: impl Shape,
+    width: f64,
+    style: &StrokeStyle,
+    transform: Affine,
+) -> Option<BezPath> {
+    let det = transform.determinant();
+    if det == 0.0 || !det.is_finite() {
+        return None;
+    }
+
+    // Curves are split more finely when they are scaled up.
+    let outline = skia_path(&shape.into_path(device_tolerance(transform)))?
+        .stroke(&skia_stroke(width, style), det.abs().sqrt() as f32)?;
+    Some(bez_path(&outline))
+}
+
+/// Split a shape into the dashes of a stroke style, in the coordinates of the shape.
+///
+/// Returns `None` if the style has no dashes. Patterns that can't be dashed, like those with
+/// negative lengths, leave the shape whole so that it is drawn as a solid line.
+fn dash_shape(shape: &impl Shape, style: &StrokeStyle, transform: Affine) -> Option<BezPath> {
+    if style.dash_pattern.is_empty() {
+        return None;
+    }
+
+    let path = shape.to_path(device_tolerance(transform));
+
+    // Odd patterns are repeated to make them even.
+    let mut pattern = style
+        .dash_pattern
+        .iter()
+        .map(|&length| length as f32)
+        .collect::<Vec<_>>();
+    if pattern.len() % 2 == 1 {
+        pattern.extend_from_within(..);
+    }
+    let Some(dash) = tiny_skia::StrokeDash::new(pattern, style.dash_offset as f32) else {
+        return Some(path);
+    };
+
+    // Curves are split more finely when they are scaled up.
+    let scale = transform.determinant().abs().sqrt() as f32;
+    let dashes = skia_path(&path).and_then(|path| path.dash(&dash, scale.max(1.0)));
+    Some(dashes.map_or_else(BezPath::new, |dashes| bez_path(&dashes)))
+}
+
+/// Convert premultiplied image data to straight alpha for backends that expect it.
+///
+/// The GPU renderers blend images with straight alpha, and upload premultiplied images without
+/// converting them, which darkens translucent edges.
+#[cfg(any(feature = "gl", feature = "wgpu"))]
+fn straight_alpha(buf: &[u8], format: ImageFormat) -> (Cow<'_, [u8]>, ImageFormat) {
+    match format {
+        ImageFormat::RgbaPremul => (Cow::Owned(demultiply(buf)), ImageFormat::RgbaSeparate),
+        format => (Cow::Borrowed(buf), format),
+    }
+}
+
+/// Add stops to a gradient so that interpolating it in sRGB approximates linear RGB.
+fn linearize_gradient(gradient: FixedGradient) -> FixedGradient {
+    /// The number of pieces to split the space between two stops into.
+    const STEPS: usize = 16;
+
+    fn to_linear(c: f64) -> f64 {
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    }
+
+    fn to_srgb(c: f64) -> f64 {
+        if c <= 0.0031308 {
+            c * 12.92
+        } else {
+            1.055 * c.powf(1.0 / 2.4) - 0.055
+        }
+    }
+
+    let expand = |stops: Vec<GradientStop>| {
+        let mut expanded = Vec::with_capacity(stops.len() * STEPS);
+
+        for pair in stops.windows(2) {
+            let (start, end) = (&pair[0], &pair[1]);
+            let (r0, g0, b0, a0) = start.color.as_rgba();
+            let (r1, g1, b1, a1) = end.color.as_rgba();
+
+            for i in 0..STEPS {
+                let t = i as f64 / STEPS as f64;
+                let mix =
+                    |c0: f64, c1: f64| to_srgb(to_linear(c0) + (to_linear(c1) - to_linear(c0)) * t);
+
+                expanded.push(GradientStop {
+                    pos: start.pos + (end.pos - start.pos) * t as f32,
+                    color: piet::Color::rgba(
+                        mix(r0, r1),
+                        mix(g0, g1),
+                        mix(b0, b1),
+                        a0 + (a1 - a0) * t,
+                    ),
+                });
+            }
+        }
+
+        expanded.extend(stops.last().cloned());
+        expanded
+    };
+
+    match gradient {
+        FixedGradient::Linear(mut linear) => {
+            linear.stops = expand(linear.stops);
+            FixedGradient::Linear(linear)
+        }
+        FixedGradient::Radial(mut radial) => {
+            radial.stops = expand(radial.stops);
+            FixedGradient::Radial(radial)
+        }
+    }
+}
+
+/// The most repetitions that an extended gradient is drawn with on either side of its ends.
+const MAX_GRADIENT_REPEATS: f64 = 256.0;
+
+/// The most tiles that an image brush is drawn with across or down a shape.
+const MAX_IMAGE_TILES: i64 = 64;
+
+/// Make a gradient whose repetitions cover `bbox`, or `None` if it can't be extended.
+fn extend_gradient(
+    gradient: &FixedGradient,
+    extend: ExtendMode,
+    bbox: Rect,
+) -> Option<FixedGradient> {
+    let corners = [
+        Point::new(bbox.x0, bbox.y0),
+        Point::new(bbox.x1, bbox.y0),
+        Point::new(bbox.x0, bbox.y1),
+        Point::new(bbox.x1, bbox.y1),
+    ];
+
+    match gradient {
+        FixedGradient::Linear(linear) => {
+            let axis = linear.end - linear.start;
+            let length = axis.hypot2();
+            if length <= 0.0 || !length.is_finite() {
+                return None;
+            }
+
+            // Find the positions along the gradient that the corners are at. An extra repetition
+            // is added on each side for strokes, which reach outside of the bounding box.
+            let positions = corners.map(|corner| (corner - linear.start).dot(axis) / length);
+            let first = positions.iter().copied().fold(f64::INFINITY, f64::min);
+            let last = positions.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+            let first = (first.floor() - 1.0).max(-MAX_GRADIENT_REPEATS);
+            let last = (last.ceil() + 1.0).min(MAX_GRADIENT_REPEATS + 1.0);
+
+            Some(FixedGradient::Linear(piet::FixedLinearGradient {
+                start: linear.start + axis * first,
+                end: linear.start + axis * last,
+                stops: repeat_stops(&linear.stops, extend, first as i64, last as i64)?,
+            }))
+        }
+
+        FixedGradient::Radial(radial) => {
+            // The gradient is made of circles growing from the focus to the outer circle. They
+            // only cover everything if the focus is inside of the outer circle.
+            let offset = radial.origin_offset.hypot();
+            if radial.radius <= 0.0 || offset >= radial.radius {
+                return None;
+            }
+
+            // Each circle reaches at most `radius - offset` further from the focus per repetition.
+            let focus = radial.center + radial.origin_offset;
+            let last = corners
+                .iter()
+                .map(|corner| (*corner - focus).hypot() / (radial.radius - offset))
+                .fold(0.0, f64::max);
+            let last = (last.ceil() + 1.0).min(MAX_GRADIENT_REPEATS + 1.0);
+
+            // Scale the outer circle around the focus.
+            Some(FixedGradient::Radial(piet::FixedRadialGradient {
+                center: focus - radial.origin_offset * last,
+                origin_offset: radial.origin_offset * last,
+                radius: radial.radius * last,
+                stops: repeat_stops(&radial.stops, extend, 0, last as i64)?,
+            }))
+        }
+    }
+}
+
+/// Repeat the stops of a gradient for each repetition from `first` to `last`.
+///
+/// The positions of the result are scaled so that it covers all of the repetitions.
+fn repeat_stops(
+    stops: &[GradientStop],
+    extend: ExtendMode,
+    first: i64,
+    last: i64,
+) -> Option<Vec<GradientStop>> {
+    // Sort the stops and make sure that they reach both ends, so that the colors past the first
+    // and last stops don't blend into the neighboring repetitions.
+    let mut period = stops.to_vec();
+    for stop in &mut period {
+        stop.pos = stop.pos.clamp(0.0, 1.0);
+    }
+    period.sort_by(|a, b| a.pos.total_cmp(&b.pos));
+    let (start, end) = (period.first()?.clone(), period.last()?.clone());
+    if start.pos > 0.0 {
+        period.insert(0, GradientStop { pos: 0.0, ..start });
+    }
+    if end.pos < 1.0 {
+        period.push(GradientStop { pos: 1.0, ..end });
+    }
+
+    let count = (last - first) as f32;
+    let mut repeated = Vec::with_capacity(period.len() * (last - first) as usize);
+    for index in first..last {
+        let offset = (index - first) as f32;
+        let mut push = |stop: &GradientStop, pos: f32| {
+            repeated.push(GradientStop {
+                pos: (offset + pos) / count,
+                color: stop.color,
+            });
+        };
+
+        if extend == ExtendMode::Reflect && index.rem_euclid(2) == 1 {
+            period
+                .iter()
+                .rev()
+                .for_each(|stop| push(stop, 1.0 - stop.pos));
+        } else {
+            period.iter().for_each(|stop| push(stop, stop.pos));
+        }
+    }
+
+    Some(repeated)
+}
+
+/// Make sure that an image can be created without overflowing.
+///
+/// Backends compute the size of the image's storage from its dimensions, which can overflow
+/// and panic for very large images.
+fn check_image_size(width: usize, height: usize, max_size: u32) -> Result<(), Error> {
+    let max_size = usize::try_from(max_size).unwrap_or(usize::MAX);
+    if width > max_size || height > max_size {
+        tracing::error!(
+            "Cannot create a {}x{} image, the maximum width and height is {}",
+            width,
+            height,
+            max_size
+        );
+        return Err(Error::InvalidInput);
+    }
+
+    // Images are stored with four bytes per pixel, and some backends count the bytes in a `u32`.
+    let bytes = width
+        .checked_mul(height)
+        .and_then(|pixels| pixels.checked_mul(4))
+        .filter(|&bytes| u32::try_from(bytes).is_ok());
+    if bytes.is_none() {
+        tracing::error!(
+            "Cannot create a {}x{} image, it is too large to be addressed",
+            width,
+            height
+        );
+        return Err(Error::InvalidInput);
+    }
+
+    Ok(())
+}
+
+/// The brushes used to draw to a [`Surface`].
+///
+/// See the documentation for [`Brush`] for more information.
+///
+/// Brushes are not tied to the [`RenderContext`] that created them, and can be kept around and
+/// reused in later frames. If a brush is used with a context from a different backend, it is
+/// recreated from its color or gradient for that context.
+///
+/// # Example
+///
+/// ```
+/// use piet::kurbo::Rect;
+/// use piet::RenderContext as _;
+/// use theo::{Display, RenderContext};
+///
+/// let mut display = Display::offscreen();
+/// let mut surface = display.make_offscreen_surface(8, 8).unwrap();
+///
+/// let mut brush = None;
+/// for _ in 0..2 {
+///     let mut ctx = RenderContext::new(&mut display, &mut surface, 8, 8).unwrap();
+///     let brush = brush.get_or_insert_with(|| ctx.solid_brush(piet::Color::RED));
+///     ctx.fill(Rect::new(0.0, 0.0, 8.0, 8.0), &*brush);
+///     ctx.finish().unwrap();
+/// }
+/// ```
+///
+/// [`Brush`]: https://docs.rs/piet/0.6.2/piet/trait.RenderContext.html#associatedtype.Brush
+#[derive(Clone)]
+pub struct Brush {
+    dispatch: Rc<BrushDispatch>,
+    source: BrushSource,
+    _thread_unsafe: PhantomData<*mut ()>,
+}
+
+/// The description used to create a [`Brush`].
+///
+/// This is kept around so that the brush can be recreated with a different opacity.
+#[derive(Clone)]
+enum BrushSource {
+    /// A solid color.
+    Solid(piet::Color),
+
+    /// A gradient.
+    Gradient(Rc<FixedGradient>),
+
+    /// An image, along with the alpha that it is drawn with.
+    ///
+    /// The backend brush for this is transparent, and the image is drawn separately.
+    Image(Image, ExtendMode, f64),
+
+    /// A gradient that repeats past its ends.
+    ///
+    /// The backend brush for this is the gradient clamped at its ends. When a shape is drawn,
+    /// it is replaced with a gradient whose repetitions cover the shape.
+    ExtendedGradient(Rc<FixedGradient>, ExtendMode),
+
+    /// A conic gradient.
+    ///
+    /// The backend brush for this is transparent, and the gradient is drawn separately.
+    Conic(Rc<ConicGradient>),
+}
+
+/// A gradient that sweeps around a center point.
+struct ConicGradient {
+    /// The point that the gradient sweeps around.
+    center: Point,
+
+    /// The angle where the gradient starts, in radians.
+    start_angle: f64,
+
+    /// The stops of the gradient, sorted by position.
+    stops: Vec<GradientStop>,
+}
+
+impl ConicGradient {
+    /// Get the color at `pos`, where `0.0` is the start and `1.0` is a full turn.
+    fn color_at(&self, pos: f64) -> piet::Color {
+        let stops = &self.stops;
+        let (first, last) = match (stops.first(), stops.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return piet::Color::TRANSPARENT,
+        };
+
+        if pos <= first.pos as f64 {
+            return first.color;
+        }
+
+        for pair in stops.windows(2) {
+            let (start, end) = (&pair[0], &pair[1]);
+            if pos <= end.pos as f64 {
+                let span = (end.pos - start.pos) as f64;
+                let t = if span > 0.0 {
+                    (pos - start.pos as f64) / span
+                } else {
+                    1.0
+                };
+
+                let (r0, g0, b0, a0) = start.color.as_rgba();
+                let (r1, g1, b1, a1) = end.color.as_rgba();
+                let mix = |c0: f64, c1: f64| c0 + (c1 - c0) * t;
+                return piet::Color::rgba(mix(r0, r1), mix(g0, g1), mix(b0, b1), mix(a0, a1));
+            }
+        }
+
+        last.color
+    }
+}
+
+impl Brush {
+    fn new(dispatch: BrushDispatch, source: BrushSource) -> Self {
+        Self {
+            dispatch: Rc::new(dispatch),
+            source,
+            _thread_unsafe: PhantomData,
+        }
+    }
+
+    /// Create a copy of this brush with its alpha multiplied by `alpha`.
+    fn with_opacity(&self, ctx: &mut RenderContext<'_, '_>, alpha: f64) -> Option<Self> {
+        use piet::RenderContext as _;
+
+        let fade = |color: piet::Color| {
+            let (r, g, b, a) = color.as_rgba();
+            piet::Color::rgba(r, g, b, a * alpha)
+        };
+
+        match &self.source {
+            BrushSource::Solid(color) => Some(ctx.solid_brush(fade(*color))),
+            BrushSource::Gradient(source) | BrushSource::ExtendedGradient(source, _) => {
+                let key = ctx.cache_key();
+                let faded = match ctx.gradients.get_faded(key, source, alpha) {
+                    Some(faded) => faded,
+                    None => {
+                        let mut gradient = (**source).clone();
+                        let stops = match &mut gradient {
+                            FixedGradient::Linear(linear) => &mut linear.stops,
+                            FixedGradient::Radial(radial) => &mut radial.stops,
+                        };
+                        for stop in stops {
+                            stop.color = fade(stop.color);
+                        }
+
+                        // The stops were already interpolated when the brush was created.
+                        let faded = ctx.cached_gradient(gradient).ok()?;
+                        ctx.gradients
+                            .insert_faded(key, source, alpha, faded.clone());
+                        faded
+                    }
+                };
+
+                if let BrushSource::ExtendedGradient(_, extend) = &self.source {
+                    let gradient = match &faded.source {
+                        BrushSource::Gradient(gradient) => gradient.clone(),
+                        _ => return Some(faded),
+                    };
+                    return Some(Brush {
+                        source: BrushSource::ExtendedGradient(gradient, *extend),
+                        ..faded
+                    });
+                }
+
+                Some(faded)
+            }
+            BrushSource::Image(image, extend, image_alpha) => {
+                let mut brush = ctx.image_brush(image, *extend);
+                brush.source = BrushSource::Image(image.clone(), *extend, image_alpha * alpha);
+                Some(brush)
+            }
+            BrushSource::Conic(conic) => {
+                let stops = conic
+                    .stops
+                    .iter()
+                    .map(|stop| GradientStop {
+                        pos: stop.pos,
+                        color: fade(stop.color),
+                    })
+                    .collect::<Vec<_>>();
+
+                Some(ctx.conic_gradient(conic.center, conic.start_angle, stops))
+            }
+        }
+    }
+}
+
+/// How a brush fills the area outside of its image or gradient.
+///
+/// See [`RenderContext::image_brush`] and [`RenderContext::extended_gradient`] for more
+/// information.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[non_exhaustive]
+pub enum ExtendMode {
+    /// Extend the pixels on the edges of the image, or the colors at the ends of the gradient.
+    #[default]
+    Clamp,
+
+    /// Repeat the image or gradient.
+    Repeat,
+
+    /// Repeat the image or gradient, mirroring every other repetition.
+    Reflect,
+}
+
+impl fmt::Debug for Brush {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Brush").finish_non_exhaustive()
+    }
+}
+
+/// The images used to draw to a [`Surface`].
+///
+/// See the documentation for [`Image`] for more information.
+///
+/// Images can be created from either premultiplied ([`ImageFormat::RgbaPremul`]) or straight
+/// ([`ImageFormat::RgbaSeparate`]) alpha data. Every backend converts the data to the form that
+/// it blends with, so the same image looks the same in either format.
+///
+/// Images can only be drawn by contexts from the backend that created them. Drawing an image from
+/// another backend does nothing, and [`status`] returns [`Error::InvalidInput`].
+///
+/// # Example
+///
+/// ```
+/// use piet::kurbo::Rect;
+/// use piet::{ImageFormat, InterpolationMode, RenderContext as _};
+/// # let mut display = theo::Display::offscreen();
+/// # let mut surface = display.make_offscreen_surface(1, 1).unwrap();
+/// # let mut ctx = theo::RenderContext::new(&mut display, &mut surface, 1, 1).unwrap();
+///
+/// // Half-transparent red, with straight alpha.
+/// let image = ctx
+///     .make_image(1, 1, &[255, 0, 0, 128], ImageFormat::RgbaSeparate)
+///     .unwrap();
+/// let rect = Rect::new(0.0, 0.0, 1.0, 1.0);
+/// ctx.draw_image(&image, rect, InterpolationMode::NearestNeighbor);
+/// ```
+///
+/// [`Image`]: https://docs.rs/piet/0.6.2/piet/trait.RenderContext.html#associatedtype.Image
+/// [`status`]: piet::RenderContext::status
+#[derive(Clone)]
+pub struct Image {
+    dispatch: Rc<ImageDispatch>,
+
+    /// The format of the pixel data that the image was created from.
+    format: ImageFormat,
+
+    /// The downsampled copies of the image, each half the size of the one before it.
+    ///
+    /// This is empty unless the image was created with `make_image_mipmapped`.
+    mipmaps: Rc<[Image]>,
+
+    /// A copy of the pixels of the image, used to make faded and tiled copies of it.
+    ///
+    /// This is `None` for images captured from the surface.
+    pixels: Option<Rc<ImagePixels>>,
+
+    _thread_unsafe: PhantomData<*mut ()>,
+}
+
+/// The premultiplied RGBA pixels of an [`Image`].
+struct ImagePixels {
+    /// The width of the image, in pixels.
+    width: usize,
+
+    /// The height of the image, in pixels.
+    height: usize,
+
+    /// The pixels, row by row from the top-left corner.
+    data: Vec<u8>,
+}
+
+impl Image {
+    fn new(dispatch: ImageDispatch, format: ImageFormat) -> Self {
+        Self {
+            dispatch: Rc::new(dispatch),
+            format,
+            mipmaps: Rc::new([]),
+            pixels: None,
+            _thread_unsafe: PhantomData,
+        }
+    }
+
+    /// Get the copy of this image to draw `src` scaled to `dst`.
+    ///
+    /// Returns the image, along with the scale from this image's pixels to its pixels.
+    fn mip_level(&self, transform: Affine, src: Size, dst: Size) -> (&Image, Vec2) {
+        use piet::Image as _;
+
+        let Some(last) = self.mipmaps.len().checked_sub(1) else {
+            return (self, Vec2::new(1.0, 1.0));
+        };
+
+        // The number of pixels in the image that cover one pixel on the surface.
+        let [a, b, c, d, _, _] = transform.as_coeffs();
+        let scale_x = a.hypot(b) * dst.width / src.width;
+        let scale_y = c.hypot(d) * dst.height / src.height;
+        let minification = 1.0 / scale_x.max(scale_y);
+
+        // Use the smallest copy that still has at least one pixel per surface pixel.
+        if minification.is_nan() || minification < 2.0 {
+            return (self, Vec2::new(1.0, 1.0));
+        }
+        let level = if minification.is_finite() {
+            (minification.log2().floor() as usize - 1).min(last)
+        } else {
+            last
+        };
+
+        let image = &self.mipmaps[level];
+        let (size, mip_size) = (self.size(), image.size());
+        (
+            image,
+            Vec2::new(mip_size.width / size.width, mip_size.height / size.height),
+        )
+    }
+
+    /// Get the format of the pixel data that this image was created from.
+    ///
+    /// This is the format passed to [`make_image`]. Images captured with
+    /// [`capture_image_area`] or created with `image_from_texture` report
+    /// [`ImageFormat::RgbaPremul`] and [`ImageFormat::RgbaSeparate`] respectively. Like
+    /// [`size`], this doesn't need a [`RenderContext`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use piet::{ImageFormat, RenderContext as _};
+    /// use theo::{Display, RenderContext};
+    ///
+    /// let mut display = Display::offscreen();
+    /// let mut surface = display.make_offscreen_surface(1, 1).unwrap();
+    /// let mut ctx = RenderContext::new(&mut display, &mut surface, 1, 1).unwrap();
+    ///
+    /// let image = ctx.make_image(1, 1, &[0, 0, 0], ImageFormat::Rgb).unwrap();
+    /// drop(ctx);
+    ///
+    /// assert_eq!(image.format(), ImageFormat::Rgb);
+    /// assert!(!image.has_alpha());
+    /// ```
+    ///
+    /// [`make_image`]: piet::RenderContext::make_image
+    /// [`capture_image_area`]: piet::RenderContext::capture_image_area
+    /// [`size`]: piet::Image::size
+    pub fn format(&self) -> ImageFormat {
+        self.format
+    }
+
+    /// Tell whether this image has an alpha channel.
+    ///
+    /// This is true for images created from [`ImageFormat::RgbaPremul`] or
+    /// [`ImageFormat::RgbaSeparate`] data. It doesn't check whether any of the pixels are
+    /// actually translucent.
+    pub fn has_alpha(&self) -> bool {
+        matches!(
+            self.format,
+            ImageFormat::RgbaPremul | ImageFormat::RgbaSeparate
+        )
+    }
+}
+
+impl fmt::Debug for Image {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Image").finish_non_exhaustive()
+    }
+}
+
+/// The color space that the colors between the stops of a gradient are computed in.
+///
+/// See [`RenderContext::set_gradient_interpolation`] for more information.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[non_exhaustive]
+pub enum GradientInterpolation {
+    /// Interpolate the sRGB-encoded color components directly.
+    ///
+    /// This is what the other [`piet`] backends and most web browsers do. Gradients between
+    /// saturated colors look darker in the middle than either end.
+    ///
+    /// [`piet`]: https://crates.io/crates/piet
+    #[default]
+    Srgb,
+
+    /// Interpolate the color components in linear RGB.
+    ///
+    /// This mixes colors the way light does, which avoids the dark band in the middle of
+    /// gradients. The backends only interpolate sRGB-encoded colors, so this is done by adding
+    /// stops between the given ones, which makes the gradient slightly more expensive to create.
+    LinearRgb,
+}
+
+/// The rule that decides which parts of a path are inside of it.
+///
+/// See [`RenderContext::fill_with_rules`] for more information.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum FillRule {
+    /// Points that the path winds around a nonzero number of times are inside.
+    ///
+    /// This is the rule used by [`fill`](piet::RenderContext::fill).
+    #[default]
+    NonZero,
+
+    /// Points that the path winds around an odd number of times are inside.
+    ///
+    /// This is the rule used by [`fill_even_odd`](piet::RenderContext::fill_even_odd).
+    EvenOdd,
+}
+
+/// A saved drawing state, returned by [`RenderContext::save_point`].
+///
+/// Pass this to [`RenderContext::restore_to`] to return to the state it was created in.
+#[derive(Debug)]
+#[must_use = "the state is only restored by passing this to `RenderContext::restore_to`"]
+pub struct SavePoint {
+    /// The number of saved states before this one was saved.
+    depth: usize,
+}
+
+/// Statistics about a frame, returned by [`RenderContext::finish_with_stats`].
+///
+/// The hardware-accelerated backends record most of their work and only submit it in
+/// [`finish`](piet::RenderContext::finish), so a long [`finish_time`] usually means that the GPU or
+/// the driver is the bottleneck. The software rasterizer draws shapes as soon as they are issued
+/// instead, so its drawing time is included in [`frame_time`] but not in [`finish_time`].
+///
+/// The number of pipeline switches and the time spent on the GPU aren't measured yet. The
+/// renderers don't expose their pipelines or command encoders, so `theo` can't count the
+/// switches or write timestamp queries.
+///
+/// [`frame_time`]: FrameStats::frame_time
+/// [`finish_time`]: FrameStats::finish_time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub struct FrameStats {
+    /// The time from the start of the frame until it was finished.
+    ///
+    /// This includes the time that the application spent between drawing calls.
+    pub frame_time: Duration,
+
+    /// The time spent finishing the frame and presenting it.
+    pub finish_time: Duration,
+
+    /// The number of drawing operations issued to the backend.
+    ///
+    /// Some operations are split into several, such as filling a shape with a conic gradient,
+    /// while others are skipped entirely, such as drawing with an unsupported brush.
+    pub draw_calls: u32,
+}
+
+/// A point in time, used to measure frames.
+///
+/// `std::time::Instant` isn't available on the web, so the browser's clock is used there instead.
+#[derive(Debug, Clone, Copy)]
+struct Timestamp {
+    #[cfg(not(wasm))]
+    instant: std::time::Instant,
+
+    #[cfg(wasm)]
+    millis: f64,
+}
+
+impl Timestamp {
+    /// Get the current time.
+    fn now() -> Self {
+        Self {
+            #[cfg(not(wasm))]
+            instant: std::time::Instant::now(),
+
+            #[cfg(wasm)]
+            millis: web_sys::window()
+                .and_then(|window| window.performance())
+                .map_or(0.0, |performance| performance.now()),
+        }
+    }
+
+    /// Get the time that has passed since this timestamp.
+    fn elapsed(&self) -> Duration {
+        #[cfg(not(wasm))]
+        {
+            self.instant.elapsed()
+        }
+
+        #[cfg(wasm)]
+        {
+            let millis = Self::now().millis - self.millis;
+            Duration::from_secs_f64(millis.max(0.0) / 1000.0)
+        }
+    }
+}
+
+/// The flavors of OpenGL that contexts can be created for.
+///
+/// See [`DisplayBuilder::gl_version`] for more information.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum GlApi {
+    /// Desktop OpenGL.
+    OpenGl,
+
+    /// OpenGL ES.
+    Gles,
+}
+
+/// The ways that drawn content can be blended with the content already on a [`Surface`].
+///
+/// See [`RenderContext::with_blend_mode`] for more information.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[non_exhaustive]
+pub enum BlendMode {
+    /// Draw the source over the destination.
+    ///
+    /// This is the default blend mode.
+    #[default]
+    SourceOver,
+
+    /// Multiply the source and destination colors, which darkens the result.
+    Multiply,
+
+    /// Invert, multiply and invert the colors again, which lightens the result.
+    Screen,
+
+    /// Add the source and destination colors together.
+    Add,
+}
+
+/// Information about the renderer used by a [`Display`].
+///
+/// This is intended for diagnostics, like bug reports. The exact contents of these strings
+/// depend on the backend and the driver, and shouldn't be relied upon.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RendererInfo {
+    /// The graphics API in use, like "Vulkan", "OpenGL" or "Software".
+    pub backend: String,
+
+    /// The name of the device, like the adapter name or the `GL_RENDERER` string.
+    pub name: String,
+
+    /// The vendor of the device.
+    pub vendor: String,
+
+    /// Information about the driver, like the `GL_VERSION` string.
+    pub driver: String,
+}
+
+/// The X11 visual that windows need to be created with to be drawn to by a [`Display`].
+///
+/// It can be retrieved with [`Display::x11_visual_info`]. Windowing code that creates its X11
+/// windows itself needs all of these to create a matching window. A window with a visual other
+/// than the default one of its screen also needs a colormap for the visual, even if it's unused.
+/// `theo` doesn't create one, so create it with `XCreateColormap` and `AllocNone`, and pass it
+/// along with the depth and the visual to `XCreateWindow`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct X11VisualInfo {
+    /// The `Visual` pointer.
+    ///
+    /// This belongs to the X11 display, so it doesn't need to be freed.
+    pub visual: std::ptr::NonNull<()>,
+
+    /// The ID of the visual.
+    pub visual_id: u64,
+
+    /// The number of bits per pixel of windows with this visual, like 24 or 32.
+    pub depth: u8,
+
+    /// The number of the screen that the visual belongs to.
+    pub screen: i32,
+
+    /// Whether the visual has an alpha channel, which makes windows created with it
+    /// transparent.
+    pub supports_transparency: bool,
+}
+
+/// The pixel format of a [`Surface`].
+///
+/// This is intended for diagnostics, like tracking down colors that look wrong on some
+/// machines. It can be retrieved with [`Surface::format_info`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct FormatInfo {
+    /// The number of bits in the red channel.
+    pub red_bits: u8,
+
+    /// The number of bits in the green channel.
+    pub green_bits: u8,
+
+    /// The number of bits in the blue channel.
+    pub blue_bits: u8,
+
+    /// The number of bits in the alpha channel, or zero if there isn't one.
+    pub alpha_bits: u8,
+
+    /// How the surface encodes the colors written to it.
+    pub color_space: ColorSpace,
+
+    /// The texture format that was chosen for the surface, if it uses [`wgpu`].
+    ///
+    /// The channel sizes are zero for formats that aren't normally used for windows.
+    #[cfg(feature = "wgpu")]
+    pub wgpu_format: Option<wgpu::TextureFormat>,
+}
+
+/// How a [`Surface`] encodes the colors written to it.
+///
+/// [`piet`] colors are already in the sRGB color space, and `theo` always writes them to the
+/// surface as they are. On an [`Srgb`] surface, `theo` draws through a view of the surface
+/// that skips the conversion, so colors look the same either way.
+///
+/// [`piet`]: https://crates.io/crates/piet
+/// [`Srgb`]: ColorSpace::Srgb
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ColorSpace {
+    /// The surface stores values as they are written.
+    Linear,
+
+    /// The surface has an sRGB format, which converts linear values to sRGB when they are
+    /// written through an sRGB view.
+    Srgb,
+}
+
+/// The error returned when a [`RenderContext`] is already active on this thread.
+///
+/// [`RenderContext::new`] returns this wrapped in [`Error::BackendError`], so it can be
+/// detected by downcasting the inner error. At the moment, only the desktop OpenGL backend
+/// needs exclusive access to the thread.
+///
+/// # Example
+///
+/// ```no_run
+/// use theo::{ContextInUse, Display, RenderContext};
+/// use winit::event_loop::EventLoop;
+/// use winit::window::Window;
+///
+/// let event_loop = EventLoop::new();
+/// let first_window = Window::new(&event_loop).unwrap();
+/// let second_window = Window::new(&event_loop).unwrap();
+///
+/// let mut first = unsafe { Display::new(&event_loop) }.unwrap();
+/// let mut second = unsafe { Display::new(&event_loop) }.unwrap();
+/// let mut first_surface = unsafe { first.make_surface_blocking(&first_window, 64, 64) }.unwrap();
+/// let mut second_surface =
+///     unsafe { second.make_surface_blocking(&second_window, 64, 64) }.unwrap();
+///
+/// // Try to create a second context while the first one is still active.
+/// let first_ctx = RenderContext::new(&mut first, &mut first_surface, 64, 64).unwrap();
+/// let result = RenderContext::new(&mut second, &mut second_surface, 64, 64);
+/// drop(first_ctx);
+///
+/// // Only the desktop OpenGL backend needs the thread to itself.
+/// if first.renderer_info().map_or(false, |info| info.backend == "OpenGL") {
+///     match result {
+///         Err(piet::Error::BackendError(err)) => assert!(err.is::<ContextInUse>()),
+///         _ => panic!("expected the context to be in use"),
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContextInUse {
+    _private: (),
+}
+
+impl fmt::Display for ContextInUse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Only one context can be active per thread.")
+    }
+}
+
+impl std::error::Error for ContextInUse {}
+
+/// The error returned when none of the backends compiled into `theo` support the platform.
+///
+/// [`DisplayBuilder::build`] returns this wrapped in [`Error::BackendError`], so it can be
+/// detected by downcasting the inner error. It is only returned if every backend reported that
+/// the platform isn't supported. If a backend fails for another reason, that error is returned
+/// instead.
+///
+/// # Example
+///
+/// ```
+/// use theo::NoBackendAvailable;
+///
+/// fn is_misconfigured(err: &piet::Error) -> bool {
+///     match err {
+///         piet::Error::BackendError(err) => err.is::<NoBackendAvailable>(),
+///         _ => false,
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NoBackendAvailable {
+    _private: (),
+}
+
+impl fmt::Display for NoBackendAvailable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("None of the enabled backends support this platform.")
+    }
+}
+
+impl std::error::Error for NoBackendAvailable {}
+
+macro_rules! make_dispatch {
+    // Backends before the semicolon are created from a display handle, the ones after it can
+    // only be created from existing objects.
+    (
+        $($(#[$meta:meta])* $name:ident ($display:ty $(, $rest:ty)*)),* ;
+        $($(#[$xmeta:meta])* $xname:ident ($($xtypes:ty),*)),* $(,)?
+    ) => {
+        make_dispatch! {
+            @raw [$($(#[$meta])* $name($display),)*]
+            $($(#[$meta])* $name($display $(, $rest)*),)*
+            $($(#[$xmeta])* $xname($($xtypes),*),)*
+        }
+    };
+
+    (
+        @raw [$($(#[$rmeta:meta])* $rname:ident ($rdisplay:ty),)*]
+        $($(#[$meta:meta])* $name:ident (
+            $display:ty,
+            $surface:ty,
+            $ctx:ty,
+            $brush:ty,
+            $image:ty
+        ),)*
+    ) => {
+        enum DisplayDispatch {
+            $(
+                $(#[$meta])*
+                $name($display),
+            )*
+        }
+
+        enum SurfaceDispatch {
+            $(
+                $(#[$meta])*
+                $name($surface),
+            )*
+        }
+
+        enum ContextDispatch<'dsp, 'surf> {
+            $(
+                $(#[$meta])*
+                $name($ctx),
+            )*
+        }
+
+        enum BrushDispatch {
+            $(
+                $(#[$meta])*
+                $name($brush),
+            )*
+        }
+
+        enum ImageDispatch {
+            $(
+                $(#[$meta])*
+                $name($image),
+            )*
+        }
+
+        impl DisplayBuilder {
+            /// Build the [`Display`] from a raw display handle.
+            ///
+            /// This is equivalent to [`DisplayBuilder::build`], but takes a raw display handle
+            /// instead of a type that implements [`HasRawDisplayHandle`]. This is useful for
+            /// implementing [`HasRawDisplayHandle`] for types that don't own their display.
+            ///
+            /// # Safety
+            ///
+            /// The `raw` handle must be a valid `display` that isn't currently suspended.
+            /// The `raw` handle must be valid for the duration of the [`Display`].
+            #[allow(unused_assignments, unused_mut)]
+            pub unsafe fn build_from_raw(
+                mut self,
+                raw: RawDisplayHandle
+            ) -> Result<Display, Error> {
+                let mut last_error = None;
+
+                $(
+                    $(#[$rmeta])*
+                    {
+                        match <$rdisplay>::new(&mut self, raw) {
+                            Ok(display) => {
+                                tracing::trace!("Created `{}` display", stringify!($rname));
+                                return Ok(Display::from_dispatch(
+                                    DisplayDispatch::$rname(display),
+                                    &self,
+                                ));
+                            },
+
+                            Err(Error::NotSupported) => {
+                                tracing::trace!(
+                                    "`{}` display is not supported here",
+                                    stringify!($rname)
+                                );
+
+                            }
+
+                            Err(e) => {
+                                tracing::warn!(
+                                    "Failed to create `{}` display: {}",
+                                    stringify!($rname),
+                                    e
+                                );
+
+                                last_error = Some(e);
+                            }
+                        }
+                    }
+                )*
+
+                Err(last_error.unwrap_or_else(|| {
+                    Error::BackendError(Box::new(NoBackendAvailable { _private: () }))
+                }))
+            }
+        }
+
+        impl Display {
+            /// Whether or not this display supports transparent backgrounds.
+            ///
+            /// If this is `true`, then the [`Surface`]s created from this display will support
+            /// transparency if the underlying windowing system supports it. You should use this
+            /// to decide whether or not to use a transparent background.
+            ///
+            /// This is always `false` for the software rasterizer, which can't present an alpha
+            /// channel.
+            ///
+            /// # Example
+            ///
+            /// ```no_run
+            /// use theo::Display;
+            ///
+            /// let event_loop = winit::event_loop::EventLoop::new();
+            /// let display = unsafe { Display::new(&event_loop) }.unwrap();
+            ///
+            /// if display.supports_transparency() {
+            ///     create_transparent_window();
+            /// } else {
+            ///     create_opaque_window();
+            /// }
+            /// # fn create_transparent_window() {}
+            /// # fn create_opaque_window() {}
+            /// ```
+            pub fn supports_transparency(&self) -> bool {
+                match &*self.dispatch {
+                    $(
+                        $(#[$meta])*
+                        DisplayDispatch::$name(display) => display.supports_transparency(),
+                    )*
+                }
+            }
+
+            /// The X11 visual used by this display, if any.
+            ///
+            /// This is useful for creating [`Surface`]s with a specific visual. On X11, you can
+            /// use this to create a surface.
+            ///
+            /// # Example
+            ///
+            /// ```no_run
+            /// use theo::Display;
+            ///
+            /// let event_loop = winit::event_loop::EventLoop::new();
+            /// let display = unsafe { Display::new(&event_loop) }.unwrap();
+            ///
+            /// let visual = display.x11_visual().unwrap();
+            /// ```
+            pub fn x11_visual(&self) -> Option<std::ptr::NonNull<()>> {
+                match &*self.dispatch {
+                    $(
+                        $(#[$meta])*
+                        DisplayDispatch::$name(display) => display.x11_visual(),
+                    )*
+                }
+            }
+
+            /// Information about the X11 visual used by this display, if any.
+            ///
+            /// This returns the same visual as [`Display::x11_visual`], along with its depth and
+            /// screen, which are needed to create an X11 window for it without the help of
+            /// `winit`. Only the OpenGL backend picks its own visual on X11. The other backends
+            /// return `None`, and draw to windows with any visual.
+            ///
+            /// # Example
+            ///
+            /// ```no_run
+            /// use theo::Display;
+            ///
+            /// let event_loop = winit::event_loop::EventLoop::new();
+            /// let display = unsafe { Display::new(&event_loop) }.unwrap();
+            ///
+            /// if let Some(info) = display.x11_visual_info() {
+            ///     println!("Create the window with a depth of {}", info.depth);
+            /// }
+            /// ```
+            pub fn x11_visual_info(&self) -> Option<X11VisualInfo> {
+                match &*self.dispatch {
+                    $(
+                        $(#[$meta])*
+                        DisplayDispatch::$name(display) => display.x11_visual_info(),
+                    )*
+                }
+            }
+
+            /// Get a [`Text`] that can be used to build layouts outside of a frame.
+            ///
+            /// Layouts built with the returned [`Text`] can be drawn by any [`RenderContext`]
+            /// created from this display. This makes it possible to build [`TextLayout`]s ahead of
+            /// time, for instance while the application is starting up, so that the first frame
+            /// doesn't need to do all of the shaping work.
+            ///
+            /// The returned [`Text`] uses `cosmic-text` directly. For the software rasterizer
+            /// this is the same text backend used by the [`RenderContext`]. Other backends rebuild
+            /// the layout with their own text backend the first time it is drawn, and keep the
+            /// result with the layout for later frames. Fonts loaded through [`load_font`] on this
+            /// [`Text`] must also be loaded on [`RenderContext::text`].
+            ///
+            /// # Example
+            ///
+            /// ```no_run
+            /// use piet::{Text as _, TextLayoutBuilder as _};
+            /// use theo::Display;
+            ///
+            /// let event_loop = winit::event_loop::EventLoop::new();
+            /// let mut display = unsafe { Display::new(&event_loop) }.unwrap();
+            ///
+            /// let layout = display
+            ///     .text()
+            ///     .new_text_layout("Hello, world!")
+            ///     .build()
+            ///     .unwrap();
+            /// ```
+            ///
+            /// [`load_font`]: piet::Text::load_font
+            /// [`RenderContext::text`]: piet::RenderContext::text
+            pub fn text(&mut self) -> Text {
+                let mut text = match &mut *self.dispatch {
+                    $(
+                        $(#[$meta])*
+                        DisplayDispatch::$name(display) => display.text(),
+                    )*
+                };
+
+                text.set_missing_glyph(self.missing_glyph.clone());
+                text
+            }
+
+            /// Create a new [`Surface`] from a raw window handle.
+            ///
+            /// This is equivalent to [`Display::make_surface`], except that it takes a raw window
+            /// handle instead of a window. This is useful if you want to create a surface from a
+            /// window that doesn't implement [`RawWindowHandle`].
+            ///
+            /// # Asynchronous
+            ///
+            /// This function is asynchronous, as it may be necessary to wait for data to become available.
+            /// This is only used for the [`wgpu`] backend, as it requires the adapter to be created
+            /// asynchronously. For remaining backends, this future will not return `Pending`.
+            ///
+            /// # Safety
+            ///
+            /// The `window` handle must be a valid `window` that isn't currently suspended. The
+            /// `width` and `height` parameters aren't necessarily required to be correct, but
+            /// it's recommended that they are in order to avoid visual bugs.
+            pub async unsafe fn make_surface_from_raw(
+                &mut self,
+                window: RawWindowHandle,
+                width: u32,
+                height: u32,
+            ) -> Result<Surface, Error> {
+                match &mut *self.dispatch {
+                    $(
+                        $(#[$meta])*
+                        DisplayDispatch::$name(display) => {
+                            let surface = display.make_surface(window, width, height).await?;
+                            Ok(SurfaceDispatch::$name(surface).into())
+                        },
+                    )*
+                }
+            }
+
+            /// Get information about the renderer used by this display.
+            ///
+            /// Some backends only pick a device once the first [`Surface`] or [`RenderContext`]
+            /// is created, and return `None` until then.
+            ///
+            /// # Example
+            ///
+            /// ```
+            /// use theo::Display;
+            ///
+            /// let display = Display::offscreen();
+            /// let info = display.renderer_info().unwrap();
+            /// assert_eq!(info.backend, "Software");
+            /// ```
+            pub fn renderer_info(&self) -> Option<RendererInfo> {
+                match &*self.dispatch {
+                    $(
+                        $(#[$meta])*
+                        DisplayDispatch::$name(display) => display.renderer_info(),
+                    )*
+                }
+            }
+
+            /// Get the largest width or height of an image that this display can create.
+            ///
+            /// Images larger than this in either dimension are rejected by
+            /// [`make_image`], so larger images need to be split into tiles. Like
+            /// [`renderer_info`], this returns `None` on backends that haven't picked a device
+            /// yet.
+            ///
+            /// # Example
+            ///
+            /// ```
+            /// use theo::Display;
+            ///
+            /// let display = Display::offscreen();
+            /// assert!(display.max_texture_size().unwrap() >= 8192);
+            /// ```
+            ///
+            /// [`make_image`]: piet::RenderContext::make_image
+            /// [`renderer_info`]: Display::renderer_info
+            pub fn max_texture_size(&self) -> Option<u32> {
+                match &*self.dispatch {
+                    $(
+                        $(#[$meta])*
+                        DisplayDispatch::$name(display) => display.max_texture_size(),
+                    )*
+                }
+            }
+
+            /// Destroy a [`Surface`], freeing its resources immediately.
+            ///
+            /// Dropping a [`Surface`] also frees its resources, but some backends wait until the
+            /// next call to [`Display::present`] to do so. This function frees them right away,
+            /// which is useful when the last surface is closed, or when many short-lived windows
+            /// are opened and closed.
+            ///
+            /// Returns an error if the surface wasn't created by this display.
+            ///
+            /// # Example
+            ///
+            /// ```
+            /// use theo::Display;
+            ///
+            /// let mut display = Display::offscreen();
+            /// let surface = display.make_offscreen_surface(64, 64).unwrap();
+            /// display.destroy_surface(surface).unwrap();
+            /// ```
+            #[allow(unreachable_patterns)]
+            pub fn destroy_surface(&mut self, surface: Surface) -> Result<(), Error> {
+                match (&mut *self.dispatch, *surface.dispatch) {
+                    $(
+                        $(#[$meta])*
+                        (DisplayDispatch::$name(display), SurfaceDispatch::$name(surface)) => {
+                            display.destroy_surface(surface)
+                        },
+                    )*
+                    _ => Err(Error::InvalidInput)
+                }
+            }
+
+            /// Begin drawing a frame that spans multiple [`Surface`]s.
+            ///
+            /// Between this call and the matching call to [`Display::end_frame`], finishing a
+            /// [`RenderContext`] doesn't present its surface right away. Instead, all of the
+            /// surfaces that were drawn to are presented together in [`Display::end_frame`]. This
+            /// avoids waiting for the display's refresh once per window, which would otherwise
+            /// limit applications with many windows to a fraction of the refresh rate.
+            ///
+            /// Every surface created from a [`Display`] is drawn to with the display's single
+            /// set of GPU resources, so only one [`RenderContext`] can exist at a time; draw
+            /// the surfaces one after another, as in the example below. On OpenGL, the shared
+            /// context stays current between the surfaces of a frame instead of being released
+            /// after each one.
+            ///
+            /// # Example
+            ///
+            /// ```no_run
+            /// use piet::RenderContext as _;
+            /// use theo::{Display, RenderContext, Surface};
+            ///
+            /// # fn windows() -> Vec<(Surface, u32, u32)> { vec![] }
+            /// let event_loop = winit::event_loop::EventLoop::new();
+            /// let mut display = unsafe { Display::new(&event_loop) }.unwrap();
+            /// let mut surfaces = windows();
+            ///
+            /// # theo::block_on(async {
+            /// display.begin_frame();
+            ///
+            /// for (surface, width, height) in &mut surfaces {
+            ///     let mut ctx = RenderContext::new(&mut display, surface, *width, *height).unwrap();
+            ///     ctx.clear(None, piet::Color::WHITE);
+            ///     ctx.finish().unwrap();
+            /// }
+            ///
+            /// display.end_frame().await.unwrap();
+            /// # });
+            /// ```
+            pub fn begin_frame(&mut self) {
+                match &mut *self.dispatch {
+                    $(
+                        $(#[$meta])*
+                        DisplayDispatch::$name(display) => display.begin_frame(),
+                    )*
+                }
+                self.report_device_lost();
+            }
+
+            /// Present all of the surfaces drawn since [`Display::begin_frame`].
+            ///
+            /// For OpenGL, this swaps the buffers of each surface, only waiting for the display's
+            /// refresh on the last one. Every surface is swapped even if swapping one of them
+            /// fails, and the first error is returned. For [`wgpu`], this is equivalent to
+            /// [`Display::present`]. For the remaining backends, this is a no-op.
+            pub async fn end_frame(&mut self) -> Result<(), Error> {
+                match &mut *self.dispatch {
+                    $(
+                        $(#[$meta])*
+                        DisplayDispatch::$name(display) => display.end_frame().await?,
+                    )*
+                }
+                self.report_device_lost();
+                Ok(())
+            }
+
+            /// Push the queue and present to all known surfaces.
+            ///
+            /// This must be called once per frame, after every [`RenderContext`] for that frame
+            /// has been finished and dropped. On the [`wgpu`] backend, finishing a
+            /// [`RenderContext`] only records its drawing operations, and nothing is shown on any
+            /// window until this is called. Calling it once for all windows lets the work for
+            /// every surface on the same adapter be submitted together.
+            ///
+            /// It is always safe to call this, even if nothing has been drawn; the other backends
+            /// present when a [`RenderContext`] is finished, so this is a no-op for them. Calling
+            /// it unconditionally keeps code portable between backends.
+            ///
+            /// # Asynchronous
+            ///
+            /// For most backends, this is a no-op. For [`wgpu`], it submits the queue and then
+            /// waits for all of the queues to finish submitting.
+            ///
+            /// # Example
+            ///
+            /// ```
+            /// use piet::RenderContext as _;
+            /// use theo::{Display, RenderContext};
+            ///
+            /// let mut display = Display::offscreen();
+            /// let mut surface = display.make_offscreen_surface(1, 1).unwrap();
+            ///
+            /// let mut ctx = RenderContext::new(&mut display, &mut surface, 1, 1).unwrap();
+            /// ctx.clear(None, piet::Color::WHITE);
+            /// ctx.finish().unwrap();
+            /// drop(ctx);
+            ///
+            /// // Present everything that was drawn this frame.
+            /// theo::block_on(display.present());
+            /// ```
+            pub async fn present(&mut self) {
+                match &mut *self.dispatch {
+                    $(
+                        $(#[$meta])*
+                        DisplayDispatch::$name(ctx) => ctx.present().await,
+                    )*
+                }
+                self.report_device_lost();
+            }
+
+            /// Process work that the GPU has finished, without blocking.
+            ///
+            /// For [`wgpu`], this polls the device of every adapter, which runs the callbacks for
+            /// buffers that have finished mapping. Asynchronous readbacks only complete when the
+            /// device is polled, and nothing else polls it outside of [`present`], so call this
+            /// regularly, like once per event loop iteration, while waiting for one. The other
+            /// backends do their work synchronously, so this does nothing for them.
+            ///
+            /// # Example
+            ///
+            /// ```
+            /// use theo::Display;
+            ///
+            /// let mut display = Display::offscreen();
+            /// display.poll();
+            /// ```
+            ///
+            /// [`present`]: Self::present
+            /// [`wgpu`]: https://crates.io/crates/wgpu
+            pub fn poll(&mut self) {
+                match &mut *self.dispatch {
+                    $(
+                        $(#[$meta])*
+                        DisplayDispatch::$name(display) => display.poll(),
+                    )*
+                }
+                self.report_device_lost();
+            }
+
+            /// Whether the GPU device that this display renders with has been lost.
+            ///
+            /// Once this returns `true`, it keeps returning `true`. Nothing drawn afterwards is
+            /// shown, and [`Surface::is_renderable`] returns `false` for the affected surfaces. The
+            /// application should drop this display, along with everything created from it, and
+            /// build a new one. [`DisplayBuilder::on_device_lost`] can be used to be notified
+            /// instead of polling this.
+            ///
+            /// Only the [`wgpu`] backend can detect a lost device, and only for devices that it
+            /// created itself. Any error that `wgpu` reports outside of an error scope is
+            /// treated as a lost device and logged, instead of panicking like `wgpu` does by
+            /// default, since `wgpu` doesn't say which of its errors mean that the device is
+            /// gone. A device passed to [`DisplayBuilder::wgpu_device`] keeps the error handler
+            /// that the application installed on it, so it is up to the application to notice
+            /// that it was lost. This always returns `false` for the other backends.
+            ///
+            /// # Example
+            ///
+            /// ```
+            /// use theo::Display;
+            ///
+            /// let display = Display::offscreen();
+            /// assert!(!display.device_lost());
+            /// ```
+            ///
+            /// [`wgpu`]: https://crates.io/crates/wgpu
+            pub fn device_lost(&self) -> bool {
+                match &*self.dispatch {
+                    $(
+                        $(#[$meta])*
+                        DisplayDispatch::$name(display) => display.device_lost(),
+                    )*
+                }
+            }
         }
 
-        enum ContextDispatch<'dsp, 'surf> {
-            $(
-                $(#[$meta])*
-                $name($ctx),
-            )*
-        }
+        impl Surface {
+            /// Read back the contents of this surface.
+            ///
+            /// This returns the width, the height and the pixels of the surface as tightly
+            /// packed, non-premultiplied RGBA8 pixels, starting at the top-left corner. The
+            /// pixels are the ones shown as of the last call to [`finish`]; drawing that hasn't
+            /// been finished yet is not included. The `wgpu` backend only renders frames in
+            /// [`Display::present`], so there it is the last presented frame instead. `display`
+            /// must be the [`Display`] that created this surface.
+            ///
+            /// The software rasterizer reads back what is shown in the window. This may fail on
+            /// some platforms, like Wayland, or if the window is not visible. The desktop OpenGL
+            /// backend copies every finished frame before swapping the buffers, which needs
+            /// OpenGL 3.0 or OpenGL ES 3.0. The `wgpu` backend copies every presented frame if
+            /// the platform lets it copy from the surface. Other backends return
+            /// [`Error::NotSupported`]; use [`RenderContext::finish_to_buffer`] there instead.
+            ///
+            /// # Example
+            ///
+            /// ```
+            /// use piet::RenderContext as _;
+            /// use theo::{Display, RenderContext};
+            ///
+            /// let mut display = Display::offscreen();
+            /// let mut surface = display.make_offscreen_surface(2, 1).unwrap();
+            ///
+            /// let mut ctx = RenderContext::new(&mut display, &mut surface, 2, 1).unwrap();
+            /// ctx.clear(None, piet::Color::BLUE);
+            /// ctx.finish().unwrap();
+            /// drop(ctx);
+            ///
+            /// let (width, height, pixels) = surface.snapshot(&mut display).unwrap();
+            /// assert_eq!((width, height), (2, 1));
+            /// assert_eq!(&pixels[..4], &[0, 0, 255, 255]);
+            /// ```
+            ///
+            /// [`finish`]: piet::RenderContext::finish
+            #[allow(unreachable_patterns)]
+            pub fn snapshot(&mut self, display: &mut Display) -> Result<(u32, u32, Vec<u8>), Error> {
+                match (&mut *display.dispatch, &mut *self.dispatch) {
+                    $(
+                        $(#[$meta])*
+                        (DisplayDispatch::$name(display), SurfaceDispatch::$name(surface)) => {
+                            surface.snapshot(display)
+                        },
+                    )*
+                    _ => Err(Error::InvalidInput)
+                }
+            }
 
-        enum BrushDispatch {
-            $(
-                $(#[$meta])*
-                $name($brush),
-            )*
-        }
+            /// Tell whether drawing to this surface would show up on the screen.
+            ///
+            /// This returns `false` if the surface has no area, or if it couldn't be presented to
+            /// the last time that it was drawn to, which happens when the window is minimized on
+            /// some platforms. Frames drawn to such surfaces are thrown away, so applications can
+            /// check this to skip rendering them entirely. It also returns `false` once the GPU
+            /// device of the surface is lost; see [`Display::device_lost`]. `display` must be the
+            /// [`Display`] that created this surface, or this returns `false`.
+            ///
+            /// This is a cheap check, but it can't detect every case where a window isn't visible.
+            /// Most platforms keep minimized and occluded windows at their usual size, and only
+            /// the windowing system can tell that they are hidden. The desktop OpenGL backend asks
+            /// the platform for the current size of the window, while the other backends only know
+            /// the size of the last frame. Surfaces drawn to OpenGL contexts created by the
+            /// application and to canvases on the web are always considered renderable.
+            ///
+            /// # Example
+            ///
+            /// ```
+            /// use theo::Display;
+            ///
+            /// let mut display = Display::offscreen();
+            /// let surface = display.make_offscreen_surface(1, 1).unwrap();
+            ///
+            /// assert!(surface.is_renderable(&display));
+            /// ```
+            #[allow(unreachable_patterns)]
+            pub fn is_renderable(&self, display: &Display) -> bool {
+                match (&*display.dispatch, &*self.dispatch) {
+                    $(
+                        $(#[$meta])*
+                        (DisplayDispatch::$name(display), SurfaceDispatch::$name(surface)) => {
+                            surface.is_renderable(display)
+                        },
+                    )*
+                    _ => false,
+                }
+            }
 
-        enum ImageDispatch {
-            $(
-                $(#[$meta])*
-                $name($image),
-            )*
+            /// Get the pixel format that was chosen for this surface.
+            ///
+            /// `display` must be the [`Display`] that created this surface. This returns
+            /// [`Error::NotSupported`] for surfaces drawn to an OpenGL context created by the
+            /// application, since `theo` doesn't choose their format.
+            ///
+            /// # Example
+            ///
+            /// ```
+            /// use theo::{ColorSpace, Display};
+            ///
+            /// let mut display = Display::offscreen();
+            /// let surface = display.make_offscreen_surface(1, 1).unwrap();
+            ///
+            /// let info = surface.format_info(&display).unwrap();
+            /// assert_eq!(info.red_bits, 8);
+            /// assert_eq!(info.alpha_bits, 8);
+            /// assert_eq!(info.color_space, ColorSpace::Linear);
+            /// ```
+            #[allow(unreachable_patterns)]
+            pub fn format_info(&self, display: &Display) -> Result<FormatInfo, Error> {
+                match (&*display.dispatch, &*self.dispatch) {
+                    $(
+                        $(#[$meta])*
+                        (DisplayDispatch::$name(display), SurfaceDispatch::$name(surface)) => {
+                            surface.format_info(display)
+                        },
+                    )*
+                    _ => Err(Error::InvalidInput)
+                }
+            }
         }
 
-        impl DisplayBuilder {
-            /// Build the [`Display`] from a raw display handle.
+        impl<'dsp, 'surf> RenderContext<'dsp, 'surf> {
+            /// Create a new [`RenderContext`] from a [`Surface`] and a [`Display`].
             ///
-            /// This is equivalent to [`DisplayBuilder::build`], but takes a raw display handle
-            /// instead of a type that implements [`HasRawDisplayHandle`]. This is useful for
-            /// implementing [`HasRawDisplayHandle`] for types that don't own their display.
+            /// This creates a new [`RenderContext`] from a [`Surface`] and a [`Display`]. This is
+            /// the only way to create a [`RenderContext`].
             ///
-            /// # Safety
+            /// The desktop OpenGL backend can only have one context current per thread, so only
+            /// one of its [`RenderContext`]s can be active on a thread at a time. If another one
+            /// is active, this returns a [`ContextInUse`] error, unless the check was turned off
+            /// with [`DisplayBuilder::single_context_check`]. Other backends have no such
+            /// restriction.
             ///
-            /// The `raw` handle must be a valid `display` that isn't currently suspended.
-            /// The `raw` handle must be valid for the duration of the [`Display`].
-            #[allow(unused_assignments, unused_mut)]
-            pub unsafe fn build_from_raw(
-                mut self,
-                raw: RawDisplayHandle
-            ) -> Result<Display, Error> {
-                let mut last_error;
+            /// `width` and `height` are in physical pixels. The context starts with its transform
+            /// scaled by the scale factor of the surface; see [`Surface::set_scale_factor`].
+            #[allow(unreachable_patterns)]
+            pub fn new(
+                display: &'dsp mut Display,
+                surface: &'surf mut Surface,
+                width: u32,
+                height: u32,
+            ) -> Result<Self, Error> {
+                let (background, auto_clear) = (surface.background, surface.auto_clear);
+                let scale_factor = surface.scale_factor;
+                let missing_glyph = display.missing_glyph.clone();
+                let single_context_check = display.single_context_check;
+                match (&mut *display.dispatch, &mut *surface.dispatch) {
+                    $(
+                        $(#[$meta])*
+                        (DisplayDispatch::$name(dispatch), SurfaceDispatch::$name(surface)) => {
+                            // Make sure there's only one per thread, if the backend needs it.
+                            let exclusive = <$ctx>::EXCLUSIVE && single_context_check;
+                            if exclusive {
+                                lock_thread()?;
+                            }
 
-                $(
-                    $(#[$meta])*
-                    {
-                        match <$display>::new(&mut self, raw) {
-                            Ok(display) => {
-                                tracing::trace!("Created `{}` display", stringify!($name));
-                                return Ok(DisplayDispatch::$name(display).into());
-                            },
+                            let ctx = unsafe {
+                                <$ctx>::new(dispatch, surface, width, height).map_err(|err| {
+                                    if exclusive {
+                                        unlock_thread();
+                                    }
+                                    err
+                                })?
+                            };
 
-                            Err(e) => {
-                                tracing::warn!(
-                                    "Failed to create `{}` display: {}",
-                                    stringify!($name),
-                                    e
+                            let mut ctx = RenderContext::from_dispatch(
+                                ContextDispatch::$name(ctx),
+                                &mut display.gradients,
+                                &mut display.solids,
+                                &mut display.images,
+                                &mut display.blurs,
+                                exclusive,
+                                background,
+                            );
+                            piet::RenderContext::text(&mut ctx).set_missing_glyph(missing_glyph);
+                            if auto_clear {
+                                ctx.clear_background(None);
+                            }
+                            if scale_factor != 1.0 {
+                                piet::RenderContext::transform(
+                                    &mut ctx,
+                                    Affine::scale(scale_factor),
                                 );
-
-                                last_error = e;
                             }
-                        }
-                    }
-                )*
+                            Ok(ctx)
+                        },
+                    )*
+                    _ => Err(Error::InvalidInput)
+                }
+            }
 
-                Err(last_error)
+            /// Create a new [`RenderContext`] without checking for exclusive access.
+            ///
+            /// # Safety
+            ///
+            /// The same as `new`, but you need to make sure that there are no other OpenGL contexts
+            /// active on the current thread.
+            #[allow(unreachable_patterns)]
+            pub unsafe fn new_unchecked(
+                display: &'dsp mut Display,
+                surface: &'surf mut Surface,
+                width: u32,
+                height: u32,
+            ) -> Result<Self, Error> {
+                let (background, auto_clear) = (surface.background, surface.auto_clear);
+                let scale_factor = surface.scale_factor;
+                let missing_glyph = display.missing_glyph.clone();
+                match (&mut *display.dispatch, &mut *surface.dispatch) {
+                    $(
+                        $(#[$meta])*
+                        (DisplayDispatch::$name(dispatch), SurfaceDispatch::$name(surface)) => {
+                            let ctx = <$ctx>::new_unchecked(dispatch, surface, width, height)?;
+                            let mut ctx = RenderContext::from_dispatch(
+                                ContextDispatch::$name(ctx),
+                                &mut display.gradients,
+                                &mut display.solids,
+                                &mut display.images,
+                                &mut display.blurs,
+                                false,
+                                background,
+                            );
+                            piet::RenderContext::text(&mut ctx).set_missing_glyph(missing_glyph);
+                            if auto_clear {
+                                ctx.clear_background(None);
+                            }
+                            if scale_factor != 1.0 {
+                                piet::RenderContext::transform(
+                                    &mut ctx,
+                                    Affine::scale(scale_factor),
+                                );
+                            }
+                            Ok(ctx)
+                        },
+                    )*
+                    _ => Err(Error::InvalidInput)
+                }
             }
         }
 
-        impl Display {
-            /// Whether or not this display supports transparent backgrounds.
+        impl RenderContext<'_, '_> {
+            /// Send the drawing commands issued so far to the GPU, without finishing the frame.
             ///
-            /// If this is `true`, then the [`Surface`]s created from this display will support
-            /// transparency if the underlying windowing system supports it. You should use this
-            /// to decide whether or not to use a transparent background.
+            /// Very large scenes can build up a lot of pending commands before [`finish`] is
+            /// called. Flushing every so often lets the driver start working on them early,
+            /// and keeps it from holding on to all of them at once. The frame can still be drawn
+            /// to afterwards, and is presented as usual.
+            ///
+            /// On the OpenGL backends, this calls `glFlush`. The [`wgpu`] renderer records the
+            /// whole frame and only submits it in [`Display::present`], so this does nothing
+            /// there, and neither does it for the software rasterizer, which draws immediately.
             ///
             /// # Example
             ///
-            /// ```no_run
-            /// use theo::Display;
+            /// ```
+            /// use piet::kurbo::Rect;
+            /// use piet::RenderContext as _;
+            /// # let mut display = theo::Display::offscreen();
+            /// # let mut surface = display.make_offscreen_surface(64, 64).unwrap();
+            /// # let mut ctx = theo::RenderContext::new(&mut display, &mut surface, 64, 64).unwrap();
             ///
-            /// let event_loop = winit::event_loop::EventLoop::new();
-            /// let display = unsafe { Display::new(&event_loop) }.unwrap();
+            /// for i in 0..64 {
+            ///     let rect = Rect::new(i as f64, 0.0, i as f64 + 1.0, 64.0);
+            ///     ctx.fill(rect, &piet::Color::BLUE);
             ///
-            /// if display.supports_transparency() {
-            ///     create_transparent_window();
-            /// } else {
-            ///     create_opaque_window();
+            ///     if i % 16 == 15 {
+            ///         ctx.flush().unwrap();
+            ///     }
             /// }
-            /// # fn create_transparent_window() {}
-            /// # fn create_opaque_window() {}
+            ///
+            /// ctx.finish().unwrap();
             /// ```
-            pub fn supports_transparency(&self) -> bool {
-                match &*self.dispatch {
+            ///
+            /// [`finish`]: piet::RenderContext::finish
+            /// [`wgpu`]: https://crates.io/crates/wgpu
+            pub fn flush(&mut self) -> Result<(), Error> {
+                match &mut *self.dispatch {
                     $(
                         $(#[$meta])*
-                        DisplayDispatch::$name(display) => display.supports_transparency(),
+                        ContextDispatch::$name(ctx) => ctx.flush(),
                     )*
                 }
             }
 
-            /// The X11 visual used by this display, if any.
+            /// Finish drawing and read back the rendered pixels.
             ///
-            /// This is useful for creating [`Surface`]s with a specific visual. On X11, you can
-            /// use this to create a surface.
+            /// This does the same thing as [`finish`], but also returns the contents of the
+            /// surface as tightly packed, non-premultiplied RGBA8 pixels, starting at the top-left
+            /// corner. This is intended for testing, so it isn't fast.
+            ///
+            /// This is supported by the software rasterizer, the desktop OpenGL backend and the
+            /// `wgpu` backend, as long as the surface uses an 8-bit format. Other backends return
+            /// [`Error::NotSupported`] without finishing the frame.
             ///
             /// # Example
             ///
-            /// ```no_run
-            /// use theo::Display;
+            /// ```
+            /// use piet::RenderContext as _;
+            /// use theo::{Display, RenderContext};
             ///
-            /// let event_loop = winit::event_loop::EventLoop::new();
-            /// let display = unsafe { Display::new(&event_loop) }.unwrap();
+            /// let mut display = Display::offscreen();
+            /// let mut surface = display.make_offscreen_surface(2, 2).unwrap();
             ///
-            /// let visual = display.x11_visual().unwrap();
+            /// let mut ctx = RenderContext::new(&mut display, &mut surface, 2, 2).unwrap();
+            /// ctx.clear(None, piet::Color::RED);
+            /// let pixels = ctx.finish_to_buffer().unwrap();
+            ///
+            /// assert_eq!(pixels.len(), 2 * 2 * 4);
+            /// assert_eq!(&pixels[..4], &[255, 0, 0, 255]);
             /// ```
-            pub fn x11_visual(&self) -> Option<std::ptr::NonNull<()>> {
-                match &*self.dispatch {
+            ///
+            /// [`finish`]: piet::RenderContext::finish
+            pub fn finish_to_buffer(&mut self) -> Result<Vec<u8>, Error> {
+                match &mut *self.dispatch {
                     $(
                         $(#[$meta])*
-                        DisplayDispatch::$name(display) => display.x11_visual(),
+                        ContextDispatch::$name(ctx) => ctx.finish_to_buffer(),
+                    )*
+                }
+            }
+
+            /// Finish drawing, presenting only the regions that changed.
+            ///
+            /// This does the same thing as [`finish`], but tells the platform that only
+            /// `regions` changed since the last frame, which can be much cheaper when most of the
+            /// surface stays the same. The regions are in pixels, starting at the top-left corner
+            /// of the surface, and are not affected by the current transform. Everything outside
+            /// of them should be left as it was in the previous frame; see
+            /// [`Surface::set_auto_clear`].
+            ///
+            /// The software rasterizer only uploads the damaged regions to the window. The
+            /// desktop OpenGL backend passes the regions to the compositor when it uses EGL, and
+            /// swaps the whole surface otherwise. The other backends always present the whole
+            /// surface. If `regions` is empty, the whole surface is presented. Surfaces that are
+            /// swapped at the end of a frame (see [`Display::begin_frame`]) keep their regions
+            /// until then.
+            ///
+            /// # Example
+            ///
+            /// ```
+            /// use piet::kurbo::Rect;
+            /// use piet::{Color, RenderContext as _};
+            /// # let mut display = theo::Display::offscreen();
+            /// # let mut surface = display.make_offscreen_surface(64, 64).unwrap();
+            /// # let mut ctx = theo::RenderContext::new(&mut display, &mut surface, 64, 64).unwrap();
+            ///
+            /// // Only the line that was edited is redrawn.
+            /// let line = Rect::new(0.0, 16.0, 64.0, 32.0);
+            /// ctx.clear(line, Color::WHITE);
+            /// ctx.finish_with_damage(&[line]).unwrap();
+            /// ```
+            ///
+            /// [`finish`]: piet::RenderContext::finish
+            pub fn finish_with_damage(&mut self, regions: &[Rect]) -> Result<(), Error> {
+                match &mut *self.dispatch {
+                    $(
+                        $(#[$meta])*
+                        ContextDispatch::$name(ctx) => ctx.finish_with_damage(regions),
+                    )*
+                }
+            }
+
+            /// Start drawing into a layer that is composited with the given opacity.
+            ///
+            /// Everything drawn until the matching call to [`pop_layer`] is treated as a group,
+            /// which is blended onto the surface as a whole with its opacity multiplied by
+            /// `alpha`. Unlike [`push_opacity`], shapes in the group that overlap one another do
+            /// not show through each other. `alpha` is clamped to the range `0.0..=1.0`.
+            ///
+            /// This also saves the current state, as if by [`save`]. Changes to the transform
+            /// and clip made inside of the layer are undone by [`pop_layer`]. States saved inside
+            /// of the layer must be restored before the layer is popped, and [`restore`] can't
+            /// restore states saved outside of the layer until it is popped. Layers can be
+            /// nested.
+            ///
+            /// # Backend Support
+            ///
+            /// The software rasterizer and the desktop OpenGL backend render the layer exactly.
+            /// OpenGL copies the surface into a texture when the layer is pushed and blends the
+            /// layer onto that copy when it is popped, which needs OpenGL 3.0 or OpenGL ES 3.0.
+            ///
+            /// The other backends return [`Error::NotSupported`] without saving the state, and
+            /// [`push_opacity`] can be used instead where overlapping content isn't a concern.
+            /// The `wgpu` renderer records the whole frame and draws it in a single pass when
+            /// the display presents it, so there is no point in the frame where the layer could
+            /// be drawn on its own. WebGL and OpenGL contexts created outside of `theo` don't
+            /// give access to the GL functions needed to copy the surface.
+            ///
+            /// # Example
+            ///
+            /// ```
+            /// use piet::kurbo::Rect;
+            /// use piet::{Color, RenderContext as _};
+            /// # let mut display = theo::Display::offscreen();
+            /// # let mut surface = display.make_offscreen_surface(4, 4).unwrap();
+            /// # let mut ctx = theo::RenderContext::new(&mut display, &mut surface, 4, 4).unwrap();
+            ///
+            /// // Both rectangles are faded as a group.
+            /// ctx.push_layer(0.5).unwrap();
+            /// ctx.fill(Rect::new(0.0, 0.0, 3.0, 3.0), &Color::BLACK);
+            /// ctx.fill(Rect::new(1.0, 1.0, 4.0, 4.0), &Color::BLACK);
+            /// ctx.pop_layer().unwrap();
+            /// ```
+            ///
+            /// [`pop_layer`]: Self::pop_layer
+            /// [`push_opacity`]: Self::push_opacity
+            /// [`save`]: piet::RenderContext::save
+            /// [`restore`]: piet::RenderContext::restore
+            pub fn push_layer(&mut self, alpha: f64) -> Result<(), Error> {
+                self.push_layer_impl(alpha, BlendMode::SourceOver)
+            }
+
+            /// Draw with the given blend mode inside of the closure.
+            ///
+            /// Everything drawn by `f` is treated as a group, which is blended onto the surface
+            /// using `mode` once `f` returns. This works like [`push_layer`] followed by
+            /// [`pop_layer`], so the state is saved before `f` is called and restored afterwards.
+            /// States saved and layers pushed by `f` must be restored and popped before it
+            /// returns.
+            ///
+            /// # Backend Support
+            ///
+            /// The software rasterizer and the desktop OpenGL backend support every blend mode;
+            /// OpenGL needs version 3.0 or OpenGL ES 3.0, and blends the layer with a shader of
+            /// its own since the renderer's pipeline always blends source-over. The other backends
+            /// can't render layers, for the reasons given in [`push_layer`], and return
+            /// [`Error::NotSupported`] without calling `f`. This includes the default `wgpu`
+            /// backend: its renderer builds its pipelines with a fixed source-over blend state,
+            /// so the modes can't be mapped onto it either.
+            ///
+            /// # Example
+            ///
+            /// ```
+            /// use piet::kurbo::Rect;
+            /// use piet::{Color, RenderContext as _};
+            /// use theo::BlendMode;
+            /// # let mut display = theo::Display::offscreen();
+            /// # let mut surface = display.make_offscreen_surface(4, 4).unwrap();
+            /// # let mut ctx = theo::RenderContext::new(&mut display, &mut surface, 4, 4).unwrap();
+            ///
+            /// ctx.with_blend_mode(BlendMode::Multiply, |ctx| {
+            ///     ctx.fill(Rect::new(0.0, 0.0, 4.0, 4.0), &Color::rgb8(255, 255, 0));
+            /// })
+            /// .unwrap();
+            /// ```
+            ///
+            /// [`push_layer`]: Self::push_layer
+            /// [`pop_layer`]: Self::pop_layer
+            pub fn with_blend_mode<R>(
+                &mut self,
+                mode: BlendMode,
+                f: impl FnOnce(&mut Self) -> R,
+            ) -> Result<R, Error> {
+                self.push_layer_impl(1.0, mode)?;
+                let result = f(self);
+                self.pop_layer()?;
+                Ok(result)
+            }
+
+            fn push_layer_impl(&mut self, alpha: f64, blend: BlendMode) -> Result<(), Error> {
+                let alpha = if alpha.is_nan() {
+                    1.0
+                } else {
+                    alpha.clamp(0.0, 1.0)
+                };
+
+                piet::RenderContext::save(self)?;
+
+                if let Err(err) = self.push_backend_layer(blend) {
+                    piet::RenderContext::restore(self)?;
+                    return Err(err);
+                }
+
+                self.layers.push(Layer {
+                    alpha,
+                    blend,
+                    save_depth: self.save_depth,
+                    opacity_depth: self.opacity.len(),
+                });
+
+                Ok(())
+            }
+
+            /// Composite the layer pushed by the last call to [`push_layer`].
+            ///
+            /// This also restores the state saved by [`push_layer`]. Returns an error if there is
+            /// no layer to pop, or if states saved or opacities pushed inside of the layer haven't
+            /// been restored or popped.
+            ///
+            /// [`push_layer`]: Self::push_layer
+            pub fn pop_layer(&mut self) -> Result<(), Error> {
+                match self.layers.last() {
+                    Some(layer)
+                        if layer.save_depth == self.save_depth
+                            && layer.opacity_depth == self.opacity.len() => {}
+                    _ => return Err(Error::StackUnbalance),
+                }
+                let layer = self.layers.pop().unwrap();
+                self.pop_backend_layer(layer.alpha, layer.blend);
+                piet::RenderContext::restore(self)
+            }
+
+            /// Clip to a shape that is already in device space.
+            fn clip_device(&mut self, shape: impl Shape) {
+                match &mut *self.dispatch {
+                    $(
+                        $(#[$meta])*
+                        ContextDispatch::$name(ctx) => ctx.clip(shape),
                     )*
                 }
             }
 
-            /// Create a new [`Surface`] from a raw window handle.
-            ///
-            /// This is equivalent to [`Display::make_surface`], except that it takes a raw window
-            /// handle instead of a window. This is useful if you want to create a surface from a
-            /// window that doesn't implement [`RawWindowHandle`].
-            ///
-            /// # Asynchronous
-            ///
-            /// This function is asynchronous, as it may be necessary to wait for data to become available.
-            /// This is only used for the [`wgpu`] backend, as it requires the adapter to be created
-            /// asynchronously. For remaining backends, this future will not return `Pending`.
-            ///
-            /// # Safety
-            ///
-            /// The `window` handle must be a valid `window` that isn't currently suspended. The
-            /// `width` and `height` parameters aren't necessarily required to be correct, but
-            /// it's recommended that they are in order to avoid visual bugs.
-            pub async unsafe fn make_surface_from_raw(
+            /// Draw an image, or the `src_rect` part of it, without applying the opacity.
+            #[allow(unreachable_patterns)]
+            fn draw_image_unfaded(
                 &mut self,
-                window: RawWindowHandle,
-                width: u32,
-                height: u32,
-            ) -> Result<Surface, Error> {
-                match &mut *self.dispatch {
+                image: &Image,
+                src_rect: Option<Rect>,
+                dst_rect: Rect,
+                interp: InterpolationMode,
+            ) {
+                use piet::RenderContext as _;
+
+                let Some(mut src_rect) = src_rect else {
+                    let image = match interp {
+                        InterpolationMode::Bilinear => {
+                            let size = piet::Image::size(image);
+                            image.mip_level(self.current_transform(), size, dst_rect.size()).0
+                        }
+                        _ => image,
+                    };
+
+                    self.draw_calls += 1;
+                    match (&mut *self.dispatch, &*image.dispatch) {
+                        $(
+                            $(#[$meta])*
+                            (ContextDispatch::$name(ctx), ImageDispatch::$name(img)) => {
+                                ctx.draw_image(img, dst_rect, interp)
+                            }
+                        )*
+                        _ => self.mismatch = Err(Error::InvalidInput),
+                    }
+                    return;
+                };
+
+                let mut image = image;
+                if let InterpolationMode::Bilinear = interp {
+                    let transform = self.current_transform();
+                    let (mip, scale) = image.mip_level(transform, src_rect.size(), dst_rect.size());
+                    image = mip;
+                    src_rect = Rect::new(
+                        src_rect.x0 * scale.x,
+                        src_rect.y0 * scale.y,
+                        src_rect.x1 * scale.x,
+                        src_rect.y1 * scale.y,
+                    );
+                }
+
+                self.draw_calls += 1;
+                match (&mut *self.dispatch, &*image.dispatch) {
                     $(
                         $(#[$meta])*
-                        DisplayDispatch::$name(display) => {
-                            let surface = display.make_surface(window, width, height).await?;
-                            Ok(SurfaceDispatch::$name(surface).into())
-                        },
+                        (ContextDispatch::$name(ctx), ImageDispatch::$name(img)) => {
+                            ctx.draw_image_area(
+                                img,
+                                src_rect,
+                                dst_rect,
+                                interp
+                            )
+                        }
                     )*
+                    _ => self.mismatch = Err(Error::InvalidInput),
                 }
             }
 
-            /// Push the queue and present to all known surfaces.
-            ///
-            /// This is necessary to call after all windows have been drawn to. It should be called
-            /// once all windows that need to be drawn to have already been drawn to.
-            ///
-            /// # Asynchronous
-            ///
-            /// For most backends, this is a no-op. For [`wgpu`], it submits the queue and then
-            /// waits for all of the queues to finish submitting.
-            pub async fn present(&mut self) {
+            /// Draw text without applying the opacity.
+            fn draw_text_unfaded(&mut self, layout: &TextLayout, pos: Point) {
+                self.draw_calls += 1;
                 match &mut *self.dispatch {
                     $(
                         $(#[$meta])*
-                        DisplayDispatch::$name(ctx) => ctx.present().await,
+                        ContextDispatch::$name(ctx) => ctx.draw_text(layout, pos),
                     )*
                 }
             }
-        }
 
-        impl<'dsp, 'surf> RenderContext<'dsp, 'surf> {
-            /// Create a new [`RenderContext`] from a [`Surface`] and a [`Display`].
-            ///
-            /// This creates a new [`RenderContext`] from a [`Surface`] and a [`Display`]. This is
-            /// the only way to create a [`RenderContext`].
-            #[allow(unreachable_patterns)]
-            pub fn new(
-                display: &'dsp mut Display,
-                surface: &'surf mut Surface,
-                width: u32,
-                height: u32,
-            ) -> Result<Self, Error> {
-                // Make sure there's only one per thread.
-                let prev = HAS_CONTEXT
-                    .try_with(|has_context| has_context.replace(true))
-                    .piet_err()?;
-                if prev {
-                    return Err(Error::BackendError(
-                        "Only one context can be active per thread.".into()
-                    ));
+            /// Start rendering offscreen on the backend, if it can.
+            fn push_backend_layer(&mut self, blend: BlendMode) -> Result<(), Error> {
+                match &mut *self.dispatch {
+                    $(
+                        $(#[$meta])*
+                        ContextDispatch::$name(ctx) => ctx.push_layer(blend),
+                    )*
                 }
+            }
 
-                match (&mut *display.dispatch, &mut *surface.dispatch) {
+            /// Composite the layer pushed by the last call to `push_backend_layer`.
+            fn pop_backend_layer(&mut self, alpha: f64, blend: BlendMode) {
+                match &mut *self.dispatch {
                     $(
                         $(#[$meta])*
-                        (DisplayDispatch::$name(display), SurfaceDispatch::$name(surface)) => {
-                            let ctx = unsafe {
-                                <$ctx>::new(display, surface, width, height)?
-                            };
-
-                            Ok(RenderContext::from_dispatch(
-                                ContextDispatch::$name(ctx),
-                                true
-                            ))
-                        },
+                        ContextDispatch::$name(ctx) => ctx.pop_layer(alpha, blend),
                     )*
-                    _ => Err(Error::InvalidInput)
                 }
             }
 
-            /// Create a new [`RenderContext`] without checking for exclusive access.
-            ///
-            /// # Safety
+            /// Get the brush for a gradient whose stops have already been interpolated.
+            fn cached_gradient(&mut self, gradient: FixedGradient) -> Result<Brush, Error> {
+                let key = self.cache_key();
+                let dispatch = &mut *self.dispatch;
+                self.gradients.get_or_insert(key, gradient, |gradient| {
+                    match dispatch {
+                        $(
+                            $(#[$meta])*
+                            ContextDispatch::$name(ctx) => {
+                                let brush = ctx.gradient(gradient.clone())?;
+                                Ok(Brush::new(
+                                    BrushDispatch::$name(brush),
+                                    BrushSource::Gradient(Rc::new(gradient))
+                                ))
+                            },
+                        )*
+                    }
+                })
+            }
+
+            /// The key that brushes created by this context are cached under.
             ///
-            /// The same as `new`, but you need to make sure that there are no other OpenGL contexts
-            /// active on the current thread.
-            #[allow(unreachable_patterns)]
-            pub unsafe fn new_unchecked(
-                display: &'dsp mut Display,
-                surface: &'surf mut Surface,
-                width: u32,
-                height: u32,
-            ) -> Result<Self, Error> {
-                match (&mut *display.dispatch, &mut *surface.dispatch) {
+            /// Brushes can only be shared between contexts with the same key.
+            fn cache_key(&self) -> u64 {
+                match &*self.dispatch {
                     $(
                         $(#[$meta])*
-                        (DisplayDispatch::$name(display), SurfaceDispatch::$name(surface)) => {
-                            let ctx = <$ctx>::new_unchecked(display, surface, width, height)?;
-                            Ok(RenderContext::from_dispatch(
-                                ContextDispatch::$name(ctx),
-                                false
-                            ))
-                        },
+                        ContextDispatch::$name(ctx) => ctx.cache_key(),
                     )*
-                    _ => Err(Error::InvalidInput)
                 }
             }
         }
@@ -1145,32 +6757,35 @@ macro_rules! make_dispatch {
             }
 
             fn solid_brush(&mut self, color: piet::Color) -> Self::Brush {
-                match &mut *self.dispatch {
-                    $(
-                        $(#[$meta])*
-                        ContextDispatch::$name(ctx) => {
-                            let brush = ctx.solid_brush(color);
-                            BrushDispatch::$name(brush).into()
-                        },
-                    )*
-                }
+                let key = self.cache_key();
+                let dispatch = &mut *self.dispatch;
+                self.solids.get_or_insert(key, color, || {
+                    match dispatch {
+                        $(
+                            $(#[$meta])*
+                            ContextDispatch::$name(ctx) => {
+                                let brush = ctx.solid_brush(color);
+                                Brush::new(BrushDispatch::$name(brush), BrushSource::Solid(color))
+                            },
+                        )*
+                    }
+                })
             }
 
             fn gradient(
                 &mut self,
                 gradient: impl Into<FixedGradient>
             ) -> Result<Self::Brush, Error> {
-                match &mut *self.dispatch {
-                    $(
-                        $(#[$meta])*
-                        ContextDispatch::$name(ctx) => {
-                            Ok(BrushDispatch::$name(ctx.gradient(gradient.into())?).into())
-                        },
-                    )*
+                let mut gradient = gradient.into();
+                if self.gradient_interpolation == GradientInterpolation::LinearRgb {
+                    gradient = linearize_gradient(gradient);
                 }
+
+                self.cached_gradient(gradient)
             }
 
             fn clear(&mut self, region: impl Into<Option<Rect>>, color: piet::Color) {
+                self.draw_calls += 1;
                 match &mut *self.dispatch {
                     $(
                         $(#[$meta])*
@@ -1179,18 +6794,8 @@ macro_rules! make_dispatch {
                 }
             }
 
-            #[allow(unreachable_patterns)]
             fn stroke(&mut self, shape: impl Shape, brush: &impl IntoBrush<Self>, width: f64) {
-                let brush = brush.make_brush(self, || shape.bounding_box());
-                match (&mut *self.dispatch, &*brush.dispatch) {
-                    $(
-                        $(#[$meta])*
-                        (ContextDispatch::$name(ctx), BrushDispatch::$name(brush)) => {
-                            ctx.stroke(shape, brush, width)
-                        },
-                    )*
-                    _ => unreachable!(),
-                }
+                self.stroke_styled(shape, brush, width, &StrokeStyle::new());
             }
 
             #[allow(unreachable_patterns)]
@@ -1201,21 +6806,70 @@ macro_rules! make_dispatch {
                 width: f64,
                 style: &StrokeStyle,
             ) {
+                let style = normalize_stroke_style(style);
+                if let Some(dashes) = dash_shape(&shape, &style, self.current_transform()) {
+                    let mut solid = style.into_owned();
+                    solid.set_dash_pattern(Vec::<f64>::new());
+                    solid.dash_offset = 0.0;
+                    self.stroke_styled(dashes, brush, width, &solid);
+                    return;
+                }
+
                 let brush = brush.make_brush(self, || shape.bounding_box());
+                if let BrushSource::Conic(..) = brush.source {
+                    self.mismatch = Err(Error::NotSupported);
+                    return;
+                }
+                if let BrushSource::Image(image, extend, alpha) = &brush.source {
+                    let (image, extend, alpha) = (image.clone(), *extend, *alpha);
+                    self.stroke_with_image(shape, width, &style, &image, extend, alpha);
+                    return;
+                }
+                if let (false, BrushSource::Solid(color)) = (self.antialias, &brush.source) {
+                    let color = self.fade_global(*color);
+                    self.draw_aliased(shape, color, AliasedDraw::Stroke(width, &style));
+                    return;
+                }
+                let brush = self.apply_global_alpha(brush);
+
+                // The backends join and cap lines in different ways, so the outline of the
+                // stroke is made here and filled instead.
+                let outline = stroke_outline(&shape, width, &style, self.current_transform());
+                self.draw_calls += 1;
                 match (&mut *self.dispatch, &*brush.dispatch) {
                     $(
                         $(#[$meta])*
                         (ContextDispatch::$name(ctx), BrushDispatch::$name(brush)) => {
-                            ctx.stroke_styled(shape, brush, width, style)
+                            match outline {
+                                Some(outline) => ctx.fill(outline, brush),
+                                None => ctx.stroke_styled(shape, brush, width, &style),
+                            }
                         },
                     )*
-                    _ => unreachable!(),
+                    _ => self.mismatch = Err(Error::InvalidInput),
                 }
             }
 
             #[allow(unreachable_patterns)]
             fn fill(&mut self, shape: impl Shape, brush: &impl IntoBrush<Self>) {
                 let brush = brush.make_brush(self, || shape.bounding_box());
+                if let (false, BrushSource::Solid(color)) = (self.antialias, &brush.source) {
+                    let color = self.fade_global(*color);
+                    self.draw_aliased(shape, color, AliasedDraw::Fill(FillRule::NonZero));
+                    return;
+                }
+                if let BrushSource::Image(image, extend, alpha) = &brush.source {
+                    let (image, extend, alpha) = (image.clone(), *extend, *alpha);
+                    self.fill_with_image(shape, FillRule::NonZero, &image, extend, alpha);
+                    return;
+                }
+                if let BrushSource::Conic(conic) = &brush.source {
+                    let conic = conic.clone();
+                    self.fill_with_conic(shape, FillRule::NonZero, &conic);
+                    return;
+                }
+                let brush = self.apply_global_alpha(brush);
+                self.draw_calls += 1;
                 match (&mut *self.dispatch, &*brush.dispatch) {
                     $(
                         $(#[$meta])*
@@ -1223,13 +6877,30 @@ macro_rules! make_dispatch {
                             ctx.fill(shape, brush)
                         },
                     )*
-                    _ => unreachable!(),
+                    _ => self.mismatch = Err(Error::InvalidInput),
                 }
             }
 
             #[allow(unreachable_patterns)]
             fn fill_even_odd(&mut self, shape: impl Shape, brush: &impl IntoBrush<Self>) {
                 let brush = brush.make_brush(self, || shape.bounding_box());
+                if let (false, BrushSource::Solid(color)) = (self.antialias, &brush.source) {
+                    let color = self.fade_global(*color);
+                    self.draw_aliased(shape, color, AliasedDraw::Fill(FillRule::EvenOdd));
+                    return;
+                }
+                if let BrushSource::Image(image, extend, alpha) = &brush.source {
+                    let (image, extend, alpha) = (image.clone(), *extend, *alpha);
+                    self.fill_with_image(shape, FillRule::EvenOdd, &image, extend, alpha);
+                    return;
+                }
+                if let BrushSource::Conic(conic) = &brush.source {
+                    let conic = conic.clone();
+                    self.fill_with_conic(shape, FillRule::EvenOdd, &conic);
+                    return;
+                }
+                let brush = self.apply_global_alpha(brush);
+                self.draw_calls += 1;
                 match (&mut *self.dispatch, &*brush.dispatch) {
                     $(
                         $(#[$meta])*
@@ -1237,17 +6908,12 @@ macro_rules! make_dispatch {
                             ctx.fill_even_odd(shape, brush)
                         },
                     )*
-                    _ => unreachable!(),
+                    _ => self.mismatch = Err(Error::InvalidInput),
                 }
             }
 
             fn clip(&mut self, shape: impl Shape) {
-                match &mut *self.dispatch {
-                    $(
-                        $(#[$meta])*
-                        ContextDispatch::$name(ctx) => ctx.clip(shape),
-                    )*
-                }
+                self.clip_with_rule(shape, FillRule::NonZero)
             }
 
             fn text(&mut self) -> &mut Self::Text {
@@ -1262,30 +6928,48 @@ macro_rules! make_dispatch {
             }
 
             fn draw_text(&mut self, layout: &Self::TextLayout, pos: impl Into<Point>) {
-                match &mut *self.dispatch {
-                    $(
-                        $(#[$meta])*
-                        ContextDispatch::$name(ctx) => ctx.draw_text(layout, pos.into()),
-                    )*
-                }
+                let pos = pos.into();
+                self.draw_faded(|ctx| ctx.draw_text_unfaded(layout, pos));
             }
 
             fn save(&mut self) -> Result<(), Error> {
                 match &mut *self.dispatch {
                     $(
                         $(#[$meta])*
-                        ContextDispatch::$name(ctx) => ctx.save(),
+                        ContextDispatch::$name(ctx) => ctx.save()?,
                     )*
                 }
+
+                self.save_depth += 1;
+                self.saved_states.push(SavedState {
+                    clip_bounds: self.clip_bounds,
+                    antialias: self.antialias,
+                    global_alpha: self.global_alpha,
+                });
+                Ok(())
             }
 
             fn restore(&mut self) -> Result<(), Error> {
+                // Don't restore past the state saved by the current layer.
+                let floor = self.layers.last().map_or(0, |layer| layer.save_depth);
+                if self.save_depth <= floor {
+                    return Err(Error::StackUnbalance);
+                }
+
                 match &mut *self.dispatch {
                     $(
                         $(#[$meta])*
-                        ContextDispatch::$name(ctx) => ctx.restore(),
+                        ContextDispatch::$name(ctx) => ctx.restore()?,
                     )*
                 }
+
+                self.save_depth -= 1;
+                if let Some(state) = self.saved_states.pop() {
+                    self.clip_bounds = state.clip_bounds;
+                    self.antialias = state.antialias;
+                    self.global_alpha = state.global_alpha;
+                }
+                Ok(())
             }
 
             fn finish(&mut self) -> Result<(), Error> {
@@ -1317,32 +7001,27 @@ macro_rules! make_dispatch {
                     $(
                         $(#[$meta])*
                         ContextDispatch::$name(ctx) => {
+                            check_image_size(width, height, ctx.max_texture_size())?;
                             let img = ctx.make_image(width, height, buf, format)?;
-                            Ok(ImageDispatch::$name(img).into())
+                            let mut image = Image::new(ImageDispatch::$name(img), format);
+                            image.pixels = premultiplied_rgba(buf, format).ok().map(|data| {
+                                Rc::new(ImagePixels { width, height, data })
+                            });
+                            Ok(image)
                         }
                     )*
                 }
             }
 
-            #[allow(unreachable_patterns)]
             fn draw_image(
                 &mut self,
                 image: &Self::Image,
                 dst_rect: impl Into<Rect>,
                 interp: InterpolationMode,
             ) {
-                match (&mut *self.dispatch, &*image.dispatch) {
-                    $(
-                        $(#[$meta])*
-                        (ContextDispatch::$name(ctx), ImageDispatch::$name(img)) => {
-                            ctx.draw_image(img, dst_rect.into(), interp)
-                        }
-                    )*
-                    _ => unreachable!(),
-                }
+                self.draw_image_faded(image, None, dst_rect.into(), interp);
             }
 
-            #[allow(unreachable_patterns)]
             fn draw_image_area(
                 &mut self,
                 image: &Self::Image,
@@ -1350,29 +7029,22 @@ macro_rules! make_dispatch {
                 dst_rect: impl Into<Rect>,
                 interp: InterpolationMode,
             ) {
-                match (&mut *self.dispatch, &*image.dispatch) {
-                    $(
-                        $(#[$meta])*
-                        (ContextDispatch::$name(ctx), ImageDispatch::$name(img)) => {
-                            ctx.draw_image_area(
-                                img,
-                                src_rect.into(),
-                                dst_rect.into(),
-                                interp
-                            )
-                        }
-                    )*
-                    _ => unreachable!(),
-                }
+                self.draw_image_faded(image, Some(src_rect.into()), dst_rect.into(), interp);
             }
 
             fn capture_image_area(&mut self, src_rect: impl Into<Rect>) -> Result<Self::Image, Error> {
+                // The backends truncate the rectangle in different ways, so round it here.
+                let src_rect = src_rect.into().abs().round();
+                if src_rect.is_empty() || src_rect.x0 < 0.0 || src_rect.y0 < 0.0 {
+                    return Err(Error::InvalidInput);
+                }
+
                 match &mut *self.dispatch {
                     $(
                         $(#[$meta])*
                         ContextDispatch::$name(ctx) => {
-                            let img = ctx.capture_image_area(src_rect.into())?;
-                            Ok(ImageDispatch::$name(img).into())
+                            let img = ctx.capture_image_area(src_rect)?;
+                            Ok(Image::new(ImageDispatch::$name(img), ImageFormat::RgbaPremul))
                         }
                     )*
                 }
@@ -1381,6 +7053,16 @@ macro_rules! make_dispatch {
             #[allow(unreachable_patterns)]
             fn blurred_rect(&mut self, rect: Rect, blur_radius: f64, brush: &impl IntoBrush<Self>) {
                 let brush = brush.make_brush(self, || rect);
+                if let BrushSource::Image(..) | BrushSource::Conic(..) = brush.source {
+                    self.mismatch = Err(Error::NotSupported);
+                    return;
+                }
+                if let BrushSource::Solid(color) = brush.source {
+                    self.blurred_rect_solid(rect, blur_radius, color);
+                    return;
+                }
+                let brush = self.apply_global_alpha(brush);
+                self.draw_calls += 1;
                 match (&mut *self.dispatch, &*brush.dispatch) {
                     $(
                         $(#[$meta])*
@@ -1388,7 +7070,7 @@ macro_rules! make_dispatch {
                             ctx.blurred_rect(rect, blur_radius, brush)
                         },
                     )*
-                    _ => unreachable!(),
+                    _ => self.mismatch = Err(Error::InvalidInput),
                 }
             }
 
@@ -1402,13 +7084,109 @@ macro_rules! make_dispatch {
             }
         }
 
+        impl Brush {
+            /// Tell whether this brush was created by the same backend as `ctx`.
+            #[allow(unreachable_patterns)]
+            fn belongs_to(&self, ctx: &RenderContext<'_, '_>) -> bool {
+                match (&*ctx.dispatch, &*self.dispatch) {
+                    $(
+                        $(#[$meta])*
+                        (ContextDispatch::$name(_), BrushDispatch::$name(_)) => true,
+                    )*
+                    _ => false,
+                }
+            }
+        }
+
+        impl Image {
+            /// Read the pixels of this image back into memory.
+            ///
+            /// Returns the width and height of the image, followed by its pixels in RGBA order
+            /// with straight alpha, one byte per channel, row by row from the top-left corner.
+            /// This is the same layout as [`RenderContext::finish_to_buffer`], so the pixels can
+            /// be passed to an image encoder to save screenshots taken with
+            /// [`capture_image_area`]. `ctx` must belong to the display that created the image.
+            ///
+            /// # Backend Support
+            ///
+            /// Only the software rasterizer can read images back; it copies the image. The
+            /// hardware-accelerated backends return [`Error::NotSupported`].
+            ///
+            /// # Errors
+            ///
+            /// Returns [`Error::InvalidInput`] if the image was created by another backend.
+            ///
+            /// # Example
+            ///
+            /// ```
+            /// use piet::kurbo::Rect;
+            /// use piet::RenderContext as _;
+            /// use theo::{Display, RenderContext};
+            ///
+            /// let mut display = Display::offscreen();
+            /// let mut surface = display.make_offscreen_surface(2, 2).unwrap();
+            /// let mut ctx = RenderContext::new(&mut display, &mut surface, 2, 2).unwrap();
+            ///
+            /// ctx.clear(None, piet::Color::BLUE);
+            /// ctx.fill(Rect::new(0.0, 0.0, 1.0, 1.0), &piet::Color::RED);
+            /// let screenshot = ctx.capture_image_area(Rect::new(0.0, 0.0, 2.0, 1.0)).unwrap();
+            ///
+            /// let (width, height, pixels) = screenshot.to_rgba8(&mut ctx).unwrap();
+            /// assert_eq!((width, height), (2, 1));
+            /// assert_eq!(pixels, [255, 0, 0, 255, 0, 0, 255, 255]);
+            /// ```
+            ///
+            /// [`capture_image_area`]: piet::RenderContext::capture_image_area
+            #[allow(unreachable_patterns)]
+            pub fn to_rgba8(
+                &self,
+                ctx: &mut RenderContext<'_, '_>,
+            ) -> Result<(u32, u32, Vec<u8>), Error> {
+                match (&mut *ctx.dispatch, &*self.dispatch) {
+                    $(
+                        $(#[$meta])*
+                        (ContextDispatch::$name(ctx), ImageDispatch::$name(img)) => {
+                            ctx.read_image(img)
+                        }
+                    )*
+                    _ => Err(Error::InvalidInput),
+                }
+            }
+        }
+
         impl piet::IntoBrush<RenderContext<'_, '_>> for Brush {
             fn make_brush<'a>(
                 &'a self,
-                _piet: &mut RenderContext<'_, '_>,
-                _bbox: impl FnOnce() -> Rect,
-            ) -> Cow<'a, <RenderContext<'_, '_> as piet::RenderContext>::Brush> {
-                Cow::Borrowed(self)
+                piet: &mut RenderContext<'_, '_>,
+                bbox: impl FnOnce() -> Rect,
+            ) -> Cow<'a, Brush> {
+                let mut brush = Cow::Borrowed(self);
+
+                // Apply the opacity from push_opacity, if any.
+                let opacity = piet.current_opacity();
+                if opacity < 1.0 {
+                    if let Some(faded) = self.with_opacity(piet, opacity) {
+                        brush = Cow::Owned(faded);
+                    }
+                }
+
+                // Recreate brushes that were made by another backend.
+                if !brush.belongs_to(piet) {
+                    if let Some(recreated) = brush.with_opacity(piet, 1.0) {
+                        brush = Cow::Owned(recreated);
+                    }
+                }
+
+                // Repeat extended gradients across the shape.
+                if let BrushSource::ExtendedGradient(gradient, extend) = &brush.source {
+                    if let Some(gradient) = extend_gradient(gradient, *extend, bbox()) {
+                        if let Ok(extended) = piet.cached_gradient(gradient) {
+                            return Cow::Owned(extended);
+                        }
+                    }
+                }
+
+                brush
             }
         }
 
@@ -1459,7 +7237,16 @@ make_dispatch! {
         swrast::RenderContext<'dsp, 'surf>,
         swrast::Brush,
         swrast::Image
-    ),
+    );
+
+    #[cfg(all(feature = "gl", not(target_arch = "wasm32")))]
+    ExternalGl(
+        external_gl::Display,
+        external_gl::Surface,
+        external_gl::RenderContext<'dsp, 'surf>,
+        piet_glow::Brush<glow::Context>,
+        piet_glow::Image<glow::Context>
+    )
 }
 
 /// A wrapper around an error that doesn't expose it to public API.
@@ -1489,16 +7276,6 @@ impl<T, E: std::error::Error + 'static> ResultExt<T, E> for Result<T, E> {
     }
 }
 
-trait OptionExt<T> {
-    fn piet_err(self, message: impl Into<String>) -> Result<T, Error>;
-}
-
-impl<T> OptionExt<T> for Option<T> {
-    fn piet_err(self, message: impl Into<String>) -> Result<T, Error> {
-        self.ok_or_else(|| Error::BackendError(message.into().into()))
-    }
-}
-
 #[derive(Debug)]
 struct SwitchToSwrast;
 
@@ -1512,3 +7289,86 @@ impl fmt::Display for SwitchToSwrast {
 }
 
 impl std::error::Error for SwitchToSwrast {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triangle() -> impl Iterator<Item = PathEl> {
+        [
+            PathEl::MoveTo(Point::new(1.0, 2.0)),
+            PathEl::LineTo(Point::new(9.0, 2.0)),
+            PathEl::LineTo(Point::new(5.0, 7.0)),
+            PathEl::ClosePath,
+        ]
+        .into_iter()
+    }
+
+    /// The triangle's elements, counting how many were pulled in `pulls`.
+    fn counted(pulls: &Cell<usize>) -> impl Iterator<Item = PathEl> + '_ {
+        triangle().inspect(move |_| pulls.set(pulls.get() + 1))
+    }
+
+    #[test]
+    fn streamed_path_is_not_buffered() {
+        let pulls = Cell::new(0);
+        let path = StreamedPath::new(counted(&pulls));
+        let mut elements = path.path_elements(0.1);
+        assert_eq!(elements.next(), triangle().next());
+        assert_eq!(pulls.get(), 1);
+        assert_eq!(elements.count(), 3);
+        assert_eq!(pulls.get(), 4);
+        assert!(path.path.get().is_none());
+        assert!(!path.reused.get());
+    }
+
+    #[test]
+    fn streamed_path_reused_after_streaming() {
+        let path = StreamedPath::new(triangle());
+        assert_eq!(path.path_elements(0.1).count(), 4);
+        assert_eq!(path.path_elements(0.1).count(), 0);
+        assert!(path.reused.get());
+    }
+
+    #[test]
+    fn streamed_path_collected_first() {
+        let path = StreamedPath::new(triangle());
+        assert_eq!(path.bounding_box(), Rect::new(1.0, 2.0, 9.0, 7.0));
+        assert_eq!(path.path_elements(0.1).count(), 4);
+        assert_eq!(path.path_elements(0.1).count(), 4);
+        assert!(!path.reused.get());
+    }
+
+    #[test]
+    fn fill_streamed_solid_brush() {
+        use piet::RenderContext as _;
+
+        let mut display = Display::offscreen();
+        let mut surface = display.make_offscreen_surface(10, 10).unwrap();
+        let mut ctx = RenderContext::new(&mut display, &mut surface, 10, 10).unwrap();
+
+        let pulls = Cell::new(0);
+        let path = StreamedPath::new(counted(&pulls));
+        ctx.fill_streamed(&path, &piet::Color::WHITE);
+        assert_eq!(pulls.get(), 4);
+        assert!(path.path.get().is_none());
+        ctx.status().unwrap();
+    }
+
+    #[test]
+    fn fill_streamed_conic_gradient() {
+        use piet::RenderContext as _;
+
+        let mut display = Display::offscreen();
+        let mut surface = display.make_offscreen_surface(10, 10).unwrap();
+        let mut ctx = RenderContext::new(&mut display, &mut surface, 10, 10).unwrap();
+
+        // Conic gradients are drawn in more than one pass, so the path is collected first.
+        let stops = [piet::Color::WHITE, piet::Color::BLACK];
+        let brush = ctx.conic_gradient((5.0, 5.0), 0.0, &stops[..]);
+        let path = StreamedPath::new(triangle());
+        ctx.fill_streamed(&path, &brush);
+        assert_eq!(path.path.get().map(|path| path.elements().len()), Some(4));
+        ctx.status().unwrap();
+    }
+}
@@ -0,0 +1,70 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later OR MPL-2.0
+// This file is a part of `theo`.
+//
+// `theo` is free software: you can redistribute it and/or modify it under the terms of
+// either:
+//
+// * GNU Lesser General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+// * Mozilla Public License as published by the Mozilla Foundation, version 2.
+//
+// `theo` is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+// See the GNU Lesser General Public License or the Mozilla Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License and the Mozilla
+// Public License along with `theo`. If not, see <https://www.gnu.org/licenses/>.
+
+//! A cache for solid color brushes.
+
+use crate::Brush;
+
+use piet::Color;
+
+/// The number of solid brushes to keep around.
+const CAPACITY: usize = 16;
+
+/// A least-recently-used cache of solid color brushes.
+pub(super) struct SolidCache {
+    /// The cached brushes, keyed by the context that created them and their color, from least
+    /// to most recently used.
+    entries: Vec<((u64, u32), Brush)>,
+}
+
+impl SolidCache {
+    /// Create a new, empty cache.
+    pub(super) fn new() -> Self {
+        Self {
+            entries: Vec::with_capacity(CAPACITY),
+        }
+    }
+
+    /// Get the brush for a color, or create it if it isn't in the cache.
+    ///
+    /// `context` identifies the context that creates the brush, since brushes can't be used
+    /// with other contexts.
+    pub(super) fn get_or_insert(
+        &mut self,
+        context: u64,
+        color: Color,
+        create: impl FnOnce() -> Brush,
+    ) -> Brush {
+        let key = (context, color.as_rgba_u32());
+
+        // Most lookups are for the color that was just used, so search from the back.
+        if let Some(position) = self.entries.iter().rposition(|(entry, _)| *entry == key) {
+            let entry = self.entries.remove(position);
+            let brush = entry.1.clone();
+            self.entries.push(entry);
+            return brush;
+        }
+
+        let brush = create();
+        if self.entries.len() >= CAPACITY {
+            self.entries.remove(0);
+        }
+        self.entries.push((key, brush.clone()));
+
+        brush
+    }
+}
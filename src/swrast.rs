@@ -19,34 +19,70 @@
 
 use crate::text::TextLayoutInner;
 
-use super::text::{Text, TextLayout};
-use super::{DisplayBuilder, Error};
+use super::text::{BundledFonts, Text, TextLayout};
+use super::{
+    demultiply, BlendMode, ColorSpace, DisplayBuilder, Error, FormatInfo, RendererInfo, ResultExt,
+};
 
 use softbuffer as sb;
 
 use piet::kurbo::{Affine, Point, Rect, Shape};
 use piet::{FixedGradient, ImageFormat, InterpolationMode, RenderContext as _, StrokeStyle};
+use piet_tiny_skia::AsPixmapMut as _;
 
 use raw_window_handle::{RawDisplayHandle, RawWindowHandle};
-use tiny_skia::PixmapMut;
+use tiny_skia::{Pixmap, PixmapMut};
 
 use std::mem;
 use std::num::NonZeroU32;
 use std::ptr::NonNull;
 
+/// The largest width or height of an image.
+///
+/// `tiny-skia` stores the length of a row in bytes in an `i32`.
+const MAX_IMAGE_SIZE: u32 = i32::MAX as u32 / 4;
+
 /// The display for the software rasterizer.
 pub(super) struct Display {
     /// The root display for the backend.
-    root: sb::Context,
+    ///
+    /// This is `None` for offscreen displays.
+    root: Option<sb::Context>,
 
     /// `piet-tiny-skia`-specific rendering information.
     cache: piet_tiny_skia::Cache,
+
+    /// The cache used to read images back, created when it's first needed.
+    ///
+    /// `cache` is borrowed by render contexts, so images are drawn into pixmaps with this one.
+    readback: Option<piet_tiny_skia::Cache>,
+
+    /// The number of threads used to convert frames for presentation.
+    threads: usize,
+
+    /// The fonts to load into the text backend of `cache`.
+    fonts: BundledFonts,
+
+    /// Whether `fonts` were loaded yet.
+    fonts_loaded: bool,
 }
 
 /// The surface for the software rasterizer.
 pub(super) struct Surface {
     /// The software rasterizer surface.
-    surface: sb::Surface,
+    surface: SurfaceTarget,
+
+    /// The size of the surface as of the last frame.
+    size: (u32, u32),
+}
+
+/// The target that a surface draws to.
+enum SurfaceTarget {
+    /// A window.
+    Window(sb::Surface),
+
+    /// An in-memory pixmap.
+    Offscreen(Pixmap),
 }
 
 /// The rendering context for the software rasterizer.
@@ -62,19 +98,40 @@ pub(super) struct RenderContext<'dsp, 'surf> {
 
     /// Error from mismatched type usages.
     mismatch_err: Result<(), piet::Error>,
+
+    /// The contents of the surface before each layer was pushed.
+    layers: Vec<Vec<u8>>,
+
+    /// The number of threads used to convert the frame for presentation.
+    threads: usize,
+
+    /// The cache used to read images back.
+    readback: &'dsp mut Option<piet_tiny_skia::Cache>,
 }
 
-struct Buffer<'a> {
-    buffer: sb::Buffer<'a>,
-    width: u32,
-    height: u32,
+enum Buffer<'a> {
+    /// The buffer for a window.
+    Window {
+        buffer: sb::Buffer<'a>,
+        width: u32,
+        height: u32,
+    },
+
+    /// An in-memory pixmap.
+    Offscreen(&'a mut Pixmap),
 }
 
 impl piet_tiny_skia::AsPixmapMut for Buffer<'_> {
     fn as_pixmap_mut(&mut self) -> PixmapMut<'_> {
-        let (width, height) = (self.width, self.height);
-        PixmapMut::from_bytes(bytemuck::cast_slice_mut(&mut self.buffer), width, height)
-            .expect("This should never fail")
+        match self {
+            Buffer::Window {
+                buffer,
+                width,
+                height,
+            } => PixmapMut::from_bytes(bytemuck::cast_slice_mut(buffer), *width, *height)
+                .expect("This should never fail"),
+            Buffer::Offscreen(pixmap) => pixmap.as_mut(),
+        }
     }
 }
 
@@ -83,34 +140,128 @@ pub(crate) type Image = piet_tiny_skia::Image;
 
 impl Display {
     pub(super) unsafe fn new(
-        _builder: &mut DisplayBuilder,
+        builder: &mut DisplayBuilder,
         raw: RawDisplayHandle,
     ) -> Result<Self, Error> {
+        let root = match builder.softbuffer_context.take() {
+            Some(root) => root,
+            None => match sb::Context::from_raw(raw) {
+                Ok(root) => root,
+                Err(sb::SoftBufferError::UnsupportedDisplayPlatform { .. }) => {
+                    return Err(Error::NotSupported)
+                }
+                Err(err) => return Err(err).piet_err(),
+            },
+        };
+
+        let threads = match builder.swrast_threads {
+            0 => std::thread::available_parallelism().map_or(1, |threads| threads.get()),
+            threads => threads,
+        };
+
         Ok(Self {
-            root: sb::Context::from_raw(raw).unwrap(),
+            root: Some(root),
             cache: piet_tiny_skia::Cache::new(),
+            readback: None,
+            threads,
+            fonts: BundledFonts::new(&builder.fonts),
+            fonts_loaded: false,
         })
     }
 
+    pub(super) fn offscreen(builder: &mut DisplayBuilder) -> Self {
+        Self {
+            root: None,
+            cache: piet_tiny_skia::Cache::new(),
+            readback: None,
+            threads: 1,
+            fonts: BundledFonts::new(&builder.fonts),
+            fonts_loaded: false,
+        }
+    }
+
+    /// Load the bundled fonts into the text backend shared by the render contexts.
+    fn load_fonts(&mut self) {
+        if !self.fonts_loaded {
+            let mut text = Text::cosmic(&mut self.cache);
+            self.fonts.load_into(&mut text, &mut self.fonts_loaded);
+        }
+    }
+
     pub(super) async unsafe fn make_surface(
         &mut self,
         raw: RawWindowHandle,
         width: u32,
         height: u32,
     ) -> Result<Surface, Error> {
-        let mut surface = unsafe { sb::Surface::from_raw(&self.root, raw).unwrap() };
+        let root = self.root.as_ref().ok_or(Error::NotSupported)?;
+        let surface = unsafe { sb::Surface::from_raw(root, raw).piet_err()? };
+        self.make_softbuffer_surface(surface, width, height)
+    }
 
+    pub(super) fn make_softbuffer_surface(
+        &mut self,
+        mut surface: sb::Surface,
+        width: u32,
+        height: u32,
+    ) -> Result<Surface, Error> {
         surface
             .resize(
-                NonZeroU32::new(width).unwrap(),
-                NonZeroU32::new(height).unwrap(),
+                NonZeroU32::new(width).ok_or(Error::InvalidInput)?,
+                NonZeroU32::new(height).ok_or(Error::InvalidInput)?,
             )
-            .unwrap();
+            .piet_err()?;
+
+        Ok(Surface {
+            surface: SurfaceTarget::Window(surface),
+            size: (width, height),
+        })
+    }
+
+    pub(super) fn softbuffer_context(&self) -> Option<&sb::Context> {
+        self.root.as_ref()
+    }
+
+    pub(super) fn make_offscreen_surface(
+        &mut self,
+        width: u32,
+        height: u32,
+    ) -> Result<Surface, Error> {
+        let pixmap = Pixmap::new(width, height).ok_or(Error::InvalidInput)?;
+
+        Ok(Surface {
+            surface: SurfaceTarget::Offscreen(pixmap),
+            size: (width, height),
+        })
+    }
+
+    pub(super) fn make_pixmap_surface(&mut self, pixmap: Pixmap) -> Surface {
+        Surface {
+            size: (pixmap.width(), pixmap.height()),
+            surface: SurfaceTarget::Offscreen(pixmap),
+        }
+    }
+
+    pub(super) fn text(&mut self) -> Text {
+        self.load_fonts();
+        Text::cosmic(&mut self.cache)
+    }
 
-        Ok(Surface { surface })
+    pub(super) fn max_texture_size(&self) -> Option<u32> {
+        Some(MAX_IMAGE_SIZE)
+    }
+
+    pub(super) fn renderer_info(&self) -> Option<RendererInfo> {
+        Some(RendererInfo {
+            backend: "Software".into(),
+            name: "software rasterizer".into(),
+            vendor: "tiny-skia".into(),
+            driver: String::new(),
+        })
     }
 
     pub(super) fn supports_transparency(&self) -> bool {
+        // softbuffer requires the alpha channel to be zero on every platform.
         false
     }
 
@@ -118,12 +269,128 @@ impl Display {
         None
     }
 
+    pub(super) fn x11_visual_info(&self) -> Option<crate::X11VisualInfo> {
+        None
+    }
+
+    pub(super) fn destroy_surface(&mut self, surface: Surface) -> Result<(), Error> {
+        // The surface's resources are freed when it is dropped.
+        drop(surface);
+        Ok(())
+    }
+
     pub(super) async fn present(&mut self) {
         // no-op
     }
+
+    pub(super) fn poll(&mut self) {
+        // no-op
+    }
+
+    pub(super) fn device_lost(&self) -> bool {
+        // There is no device to lose.
+        false
+    }
+
+    pub(super) fn begin_frame(&mut self) {
+        // no-op
+    }
+
+    pub(super) async fn end_frame(&mut self) -> Result<(), Error> {
+        // no-op
+        Ok(())
+    }
+}
+
+impl Surface {
+    pub(super) fn pixmap(&self) -> Option<&Pixmap> {
+        match &self.surface {
+            SurfaceTarget::Window(_) => None,
+            SurfaceTarget::Offscreen(pixmap) => Some(pixmap),
+        }
+    }
+
+    pub(super) fn pixmap_mut(&mut self) -> Option<&mut Pixmap> {
+        match &mut self.surface {
+            SurfaceTarget::Window(_) => None,
+            SurfaceTarget::Offscreen(pixmap) => Some(pixmap),
+        }
+    }
+
+    pub(super) fn softbuffer_surface(&self) -> Option<&sb::Surface> {
+        match &self.surface {
+            SurfaceTarget::Window(surface) => Some(surface),
+            SurfaceTarget::Offscreen(_) => None,
+        }
+    }
+
+    pub(super) fn softbuffer_surface_mut(&mut self) -> Option<&mut sb::Surface> {
+        match &mut self.surface {
+            SurfaceTarget::Window(surface) => Some(surface),
+            SurfaceTarget::Offscreen(_) => None,
+        }
+    }
+
+    pub(super) fn format_info(&self, _display: &Display) -> Result<FormatInfo, Error> {
+        // softbuffer uses 0RGB pixels, while pixmaps are premultiplied RGBA.
+        let alpha_bits = match &self.surface {
+            SurfaceTarget::Window(_) => 0,
+            SurfaceTarget::Offscreen(_) => 8,
+        };
+
+        Ok(FormatInfo {
+            red_bits: 8,
+            green_bits: 8,
+            blue_bits: 8,
+            alpha_bits,
+            color_space: ColorSpace::Linear,
+            #[cfg(feature = "wgpu")]
+            wgpu_format: None,
+        })
+    }
+
+    pub(super) fn is_renderable(&self, _display: &Display) -> bool {
+        // Software buffers can't be lost, so only the size matters.
+        self.size.0 > 0 && self.size.1 > 0
+    }
+
+    pub(super) fn snapshot(
+        &mut self,
+        _display: &mut Display,
+    ) -> Result<(u32, u32, Vec<u8>), Error> {
+        match &mut self.surface {
+            SurfaceTarget::Window(surface) => {
+                // Read back what is actually shown in the window. The buffer has the size that
+                // the surface was last resized to.
+                let buffer = surface.fetch().piet_err()?;
+                let (width, height) = self.size;
+                if buffer.len() != width as usize * height as usize {
+                    return Err(Error::BackendError(
+                        "The window contents don't match the size of the surface".into(),
+                    ));
+                }
+
+                let pixels = buffer
+                    .into_iter()
+                    .flat_map(|pixel| {
+                        let [b, g, r, _] = pixel.to_le_bytes();
+                        [r, g, b, 0xFF]
+                    })
+                    .collect();
+                Ok((width, height, pixels))
+            }
+
+            SurfaceTarget::Offscreen(pixmap) => {
+                Ok((pixmap.width(), pixmap.height(), demultiply(pixmap.data())))
+            }
+        }
+    }
 }
 
 impl<'dsp, 'surf> RenderContext<'dsp, 'surf> {
+    /// Drawing to a buffer doesn't involve any thread-local state.
+    pub(super) const EXCLUSIVE: bool = false;
+
     pub(super) unsafe fn new(
         display: &'dsp mut Display,
         surface: &'surf mut Surface,
@@ -133,21 +400,44 @@ impl<'dsp, 'surf> RenderContext<'dsp, 'surf> {
         let width = NonZeroU32::new(width).ok_or(Error::InvalidInput)?;
         let height = NonZeroU32::new(height).ok_or(Error::InvalidInput)?;
 
-        // Resize the surface.
-        surface.surface.resize(width, height).unwrap();
+        let buffer = match &mut surface.surface {
+            SurfaceTarget::Window(window) => {
+                // Resize the surface. Only record the new size once the buffer has it.
+                window.resize(width, height).piet_err()?;
+                surface.size = (width.get(), height.get());
+
+                Buffer::Window {
+                    buffer: window.buffer_mut().piet_err()?,
+                    width: width.get(),
+                    height: height.get(),
+                }
+            }
+
+            SurfaceTarget::Offscreen(pixmap) => {
+                // Reallocate the pixmap if the size changed.
+                if pixmap.width() != width.get() || pixmap.height() != height.get() {
+                    *pixmap = Pixmap::new(width.get(), height.get()).ok_or(Error::InvalidInput)?;
+                }
+                surface.size = (width.get(), height.get());
+
+                Buffer::Offscreen(pixmap)
+            }
+        };
 
         // Create the context.
-        let mut context = display.cache.render_context(Buffer {
-            buffer: surface.surface.buffer_mut().unwrap(),
-            width: width.get(),
-            height: height.get(),
-        });
+        display.load_fonts();
+        let threads = display.threads;
+        let readback = &mut display.readback;
+        let mut context = display.cache.render_context(buffer);
 
         Ok(Self {
-            text: Text(crate::text::TextInner::Cosmic(context.text().clone())),
+            text: Text::new(crate::text::TextInner::Cosmic(context.text().clone())),
             inner: Some(context),
             dirty: false,
             mismatch_err: Ok(()),
+            layers: Vec::new(),
+            threads,
+            readback,
         })
     }
 
@@ -160,6 +450,11 @@ impl<'dsp, 'surf> RenderContext<'dsp, 'surf> {
         Self::new(display, surface, width, height)
     }
 
+    /// Brushes don't depend on the surface they are drawn to.
+    pub(super) fn cache_key(&self) -> u64 {
+        0
+    }
+
     fn inner(&mut self) -> &mut piet_tiny_skia::RenderContext<'dsp, Buffer<'surf>> {
         self.inner
             .as_mut()
@@ -185,7 +480,15 @@ impl<'dsp, 'surf> RenderContext<'dsp, 'surf> {
     }
 
     pub(super) fn clear(&mut self, region: Option<Rect>, color: piet::Color) {
-        self.inner().clear(region, color);
+        // The renderer always clears to an opaque color, so fill translucent colors ourselves.
+        let (r, g, b, a) = color.as_rgba();
+        if region.is_none() && a < 1.0 {
+            let color = tiny_skia::Color::from_rgba(r as f32, g as f32, b as f32, a as f32)
+                .unwrap_or(tiny_skia::Color::TRANSPARENT);
+            self.inner().target_mut().as_pixmap_mut().fill(color);
+        } else {
+            self.inner().clear(region, color);
+        }
         self.dirty = true;
     }
 
@@ -226,10 +529,13 @@ impl<'dsp, 'surf> RenderContext<'dsp, 'surf> {
 
     #[allow(unreachable_patterns)]
     pub(super) fn draw_text(&mut self, layout: &TextLayout, pos: impl Into<Point>) {
+        let pos = pos.into();
         let layout = match &layout.0 {
             TextLayoutInner::Cosmic(ct) => ct,
+
+            // Layouts from the GPU renderers can't be drawn by this one.
             _ => {
-                self.mismatch_err = Err(piet::Error::NotSupported);
+                self.mismatch_err = Err(Error::InvalidInput);
                 return;
             }
         };
@@ -245,27 +551,144 @@ impl<'dsp, 'surf> RenderContext<'dsp, 'surf> {
         self.inner().restore()
     }
 
-    pub(super) fn finish(&mut self) -> Result<(), Error> {
-        // Wrap and get the inner buffer.
-        let Buffer { mut buffer, .. } = self.inner.take().unwrap().into_target();
+    pub(super) fn flush(&mut self) -> Result<(), Error> {
+        // Drawing happens immediately.
+        Ok(())
+    }
 
-        // tiny-skia uses an RGBA format, while softbuffer uses XRGB. To convert, we need to
-        // iterate over the pixels and shift the pixels over.
-        buffer.iter_mut().for_each(|pixel| {
-            let [r, g, b, _] = pixel.to_ne_bytes();
-            *pixel = (b as u32) | ((g as u32) << 8) | ((r as u32) << 16);
-        });
+    pub(super) fn push_layer(&mut self, blend: BlendMode) -> Result<(), Error> {
+        // Remember what the surface looked like before the layer was drawn.
+        let mut target = self.inner().target_mut().as_pixmap_mut();
+        let backdrop = target.data_mut().to_vec();
 
-        // Upload the buffer.
-        buffer.present().unwrap();
+        // For blend modes other than source-over, the layer needs to be drawn on its own.
+        if blend != BlendMode::SourceOver {
+            target.data_mut().fill(0);
+        }
 
+        self.layers.push(backdrop);
         Ok(())
     }
 
+    pub(super) fn pop_layer(&mut self, alpha: f64, blend: BlendMode) {
+        let backdrop = match self.layers.pop() {
+            Some(backdrop) => backdrop,
+            None => return,
+        };
+
+        let mut target = self.inner().target_mut().as_pixmap_mut();
+
+        if blend == BlendMode::SourceOver {
+            // The layer has been drawn at full opacity on top of the backdrop. Interpolating
+            // between the two is the same as blending the layer onto the backdrop with its
+            // opacity scaled.
+            for (pixel, backdrop) in target.data_mut().iter_mut().zip(backdrop) {
+                let (pixel_f, backdrop_f) = (f64::from(*pixel), f64::from(backdrop));
+                *pixel = (backdrop_f + (pixel_f - backdrop_f) * alpha).round() as u8;
+            }
+        } else {
+            // The layer has been drawn on its own, so blend it onto the backdrop.
+            let data = target.data_mut();
+            for (pixel, backdrop) in data.chunks_exact_mut(4).zip(backdrop.chunks_exact(4)) {
+                let to_float = |channel: u8| f64::from(channel) / 255.0;
+                let src = [0, 1, 2, 3].map(|i| to_float(pixel[i]) * alpha);
+                let dst = [0, 1, 2, 3].map(|i| to_float(backdrop[i]));
+                let (src_alpha, dst_alpha) = (src[3], dst[3]);
+
+                for i in 0..4 {
+                    let (s, d) = (src[i], dst[i]);
+                    let result = match blend {
+                        BlendMode::Multiply if i < 3 => {
+                            s * d + s * (1.0 - dst_alpha) + d * (1.0 - src_alpha)
+                        }
+                        BlendMode::Multiply | BlendMode::Screen => s + d - s * d,
+                        BlendMode::Add => s + d,
+                        BlendMode::SourceOver => s + d * (1.0 - src_alpha),
+                    };
+
+                    pixel[i] = (result.clamp(0.0, 1.0) * 255.0).round() as u8;
+                }
+            }
+        }
+
+        self.dirty = true;
+    }
+
+    pub(super) fn finish(&mut self) -> Result<(), Error> {
+        self.finish_impl(false, &[]).map(|_| ())
+    }
+
+    pub(super) fn finish_with_damage(&mut self, regions: &[Rect]) -> Result<(), Error> {
+        self.finish_impl(false, regions).map(|_| ())
+    }
+
+    pub(super) fn finish_to_buffer(&mut self) -> Result<Vec<u8>, Error> {
+        self.finish_impl(true, &[]).map(Option::unwrap_or_default)
+    }
+
+    fn finish_impl(&mut self, read: bool, damage: &[Rect]) -> Result<Option<Vec<u8>>, Error> {
+        // Wrap and get the inner buffer.
+        let target = self
+            .inner
+            .take()
+            .expect("Tried to use context after finish()")
+            .into_target();
+
+        match target {
+            Buffer::Window {
+                mut buffer,
+                width,
+                height,
+            } => {
+                let pixels = read.then(|| demultiply(bytemuck::cast_slice(&buffer)));
+
+                // tiny-skia uses an RGBA format, while softbuffer uses XRGB. To convert, we need
+                // to iterate over the pixels and shift the pixels over. softbuffer requires the
+                // top byte to be zero, so the alpha channel is dropped; since the colors are
+                // premultiplied, this composites them over black.
+                for_each_band(&mut buffer, width as usize, self.threads, |band| {
+                    band.iter_mut().for_each(|pixel| {
+                        let [r, g, b, _] = pixel.to_ne_bytes();
+                        *pixel = (b as u32) | ((g as u32) << 8) | ((r as u32) << 16);
+                    });
+                });
+
+                // Only upload the damaged parts of the buffer, if there are any.
+                let bounds = Rect::new(0.0, 0.0, width as f64, height as f64);
+                let damage = damage
+                    .iter()
+                    .filter_map(|region| {
+                        let region = region.abs().expand().intersect(bounds);
+                        Some(sb::Rect {
+                            x: region.x0 as u32,
+                            y: region.y0 as u32,
+                            width: NonZeroU32::new(region.width() as u32)?,
+                            height: NonZeroU32::new(region.height() as u32)?,
+                        })
+                    })
+                    .collect::<Vec<_>>();
+
+                if damage.is_empty() {
+                    buffer.present().piet_err()?;
+                } else {
+                    buffer.present_with_damage(&damage).piet_err()?;
+                }
+
+                Ok(pixels)
+            }
+
+            Buffer::Offscreen(pixmap) => Ok(read.then(|| demultiply(pixmap.data()))),
+        }
+    }
+
     pub(super) fn transform(&mut self, transform: Affine) {
         self.inner().transform(transform);
     }
 
+    pub(super) fn max_texture_size(&self) -> u32 {
+        MAX_IMAGE_SIZE
+    }
+
     pub(super) fn make_image(
         &mut self,
         width: usize,
@@ -297,6 +720,21 @@ impl<'dsp, 'surf> RenderContext<'dsp, 'surf> {
         self.inner().capture_image_area(src_rect)
     }
 
+    pub(super) fn read_image(&mut self, image: &Image) -> Result<(u32, u32, Vec<u8>), Error> {
+        let size = piet::Image::size(image);
+        let (width, height) = (size.width as u32, size.height as u32);
+        let mut pixmap = Pixmap::new(width, height).ok_or(Error::InvalidInput)?;
+
+        // The image can't be accessed directly, so copy it into a pixmap of the same size.
+        let cache = self.readback.get_or_insert_with(piet_tiny_skia::Cache::new);
+        let mut context = cache.render_context(Buffer::Offscreen(&mut pixmap));
+        context.draw_image(image, size.to_rect(), InterpolationMode::NearestNeighbor);
+        context.finish()?;
+        drop(context);
+
+        Ok((width, height, demultiply(pixmap.data())))
+    }
+
     pub(super) fn blurred_rect(&mut self, _rect: Rect, _blur_radius: f64, _brush: &Brush) {
         self.inner().blurred_rect(_rect, _blur_radius, _brush);
         self.dirty = true;
@@ -306,3 +744,33 @@ impl<'dsp, 'surf> RenderContext<'dsp, 'surf> {
         self.inner.as_ref().unwrap().current_transform()
     }
 }
+
+/// The fewest pixels worth handing to another thread.
+const MIN_PIXELS_PER_THREAD: usize = 256 * 256;
+
+/// Run `f` over bands of whole rows of `pixels`, using up to `threads` threads.
+fn for_each_band(pixels: &mut [u32], width: usize, threads: usize, f: impl Fn(&mut [u32]) + Sync) {
+    let threads = threads.min(pixels.len() / MIN_PIXELS_PER_THREAD).max(1);
+    if threads == 1 || width == 0 {
+        f(pixels);
+        return;
+    }
+
+    let rows = pixels.len() / width;
+    let band_len = ((rows + threads - 1) / threads) * width;
+    let f = &f;
+
+    std::thread::scope(|scope| {
+        let mut bands = pixels.chunks_mut(band_len);
+
+        // Keep the first band for this thread.
+        let first = bands.next();
+        for band in bands {
+            scope.spawn(move || f(band));
+        }
+
+        if let Some(band) = first {
+            f(band);
+        }
+    });
+}
@@ -30,9 +30,94 @@ use piet_wgpu::{
     Text as WgpuText, TextLayout as WgpuTextLayout, TextLayoutBuilder as WgpuTextLayoutBuilder,
 };
 
+use piet::kurbo::{Point, Rect};
+use piet::{RenderContext as _, Text as _, TextAlignment, TextAttribute, TextLayoutBuilder as _};
+use piet_cosmic_text::Text as MeasureText;
+
+use std::cell::{Ref, RefCell};
+use std::ops::{Bound, Range, RangeBounds};
+use std::rc::Rc;
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+
 /// The text backend for the system.
+///
+/// The fonts installed on the system are loaded in the background when the backend is created.
+/// Looking up one of them with [`font_family`] works even if they haven't finished loading, and
+/// [`available_families`] lists all of them.
+///
+/// # Emoji
+///
+/// Emoji are drawn from whichever font installed on the system, or loaded with [`load_font`],
+/// has glyphs for them. Without one, they are missing glyphs, and are drawn according to the
+/// [`MissingGlyphPolicy`]. Color glyphs aren't supported yet, so depending on the font and the
+/// backend, emoji are either drawn in the color of the text or not drawn at all.
+///
+/// # Antialiasing
+///
+/// Glyphs are drawn with grayscale antialiasing and without hinting on every backend. The GPU
+/// backends rasterize each glyph into a texture with `swash`, and the software rasterizer fills
+/// the glyph outlines with `tiny-skia`. Neither renderer offers hinting or subpixel antialiasing
+/// yet, so these can't be configured.
+///
+/// # Hit Testing
+///
+/// [`hit_test_point`] and [`hit_test_text_position`] work with byte indices into the whole
+/// text, and give the same results on every backend. Positions are placed on the baseline of
+/// their line, at the leading edge of the glyph they start, or at the trailing edge of the last
+/// glyph on the line if they are at its end. Like [`TextLayout::glyph_rects`], the layout is
+/// measured again the first time it is hit tested.
+///
+/// ```
+/// use piet::{Text as _, TextLayout as _, TextLayoutBuilder as _};
+/// use theo::Display;
+///
+/// let mut display = Display::offscreen();
+/// let text = "Hello\nworld";
+/// let layout = display.text().new_text_layout(text).build().unwrap();
+///
+/// // Every caret position maps back to the same index.
+/// for idx in (0..=text.len()).filter(|&idx| text.is_char_boundary(idx)) {
+///     let position = layout.hit_test_text_position(idx);
+///     assert_eq!(position.line, if idx <= 5 { 0 } else { 1 });
+///     assert_eq!(layout.hit_test_point(position.point).idx, idx);
+/// }
+///
+/// // Clicking just right of the middle of the "w" puts the caret after it.
+/// let w = layout.hit_test_text_position(6).point;
+/// let o = layout.hit_test_text_position(7).point;
+/// let hit = layout.hit_test_point(w + ((o.x - w.x) * 0.6, 0.0));
+/// assert_eq!(hit.idx, 7);
+/// assert!(hit.is_inside);
+/// ```
+///
+/// [`font_family`]: piet::Text::font_family
+/// [`available_families`]: Text::available_families
+/// [`load_font`]: piet::Text::load_font
+/// [`hit_test_point`]: piet::TextLayout::hit_test_point
+/// [`hit_test_text_position`]: piet::TextLayout::hit_test_text_position
 #[derive(Clone)]
-pub struct Text(pub(crate) TextInner);
+pub struct Text(pub(crate) TextInner, pub(crate) MissingGlyphPolicy);
+
+/// What to draw for characters that none of the fonts have a glyph for.
+///
+/// See [`DisplayBuilder::missing_glyph`] for more information.
+///
+/// [`DisplayBuilder::missing_glyph`]: crate::DisplayBuilder::missing_glyph
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum MissingGlyphPolicy {
+    /// Draw the font's placeholder glyph, which is usually an empty box.
+    #[default]
+    Tofu,
+
+    /// Draw nothing, but keep the space that the placeholder glyph would take up.
+    Blank,
+
+    /// Lay the characters out again with another font family.
+    ///
+    /// If that family doesn't have them either, its placeholder glyph is drawn.
+    FallbackFont(piet::FontFamily),
+}
 
 #[derive(Clone)]
 pub(crate) enum TextInner {
@@ -43,8 +128,119 @@ pub(crate) enum TextInner {
     Cosmic(CosmicText),
 }
 
+impl Text {
+    /// Wrap a renderer's text backend.
+    pub(crate) fn new(inner: TextInner) -> Self {
+        Self(inner, MissingGlyphPolicy::default())
+    }
+
+    /// Set what layouts created from now on draw for missing glyphs.
+    pub(crate) fn set_missing_glyph(&mut self, policy: MissingGlyphPolicy) {
+        self.1 = policy;
+    }
+
+    /// List the names of the font families installed on the system.
+    ///
+    /// The names are sorted, and can be passed to [`font_family`]. Fonts added with
+    /// [`load_font`] are not included.
+    ///
+    /// The system fonts are scanned the first time this is called, which can take a while. The
+    /// result is reused afterwards.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use piet::Text as _;
+    /// use theo::Display;
+    ///
+    /// let mut display = Display::offscreen();
+    /// let mut text = display.text();
+    ///
+    /// for name in text.available_families() {
+    ///     assert!(text.font_family(&name).is_some());
+    /// }
+    /// ```
+    ///
+    /// [`font_family`]: piet::Text::font_family
+    /// [`load_font`]: piet::Text::load_font
+    pub fn available_families(&self) -> Vec<String> {
+        system_families().to_vec()
+    }
+
+    /// Load a font into the renderer's text backend, without making it available for measuring.
+    fn load_font_into_backend(&mut self, data: &[u8]) -> Result<piet::FontFamily, piet::Error> {
+        match &mut self.0 {
+            #[cfg(feature = "gl")]
+            TextInner::Glow(inner) => inner.load_font(data),
+            #[cfg(feature = "wgpu")]
+            TextInner::Wgpu(inner) => inner.load_font(data),
+            TextInner::Cosmic(inner) => inner.load_font(data),
+        }
+    }
+
+    /// Create a text backend that uses `cosmic-text` directly, sharing the fonts in `cache`.
+    pub(crate) fn cosmic(cache: &mut piet_tiny_skia::Cache) -> Self {
+        // The text backend is only exposed through a render context, so use a dummy target.
+        let target = tiny_skia::Pixmap::new(1, 1).expect("1x1 pixmap is always valid");
+        let mut context = cache.render_context(target);
+        Self::new(TextInner::Cosmic(context.text().clone()))
+    }
+}
+
+/// The fonts passed to [`DisplayBuilder::font`], which every text backend of a display loads.
+///
+/// [`DisplayBuilder::font`]: crate::DisplayBuilder::font
+#[derive(Clone, Default)]
+pub(crate) struct BundledFonts(Rc<[Arc<[u8]>]>);
+
+impl BundledFonts {
+    pub(crate) fn new(fonts: &[Arc<[u8]>]) -> Self {
+        Self(fonts.into())
+    }
+
+    /// Make the fonts available for measuring glyphs.
+    ///
+    /// This is done once for each display, as the glyphs are measured by a single text backend.
+    pub(crate) fn register(&self) {
+        if !self.0.is_empty() {
+            loaded_fonts()
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .extend(self.0.iter().cloned());
+        }
+    }
+
+    /// Create a text backend that uses `cosmic-text` directly, with the fonts loaded.
+    pub(crate) fn cosmic_text(&self) -> Text {
+        let mut text = Text::cosmic(&mut piet_tiny_skia::Cache::new());
+        self.load_into(&mut text, &mut false);
+        text
+    }
+
+    /// Load the fonts into a text backend, unless `loaded` says that it already has them.
+    ///
+    /// The renderers hand out a new [`Text`] for every frame that shares their fonts, so they keep
+    /// track of whether the fonts were loaded themselves.
+    pub(crate) fn load_into(&self, text: &mut Text, loaded: &mut bool) {
+        if *loaded {
+            return;
+        }
+        *loaded = true;
+
+        for data in self.0.iter() {
+            if let Err(err) = text.load_font_into_backend(data) {
+                tracing::warn!("Failed to load bundled font: {}", err);
+            }
+        }
+    }
+}
+
 /// The text layout builder for the system.
-pub struct TextLayoutBuilder(pub(crate) TextLayoutBuilderInner);
+pub struct TextLayoutBuilder(
+    pub(crate) TextLayoutBuilderInner,
+    pub(crate) LayoutSource,
+    pub(crate) Text,
+);
 
 pub(crate) enum TextLayoutBuilderInner {
     #[cfg(feature = "gl")]
@@ -55,8 +251,485 @@ pub(crate) enum TextLayoutBuilderInner {
 }
 
 /// The text layout for the system.
+///
+/// A layout can be drawn by the renderer whose [`Text`] built it, or by any renderer if it was
+/// built with [`Display::text`]. Drawing a layout built by another renderer draws nothing, and
+/// makes [`status`] return [`Error::InvalidInput`].
+///
+/// [`Display::text`]: crate::Display::text
+/// [`status`]: piet::RenderContext::status
+/// [`Error::InvalidInput`]: piet::Error::InvalidInput
+#[derive(Clone)]
+pub struct TextLayout(
+    pub(crate) TextLayoutInner,
+    pub(crate) Rc<LayoutSource>,
+    pub(crate) Text,
+);
+
+/// The parameters used to build a [`TextLayout`].
+///
+/// This is kept around so that the layout can be rebuilt by a different text backend.
+#[derive(Clone)]
+pub(crate) struct LayoutSource {
+    /// The text to lay out.
+    text: Arc<str>,
+
+    /// The maximum width of the layout.
+    max_width: Option<f64>,
+
+    /// The alignment of the layout.
+    alignment: Option<TextAlignment>,
+
+    /// The attributes applied to the entire layout.
+    default_attributes: Vec<TextAttribute>,
+
+    /// The attributes applied to ranges of the layout.
+    range_attributes: Vec<(Range<usize>, TextAttribute)>,
+
+    /// What to draw for glyphs that are missing from every font.
+    missing_glyph: MissingGlyphPolicy,
+
+    /// The measured lines of the layout, filled in the first time they are needed.
+    lines: OnceLock<Vec<LineInfo>>,
+
+    /// The layout rebuilt by the text backend of the renderer that last drew it.
+    rebuilt: RefCell<Option<TextLayout>>,
+}
+
+/// A visual line of a measured layout.
 #[derive(Clone)]
-pub struct TextLayout(pub(crate) TextLayoutInner);
+struct LineInfo {
+    /// The byte index where the line starts.
+    start: usize,
+
+    /// The top of the line.
+    top: f64,
+
+    /// The baseline of the line.
+    baseline: f64,
+
+    /// The height of the line.
+    height: f64,
+
+    /// The glyphs on the line, in visual order.
+    glyphs: Vec<GlyphInfo>,
+}
+
+/// A glyph of a measured layout.
+#[derive(Clone)]
+struct GlyphInfo {
+    /// The byte index of the text that the glyph starts at.
+    start: usize,
+
+    /// The byte index of the text that the glyph ends at.
+    end: usize,
+
+    /// The left edge of the glyph.
+    x: f64,
+
+    /// The advance of the glyph.
+    width: f64,
+
+    /// Whether the glyph is laid out from right to left.
+    rtl: bool,
+}
+
+impl TextLayout {
+    /// Get this layout as built by another text backend.
+    ///
+    /// This is used to draw layouts created by [`Display::text`] on backends that do not use
+    /// `cosmic-text` directly. The layout is only shaped again the first time it is drawn by a
+    /// kind of backend, and the result is shared by all clones of the layout.
+    ///
+    /// [`Display::text`]: crate::Display::text
+    pub(crate) fn rebuild(&self, text: &mut Text) -> Result<Ref<'_, TextLayout>, piet::Error> {
+        let cached = self.1.rebuilt.borrow();
+        if cached
+            .as_ref()
+            .is_some_and(|layout| layout.0.built_by(&text.0))
+        {
+            return Ok(Ref::map(cached, |cached| cached.as_ref().unwrap()));
+        }
+        drop(cached);
+
+        // The missing glyphs were already replaced when this layout was built.
+        let policy = std::mem::take(&mut text.1);
+        let layout = self.1.build(text);
+        text.1 = policy;
+
+        *self.1.rebuilt.borrow_mut() = Some(layout?);
+        Ok(Ref::map(self.1.rebuilt.borrow(), |cached| {
+            cached.as_ref().unwrap()
+        }))
+    }
+
+    /// Change the width that the text is wrapped to.
+    ///
+    /// This is like building the layout again with the same text and attributes, but a different
+    /// [`max_width`], without having to go through the [`TextLayoutBuilder`] again. It is meant
+    /// for reflowing text while a window is being resized. The layout is rebuilt with the same
+    /// text backend that built it, and nothing is done if the width hasn't changed.
+    ///
+    /// The shaped text is kept in an immutable buffer by the text backends, so the text is
+    /// shaped again along with being wrapped. Changing the width every frame costs about as much
+    /// as building a new layout every frame.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use piet::{Text as _, TextLayout as _, TextLayoutBuilder as _};
+    /// use theo::Display;
+    ///
+    /// let mut display = Display::offscreen();
+    /// let mut layout = display.text().new_text_layout("Hello world").build().unwrap();
+    /// assert_eq!(layout.line_count(), 1);
+    ///
+    /// // The words no longer fit next to each other.
+    /// let width = layout.size().width;
+    /// layout.set_max_width(width * 0.75).unwrap();
+    /// assert_eq!(layout.line_count(), 2);
+    ///
+    /// layout.set_max_width(width * 2.0).unwrap();
+    /// assert_eq!(layout.line_count(), 1);
+    /// ```
+    ///
+    /// [`max_width`]: piet::TextLayoutBuilder::max_width
+    pub fn set_max_width(&mut self, max_width: f64) -> Result<(), piet::Error> {
+        if self.1.max_width == Some(max_width) {
+            return Ok(());
+        }
+
+        let mut source = LayoutSource::clone(&self.1);
+        source.max_width = Some(max_width);
+
+        // The missing glyphs were already replaced when this layout was built.
+        let mut text = self.2.clone();
+        text.1 = MissingGlyphPolicy::Tofu;
+        let mut layout = source.build(&mut text)?;
+        layout.2 = self.2.clone();
+
+        *self = layout;
+        Ok(())
+    }
+
+    /// Get the rectangles covered by the glyphs in a range of the text.
+    ///
+    /// Each glyph gets one rectangle, spanning from its left edge to its advance horizontally and
+    /// covering its whole line vertically. Glyphs from the same line are returned in visual order,
+    /// and lines are returned from top to bottom. This can be used to draw custom highlights,
+    /// squiggly underlines or other decorations under parts of the text.
+    ///
+    /// `range` is in bytes, like the indices used by [`hit_test_text_position`]. A glyph is
+    /// included if any of the text it was shaped from lies in `range`. Line breaks aren't drawn,
+    /// so they don't have rectangles.
+    ///
+    /// The glyphs are measured by laying the text out again on this thread with the same fonts.
+    /// This is about as expensive as building the layout was, but it only happens once per
+    /// layout, and the result is shared with hit testing.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use piet::{Text as _, TextLayout as _, TextLayoutBuilder as _};
+    /// use theo::Display;
+    ///
+    /// let mut display = Display::offscreen();
+    /// let layout = display.text().new_text_layout("Hello world").build().unwrap();
+    ///
+    /// // "world" is the last five bytes of the text.
+    /// let rects = layout.glyph_rects(6..);
+    /// assert_eq!(rects.len(), 5);
+    ///
+    /// // The glyphs are in order and on the same line.
+    /// for pair in rects.windows(2) {
+    ///     assert!(pair[0].x0 < pair[1].x0);
+    ///     assert_eq!(pair[0].y0, pair[1].y0);
+    /// }
+    /// assert!(rects[4].x1 <= layout.size().width + 1.0);
+    /// ```
+    ///
+    /// [`hit_test_text_position`]: piet::TextLayout::hit_test_text_position
+    pub fn glyph_rects(&self, range: impl RangeBounds<usize>) -> Vec<Rect> {
+        let start = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start.saturating_add(1),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&end) => end.saturating_add(1),
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => usize::MAX,
+        };
+
+        self.1
+            .lines()
+            .iter()
+            .flat_map(|line| {
+                line.glyphs
+                    .iter()
+                    .filter(|glyph| glyph.start < end && glyph.end > start)
+                    .map(|glyph| {
+                        Rect::new(
+                            glyph.x,
+                            line.top,
+                            glyph.x + glyph.width,
+                            line.top + line.height,
+                        )
+                    })
+            })
+            .collect()
+    }
+}
+
+impl LayoutSource {
+    /// Build a layout from these parameters with a text backend.
+    fn build<T: piet::Text>(&self, text: &mut T) -> Result<T::TextLayout, piet::Error> {
+        let mut builder = text.new_text_layout(self.text.clone());
+
+        if let Some(width) = self.max_width {
+            builder = builder.max_width(width);
+        }
+        if let Some(alignment) = self.alignment {
+            builder = builder.alignment(alignment);
+        }
+        for attribute in &self.default_attributes {
+            builder = builder.default_attribute(attribute.clone());
+        }
+        for (range, attribute) in &self.range_attributes {
+            builder = builder.range_attribute(range.clone(), attribute.clone());
+        }
+
+        builder.build()
+    }
+
+    /// Lay the text out again on this thread with `cosmic-text`, and pass the layout to `f`.
+    ///
+    /// Glyph indices are relative to the paragraph they are in, so `f` also gets the byte offset
+    /// of each paragraph. This returns `None` if the text couldn't be laid out.
+    fn measure<R>(
+        &self,
+        f: impl FnOnce(&piet_cosmic_text::TextLayout, &[usize]) -> R,
+    ) -> Option<R> {
+        MEASURE.with(|measure| {
+            let mut measure = measure.borrow_mut();
+            let (text, loaded) = measure.get_or_insert_with(|| (MeasureText::new(), 0));
+            text.wait_for_load_blocking();
+
+            // Catch up on any fonts that were loaded since the last measurement.
+            let fonts = loaded_fonts()
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            for data in &fonts[*loaded..] {
+                if let Err(err) = text.load_font(data) {
+                    tracing::warn!("Failed to load font for measuring glyphs: {}", err);
+                }
+            }
+            *loaded = fonts.len();
+            drop(fonts);
+
+            let layout = match self.build(text) {
+                Ok(layout) => layout,
+                Err(err) => {
+                    tracing::warn!("Failed to lay out text for measuring glyphs: {}", err);
+                    return None;
+                }
+            };
+
+            let paragraph_starts = cosmic_text::BidiParagraphs::new(&self.text)
+                .scan(0, |offset, paragraph| {
+                    let start = *offset;
+                    *offset += paragraph.len() + 1;
+                    Some(start)
+                })
+                .collect::<Vec<_>>();
+
+            Some(f(&layout, &paragraph_starts))
+        })
+    }
+
+    /// Get the ranges of the text that none of the fonts have glyphs for.
+    fn missing_glyphs(&self) -> Vec<Range<usize>> {
+        self.measure(|layout, paragraph_starts| {
+            let mut ranges: Vec<Range<usize>> = Vec::new();
+
+            for run in layout.layout_runs() {
+                let offset = paragraph_starts.get(run.line_i).copied().unwrap_or(0);
+
+                // Glyph zero is the placeholder for missing glyphs.
+                for glyph in run.glyphs.iter().filter(|glyph| glyph.glyph_id == 0) {
+                    let range = offset + glyph.start..offset + glyph.end;
+                    match ranges.last_mut() {
+                        Some(last) if last.end == range.start => last.end = range.end,
+                        _ => ranges.push(range),
+                    }
+                }
+            }
+
+            ranges
+        })
+        .unwrap_or_default()
+    }
+
+    /// Get the visual lines of the layout, measuring them if they haven't been yet.
+    fn lines(&self) -> &[LineInfo] {
+        self.lines.get_or_init(|| {
+            self.measure(|layout, paragraph_starts| {
+                let line_height = f64::from(layout.buffer().metrics().line_height);
+
+                layout
+                    .layout_runs()
+                    .map(|run| {
+                        let offset = paragraph_starts.get(run.line_i).copied().unwrap_or(0);
+                        let glyphs = run
+                            .glyphs
+                            .iter()
+                            .map(|glyph| GlyphInfo {
+                                start: offset + glyph.start,
+                                end: offset + glyph.end,
+                                x: f64::from(glyph.x),
+                                width: f64::from(glyph.w),
+                                rtl: glyph.level.is_rtl(),
+                            })
+                            .collect::<Vec<_>>();
+
+                        LineInfo {
+                            start: glyphs
+                                .iter()
+                                .map(|glyph| glyph.start)
+                                .min()
+                                .unwrap_or(offset),
+                            top: f64::from(run.line_top),
+                            baseline: f64::from(run.line_y),
+                            height: line_height,
+                            glyphs,
+                        }
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+        })
+    }
+
+    /// Find the text position closest to a point.
+    fn hit_test_point(&self, point: Point) -> piet::HitTestPoint {
+        let mut hit = piet::HitTestPoint::default();
+        let lines = self.lines();
+
+        // Use the line under the point, or the closest one if it is above or below the text.
+        let line = match lines
+            .iter()
+            .position(|line| point.y < line.top + line.height)
+            .or_else(|| lines.len().checked_sub(1))
+        {
+            Some(index) => &lines[index],
+            None => {
+                hit.idx = 0;
+                hit.is_inside = false;
+                return hit;
+            }
+        };
+
+        let left = line.glyphs.first().map_or(0.0, |glyph| glyph.x);
+        let right = line
+            .glyphs
+            .last()
+            .map_or(0.0, |glyph| glyph.x + glyph.width);
+        hit.is_inside = point.y >= line.top
+            && point.y < line.top + line.height
+            && point.x >= left
+            && point.x <= right;
+
+        // Pick the edge of the glyph under the point that it is closest to.
+        let edge = |glyph: &GlyphInfo, right_half: bool| {
+            if right_half != glyph.rtl {
+                glyph.end
+            } else {
+                glyph.start
+            }
+        };
+        hit.idx = match (line.glyphs.first(), line.glyphs.last()) {
+            (Some(first), _) if point.x < left => edge(first, false),
+            (_, Some(last)) if point.x > right => edge(last, true),
+            _ => line
+                .glyphs
+                .iter()
+                .find(|glyph| point.x <= glyph.x + glyph.width)
+                .map_or(line.start, |glyph| {
+                    edge(glyph, point.x >= glyph.x + glyph.width / 2.0)
+                }),
+        };
+
+        hit
+    }
+
+    /// Find where the caret for a text position goes.
+    fn hit_test_text_position(&self, idx: usize) -> piet::HitTestPosition {
+        let mut hit = piet::HitTestPosition::default();
+        let idx = idx.min(self.text.len());
+
+        // Prefer the leading edge of the glyph at the position. If it is at the end of a line,
+        // use the trailing edge of the glyph before it instead.
+        let mut found = None;
+        'lines: for (index, line) in self.lines().iter().enumerate() {
+            if line.glyphs.is_empty() && line.start == idx {
+                found = Some((index, 0.0));
+                break;
+            }
+
+            for glyph in &line.glyphs {
+                let (leading, trailing) = if glyph.rtl {
+                    (glyph.x + glyph.width, glyph.x)
+                } else {
+                    (glyph.x, glyph.x + glyph.width)
+                };
+
+                if (glyph.start..glyph.end).contains(&idx) {
+                    found = Some((index, leading));
+                    break 'lines;
+                } else if glyph.end == idx && found.is_none() {
+                    found = Some((index, trailing));
+                }
+            }
+        }
+
+        if let Some((index, x)) = found {
+            hit.line = index;
+            hit.point = Point::new(x, self.lines()[index].baseline);
+        }
+
+        hit
+    }
+}
+
+thread_local! {
+    /// The text backend used to measure glyphs, and the number of loaded fonts it has seen.
+    static MEASURE: RefCell<Option<(MeasureText, usize)>> = const { RefCell::new(None) };
+}
+
+/// The font data passed to [`piet::Text::load_font`], so glyphs can be measured with it.
+fn loaded_fonts() -> &'static Mutex<Vec<Arc<[u8]>>> {
+    static FONTS: Mutex<Vec<Arc<[u8]>>> = Mutex::new(Vec::new());
+    &FONTS
+}
+
+/// Get the sorted names of the font families installed on the system.
+fn system_families() -> &'static [String] {
+    static FAMILIES: OnceLock<Vec<String>> = OnceLock::new();
+
+    FAMILIES.get_or_init(|| {
+        let mut db = cosmic_text::fontdb::Database::new();
+        db.load_system_fonts();
+
+        let mut families = db
+            .faces()
+            .flat_map(|face| &face.families)
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        families.sort_unstable();
+        families.dedup();
+        families
+    })
+}
 
 #[derive(Clone)]
 pub(crate) enum TextLayoutInner {
@@ -67,43 +740,87 @@ pub(crate) enum TextLayoutInner {
     Cosmic(CosmicTextLayout),
 }
 
+impl TextLayoutInner {
+    /// Whether this layout was built by the same kind of text backend as `text`.
+    #[allow(unreachable_patterns)]
+    fn built_by(&self, text: &TextInner) -> bool {
+        match (self, text) {
+            #[cfg(feature = "gl")]
+            (TextLayoutInner::Glow(_), TextInner::Glow(_)) => true,
+            #[cfg(feature = "wgpu")]
+            (TextLayoutInner::Wgpu(_), TextInner::Wgpu(_)) => true,
+            (TextLayoutInner::Cosmic(_), TextInner::Cosmic(_)) => true,
+            _ => false,
+        }
+    }
+}
+
 impl piet::Text for Text {
     type TextLayoutBuilder = TextLayoutBuilder;
     type TextLayout = TextLayout;
 
     fn font_family(&mut self, family_name: &str) -> Option<piet::FontFamily> {
-        match &mut self.0 {
+        let family = match &mut self.0 {
             #[cfg(feature = "gl")]
             TextInner::Glow(inner) => inner.font_family(family_name),
             #[cfg(feature = "wgpu")]
             TextInner::Wgpu(inner) => inner.font_family(family_name),
             TextInner::Cosmic(inner) => inner.font_family(family_name),
-        }
+        };
+
+        // The renderer doesn't know about any fonts until it has finished loading them in the
+        // background. It loads the same system fonts, so check those in the meantime.
+        family.or_else(|| {
+            system_families()
+                .binary_search_by(|name| name.as_str().cmp(family_name))
+                .ok()
+                .map(|_| piet::FontFamily::new_unchecked(family_name))
+        })
     }
 
     fn load_font(&mut self, data: &[u8]) -> Result<piet::FontFamily, piet::Error> {
-        match &mut self.0 {
-            #[cfg(feature = "gl")]
-            TextInner::Glow(inner) => inner.load_font(data),
-            #[cfg(feature = "wgpu")]
-            TextInner::Wgpu(inner) => inner.load_font(data),
-            TextInner::Cosmic(inner) => inner.load_font(data),
-        }
+        let family = self.load_font_into_backend(data)?;
+
+        loaded_fonts()
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(Arc::from(data));
+        Ok(family)
     }
 
     fn new_text_layout(&mut self, text: impl piet::TextStorage) -> Self::TextLayoutBuilder {
+        // Share the text with the renderer's layout instead of copying it again.
+        let text: Arc<str> = Arc::from(text.as_str());
+        let source = LayoutSource {
+            text: text.clone(),
+            max_width: None,
+            alignment: None,
+            default_attributes: Vec::new(),
+            range_attributes: Vec::new(),
+            missing_glyph: self.1.clone(),
+            lines: OnceLock::new(),
+            rebuilt: RefCell::new(None),
+        };
+
+        let handle = self.clone();
         match &mut self.0 {
             #[cfg(feature = "gl")]
-            TextInner::Glow(inner) => {
-                TextLayoutBuilder(TextLayoutBuilderInner::Glow(inner.new_text_layout(text)))
-            }
+            TextInner::Glow(inner) => TextLayoutBuilder(
+                TextLayoutBuilderInner::Glow(inner.new_text_layout(text)),
+                source,
+                handle,
+            ),
             #[cfg(feature = "wgpu")]
-            TextInner::Wgpu(inner) => {
-                TextLayoutBuilder(TextLayoutBuilderInner::Wgpu(inner.new_text_layout(text)))
-            }
-            TextInner::Cosmic(inner) => {
-                TextLayoutBuilder(TextLayoutBuilderInner::Cosmic(inner.new_text_layout(text)))
-            }
+            TextInner::Wgpu(inner) => TextLayoutBuilder(
+                TextLayoutBuilderInner::Wgpu(inner.new_text_layout(text)),
+                source,
+                handle,
+            ),
+            TextInner::Cosmic(inner) => TextLayoutBuilder(
+                TextLayoutBuilderInner::Cosmic(inner.new_text_layout(text)),
+                source,
+                handle,
+            ),
         }
     }
 }
@@ -112,51 +829,67 @@ impl piet::TextLayoutBuilder for TextLayoutBuilder {
     type Out = TextLayout;
 
     fn max_width(self, width: f64) -> Self {
-        match self.0 {
+        let TextLayoutBuilder(inner, mut source, text) = self;
+        source.max_width = Some(width);
+
+        let inner = match inner {
             #[cfg(feature = "gl")]
             TextLayoutBuilderInner::Glow(inner) => {
-                TextLayoutBuilder(TextLayoutBuilderInner::Glow(inner.max_width(width)))
+                TextLayoutBuilderInner::Glow(inner.max_width(width))
             }
             #[cfg(feature = "wgpu")]
             TextLayoutBuilderInner::Wgpu(inner) => {
-                TextLayoutBuilder(TextLayoutBuilderInner::Wgpu(inner.max_width(width)))
+                TextLayoutBuilderInner::Wgpu(inner.max_width(width))
             }
             TextLayoutBuilderInner::Cosmic(inner) => {
-                TextLayoutBuilder(TextLayoutBuilderInner::Cosmic(inner.max_width(width)))
+                TextLayoutBuilderInner::Cosmic(inner.max_width(width))
             }
-        }
+        };
+
+        TextLayoutBuilder(inner, source, text)
     }
 
     fn alignment(self, alignment: piet::TextAlignment) -> Self {
-        match self.0 {
+        let TextLayoutBuilder(inner, mut source, text) = self;
+        source.alignment = Some(alignment);
+
+        let inner = match inner {
             #[cfg(feature = "gl")]
             TextLayoutBuilderInner::Glow(inner) => {
-                TextLayoutBuilder(TextLayoutBuilderInner::Glow(inner.alignment(alignment)))
+                TextLayoutBuilderInner::Glow(inner.alignment(alignment))
             }
             #[cfg(feature = "wgpu")]
             TextLayoutBuilderInner::Wgpu(inner) => {
-                TextLayoutBuilder(TextLayoutBuilderInner::Wgpu(inner.alignment(alignment)))
+                TextLayoutBuilderInner::Wgpu(inner.alignment(alignment))
             }
             TextLayoutBuilderInner::Cosmic(inner) => {
-                TextLayoutBuilder(TextLayoutBuilderInner::Cosmic(inner.alignment(alignment)))
+                TextLayoutBuilderInner::Cosmic(inner.alignment(alignment))
             }
-        }
+        };
+
+        TextLayoutBuilder(inner, source, text)
     }
 
     fn default_attribute(self, attribute: impl Into<piet::TextAttribute>) -> Self {
-        match self.0 {
+        let TextLayoutBuilder(inner, mut source, text) = self;
+        let attribute = attribute.into();
+        source.default_attributes.push(attribute.clone());
+
+        let inner = match inner {
             #[cfg(feature = "gl")]
-            TextLayoutBuilderInner::Glow(inner) => TextLayoutBuilder(TextLayoutBuilderInner::Glow(
-                inner.default_attribute(attribute),
-            )),
+            TextLayoutBuilderInner::Glow(inner) => {
+                TextLayoutBuilderInner::Glow(inner.default_attribute(attribute))
+            }
             #[cfg(feature = "wgpu")]
-            TextLayoutBuilderInner::Wgpu(inner) => TextLayoutBuilder(TextLayoutBuilderInner::Wgpu(
-                inner.default_attribute(attribute),
-            )),
-            TextLayoutBuilderInner::Cosmic(inner) => TextLayoutBuilder(
-                TextLayoutBuilderInner::Cosmic(inner.default_attribute(attribute)),
-            ),
-        }
+            TextLayoutBuilderInner::Wgpu(inner) => {
+                TextLayoutBuilderInner::Wgpu(inner.default_attribute(attribute))
+            }
+            TextLayoutBuilderInner::Cosmic(inner) => {
+                TextLayoutBuilderInner::Cosmic(inner.default_attribute(attribute))
+            }
+        };
+
+        TextLayoutBuilder(inner, source, text)
     }
 
     fn range_attribute(
@@ -164,35 +897,67 @@ impl piet::TextLayoutBuilder for TextLayoutBuilder {
         range: impl std::ops::RangeBounds<usize>,
         attribute: impl Into<piet::TextAttribute>,
     ) -> Self {
-        match self.0 {
-            #[cfg(feature = "gl")]
-            TextLayoutBuilderInner::Glow(inner) => TextLayoutBuilder(TextLayoutBuilderInner::Glow(
-                inner.range_attribute(range, attribute),
-            )),
-            #[cfg(feature = "wgpu")]
-            TextLayoutBuilderInner::Wgpu(inner) => TextLayoutBuilder(TextLayoutBuilderInner::Wgpu(
-                inner.range_attribute(range, attribute),
-            )),
-            TextLayoutBuilderInner::Cosmic(inner) => TextLayoutBuilder(
-                TextLayoutBuilderInner::Cosmic(inner.range_attribute(range, attribute)),
-            ),
-        }
-    }
+        let TextLayoutBuilder(inner, mut source, text) = self;
+        let range = piet::util::resolve_range(range, source.text.len());
+        let attribute = attribute.into();
+        source
+            .range_attributes
+            .push((range.clone(), attribute.clone()));
 
-    fn build(self) -> Result<Self::Out, piet::Error> {
-        match self.0 {
+        let inner = match inner {
             #[cfg(feature = "gl")]
             TextLayoutBuilderInner::Glow(inner) => {
-                Ok(TextLayout(TextLayoutInner::Glow(inner.build()?)))
+                TextLayoutBuilderInner::Glow(inner.range_attribute(range, attribute))
             }
             #[cfg(feature = "wgpu")]
             TextLayoutBuilderInner::Wgpu(inner) => {
-                Ok(TextLayout(TextLayoutInner::Wgpu(inner.build()?)))
+                TextLayoutBuilderInner::Wgpu(inner.range_attribute(range, attribute))
             }
             TextLayoutBuilderInner::Cosmic(inner) => {
-                Ok(TextLayout(TextLayoutInner::Cosmic(inner.build()?)))
+                TextLayoutBuilderInner::Cosmic(inner.range_attribute(range, attribute))
+            }
+        };
+
+        TextLayoutBuilder(inner, source, text)
+    }
+
+    fn build(mut self) -> Result<Self::Out, piet::Error> {
+        // Cover up the glyphs that are missing from every font.
+        let replacement = match &self.1.missing_glyph {
+            MissingGlyphPolicy::Tofu => None,
+            MissingGlyphPolicy::Blank => Some(TextAttribute::TextColor(piet::Color::TRANSPARENT)),
+            MissingGlyphPolicy::FallbackFont(family) => {
+                Some(TextAttribute::FontFamily(family.clone()))
+            }
+        };
+        if let Some(attribute) = replacement {
+            for range in self.1.missing_glyphs() {
+                self = self.range_attribute(range, attribute.clone());
             }
         }
+
+        let TextLayoutBuilder(inner, source, text) = self;
+        let source = Rc::new(source);
+
+        match inner {
+            #[cfg(feature = "gl")]
+            TextLayoutBuilderInner::Glow(inner) => Ok(TextLayout(
+                TextLayoutInner::Glow(inner.build()?),
+                source,
+                text,
+            )),
+            #[cfg(feature = "wgpu")]
+            TextLayoutBuilderInner::Wgpu(inner) => Ok(TextLayout(
+                TextLayoutInner::Wgpu(inner.build()?),
+                source,
+                text,
+            )),
+            TextLayoutBuilderInner::Cosmic(inner) => Ok(TextLayout(
+                TextLayoutInner::Cosmic(inner.build()?),
+                source,
+                text,
+            )),
+        }
     }
 }
 
@@ -268,22 +1033,12 @@ impl piet::TextLayout for TextLayout {
     }
 
     fn hit_test_point(&self, point: piet::kurbo::Point) -> piet::HitTestPoint {
-        match &self.0 {
-            #[cfg(feature = "gl")]
-            TextLayoutInner::Glow(inner) => inner.hit_test_point(point),
-            #[cfg(feature = "wgpu")]
-            TextLayoutInner::Wgpu(inner) => inner.hit_test_point(point),
-            TextLayoutInner::Cosmic(inner) => inner.hit_test_point(point),
-        }
+        // The renderers' hit testing uses indices relative to each paragraph, so use the
+        // measured layout instead. This also gives the same results on every backend.
+        self.1.hit_test_point(point)
     }
 
     fn hit_test_text_position(&self, idx: usize) -> piet::HitTestPosition {
-        match &self.0 {
-            #[cfg(feature = "gl")]
-            TextLayoutInner::Glow(inner) => inner.hit_test_text_position(idx),
-            #[cfg(feature = "wgpu")]
-            TextLayoutInner::Wgpu(inner) => inner.hit_test_text_position(idx),
-            TextLayoutInner::Cosmic(inner) => inner.hit_test_text_position(idx),
-        }
+        self.1.hit_test_text_position(idx)
     }
 }
@@ -17,7 +17,11 @@
 
 use std::marker::PhantomData;
 
-use crate::{text::Text, DisplayBuilder, Error, OptionExt, SwitchToSwrast};
+use crate::{
+    gl_info::{query_max_texture_size, renderer_info},
+    text::{BundledFonts, Text},
+    BlendMode, ColorSpace, DisplayBuilder, Error, FormatInfo, RendererInfo, SwitchToSwrast,
+};
 
 use piet::kurbo::{Point, Rect, Shape};
 use piet::{RenderContext as _, StrokeStyle};
@@ -34,12 +38,33 @@ pub(crate) struct Display {
 
     /// Allow the use of transparency.
     transparency: bool,
+
+    /// The fonts to load into the text backends.
+    fonts: BundledFonts,
+
+    /// The text backend shared by layouts built outside of a frame.
+    text: Option<Text>,
+
+    /// The ID to give to the next surface.
+    next_surface_id: u64,
+
+    /// Information about the renderer, queried from the context of the first surface.
+    info: Option<RendererInfo>,
 }
 
 /// The window for the WebGL backend.
 pub(crate) struct Surface {
     /// The OpenGL context.
     context: GlContext<glow::Context>,
+
+    /// The unique ID of `context`, used to keep brushes from being shared with other surfaces.
+    id: u64,
+
+    /// The largest width or height of a texture.
+    max_texture_size: u32,
+
+    /// Whether the bundled fonts were loaded into the text backend of `context` yet.
+    fonts_loaded: bool,
 }
 
 /// The render context for the WebGL backend.
@@ -50,6 +75,15 @@ pub(crate) struct RenderContext<'dsp, 'surf> {
     /// Text data.
     text: Text,
 
+    /// Error from mismatched type usages.
+    mismatch_err: Result<(), Error>,
+
+    /// The largest width or height of a texture.
+    max_texture_size: u32,
+
+    /// The ID of the surface's context.
+    cache_key: u64,
+
     /// Eat the display lifetime.
     _display: PhantomData<&'dsp mut Display>,
 }
@@ -72,14 +106,34 @@ impl Display {
         // Load the document.
         let document = web_sys::window()
             .and_then(|window| window.document())
-            .piet_err("Failed to load document")?;
+            .ok_or_else(|| Error::BackendError("Failed to load document".into()))?;
 
         Ok(Self {
             document,
             transparency: builder.transparent,
+            fonts: BundledFonts::new(&builder.fonts),
+            text: None,
+            next_surface_id: 0,
+            info: None,
         })
     }
 
+    pub(super) fn text(&mut self) -> Text {
+        self.text
+            .get_or_insert_with(|| self.fonts.cosmic_text())
+            .clone()
+    }
+
+    pub(super) fn renderer_info(&self) -> Option<RendererInfo> {
+        // The context belongs to the canvas, so this is queried when the first surface is made.
+        self.info.clone()
+    }
+
+    pub(super) fn max_texture_size(&self) -> Option<u32> {
+        // The context belongs to the canvas, not the display.
+        None
+    }
+
     pub(super) fn supports_transparency(&self) -> bool {
         self.transparency
     }
@@ -88,6 +142,10 @@ impl Display {
         None
     }
 
+    pub(super) fn x11_visual_info(&self) -> Option<crate::X11VisualInfo> {
+        None
+    }
+
     pub(super) async unsafe fn make_surface(
         &mut self,
         raw: RawWindowHandle,
@@ -108,53 +166,118 @@ impl Display {
             .piet_err(format!("Failed to load canvas with id {id}"))?
             .unchecked_into::<web_sys::HtmlCanvasElement>();
 
-        // Try to get a WebGL2 context.
-        if let Some(webgl_ctx) = canvas
+        // Try to get a WebGL2 context, or a WebGL1 context instead.
+        let glow_ctx = if let Some(webgl_ctx) = canvas
             .get_context("webgl2")
             .map_err(|_| Error::BackendError("Failed to get WebGL2 context".into()))?
             .and_then(|ctx| ctx.dyn_into::<web_sys::WebGl2RenderingContext>().ok())
         {
-            // Create the context.
-            let glow_ctx = glow::Context::from_webgl2_context(webgl_ctx);
-
-            // Use the context.
-            Ok(Surface {
-                context: unsafe { GlContext::new(glow_ctx)? },
-            })
+            glow::Context::from_webgl2_context(webgl_ctx)
         } else {
-            // Create a WebGL1 context instead.
             let webgl_ctx = canvas
                 .get_context("webgl")
                 .map_err(|_| Error::BackendError("Failed to get WebGL context".into()))?
                 .and_then(|ctx| ctx.dyn_into::<web_sys::WebGlRenderingContext>().ok())
-                .piet_err("Failed to get WebGL context")?;
+                .ok_or_else(|| Error::BackendError("Failed to get WebGL context".into()))?;
 
-            // Create the context.
-            let glow_ctx = glow::Context::from_webgl1_context(webgl_ctx);
+            glow::Context::from_webgl1_context(webgl_ctx)
+        };
 
-            // Use the context.
-            Ok(Surface {
-                context: unsafe { GlContext::new(glow_ctx)? },
-            })
+        let max_texture_size = unsafe { query_max_texture_size(&glow_ctx) };
+        if self.info.is_none() {
+            self.info = Some(unsafe { query_renderer_info(&glow_ctx) });
         }
+        let surface_id = self.next_surface_id;
+        self.next_surface_id += 1;
+
+        // Use the context.
+        Ok(Surface {
+            context: unsafe { GlContext::new(glow_ctx)? },
+            id: surface_id,
+            max_texture_size,
+            fonts_loaded: false,
+        })
+    }
+
+    pub(super) fn destroy_surface(&mut self, surface: Surface) -> Result<(), Error> {
+        // The surface's resources are freed when it is dropped.
+        drop(surface);
+        Ok(())
     }
 
     pub(super) async fn present(&mut self) {
         // no-op
     }
+
+    pub(super) fn poll(&mut self) {
+        // no-op
+    }
+
+    pub(super) fn device_lost(&self) -> bool {
+        // Lost WebGL contexts aren't detected.
+        false
+    }
+
+    pub(super) fn begin_frame(&mut self) {
+        // no-op
+    }
+
+    pub(super) async fn end_frame(&mut self) -> Result<(), Error> {
+        // no-op
+        Ok(())
+    }
+}
+
+impl Surface {
+    pub(super) fn snapshot(
+        &mut self,
+        _display: &mut Display,
+    ) -> Result<(u32, u32, Vec<u8>), Error> {
+        // The contents of the drawing buffer are cleared after it is presented.
+        Err(Error::NotSupported)
+    }
+
+    pub(super) fn is_renderable(&self, _display: &Display) -> bool {
+        // The browser stops running animation frames for hidden pages by itself.
+        true
+    }
+
+    pub(super) fn format_info(&self, _display: &Display) -> Result<FormatInfo, Error> {
+        // The drawing buffer is created with the default attributes, which include alpha.
+        Ok(FormatInfo {
+            red_bits: 8,
+            green_bits: 8,
+            blue_bits: 8,
+            alpha_bits: 8,
+            color_space: ColorSpace::Linear,
+            #[cfg(feature = "wgpu")]
+            wgpu_format: None,
+        })
+    }
 }
 
 impl<'dsp, 'surf> RenderContext<'dsp, 'surf> {
+    /// WebGL contexts are never current.
+    pub(super) const EXCLUSIVE: bool = false;
+
     pub(super) unsafe fn new(
-        _display: &'dsp mut Display,
+        display: &'dsp mut Display,
         surface: &'surf mut Surface,
         width: u32,
         height: u32,
     ) -> Result<Self, Error> {
         let mut ctx = unsafe { surface.context.render_context(width, height) };
+        let mut text = Text::new(crate::text::TextInner::Glow(ctx.text().clone()));
+        display
+            .fonts
+            .load_into(&mut text, &mut surface.fonts_loaded);
+
         Ok(Self {
-            text: Text(crate::text::TextInner::Glow(ctx.text().clone())),
+            text,
             inner: ctx,
+            mismatch_err: Ok(()),
+            max_texture_size: surface.max_texture_size,
+            cache_key: surface.id,
             _display: PhantomData,
         })
     }
@@ -168,8 +291,19 @@ impl<'dsp, 'surf> RenderContext<'dsp, 'surf> {
         Self::new(display, surface, width, height)
     }
 
+    /// Each canvas has its own context, so brushes can't be shared between surfaces.
+    pub(super) fn cache_key(&self) -> u64 {
+        self.cache_key
+    }
+
     pub(super) fn status(&mut self) -> Result<(), Error> {
-        self.inner.status()
+        let status = self.inner.status();
+        let mismatch = std::mem::replace(&mut self.mismatch_err, Ok(()));
+        status.and(mismatch)
+    }
+
+    pub(super) fn max_texture_size(&self) -> u32 {
+        self.max_texture_size
     }
 
     pub(super) fn solid_brush(&mut self, color: piet::Color) -> Brush {
@@ -218,7 +352,15 @@ impl<'dsp, 'surf> RenderContext<'dsp, 'surf> {
         match layout.0 {
             crate::text::TextLayoutInner::Glow(ref layout) => self.inner.draw_text(layout, pos),
 
-            _ => panic!("invalid text layout"),
+            // Layouts from `Display::text` need to be rebuilt with our text backend.
+            crate::text::TextLayoutInner::Cosmic(_) => match layout.rebuild(&mut self.text) {
+                Ok(layout) => self.draw_text(&layout, pos),
+                Err(err) => self.mismatch_err = Err(err),
+            },
+
+            // Layouts from other renderers can't be drawn by this one.
+            #[cfg(feature = "wgpu")]
+            crate::text::TextLayoutInner::Wgpu(_) => self.mismatch_err = Err(Error::InvalidInput),
         }
     }
 
@@ -234,6 +376,29 @@ impl<'dsp, 'surf> RenderContext<'dsp, 'surf> {
         self.inner.finish()
     }
 
+    pub(super) fn finish_with_damage(&mut self, _regions: &[Rect]) -> Result<(), Error> {
+        // The browser composites the whole canvas.
+        self.finish()
+    }
+
+    pub(super) fn flush(&mut self) -> Result<(), Error> {
+        // piet-glow's `finish` only flushes the GL command stream.
+        self.inner.finish()
+    }
+
+    pub(super) fn push_layer(&mut self, _blend: BlendMode) -> Result<(), Error> {
+        // The GL functions needed to copy the surface aren't available.
+        Err(Error::NotSupported)
+    }
+
+    pub(super) fn pop_layer(&mut self, _alpha: f64, _blend: BlendMode) {
+        // Layers are never pushed.
+    }
+
+    pub(super) fn finish_to_buffer(&mut self) -> Result<Vec<u8>, Error> {
+        Err(Error::NotSupported)
+    }
+
     pub(super) fn transform(&mut self, transform: piet::kurbo::Affine) {
         self.inner.transform(transform)
     }
@@ -245,7 +410,8 @@ impl<'dsp, 'surf> RenderContext<'dsp, 'surf> {
         buf: &[u8],
         format: piet::ImageFormat,
     ) -> Result<Image, Error> {
-        self.inner.make_image(width, height, buf, format)
+        let (buf, format) = crate::straight_alpha(buf, format);
+        self.inner.make_image(width, height, &buf, format)
     }
 
     pub(super) fn draw_image(
@@ -272,6 +438,11 @@ impl<'dsp, 'surf> RenderContext<'dsp, 'surf> {
         self.inner.capture_image_area(src_rect)
     }
 
+    pub(super) fn read_image(&mut self, _image: &Image) -> Result<(u32, u32, Vec<u8>), Error> {
+        // The renderer can't read textures back.
+        Err(Error::NotSupported)
+    }
+
     pub(super) fn blurred_rect(&mut self, rect: Rect, blur_radius: f64, brush: &Brush) {
         self.inner.blurred_rect(rect, blur_radius, brush)
     }
@@ -283,3 +454,41 @@ impl<'dsp, 'surf> RenderContext<'dsp, 'surf> {
 
 type Image = piet_glow::Image<glow::Context>;
 type Brush = piet_glow::Brush<glow::Context>;
+
+/// Query the renderer information for a WebGL context.
+///
+/// Browsers usually report a generic renderer and vendor, so the real ones are used instead if
+/// the `WEBGL_debug_renderer_info` extension is available.
+unsafe fn query_renderer_info(gl: &glow::Context) -> RendererInfo {
+    use glow::HasContext;
+
+    /// `UNMASKED_VENDOR_WEBGL` from `WEBGL_debug_renderer_info`.
+    const UNMASKED_VENDOR_WEBGL: u32 = 0x9245;
+
+    /// `UNMASKED_RENDERER_WEBGL` from `WEBGL_debug_renderer_info`.
+    const UNMASKED_RENDERER_WEBGL: u32 = 0x9246;
+
+    let mut info = unsafe { renderer_info(gl, "WebGL") };
+
+    if gl
+        .supported_extensions()
+        .contains("WEBGL_debug_renderer_info")
+    {
+        let (vendor, name) = unsafe {
+            (
+                gl.get_parameter_string(UNMASKED_VENDOR_WEBGL),
+                gl.get_parameter_string(UNMASKED_RENDERER_WEBGL),
+            )
+        };
+
+        // Failed queries return empty strings.
+        if !vendor.is_empty() {
+            info.vendor = vendor;
+        }
+        if !name.is_empty() {
+            info.name = name;
+        }
+    }
+
+    info
+}
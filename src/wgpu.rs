@@ -17,23 +17,33 @@
 
 //! The `wgpu` backend.
 
-use crate::text::{Text, TextInner};
-use crate::{DisplayBuilder, Error, ResultExt, SwitchToSwrast};
+use crate::text::{BundledFonts, Text, TextInner};
+use crate::{
+    BlendMode, ColorSpace, DisplayBuilder, Error, FormatInfo, RendererInfo, ResultExt,
+    SwitchToSwrast,
+};
 
-use piet::kurbo::{Point, Rect, Shape};
+use piet::kurbo::{Affine, BezPath, Point, Rect, Shape};
 use piet::{RenderContext as _, StrokeStyle};
 use piet_wgpu::WgpuContext;
 use raw_window_handle::{
     HasRawDisplayHandle, HasRawWindowHandle, RawDisplayHandle, RawWindowHandle,
 };
 use slab::Slab;
+use wgpu0 as wgpu;
 
+use std::ptr::NonNull;
 use std::rc::{Rc, Weak};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
 
 /// The display for the `wgpu` backend.
 pub(super) struct Display {
     /// The instance.
-    instance: wgpu::Instance,
+    ///
+    /// This is shared with the thread that requests adapters when there is a timeout.
+    instance: Arc<wgpu::Instance>,
 
     /// The underlying raw display handle.
     raw: RawDisplayHandle,
@@ -41,11 +51,38 @@ pub(super) struct Display {
     /// Do we support transparency?
     supports_transparency: bool,
 
+    /// The number of MSAA samples requested by the user.
+    msaa: Option<u32>,
+
+    /// How the user wants surfaces to encode colors.
+    color_space: Option<ColorSpace>,
+
+    /// The extra features requested by the user.
+    features: wgpu::Features,
+
+    /// The limits requested by the user.
+    limits: Option<wgpu::Limits>,
+
+    /// How long to wait for an adapter before giving up.
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    adapter_timeout: Option<Duration>,
+
     /// The list of known adapters.
     adapters: Vec<AdapterInfo>,
 
     /// The list of known surfaces.
     surfaces: Slab<SurfaceInfo>,
+
+    /// The ID to give to the next surface.
+    ///
+    /// Unlike the indices into `surfaces`, these are never reused.
+    next_surface_id: u64,
+
+    /// The fonts to load into the text backends.
+    fonts: BundledFonts,
+
+    /// The text backend shared by layouts built outside of a frame.
+    text: Option<Text>,
 }
 
 /// The surface for the `wgpu` backend.
@@ -54,13 +91,51 @@ pub(super) struct Surface {
     surface_index: usize,
 
     /// Shared state indicating that this has been dropped.
-    _dropped: Rc<()>,
+    dropped: Rc<()>,
+}
+
+/// A device provided by the user.
+pub(super) struct ExternalDevice {
+    pub(super) instance: wgpu::Instance,
+    pub(super) adapter: wgpu::Adapter,
+    pub(super) device: wgpu::Device,
+    pub(super) queue: wgpu::Queue,
 }
 
 struct AdapterInfo {
     adapter: wgpu::Adapter,
     device: wgpu::Device,
     queue: wgpu::Queue,
+
+    /// The last frame submitted to `queue`.
+    last_submission: Option<wgpu::SubmissionIndex>,
+
+    /// Whether `device` has been lost, or reported an error that nothing captured.
+    ///
+    /// This is set by the error handler of the device, which can run on any thread.
+    lost: Arc<AtomicBool>,
+}
+
+impl AdapterInfo {
+    /// Wait for the GPU to finish the last frame submitted to this adapter.
+    ///
+    /// Surfaces must not be reconfigured or destroyed while a frame that uses them is still
+    /// running, or Vulkan complains about destroying framebuffers that are in use.
+    fn wait_for_last_submission(&self) {
+        if self.is_lost() {
+            return;
+        }
+
+        if let Some(index) = &self.last_submission {
+            self.device
+                .poll(wgpu::Maintain::WaitForSubmissionIndex(index.clone()));
+        }
+    }
+
+    /// Whether the device has been lost.
+    fn is_lost(&self) -> bool {
+        self.lost.load(Ordering::Relaxed)
+    }
 }
 
 struct SurfaceInfo {
@@ -70,29 +145,110 @@ struct SurfaceInfo {
     /// The surface configuration.
     config: wgpu::SurfaceConfiguration,
 
+    /// Whether `config` has been applied to the surface yet.
+    configured: bool,
+
     /// The WGPU context.
     context: WgpuContext,
 
+    /// The unique ID of `context`, used to keep brushes from being shared with other surfaces.
+    id: u64,
+
+    /// Whether the bundled fonts were loaded into the text backend of `context` yet.
+    fonts_loaded: bool,
+
     /// The index of the adapter that this surface is associated with.
     adapter_index: usize,
 
     /// The texture associated with the surface.
     texture: Option<wgpu::SurfaceTexture>,
 
+    /// A copy of the last frame presented to the surface, for `Surface::snapshot`.
+    ///
+    /// This is only kept if the surface texture can be copied from.
+    frame: Option<wgpu::Texture>,
+
+    /// Whether no texture could be acquired for the last frame, even after reconfiguring.
+    lost: bool,
+
+    /// The number of samples used for MSAA.
+    samples: u32,
+
+    /// The multisampled texture to render into, if MSAA is enabled.
+    msaa: Option<wgpu::TextureView>,
+
+    /// The translucent color to clear the surface to before rendering, if any.
+    clear_color: Option<wgpu::Color>,
+
     /// Whether or not the representative `Surface` has been dropped.
     dropped: Weak<()>,
 }
 
+/// A level of the save stack of a `RenderContext`.
+#[derive(Default)]
+struct SaveLevel {
+    /// The transform at the time that the level was saved.
+    transform: Affine,
+
+    /// The clips applied in this level, along with the transforms they were applied with.
+    clips: Vec<(Affine, BezPath)>,
+}
+
 /// The rendering context.
 pub(super) struct RenderContext<'dsp, 'srf> {
     /// The inner context.
-    inner: piet_wgpu::RenderContext<'dsp, 'dsp, 'dsp>,
+    ///
+    /// This is `None` once the frame has been read back by `finish_to_buffer`.
+    inner: Option<piet_wgpu::RenderContext<'dsp, 'dsp, 'dsp>>,
+
+    /// The context that `inner` was prepared from.
+    ///
+    /// `inner` borrows this mutably, so it may only be used once `inner` is gone.
+    context: NonNull<WgpuContext>,
+
+    /// The size of the surface.
+    size: (u32, u32),
+
+    /// The format that the surface is rendered in.
+    format: wgpu::TextureFormat,
+
+    /// The number of samples used for MSAA.
+    samples: u32,
+
+    /// Whether anything has been drawn since the frame started.
+    drawn: bool,
+
+    /// The levels of the save stack, used to rebuild it if the frame is started over.
+    ///
+    /// The last level is the current one.
+    levels: Vec<SaveLevel>,
 
     /// The surface we're drawing to.
     _surface: &'srf mut Surface,
 
+    /// The translucent color to clear the surface to.
+    clear_color: &'dsp mut Option<wgpu::Color>,
+
     /// The text context.
     text: Text,
+
+    /// Error from mismatched type usages.
+    mismatch_err: Result<(), Error>,
+
+    /// The largest width or height of a texture on this device.
+    max_texture_size: u32,
+
+    /// The device that the surface belongs to.
+    device: &'dsp wgpu::Device,
+
+    /// The queue for `device`.
+    queue: &'dsp wgpu::Queue,
+
+    /// Whether `device` has been lost.
+    lost: &'dsp AtomicBool,
+
+    /// The ID of the surface's context.
+    cache_key: u64,
 }
 
 impl Display {
@@ -104,18 +260,83 @@ impl Display {
             return Err(Error::BackendError(SwitchToSwrast.into()));
         }
 
-        // Create the instance.
-        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::all(),
-            dx12_shader_compiler: wgpu::Dx12Compiler::default(),
-        });
+        // Use the user's device or instance if they provided one, or create a new instance.
+        let (instance, adapters) = match builder.wgpu_device.take() {
+            Some(ExternalDevice {
+                instance,
+                adapter,
+                device,
+                queue,
+            }) => (
+                Arc::new(instance),
+                vec![AdapterInfo {
+                    adapter,
+                    device,
+                    queue,
+                    last_submission: None,
+                    lost: Arc::new(AtomicBool::new(false)),
+                }],
+            ),
+            None => {
+                let instance = builder.wgpu_instance.take().unwrap_or_else(|| {
+                    Arc::new(wgpu::Instance::new(wgpu::InstanceDescriptor {
+                        backends: builder.wgpu_backends,
+                        dx12_shader_compiler: wgpu::Dx12Compiler::default(),
+                    }))
+                });
+
+                (instance, vec![])
+            }
+        };
 
         Ok(Self {
             instance,
             raw,
             supports_transparency: builder.transparent,
-            adapters: vec![],
+            msaa: builder.msaa,
+            color_space: builder.color_space,
+            features: builder.wgpu_features,
+            limits: builder.wgpu_limits.take(),
+            adapter_timeout: builder.adapter_timeout,
+            adapters,
             surfaces: Slab::new(),
+            next_surface_id: 0,
+            fonts: BundledFonts::new(&builder.fonts),
+            text: None,
+        })
+    }
+
+    pub(super) fn text(&mut self) -> Text {
+        self.text
+            .get_or_insert_with(|| self.fonts.cosmic_text())
+            .clone()
+    }
+
+    pub(super) fn device(&self) -> Option<&wgpu::Device> {
+        self.adapters.first().map(|adapter| &adapter.device)
+    }
+
+    pub(super) fn queue(&self) -> Option<&wgpu::Queue> {
+        self.adapters.first().map(|adapter| &adapter.queue)
+    }
+
+    pub(super) fn max_texture_size(&self) -> Option<u32> {
+        Some(self.device()?.limits().max_texture_dimension_2d)
+    }
+
+    pub(super) fn renderer_info(&self) -> Option<RendererInfo> {
+        let info = self.adapters.first()?.adapter.get_info();
+        let driver = if info.driver_info.is_empty() {
+            info.driver
+        } else {
+            format!("{} ({})", info.driver, info.driver_info)
+        };
+
+        Some(RendererInfo {
+            backend: format!("{:?}", info.backend),
+            name: info.name,
+            vendor: format!("{:#06x}", info.vendor),
+            driver,
         })
     }
 
@@ -127,14 +348,66 @@ impl Display {
         None
     }
 
+    pub(super) fn x11_visual_info(&self) -> Option<crate::X11VisualInfo> {
+        None
+    }
+
+    /// Request an adapter that is compatible with `surface`, giving up after the timeout.
+    ///
+    /// The surface is handed back alongside the adapter.
+    async fn request_adapter(
+        &self,
+        surface: wgpu::Surface,
+    ) -> Result<(wgpu::Surface, Option<wgpu::Adapter>), Error> {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(timeout) = self.adapter_timeout {
+            use std::thread;
+
+            // On native platforms the request blocks inside of the driver, so it has to run on
+            // another thread to be able to stop waiting on it.
+            let instance = self.instance.clone();
+            let (send, recv) = mpsc::channel();
+            thread::Builder::new()
+                .name("theo adapter request".into())
+                .spawn(move || {
+                    let adapter =
+                        crate::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+                            compatible_surface: Some(&surface),
+                            ..Default::default()
+                        }));
+                    send.send((surface, adapter)).ok();
+                })
+                .piet_err()?;
+
+            return recv.recv_timeout(timeout).map_err(|_| {
+                tracing::warn!("timed out after {:?} waiting for a wgpu adapter", timeout);
+                Error::BackendError("Timed out waiting for an adapter".into())
+            });
+        }
+
+        let adapter = self
+            .instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                compatible_surface: Some(&surface),
+                ..Default::default()
+            })
+            .await;
+
+        Ok((surface, adapter))
+    }
+
     pub(super) async unsafe fn make_surface(
         &mut self,
         raw: RawWindowHandle,
         width: u32,
         height: u32,
     ) -> Result<Surface, Error> {
+        // Surfaces dropped while the application was suspended may still refer to windows that
+        // no longer exist, so get rid of them before making new ones.
+        self.collect_dropped_surfaces();
+
         // Create a new surface.
-        let surface = self
+        let mut surface = self
             .instance
             .create_surface(&RawHandles(self.raw, raw))
             .piet_err()?;
@@ -149,33 +422,69 @@ impl Display {
             adapter
         } else {
             // Request a new adapter.
-            let adapter = self
-                .instance
-                .request_adapter(&wgpu::RequestAdapterOptions {
-                    compatible_surface: Some(&surface),
-                    ..Default::default()
-                })
-                .await
-                .ok_or_else(|| Error::NotSupported)?;
+            let (returned, adapter) = self.request_adapter(surface).await?;
+            surface = returned;
+            let adapter = adapter.ok_or(Error::NotSupported)?;
+
+            // piet-wgpu samples its glyph atlas and clip masks with clamp-to-border, and there
+            // is no way to ask it for a different sampler. Without the feature, the device
+            // would raise a validation error at the first draw, so reject the adapter here
+            // with a clear reason instead.
+            let adapter_features = adapter.features();
+            if !adapter_features.contains(wgpu::Features::ADDRESS_MODE_CLAMP_TO_BORDER) {
+                tracing::warn!(
+                    "adapter {:?} does not support clamp-to-border samplers",
+                    adapter.get_info().name
+                );
+                return Err(Error::NotSupported);
+            }
+
+            // WebGL2 and other GL backends can't provide the default limits, so ask for the
+            // downlevel ones unless the user picked their own.
+            let limits = match &self.limits {
+                Some(limits) => limits.clone(),
+                None if adapter.get_info().backend == wgpu::Backend::Gl => {
+                    wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits())
+                }
+                None => wgpu::Limits::default(),
+            };
 
             // Create the device and queue.
             let (device, queue) = adapter
                 .request_device(
                     &wgpu::DeviceDescriptor {
                         label: Some("theo device and queue"),
-                        features: wgpu::Features::ADDRESS_MODE_CLAMP_TO_BORDER,
-                        limits: wgpu::Limits::default(),
+                        features: wgpu::Features::ADDRESS_MODE_CLAMP_TO_BORDER
+                            | (adapter_features
+                                & wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES)
+                            | self.features,
+                        limits,
                     },
                     None,
                 )
                 .await
                 .piet_err()?;
 
+            // The default error handler panics on every error. `wgpu` doesn't say which errors
+            // mean that the device is lost, and after any uncaptured error the frame can't be
+            // trusted anyway, so treat them all as a lost device and let the application
+            // rebuild the display.
+            let lost = Arc::new(AtomicBool::new(false));
+            device.on_uncaptured_error(Box::new({
+                let lost = lost.clone();
+                move |err| {
+                    tracing::error!("uncaptured wgpu error: {err}");
+                    lost.store(true, Ordering::Relaxed);
+                }
+            }));
+
             // Add it to the list of known adapters.
             self.adapters.push(AdapterInfo {
                 adapter,
                 device,
                 queue,
+                last_submission: None,
+                lost,
             });
             (self.adapters.len() - 1, self.adapters.last().unwrap())
         };
@@ -184,69 +493,173 @@ impl Display {
         let cap = surface.get_capabilities(&adapter.adapter);
 
         // Create the surface configuration.
-        let format = cap
-            .formats
-            .iter()
-            .find(|format| {
-                matches!(format, wgpu::TextureFormat::Rgba8Unorm)
-                    | matches!(format, wgpu::TextureFormat::Bgra8Unorm)
+        // Prefer the sRGB formats if the user asked for them.
+        let find_format = |formats: &[wgpu::TextureFormat]| {
+            cap.formats.iter().find(|format| formats.contains(format))
+        };
+        let srgb_format = match self.color_space {
+            Some(ColorSpace::Srgb) => find_format(&[
+                wgpu::TextureFormat::Rgba8UnormSrgb,
+                wgpu::TextureFormat::Bgra8UnormSrgb,
+            ]),
+            _ => None,
+        };
+        let format = srgb_format
+            .or_else(|| {
+                find_format(&[
+                    wgpu::TextureFormat::Rgba8Unorm,
+                    wgpu::TextureFormat::Bgra8Unorm,
+                ])
             })
             .or_else(|| cap.formats.first())
             .ok_or(Error::NotSupported)?;
-        let alpha_mode = cap
-            .alpha_modes
+
+        // piet colors are already in sRGB space, so we need to render into a view that doesn't
+        // convert them again. Otherwise, everything would come out lighter than it should.
+        let render_format = format.remove_srgb_suffix();
+        // Blending leaves premultiplied colors in the surface, so prefer telling the compositor
+        // that, and fall back to the other modes that keep the alpha channel.
+        let transparent_modes = [
+            wgpu::CompositeAlphaMode::PreMultiplied,
+            wgpu::CompositeAlphaMode::PostMultiplied,
+            wgpu::CompositeAlphaMode::Inherit,
+        ];
+        let alpha_mode = transparent_modes
             .iter()
-            .find(|am| {
-                if self.supports_transparency {
-                    matches!(
-                        am,
-                        wgpu::CompositeAlphaMode::PostMultiplied
-                            | wgpu::CompositeAlphaMode::Inherit
-                    )
-                } else {
-                    true
-                }
-            })
+            .filter(|_| self.supports_transparency)
+            .find(|am| cap.alpha_modes.contains(am))
             .or_else(|| cap.alpha_modes.first())
             .ok_or(Error::NotSupported)?;
 
+        // Copy the frames out of the surface texture for snapshots, if the platform allows it.
+        let usage =
+            wgpu::TextureUsages::RENDER_ATTACHMENT | (cap.usages & wgpu::TextureUsages::COPY_SRC);
         let config = wgpu::SurfaceConfiguration {
             format: *format,
             width,
             height,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            usage,
             present_mode: wgpu::PresentMode::AutoVsync,
             alpha_mode: *alpha_mode,
-            view_formats: vec![*format],
+            view_formats: vec![render_format],
+        };
+
+        // Figure out how many samples we can use for MSAA.
+        let samples = {
+            let flags = if adapter
+                .device
+                .features()
+                .contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES)
+            {
+                adapter
+                    .adapter
+                    .get_texture_format_features(render_format)
+                    .flags
+            } else {
+                render_format
+                    .guaranteed_format_features(adapter.device.features())
+                    .flags
+            };
+            let requested = self.msaa.unwrap_or(1);
+
+            [16, 8, 4, 2, 1]
+                .into_iter()
+                .find(|&count| count <= requested && flags.sample_count_supported(count))
+                .unwrap_or(1)
         };
 
         // Create a signal to indicate that the surface has been dropped.
         let signal = Rc::new(());
 
         let info = SurfaceInfo {
+            msaa: create_msaa_view(&adapter.device, &config, samples),
+            clear_color: None,
             surface,
             config,
-            context: WgpuContext::new(&adapter.device, &adapter.queue, *format, None, 1),
+            configured: false,
+            context: WgpuContext::new(
+                &adapter.device,
+                &adapter.queue,
+                render_format,
+                None,
+                samples,
+            ),
+            fonts_loaded: false,
+            id: self.next_surface_id,
             texture: None,
+            frame: None,
+            lost: false,
+            samples,
             adapter_index: index,
             dropped: Rc::downgrade(&signal),
         };
 
         // Put the surface in our list.
+        self.next_surface_id += 1;
         let surface_index = self.surfaces.insert(info);
 
         Ok(Surface {
             surface_index,
-            _dropped: signal,
+            dropped: signal,
         })
     }
 
+    /// Free the resources of surfaces that have been dropped.
+    fn collect_dropped_surfaces(&mut self) {
+        self.surfaces.retain(|_, surface| {
+            let alive = surface.dropped.upgrade().is_some();
+            if !alive {
+                self.adapters[surface.adapter_index].wait_for_last_submission();
+            }
+            alive
+        });
+    }
+
+    pub(super) fn destroy_surface(&mut self, surface: Surface) -> Result<(), Error> {
+        // Make sure that the surface actually belongs to this display.
+        let owned = self
+            .surfaces
+            .get(surface.surface_index)
+            .is_some_and(|info| Weak::ptr_eq(&info.dropped, &Rc::downgrade(&surface.dropped)));
+        if !owned {
+            return Err(Error::InvalidInput);
+        }
+
+        // Free the surface's resources now instead of waiting for the next present.
+        self.surfaces.remove(surface.surface_index);
+        Ok(())
+    }
+
+    pub(super) fn begin_frame(&mut self) {
+        // Surfaces are always presented together in `present`.
+    }
+
+    pub(super) async fn end_frame(&mut self) -> Result<(), Error> {
+        self.present().await;
+        Ok(())
+    }
+
     #[inline]
-    pub(crate) async fn present(&mut self) {
+    pub(super) fn poll(&mut self) {
+        for adapter in self.adapters.iter().filter(|adapter| !adapter.is_lost()) {
+            adapter.device.poll(wgpu::Maintain::Poll);
+        }
+    }
+
+    pub(super) fn device_lost(&self) -> bool {
+        self.adapters.iter().any(AdapterInfo::is_lost)
+    }
+
+    pub(super) async fn present(&mut self) {
         // TODO: Use an executor to .await on the queues finishing.
 
         // Run submit operations for each adapter.
-        for (adapter_index, adapter) in self.adapters.iter().enumerate() {
+        for (adapter_index, adapter) in self.adapters.iter_mut().enumerate() {
+            // Submitting to a lost device panics.
+            if adapter.is_lost() {
+                continue;
+            }
+
             let mut encoder =
                 adapter
                     .device
@@ -258,32 +671,84 @@ impl Display {
             // TODO: Could this be more efficient?
             for (i, surface) in &mut self.surfaces {
                 if surface.adapter_index == adapter_index {
-                    let surface_texture = surface
-                        .texture
-                        .get_or_insert_with(|| surface.surface.get_current_texture().unwrap());
+                    if surface.texture.is_none() {
+                        surface.texture =
+                            acquire_texture(&surface.surface, adapter, &surface.config);
+                        surface.lost = surface.texture.is_none();
+                    }
+
+                    // Skip the frame if the surface can't be drawn to right now.
+                    let surface_texture = match &surface.texture {
+                        Some(texture) => texture,
+                        None => continue,
+                    };
                     let view = surface_texture
                         .texture
-                        .create_view(&wgpu::TextureViewDescriptor::default());
-
-                    // TODO: MSAA
-                    let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                        label: Some(&format!("theo render pass for surface #{i}")),
-                        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                            view: &view,
-                            resolve_target: None,
-                            ops: wgpu::Operations {
-                                load: wgpu::LoadOp::Load,
-                                store: true,
-                            },
-                        })],
-                        depth_stencil_attachment: None,
-                    });
-                    surface.context.render(&mut pass);
+                        .create_view(&wgpu::TextureViewDescriptor {
+                            format: Some(surface.config.view_formats[0]),
+                            ..Default::default()
+                        });
+
+                    // If we're using MSAA, render into the multisampled texture and resolve it
+                    // into the surface texture.
+                    let (view, resolve_target) = match &surface.msaa {
+                        Some(msaa) => (msaa, Some(&view)),
+                        None => (&view, None),
+                    };
+
+                    {
+                        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                            label: Some(&format!("theo render pass for surface #{i}")),
+                            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                                view,
+                                resolve_target,
+                                ops: wgpu::Operations {
+                                    load: match surface.clear_color.take() {
+                                        Some(color) => wgpu::LoadOp::Clear(color),
+                                        None => wgpu::LoadOp::Load,
+                                    },
+                                    store: true,
+                                },
+                            })],
+                            depth_stencil_attachment: None,
+                        });
+                        surface.context.render(&mut pass);
+                    }
+
+                    // Keep a copy of the frame, since the surface texture can't be read once
+                    // it has been presented.
+                    if surface.config.usage.contains(wgpu::TextureUsages::COPY_SRC) {
+                        let size = surface_texture.texture.size();
+                        let stale = surface
+                            .frame
+                            .as_ref()
+                            .map_or(true, |frame| frame.size() != size);
+                        if stale {
+                            surface.frame =
+                                Some(adapter.device.create_texture(&wgpu::TextureDescriptor {
+                                    label: Some("theo frame copy"),
+                                    size,
+                                    mip_level_count: 1,
+                                    sample_count: 1,
+                                    dimension: wgpu::TextureDimension::D2,
+                                    format: surface.config.format,
+                                    usage: wgpu::TextureUsages::COPY_DST
+                                        | wgpu::TextureUsages::COPY_SRC,
+                                    view_formats: &[],
+                                }));
+                        }
+                        let frame = surface.frame.as_ref().unwrap();
+                        encoder.copy_texture_to_texture(
+                            surface_texture.texture.as_image_copy(),
+                            frame.as_image_copy(),
+                            size,
+                        );
+                    }
                 }
             }
 
             // Submit the queue.
-            adapter.queue.submit(Some(encoder.finish()));
+            adapter.last_submission = Some(adapter.queue.submit(Some(encoder.finish())));
         }
 
         // Swap the buffers on each surface.
@@ -295,13 +760,78 @@ impl Display {
                 texture.present();
             }
 
-            // If we need to garbage-collect this surface, do so now.
-            surface.dropped.upgrade().is_some()
+            // If we need to garbage-collect this surface, do so once the GPU is done with it.
+            let alive = surface.dropped.upgrade().is_some();
+            if !alive {
+                adapter.wait_for_last_submission();
+            }
+            alive
         });
     }
 }
 
+impl Surface {
+    pub(super) fn format_info(&self, display: &Display) -> Result<FormatInfo, Error> {
+        use wgpu::TextureFormat;
+
+        let format = display.surfaces[self.surface_index].config.format;
+        let (red_bits, green_bits, blue_bits, alpha_bits) = match format {
+            TextureFormat::Rgba8Unorm
+            | TextureFormat::Rgba8UnormSrgb
+            | TextureFormat::Bgra8Unorm
+            | TextureFormat::Bgra8UnormSrgb => (8, 8, 8, 8),
+            TextureFormat::Rgb10a2Unorm => (10, 10, 10, 2),
+            TextureFormat::Rgba16Float => (16, 16, 16, 16),
+            _ => (0, 0, 0, 0),
+        };
+        let color_space = if format.is_srgb() {
+            ColorSpace::Srgb
+        } else {
+            ColorSpace::Linear
+        };
+
+        Ok(FormatInfo {
+            red_bits,
+            green_bits,
+            blue_bits,
+            alpha_bits,
+            color_space,
+            wgpu_format: Some(format),
+        })
+    }
+
+    pub(super) fn snapshot(&mut self, display: &mut Display) -> Result<(u32, u32, Vec<u8>), Error> {
+        let surface = &display.surfaces[self.surface_index];
+        let adapter = &display.adapters[surface.adapter_index];
+
+        // Reading back from a lost device panics.
+        if adapter.is_lost() {
+            return Err(Error::BackendError("The wgpu device was lost".into()));
+        }
+
+        // Frames are only copied if the surface texture can be copied from.
+        if !surface.config.usage.contains(wgpu::TextureUsages::COPY_SRC) {
+            return Err(Error::NotSupported);
+        }
+        let frame = surface
+            .frame
+            .as_ref()
+            .ok_or_else(|| Error::BackendError("No frame was presented to the surface".into()))?;
+
+        let pixels = read_texture(&adapter.device, &adapter.queue, frame)?;
+        Ok((frame.width(), frame.height(), crate::demultiply(&pixels)))
+    }
+
+    pub(super) fn is_renderable(&self, display: &Display) -> bool {
+        let surface = &display.surfaces[self.surface_index];
+        !surface.lost && !display.adapters[surface.adapter_index].is_lost()
+    }
+}
+
 impl<'dsp, 'surf> RenderContext<'dsp, 'surf> {
+    /// `wgpu` has no thread-local state.
+    pub(super) const EXCLUSIVE: bool = false;
+
     pub(super) unsafe fn new(
         display: &'dsp mut Display,
         surface: &'surf mut Surface,
@@ -311,23 +841,60 @@ impl<'dsp, 'surf> RenderContext<'dsp, 'surf> {
         let real_surface = &mut display.surfaces[surface.surface_index];
         let adapter = &display.adapters[real_surface.adapter_index];
 
-        // Set the texture size.
-        real_surface.config.width = width;
-        real_surface.config.height = height;
-        real_surface
-            .surface
-            .configure(&adapter.device, &real_surface.config);
+        // Configuring a surface on a lost device panics.
+        if adapter.is_lost() {
+            return Err(Error::BackendError("The wgpu device was lost".into()));
+        }
+
+        // Reconfiguring the surface recreates its swapchain, so only do it when the size changes.
+        let config = &mut real_surface.config;
+        if !real_surface.configured || config.width != width || config.height != height {
+            adapter.wait_for_last_submission();
+            config.width = width;
+            config.height = height;
+            real_surface.surface.configure(&adapter.device, config);
+            real_surface.configured = true;
+
+            // Resize the multisampled texture to match.
+            if real_surface.msaa.is_some() {
+                real_surface.msaa = create_msaa_view(&adapter.device, config, real_surface.samples);
+            }
+        }
+
+        let max_texture_size = adapter.device.limits().max_texture_dimension_2d;
 
         // Create the inner context.
-        let mut inner =
-            real_surface
-                .context
-                .prepare(&adapter.device, &adapter.queue, width, height);
+        let format = real_surface.config.view_formats[0];
+        let SurfaceInfo {
+            context,
+            clear_color,
+            fonts_loaded,
+            id,
+            samples,
+            ..
+        } = real_surface;
+        let context = NonNull::from(context);
+        let mut inner = (*context.as_ptr()).prepare(&adapter.device, &adapter.queue, width, height);
+        let mut text = Text::new(TextInner::Wgpu(inner.text().clone()));
+        display.fonts.load_into(&mut text, fonts_loaded);
 
         Ok(Self {
-            text: Text(TextInner::Wgpu(inner.text().clone())),
+            text,
             _surface: surface,
-            inner,
+            clear_color,
+            inner: Some(inner),
+            context,
+            size: (width, height),
+            format,
+            samples: *samples,
+            drawn: false,
+            levels: vec![SaveLevel::default()],
+            mismatch_err: Ok(()),
+            max_texture_size,
+            device: &adapter.device,
+            queue: &adapter.queue,
+            lost: &adapter.lost,
+            cache_key: *id,
         })
     }
 
@@ -340,24 +907,116 @@ impl<'dsp, 'surf> RenderContext<'dsp, 'surf> {
         Self::new(display, surface, width, height)
     }
 
+    /// Each surface has its own context, so brushes can't be shared between surfaces.
+    pub(super) fn cache_key(&self) -> u64 {
+        self.cache_key
+    }
+
+    fn inner(&mut self) -> &mut piet_wgpu::RenderContext<'dsp, 'dsp, 'dsp> {
+        self.inner
+            .as_mut()
+            .expect("Tried to use context after finish()")
+    }
+
+    /// Get the inner context to draw something with it.
+    fn draw(&mut self) -> &mut piet_wgpu::RenderContext<'dsp, 'dsp, 'dsp> {
+        self.drawn = true;
+        self.inner()
+    }
+
+    /// Throw away everything drawn so far in this frame, keeping the save stack.
+    fn restart(&mut self) {
+        let transform = self.current_transform();
+        drop(self.inner.take());
+
+        // SAFETY: `inner` was dropped above, so nothing else borrows the context.
+        let context = unsafe { &mut *self.context.as_ptr() };
+        context.after_submit(self.device);
+        let (width, height) = self.size;
+        let inner = self
+            .inner
+            .insert(context.prepare(self.device, self.queue, width, height));
+
+        // Rebuild the save stack, along with its clips.
+        let set_transform = |inner: &mut piet_wgpu::RenderContext<'_, '_, '_>, to: Affine| {
+            let from = inner.current_transform();
+            inner.transform(from.inverse() * to);
+        };
+        for (i, level) in self.levels.iter().enumerate() {
+            if i > 0 {
+                set_transform(inner, level.transform);
+                if let Err(err) = inner.save() {
+                    self.mismatch_err = Err(err);
+                }
+            }
+
+            for (clip_transform, clip) in &level.clips {
+                set_transform(inner, *clip_transform);
+                inner.clip(clip);
+            }
+        }
+        set_transform(inner, transform);
+
+        self.drawn = false;
+    }
+
+    /// Fail if the device has been lost since this context was created.
+    fn check_lost(&self) -> Result<(), Error> {
+        if self.lost.load(Ordering::Relaxed) {
+            return Err(Error::BackendError("The wgpu device was lost".into()));
+        }
+
+        Ok(())
+    }
+
     pub(super) fn status(&mut self) -> Result<(), Error> {
-        self.inner.status()
+        let status = match self.inner.as_mut() {
+            Some(inner) => inner.status(),
+            None => Ok(()),
+        };
+        let mismatch = std::mem::replace(&mut self.mismatch_err, Ok(()));
+        status.and(mismatch)
+    }
+
+    pub(super) fn max_texture_size(&self) -> u32 {
+        self.max_texture_size
     }
 
     pub(super) fn solid_brush(&mut self, color: piet::Color) -> Brush {
-        self.inner.solid_brush(color)
+        self.inner().solid_brush(color)
     }
 
     pub(super) fn gradient(&mut self, gradient: piet::FixedGradient) -> Result<Brush, Error> {
-        self.inner.gradient(gradient)
+        self.inner().gradient(gradient)
     }
 
     pub(super) fn clear(&mut self, region: Option<Rect>, color: piet::Color) {
-        self.inner.clear(region, color)
+        // The renderer always clears to an opaque color, which breaks transparent windows.
+        // Instead, clear the surface when the render pass starts.
+        let (r, g, b, a) = color.as_rgba();
+        if region.is_none() && a < 1.0 {
+            // Everything recorded so far would be drawn over the clear, so start over.
+            if self.drawn {
+                self.restart();
+            }
+
+            *self.clear_color = Some(wgpu::Color {
+                r: r * a,
+                g: g * a,
+                b: b * a,
+                a,
+            });
+            return;
+        }
+
+        if region.is_none() {
+            *self.clear_color = None;
+        }
+        self.draw().clear(region, color)
     }
 
     pub(super) fn stroke(&mut self, shape: impl Shape, brush: &Brush, width: f64) {
-        self.inner.stroke(shape, brush, width)
+        self.draw().stroke(shape, brush, width)
     }
 
     pub(super) fn stroke_styled(
@@ -367,19 +1026,26 @@ impl<'dsp, 'surf> RenderContext<'dsp, 'surf> {
         width: f64,
         style: &StrokeStyle,
     ) {
-        self.inner.stroke_styled(shape, brush, width, style)
+        self.draw().stroke_styled(shape, brush, width, style)
     }
 
     pub(super) fn fill(&mut self, shape: impl Shape, brush: &Brush) {
-        self.inner.fill(shape, brush)
+        self.draw().fill(shape, brush)
     }
 
     pub(super) fn fill_even_odd(&mut self, shape: impl Shape, brush: &Brush) {
-        self.inner.fill_even_odd(shape, brush)
+        self.draw().fill_even_odd(shape, brush)
     }
 
     pub(super) fn clip(&mut self, shape: impl Shape) {
-        self.inner.clip(shape)
+        let transform = self.current_transform();
+        let path = shape.to_path(0.1);
+        self.inner().clip(shape);
+        self.levels
+            .last_mut()
+            .unwrap()
+            .clips
+            .push((transform, path));
     }
 
     pub(super) fn text(&mut self) -> &mut Text {
@@ -388,27 +1054,131 @@ impl<'dsp, 'surf> RenderContext<'dsp, 'surf> {
 
     pub(super) fn draw_text(&mut self, layout: &crate::text::TextLayout, pos: Point) {
         match layout.0 {
-            crate::text::TextLayoutInner::Wgpu(ref layout) => self.inner.draw_text(layout, pos),
+            crate::text::TextLayoutInner::Wgpu(ref layout) => self.draw().draw_text(layout, pos),
 
-            _ => panic!("invalid text layout"),
+            // Layouts from `Display::text` need to be rebuilt with our text backend.
+            crate::text::TextLayoutInner::Cosmic(_) => match layout.rebuild(&mut self.text) {
+                Ok(layout) => self.draw_text(&layout, pos),
+                Err(err) => self.mismatch_err = Err(err),
+            },
+
+            // Layouts from other renderers can't be drawn by this one.
+            #[cfg(feature = "gl")]
+            crate::text::TextLayoutInner::Glow(_) => self.mismatch_err = Err(Error::InvalidInput),
         }
     }
 
     pub(super) fn save(&mut self) -> Result<(), Error> {
-        self.inner.save()
+        self.inner().save()?;
+        self.levels.push(SaveLevel {
+            transform: self.current_transform(),
+            clips: Vec::new(),
+        });
+        Ok(())
     }
 
     pub(super) fn restore(&mut self) -> Result<(), Error> {
-        self.inner.restore()
+        self.inner().restore()?;
+        if self.levels.len() > 1 {
+            self.levels.pop();
+        }
+        Ok(())
     }
 
     pub(super) fn finish(&mut self) -> Result<(), Error> {
-        self.inner.finish()?;
+        self.inner().finish()?;
+        Ok(())
+    }
+
+    pub(super) fn finish_with_damage(&mut self, _regions: &[Rect]) -> Result<(), Error> {
+        // wgpu can't present only part of a surface.
+        self.finish()
+    }
+
+    pub(super) fn flush(&mut self) -> Result<(), Error> {
+        // piet-wgpu records the frame, and it is only submitted in `Display::present`.
         Ok(())
     }
 
+    pub(super) fn push_layer(&mut self, _blend: BlendMode) -> Result<(), Error> {
+        // The frame is recorded and drawn in a single pass when the display presents it, so
+        // nothing can be drawn on its own in the middle of it.
+        Err(Error::NotSupported)
+    }
+
+    pub(super) fn pop_layer(&mut self, _alpha: f64, _blend: BlendMode) {
+        // Layers are never pushed.
+    }
+
+    pub(super) fn finish_to_buffer(&mut self) -> Result<Vec<u8>, Error> {
+        // Reading back from a lost device panics.
+        self.check_lost()?;
+        if !is_readable(self.format) {
+            return Err(Error::NotSupported);
+        }
+
+        // The surface texture can't be copied from, so render the frame a second time into a
+        // texture that can. The frame is still rendered to the surface in `Display::present`.
+        let mut inner = self
+            .inner
+            .take()
+            .expect("Tried to use context after finish()");
+        inner.finish()?;
+        drop(inner);
+
+        let (width, height) = self.size;
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("theo readback framebuffer"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let msaa = create_msaa_texture(self.device, width, height, self.format, self.samples);
+        let (view, resolve_target) = match &msaa {
+            Some(msaa) => (msaa, Some(&view)),
+            None => (&view, None),
+        };
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("theo readback encoder"),
+            });
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("theo readback render pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(
+                            self.clear_color.unwrap_or(wgpu::Color::TRANSPARENT),
+                        ),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+
+            // SAFETY: `inner` was dropped above, so nothing else borrows the context.
+            unsafe { (*self.context.as_ptr()).render(&mut pass) };
+        }
+        self.queue.submit(Some(encoder.finish()));
+
+        read_texture(self.device, self.queue, &texture)
+    }
+
     pub(super) fn transform(&mut self, transform: piet::kurbo::Affine) {
-        self.inner.transform(transform)
+        self.inner().transform(transform)
     }
 
     pub(super) fn make_image(
@@ -418,7 +1188,26 @@ impl<'dsp, 'surf> RenderContext<'dsp, 'surf> {
         buf: &[u8],
         format: piet::ImageFormat,
     ) -> Result<Image, Error> {
-        self.inner.make_image(width, height, buf, format)
+        let (buf, format) = crate::straight_alpha(buf, format);
+        self.inner().make_image(width, height, &buf, format)
+    }
+
+    pub(super) fn image_from_texture(&mut self, texture: &wgpu::Texture) -> Result<Image, Error> {
+        // Reading back from a lost device panics.
+        self.check_lost()?;
+
+        // piet-wgpu only creates images from pixel data, so read the texture back first.
+        if !texture.usage().contains(wgpu::TextureUsages::COPY_SRC) {
+            return Err(Error::InvalidInput);
+        }
+        let pixels = read_texture(self.device, self.queue, texture)?;
+
+        self.inner().make_image(
+            texture.width() as usize,
+            texture.height() as usize,
+            &pixels,
+            piet::ImageFormat::RgbaSeparate,
+        )
     }
 
     pub(super) fn draw_image(
@@ -427,7 +1216,7 @@ impl<'dsp, 'surf> RenderContext<'dsp, 'surf> {
         rect: Rect,
         interp: piet::InterpolationMode,
     ) {
-        self.inner.draw_image(image, rect, interp)
+        self.draw().draw_image(image, rect, interp)
     }
 
     pub(super) fn draw_image_area(
@@ -437,23 +1226,197 @@ impl<'dsp, 'surf> RenderContext<'dsp, 'surf> {
         dst_rect: Rect,
         interp: piet::InterpolationMode,
     ) {
-        self.inner
+        self.draw()
             .draw_image_area(image, src_rect, dst_rect, interp)
     }
 
     pub(super) fn capture_image_area(&mut self, src_rect: Rect) -> Result<Image, Error> {
-        self.inner.capture_image_area(src_rect)
+        // Reading back from a lost device panics.
+        self.check_lost()?;
+        self.inner().capture_image_area(src_rect)
+    }
+
+    pub(super) fn read_image(&mut self, _image: &Image) -> Result<(u32, u32, Vec<u8>), Error> {
+        // The renderer can't read textures back.
+        Err(Error::NotSupported)
     }
 
     pub(super) fn blurred_rect(&mut self, rect: Rect, blur_radius: f64, brush: &Brush) {
-        self.inner.blurred_rect(rect, blur_radius, brush)
+        self.draw().blurred_rect(rect, blur_radius, brush)
     }
 
     pub(super) fn current_transform(&self) -> piet::kurbo::Affine {
-        self.inner.current_transform()
+        self.inner
+            .as_ref()
+            .expect("Tried to use context after finish()")
+            .current_transform()
     }
 }
 
+/// Create the multisampled texture to render into, if we are using MSAA.
+/// Get the next texture to draw to from a surface.
+///
+/// Returns `None` if the surface can't be drawn to right now, such as when its window is
+/// minimized on some platforms.
+fn acquire_texture(
+    surface: &wgpu::Surface,
+    adapter: &AdapterInfo,
+    config: &wgpu::SurfaceConfiguration,
+) -> Option<wgpu::SurfaceTexture> {
+    match surface.get_current_texture() {
+        Ok(texture) => Some(texture),
+        Err(wgpu::SurfaceError::Outdated | wgpu::SurfaceError::Lost) => {
+            // The surface is no longer configured the way the window needs, so configure it again.
+            adapter.wait_for_last_submission();
+            surface.configure(&adapter.device, config);
+            match surface.get_current_texture() {
+                Ok(texture) => Some(texture),
+                Err(err) => {
+                    tracing::warn!("failed to acquire surface texture, skipping frame: {}", err);
+                    None
+                }
+            }
+        }
+        Err(wgpu::SurfaceError::Timeout) => {
+            tracing::warn!("timed out acquiring surface texture, skipping frame");
+            None
+        }
+        Err(err) => panic!("failed to acquire surface texture: {}", err),
+    }
+}
+
+fn create_msaa_view(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+    samples: u32,
+) -> Option<wgpu::TextureView> {
+    create_msaa_texture(
+        device,
+        config.width,
+        config.height,
+        config.view_formats[0],
+        samples,
+    )
+}
+
+/// Create a multisampled texture with the given size and format, if we are using MSAA.
+fn create_msaa_texture(
+    device: &wgpu::Device,
+    width: u32,
+    height: u32,
+    format: wgpu::TextureFormat,
+    samples: u32,
+) -> Option<wgpu::TextureView> {
+    if samples <= 1 {
+        return None;
+    }
+
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("theo multisampled framebuffer"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: samples,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        view_formats: &[],
+    });
+
+    Some(texture.create_view(&wgpu::TextureViewDescriptor::default()))
+}
+
+/// Whether textures of this format can be read back as RGBA8 pixels.
+fn is_readable(format: wgpu::TextureFormat) -> bool {
+    use wgpu::TextureFormat;
+
+    matches!(
+        format,
+        TextureFormat::Rgba8Unorm
+            | TextureFormat::Rgba8UnormSrgb
+            | TextureFormat::Bgra8Unorm
+            | TextureFormat::Bgra8UnormSrgb
+    )
+}
+
+/// Copy a texture into a mapped buffer and read it back as tightly packed RGBA8 pixels.
+///
+/// The texture must have been created with `COPY_SRC`.
+fn read_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+) -> Result<Vec<u8>, Error> {
+    use wgpu::TextureFormat;
+
+    if !is_readable(texture.format()) {
+        return Err(Error::NotSupported);
+    }
+    let bgra = matches!(
+        texture.format(),
+        TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb
+    );
+
+    let (width, height) = (texture.width(), texture.height());
+    let row_len = width * 4;
+    let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let padded_row_len = (row_len + align - 1) / align * align;
+
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("theo texture readback"),
+        size: u64::from(padded_row_len) * u64::from(height),
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("theo texture readback encoder"),
+    });
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::ImageCopyBuffer {
+            buffer: &buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_row_len),
+                rows_per_image: Some(height),
+            },
+        },
+        texture.size(),
+    );
+    queue.submit(Some(encoder.finish()));
+
+    // Wait for the copy to finish.
+    let slice = buffer.slice(..);
+    let (send, recv) = mpsc::channel();
+    slice.map_async(wgpu::MapMode::Read, move |result| {
+        send.send(result).ok();
+    });
+    device.poll(wgpu::Maintain::Wait);
+
+    // Polling can't block on the web, so the mapping may not be ready yet.
+    recv.try_recv()
+        .map_err(|_| Error::NotSupported)?
+        .piet_err()?;
+
+    let mut pixels = Vec::with_capacity((row_len * height) as usize);
+    for row in slice.get_mapped_range().chunks(padded_row_len as usize) {
+        pixels.extend_from_slice(&row[..row_len as usize]);
+    }
+    buffer.unmap();
+
+    if bgra {
+        for pixel in pixels.chunks_exact_mut(4) {
+            pixel.swap(0, 2);
+        }
+    }
+
+    Ok(pixels)
+}
+
 type Brush = piet_wgpu::Brush;
 type Image = piet_wgpu::Image;
 
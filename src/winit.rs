@@ -0,0 +1,171 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later OR MPL-2.0
+// This file is a part of `theo`.
+//
+// `theo` is free software: you can redistribute it and/or modify it under the terms of
+// either:
+//
+// * GNU Lesser General Public License as published by the Free Software Foundation, either
+// version 3 of the License, or (at your option) any later version.
+// * Mozilla Public License as published by the Mozilla Foundation, version 2.
+//
+// `theo` is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY;
+// without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+// See the GNU Lesser General Public License or the Mozilla Public License for more details.
+//
+// You should have received a copy of the GNU Lesser General Public License and the Mozilla
+// Public License along with `theo`. If not, see <https://www.gnu.org/licenses/>.
+
+//! Helpers for using `theo` with [`winit`].
+//!
+//! Creating a [`Display`] and [`Surface`] for [`winit`] takes a few platform-specific steps:
+//! registering the Xlib error hook on X11, creating a window to bootstrap WGL on Windows, and
+//! picking a window visual that the display can draw to. The functions in this module take care
+//! of these steps.
+//!
+//! This module is only available when the `winit` feature is enabled.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use winit::event::Event;
+//! use winit::event_loop::EventLoop;
+//! use winit::window::WindowBuilder;
+//!
+//! let event_loop = EventLoop::new();
+//! let mut display = unsafe { theo::winit::build_display(&event_loop) }.unwrap();
+//! let mut state = None;
+//!
+//! event_loop.run(move |event, elwt, _| {
+//!     if let Event::Resumed = event {
+//!         let window = theo::winit::window_builder(&display, WindowBuilder::new())
+//!             .build(elwt)
+//!             .unwrap();
+//!         let surface = unsafe { theo::winit::make_surface(&mut display, &window) }.unwrap();
+//!         state = Some((window, surface));
+//!     }
+//! });
+//! ```
+//!
+//! [`winit`]: https://crates.io/crates/winit
+
+use crate::{Display, DisplayBuilder, Surface};
+
+use ::winit::event_loop::EventLoopWindowTarget;
+use ::winit::window::{Window, WindowBuilder};
+
+use piet::Error;
+
+/// Create a new [`Display`] for a [`winit`] event loop with the default parameters.
+///
+/// This is a shorthand for [`build_display_with`] with a default [`DisplayBuilder`].
+///
+/// # Safety
+///
+/// The returned [`Display`] must not outlive the event loop.
+///
+/// [`winit`]: https://crates.io/crates/winit
+pub unsafe fn build_display<T>(event_loop: &EventLoopWindowTarget<T>) -> Result<Display, Error> {
+    build_display_with(DisplayBuilder::new(), event_loop)
+}
+
+/// Create a new [`Display`] for a [`winit`] event loop.
+///
+/// This sets the platform-specific parameters of `builder` that [`winit`] can provide, and then
+/// builds the [`Display`]. On X11, [`winit`]'s Xlib error hook is used for GLX. On Windows, a
+/// hidden window is created to bootstrap WGL, and is destroyed once the [`Display`] is built.
+///
+/// # Safety
+///
+/// The returned [`Display`] must not outlive the event loop.
+///
+/// [`winit`]: https://crates.io/crates/winit
+pub unsafe fn build_display_with<T>(
+    builder: DisplayBuilder,
+    event_loop: &EventLoopWindowTarget<T>,
+) -> Result<Display, Error> {
+    #[allow(unused_mut)]
+    let mut builder = builder;
+
+    #[cfg(x11_platform)]
+    {
+        builder = builder.glx_error_hook(::winit::platform::x11::register_xlib_error_hook);
+    }
+
+    // WGL needs a window to query for extensions. It only has to live until the display is built.
+    #[cfg(windows)]
+    let _bootstrap = {
+        let window = WindowBuilder::new()
+            .with_visible(false)
+            .build(event_loop)
+            .map_err(|err| Error::BackendError(err.into()))?;
+        builder = builder.window(&window);
+        window
+    };
+
+    builder.build(event_loop)
+}
+
+/// Set up a [`WindowBuilder`] so that `display` can draw to the window it creates.
+///
+/// If `display` doesn't support transparency, the window is made opaque. On X11, the window
+/// uses the visual that `display` picked.
+pub fn window_builder(display: &Display, builder: WindowBuilder) -> WindowBuilder {
+    let mut builder = builder;
+
+    if !display.supports_transparency() {
+        builder = builder.with_transparent(false);
+    }
+
+    #[cfg(x11_platform)]
+    {
+        use ::winit::platform::x11::WindowBuilderExtX11;
+
+        if let Some(visual) = display.x11_visual() {
+            builder = builder.with_x11_visual(visual.as_ptr());
+        }
+    }
+
+    builder
+}
+
+/// Create a new [`Surface`] for a [`winit`] window, using its current size.
+///
+/// This uses [`Display::make_surface_blocking`], so it only blocks for the [`wgpu`] backend.
+///
+/// # Safety
+///
+/// The returned [`Surface`] must not outlive `window`.
+///
+/// [`winit`]: https://crates.io/crates/winit
+/// [`wgpu`]: https://crates.io/crates/wgpu
+pub unsafe fn make_surface(display: &mut Display, window: &Window) -> Result<Surface, Error> {
+    let size = window.inner_size();
+    display.make_surface_blocking(window, size.width, size.height)
+}
+
+/// Get the refresh rate of the monitor that a [`winit`] window is on, in hertz.
+///
+/// This can be used to schedule redraws at the rate the monitor actually displays them, instead
+/// of assuming 60 frames per second. None of the backends know which monitor a [`Surface`] is
+/// shown on, so this is queried from the window instead. It returns `None` if the monitor or
+/// its refresh rate can't be determined, like on the web.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::time::{Duration, Instant};
+/// use winit::event_loop::EventLoop;
+/// use winit::window::Window;
+///
+/// let event_loop = EventLoop::new();
+/// let window = Window::new(&event_loop).unwrap();
+///
+/// let rate = theo::winit::refresh_rate(&window).unwrap_or(60.0);
+/// let next_frame = Instant::now() + Duration::from_secs_f64(1.0 / rate);
+/// ```
+///
+/// [`winit`]: https://crates.io/crates/winit
+pub fn refresh_rate(window: &Window) -> Option<f64> {
+    let millihertz = window.current_monitor()?.refresh_rate_millihertz()?;
+    Some(f64::from(millihertz) / 1000.0)
+}
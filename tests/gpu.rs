@@ -0,0 +1,561 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later OR MPL-2.0
+// This file is a part of `theo`.
+//
+// `theo` is free software: you can redistribute it and/or modify it under the
+// terms of either:
+//
+// * GNU Lesser General Public License as published by the Free Software Foundation, either
+//   version 3 of the License, or (at your option) any later version.
+// * Mozilla Public License as published by the Mozilla Foundation, version 2.
+//
+// `theo` is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR
+// PURPOSE. See the GNU Lesser General Public License or the Mozilla Public License for more
+// details.
+//
+// You should have received a copy of the GNU Lesser General Public License and the Mozilla
+// Public License along with `theo`. If not, see <https://www.gnu.org/licenses/>.
+
+//! Tests for the hardware-accelerated backends.
+//!
+//! These need windows, so they use a custom harness that runs on the main thread, and are
+//! skipped when there is no display server. The backend that is tested is the one that
+//! `theo` picks; build without the `wgpu` feature to test the OpenGL backend instead, like:
+//!
+//! ```sh
+//! cargo test --test gpu --no-default-features --features x11,egl,glx
+//! ```
+
+use piet::kurbo::{Affine, BezPath, Line, Point, Rect, Size};
+use piet::{
+    Color, Image as _, ImageFormat, InterpolationMode, LineJoin, RenderContext as _, StrokeStyle,
+    Text as _, TextLayout as _, TextLayoutBuilder as _,
+};
+use theo::{BlendMode, Display, RenderContext, Surface};
+
+use winit::dpi::PhysicalSize;
+use winit::event_loop::EventLoop;
+use winit::window::{Window, WindowBuilder};
+
+/// The width and height of the windows.
+const SIZE: u32 = 64;
+
+/// The tests to run, and their names.
+const TESTS: &[(&str, fn(&EventLoop<()>))] = &[
+    ("make_current_failure", make_current_failure),
+    ("finish_to_buffer", finish_to_buffer),
+    ("snapshot", snapshot),
+    ("translucent_clear", translucent_clear),
+    ("blurred_rect", blurred_rect),
+    ("layers", layers),
+    ("capture_image_area", capture_image_area),
+    ("dashes", dashes),
+    ("line_joins", line_joins),
+    ("fill_rules", fill_rules),
+    ("clip_rules", clip_rules),
+    ("nearest_neighbor", nearest_neighbor),
+    ("text_hit_tests", text_hit_tests),
+];
+
+fn main() {
+    #[cfg(all(unix, not(target_os = "macos")))]
+    if std::env::var_os("DISPLAY").is_none() {
+        println!("no X11 display, skipping the GPU tests");
+        return;
+    }
+
+    let event_loop = EventLoop::new();
+    for (name, test) in TESTS {
+        println!("test {name} ...");
+        test(&event_loop);
+        println!("test {name} ... ok");
+    }
+}
+
+/// Create a display that uses the hardware-accelerated backends.
+fn display(event_loop: &EventLoop<()>) -> Display {
+    #[allow(unused_mut)]
+    let mut builder = Display::builder();
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    {
+        builder = builder.glx_error_hook(winit::platform::x11::register_xlib_error_hook);
+    }
+
+    unsafe { builder.build(event_loop) }.expect("failed to create display")
+}
+
+/// Create a window and a surface for it.
+fn window(event_loop: &EventLoop<()>, display: &mut Display) -> (Window, Surface) {
+    let window = WindowBuilder::new()
+        .with_title("theo GPU test")
+        .with_inner_size(PhysicalSize::new(SIZE, SIZE))
+        .with_resizable(false)
+        .build(event_loop)
+        .expect("failed to create window");
+    let surface =
+        unsafe { display.make_surface_blocking(&window, SIZE, SIZE) }.expect("no surface");
+
+    (window, surface)
+}
+
+/// Get the name of the backend used by `display`.
+///
+/// The OpenGL backend only knows this after the first context has been created.
+fn backend(display: &mut Display, surface: &mut Surface) -> String {
+    let mut ctx = RenderContext::new(display, surface, SIZE, SIZE).unwrap();
+    ctx.clear(None, Color::WHITE);
+    ctx.finish().unwrap();
+    drop(ctx);
+    theo::block_on(display.present());
+
+    display.renderer_info().unwrap().backend
+}
+
+/// Failing to make the OpenGL context current leaves the display usable.
+fn make_current_failure(event_loop: &EventLoop<()>) {
+    let mut first = display(event_loop);
+    let mut second = display(event_loop);
+    let (_first_window, mut first_surface) = window(event_loop, &mut first);
+    let (_second_window, mut second_surface) = window(event_loop, &mut second);
+    if backend(&mut second, &mut second_surface) != "OpenGL" {
+        println!("not using OpenGL, skipped");
+        return;
+    }
+
+    // The surface belongs to another display, so its context can't be made current with it.
+    match RenderContext::new(&mut second, &mut first_surface, SIZE, SIZE) {
+        Err(err) => println!("making the context current failed: {err}"),
+        Ok(_) => {
+            println!("the platform accepted the surface of another display, skipped");
+            return;
+        }
+    }
+
+    // The context was put back, so the display can still draw.
+    let mut ctx = RenderContext::new(&mut second, &mut second_surface, SIZE, SIZE).unwrap();
+    ctx.clear(None, Color::RED);
+    ctx.finish().unwrap();
+    ctx.status().unwrap();
+}
+
+/// The frame can be read back, starting at the top-left corner.
+fn finish_to_buffer(event_loop: &EventLoop<()>) {
+    let mut display = display(event_loop);
+    let (_window, mut surface) = window(event_loop, &mut display);
+
+    let mut ctx = RenderContext::new(&mut display, &mut surface, SIZE, SIZE).unwrap();
+    ctx.clear(None, Color::RED);
+    let half = f64::from(SIZE / 2);
+    ctx.fill(Rect::new(0.0, 0.0, half, half), &Color::BLUE);
+    let pixels = ctx.finish_to_buffer().unwrap();
+    drop(ctx);
+    theo::block_on(display.present());
+
+    let pixel = |x: u32, y: u32| {
+        let start = ((y * SIZE + x) * 4) as usize;
+        &pixels[start..start + 4]
+    };
+    assert_eq!(pixels.len(), (SIZE * SIZE * 4) as usize);
+    assert_eq!(pixel(0, 0), [0, 0, 255, 255]);
+    assert_eq!(pixel(SIZE - 1, SIZE - 1), [255, 0, 0, 255]);
+}
+
+/// The last frame can be read back after it has been presented.
+fn snapshot(event_loop: &EventLoop<()>) {
+    let mut display = display(event_loop);
+    let (_window, mut surface) = window(event_loop, &mut display);
+
+    let mut ctx = RenderContext::new(&mut display, &mut surface, SIZE, SIZE).unwrap();
+    ctx.clear(None, Color::RED);
+    let half = f64::from(SIZE / 2);
+    ctx.fill(Rect::new(0.0, 0.0, half, half), &Color::BLUE);
+    ctx.finish().unwrap();
+    drop(ctx);
+    theo::block_on(display.present());
+
+    let (width, height, pixels) = match surface.snapshot(&mut display) {
+        Ok(snapshot) => snapshot,
+        Err(piet::Error::NotSupported) => {
+            println!("snapshots aren't supported here, skipped");
+            return;
+        }
+        Err(err) => panic!("failed to take a snapshot: {err}"),
+    };
+
+    assert_eq!((width, height), (SIZE, SIZE));
+    assert_eq!(&pixels[..4], [0, 0, 255, 255]);
+    assert_eq!(&pixels[pixels.len() - 4..], [255, 0, 0, 255]);
+}
+
+/// Clearing to a translucent color replaces what was drawn before it.
+fn translucent_clear(event_loop: &EventLoop<()>) {
+    let mut display = display(event_loop);
+    let (_window, mut surface) = window(event_loop, &mut display);
+
+    let mut ctx = RenderContext::new(&mut display, &mut surface, SIZE, SIZE).unwrap();
+    ctx.clear(None, Color::WHITE);
+    ctx.fill(
+        Rect::new(0.0, 0.0, f64::from(SIZE), f64::from(SIZE)),
+        &Color::RED,
+    );
+    ctx.clear(None, Color::TRANSPARENT);
+    let pixels = ctx.finish_to_buffer().unwrap();
+    drop(ctx);
+    theo::block_on(display.present());
+
+    // The alpha channel depends on whether the surface has one.
+    for pixel in pixels.chunks_exact(4) {
+        assert_eq!(pixel[..3], [0, 0, 0]);
+    }
+}
+
+/// Blurred rectangles look the same as with the software rasterizer.
+fn blurred_rect(event_loop: &EventLoop<()>) {
+    let draw = |ctx: &mut RenderContext<'_, '_>| {
+        ctx.clear(None, Color::WHITE);
+        for _ in 0..2 {
+            ctx.blurred_rect(Rect::new(16.0, 16.0, 48.0, 40.0), 6.0, &Color::BLACK);
+        }
+        ctx.finish_to_buffer().unwrap()
+    };
+
+    let mut display = display(event_loop);
+    let (_window, mut surface) = window(event_loop, &mut display);
+    let mut ctx = RenderContext::new(&mut display, &mut surface, SIZE, SIZE).unwrap();
+    let pixels = draw(&mut ctx);
+    drop(ctx);
+    theo::block_on(display.present());
+
+    let mut display = Display::offscreen();
+    let mut surface = display.make_offscreen_surface(SIZE, SIZE).unwrap();
+    let mut ctx = RenderContext::new(&mut display, &mut surface, SIZE, SIZE).unwrap();
+    let expected = draw(&mut ctx);
+
+    for (i, (actual, expected)) in pixels.iter().zip(&expected).enumerate() {
+        assert!(
+            actual.abs_diff(*expected) <= 2,
+            "channel {i} is {actual}, expected {expected}"
+        );
+    }
+}
+
+/// Layers and blend modes look the same as with the software rasterizer.
+fn layers(event_loop: &EventLoop<()>) {
+    let mut display = display(event_loop);
+    let (_window, mut surface) = window(event_loop, &mut display);
+
+    // wgpu draws the whole frame in one pass, so it refuses layers instead of faking them.
+    if backend(&mut display, &mut surface) != "OpenGL" {
+        let mut ctx = RenderContext::new(&mut display, &mut surface, SIZE, SIZE).unwrap();
+        assert!(matches!(
+            ctx.push_layer(0.5),
+            Err(piet::Error::NotSupported)
+        ));
+        let mut called = false;
+        let blended = ctx.with_blend_mode(BlendMode::Multiply, |_| called = true);
+        assert!(matches!(blended, Err(piet::Error::NotSupported)));
+        assert!(!called);
+        assert!(matches!(ctx.pop_layer(), Err(piet::Error::StackUnbalance)));
+        ctx.finish().unwrap();
+        ctx.status().unwrap();
+        drop(ctx);
+        theo::block_on(display.present());
+        return;
+    }
+
+    let draw = |ctx: &mut RenderContext<'_, '_>| {
+        ctx.clear(None, Color::rgb8(255, 0, 255));
+        let blended = ctx.with_blend_mode(BlendMode::Multiply, |ctx| {
+            ctx.fill(Rect::new(0.0, 0.0, 32.0, 64.0), &Color::rgb8(255, 255, 0));
+        });
+        match blended {
+            Ok(()) => {}
+            Err(piet::Error::NotSupported) => return None,
+            Err(err) => panic!("failed to blend: {err}"),
+        }
+
+        // The rectangles overlap, but they are faded as a group.
+        ctx.push_layer(0.5).unwrap();
+        ctx.fill(Rect::new(16.0, 16.0, 48.0, 48.0), &Color::BLACK);
+        ctx.fill(Rect::new(24.0, 24.0, 56.0, 56.0), &Color::BLACK);
+        ctx.pop_layer().unwrap();
+        Some(ctx.finish_to_buffer().unwrap())
+    };
+
+    let mut ctx = RenderContext::new(&mut display, &mut surface, SIZE, SIZE).unwrap();
+    let pixels = draw(&mut ctx);
+    drop(ctx);
+    theo::block_on(display.present());
+    let Some(pixels) = pixels else {
+        println!("layers need OpenGL 3.0 or OpenGL ES 3.0, skipped");
+        return;
+    };
+
+    let mut display = Display::offscreen();
+    let mut surface = display.make_offscreen_surface(SIZE, SIZE).unwrap();
+    let mut ctx = RenderContext::new(&mut display, &mut surface, SIZE, SIZE).unwrap();
+    let expected = draw(&mut ctx).unwrap();
+
+    for (i, (actual, expected)) in pixels.iter().zip(&expected).enumerate() {
+        assert!(
+            actual.abs_diff(*expected) <= 1,
+            "channel {i} is {actual}, expected {expected}"
+        );
+    }
+}
+
+/// Captured areas are in device pixels from the top-left corner, whatever the transform.
+fn capture_image_area(event_loop: &EventLoop<()>) {
+    let mut display = display(event_loop);
+    let (_window, mut surface) = window(event_loop, &mut display);
+
+    let mut ctx = RenderContext::new(&mut display, &mut surface, SIZE, SIZE).unwrap();
+    ctx.clear(None, Color::BLUE);
+    ctx.save().unwrap();
+    ctx.transform(Affine::scale(2.0));
+    ctx.fill(Rect::new(0.0, 0.0, 4.0, 4.0), &Color::RED);
+    let image = match ctx.capture_image_area(Rect::new(0.0, 0.0, 16.0, 8.0)) {
+        Ok(image) => image,
+        Err(piet::Error::NotSupported) => {
+            println!("capturing isn't supported here, skipped");
+            return;
+        }
+        Err(err) => panic!("failed to capture: {err}"),
+    };
+    ctx.restore().unwrap();
+    assert_eq!(image.size(), Size::new(16.0, 8.0));
+
+    // Draw the capture again further down, where it can be read back.
+    let dst = Rect::new(0.0, 32.0, 16.0, 40.0);
+    ctx.draw_image(&image, dst, InterpolationMode::NearestNeighbor);
+    let pixels = ctx.finish_to_buffer().unwrap();
+    drop(ctx);
+    theo::block_on(display.present());
+
+    assert_eq!(pixel(&pixels, 4, 36), [255, 0, 0, 255]);
+    assert_eq!(pixel(&pixels, 12, 36), [0, 0, 255, 255]);
+}
+
+/// Dashes and their offset are in the same places as with the software rasterizer.
+fn dashes(event_loop: &EventLoop<()>) {
+    let mut display = display(event_loop);
+    let (_window, mut surface) = window(event_loop, &mut display);
+
+    let mut ctx = RenderContext::new(&mut display, &mut surface, SIZE, SIZE).unwrap();
+    ctx.clear(None, Color::BLACK);
+    let style = StrokeStyle::new()
+        .dash_pattern(&[10.0, 5.0])
+        .dash_offset(3.0);
+    let line = Line::new((0.0, 8.0), (f64::from(SIZE), 8.0));
+    ctx.stroke_styled(line, &Color::WHITE, 4.0, &style);
+    let pixels = ctx.finish_to_buffer().unwrap();
+    drop(ctx);
+    theo::block_on(display.present());
+
+    // The dashes cover 0..7, 12..22, 27..37, 42..52 and 57..64.
+    let reds = [3, 9, 17, 24, 32, 39, 47, 54, 60].map(|x| pixel(&pixels, x, 8)[0]);
+    assert_eq!(reds, [255, 0, 255, 0, 255, 0, 255, 0, 255]);
+}
+
+/// Miter limits are respected like on the software rasterizer.
+fn line_joins(event_loop: &EventLoop<()>) {
+    let mut display = display(event_loop);
+    let (_window, mut surface) = window(event_loop, &mut display);
+
+    // A sharp corner at (15, 15), whose miter reaches up to about y = 9.6.
+    let mut path = BezPath::new();
+    path.move_to((5.0, 40.0));
+    path.line_to((15.0, 15.0));
+    path.line_to((25.0, 40.0));
+
+    let joins = [
+        (LineJoin::Miter { limit: 10.0 }, 255),
+        (LineJoin::Miter { limit: 2.0 }, 0),
+        (LineJoin::Miter { limit: 0.5 }, 0),
+        (LineJoin::Bevel, 0),
+        (LineJoin::Round, 0),
+    ];
+
+    for (join, expected) in joins {
+        let mut ctx = RenderContext::new(&mut display, &mut surface, SIZE, SIZE).unwrap();
+        ctx.clear(None, Color::BLACK);
+        let style = StrokeStyle::new().line_join(join);
+        ctx.stroke_styled(&path, &Color::WHITE, 4.0, &style);
+        let pixels = ctx.finish_to_buffer().unwrap();
+        drop(ctx);
+        theo::block_on(display.present());
+
+        // Only the unclipped miter covers the pixel above the corner.
+        assert_eq!(pixel(&pixels, 15, 12)[0], expected, "{join:?}");
+    }
+}
+
+/// Self-intersecting paths are filled with the right rule, with solid colors and with brushes
+/// that `theo` draws itself.
+fn fill_rules(event_loop: &EventLoop<()>) {
+    let mut display = display(event_loop);
+    let (_window, mut surface) = window(event_loop, &mut display);
+
+    // A pentagram, drawn by connecting every second point of a pentagon.
+    let mut star = BezPath::new();
+    for i in 0..5 {
+        let angle = std::f64::consts::PI * 4.0 / 5.0 * i as f64;
+        let point = Point::new(32.0 + 30.0 * angle.sin(), 32.0 - 30.0 * angle.cos());
+        if i == 0 {
+            star.move_to(point);
+        } else {
+            star.line_to(point);
+        }
+    }
+    star.close_path();
+
+    for conic in [false, true] {
+        for (even_odd, expected) in [(false, 255), (true, 0)] {
+            let mut ctx = RenderContext::new(&mut display, &mut surface, SIZE, SIZE).unwrap();
+            ctx.clear(None, Color::BLACK);
+            let brush = if conic {
+                let stops = [Color::WHITE, Color::WHITE];
+                ctx.conic_gradient((32.0, 32.0), 0.0, &stops[..])
+            } else {
+                ctx.solid_brush(Color::WHITE)
+            };
+            if even_odd {
+                ctx.fill_even_odd(&star, &brush);
+            } else {
+                ctx.fill(&star, &brush);
+            }
+            let pixels = ctx.finish_to_buffer().unwrap();
+            drop(ctx);
+            theo::block_on(display.present());
+
+            // The points of the star are filled either way, but the center is not.
+            let context = format!("conic: {conic}, even-odd: {even_odd}");
+            assert_eq!(pixel(&pixels, 32, 8)[0], 255, "{context}");
+            assert_eq!(pixel(&pixels, 32, 32)[0], expected, "{context}");
+        }
+    }
+}
+
+/// Clipping to a path uses the nonzero rule, and `clip_even_odd` the even-odd rule.
+fn clip_rules(event_loop: &EventLoop<()>) {
+    let mut display = display(event_loop);
+    let (_window, mut surface) = window(event_loop, &mut display);
+
+    // A square that winds around its center twice.
+    let mut square = BezPath::new();
+    for _ in 0..2 {
+        square.move_to((8.0, 8.0));
+        square.line_to((56.0, 8.0));
+        square.line_to((56.0, 56.0));
+        square.line_to((8.0, 56.0));
+        square.close_path();
+    }
+
+    for (even_odd, expected) in [(false, 255), (true, 0)] {
+        let mut ctx = RenderContext::new(&mut display, &mut surface, SIZE, SIZE).unwrap();
+        ctx.clear(None, Color::BLACK);
+        if even_odd {
+            ctx.clip_even_odd(&square);
+        } else {
+            ctx.clip(&square);
+        }
+        let all = Rect::new(0.0, 0.0, f64::from(SIZE), f64::from(SIZE));
+        ctx.fill(all, &Color::WHITE);
+        let pixels = ctx.finish_to_buffer().unwrap();
+        drop(ctx);
+        theo::block_on(display.present());
+
+        assert_eq!(pixel(&pixels, 32, 32)[0], expected, "even-odd: {even_odd}");
+        assert_eq!(pixel(&pixels, 2, 2)[0], 0, "even-odd: {even_odd}");
+    }
+}
+
+/// Images scaled up with nearest-neighbor sampling stay crisp.
+fn nearest_neighbor(event_loop: &EventLoop<()>) {
+    let mut display = display(event_loop);
+    let (_window, mut surface) = window(event_loop, &mut display);
+
+    let mut ctx = RenderContext::new(&mut display, &mut surface, SIZE, SIZE).unwrap();
+    ctx.clear(None, Color::BLACK);
+
+    // A 2x2 checkerboard, scaled up to cover the whole window.
+    let checkerboard = [255, 0, 0, 255];
+    let image = ctx
+        .make_image(2, 2, &checkerboard, ImageFormat::Grayscale)
+        .unwrap();
+    let dst = Rect::new(0.0, 0.0, f64::from(SIZE), f64::from(SIZE));
+    ctx.draw_image(&image, dst, InterpolationMode::NearestNeighbor);
+    let pixels = ctx.finish_to_buffer().unwrap();
+    drop(ctx);
+    theo::block_on(display.present());
+
+    // Every pixel is either black or white, even where the squares meet.
+    assert!(pixels
+        .chunks(4)
+        .all(|pixel| pixel[0] == 0 || pixel[0] == 255));
+    let half = SIZE / 2;
+    let corners = [
+        (half - 1, half - 1),
+        (half, half - 1),
+        (half - 1, half),
+        (half, half),
+    ];
+    assert_eq!(
+        corners.map(|(x, y)| pixel(&pixels, x, y)[0]),
+        [255, 0, 0, 255]
+    );
+}
+
+/// Text layouts hit test the same way as the ones made by the software rasterizer.
+fn text_hit_tests(event_loop: &EventLoop<()>) {
+    let mut display = display(event_loop);
+    let (_window, mut surface) = window(event_loop, &mut display);
+
+    let text = "Hello\nwide world\n\u{e9}t\u{e9}";
+    let mut offscreen = Display::offscreen();
+    let reference = offscreen.text().new_text_layout(text).build().unwrap();
+
+    let mut ctx = RenderContext::new(&mut display, &mut surface, SIZE, SIZE).unwrap();
+    let layout = ctx.text().new_text_layout(text).build().unwrap();
+    assert_eq!(layout.line_count(), reference.line_count());
+
+    for idx in (0..=text.len()).filter(|&idx| text.is_char_boundary(idx)) {
+        let position = layout.hit_test_text_position(idx);
+        let expected = reference.hit_test_text_position(idx);
+        assert_eq!(position.line, expected.line, "index {idx}");
+        assert!(
+            (position.point - expected.point).hypot() < 0.5,
+            "index {idx}: {:?} != {:?}",
+            position.point,
+            expected.point
+        );
+    }
+
+    // Include points outside of the text, which snap to the nearest line.
+    let size = reference.size();
+    for y in -2..=12 {
+        for x in -2..=12 {
+            let point = Point::new(
+                size.width * f64::from(x) / 10.0,
+                size.height * f64::from(y) / 10.0,
+            );
+            let hit = layout.hit_test_point(point);
+            let expected = reference.hit_test_point(point);
+            assert_eq!(hit.idx, expected.idx, "point {point:?}");
+            assert_eq!(hit.is_inside, expected.is_inside, "point {point:?}");
+        }
+    }
+
+    ctx.clear(None, Color::BLACK);
+    ctx.draw_text(&layout, (0.0, 0.0));
+    ctx.finish().unwrap();
+    drop(ctx);
+    theo::block_on(display.present());
+}
+
+/// Get the pixel at `x` and `y` from a frame read back with `finish_to_buffer`.
+fn pixel(pixels: &[u8], x: u32, y: u32) -> [u8; 4] {
+    let start = ((y * SIZE + x) * 4) as usize;
+    pixels[start..start + 4].try_into().unwrap()
+}
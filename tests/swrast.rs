@@ -0,0 +1,636 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later OR MPL-2.0
+// This file is a part of `theo`.
+//
+// `theo` is free software: you can redistribute it and/or modify it under the
+// terms of either:
+//
+// * GNU Lesser General Public License as published by the Free Software Foundation, either
+//   version 3 of the License, or (at your option) any later version.
+// * Mozilla Public License as published by the Mozilla Foundation, version 2.
+//
+// `theo` is distributed in the hope that it will be useful, but WITHOUT ANY
+// WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR
+// PURPOSE. See the GNU Lesser General Public License or the Mozilla Public License for more
+// details.
+//
+// You should have received a copy of the GNU Lesser General Public License and the Mozilla
+// Public License along with `theo`. If not, see <https://www.gnu.org/licenses/>.
+
+//! Tests for what the software rasterizer draws.
+//!
+//! These run on an offscreen surface, so they don't need a display server. The GPU backends
+//! are compared against the same results in `tests/gpu.rs`.
+
+use piet::kurbo::{Affine, BezPath, Circle, Line, PathEl, Point, Rect, Shape as _, Size};
+use piet::{
+    Color, FixedLinearGradient, GradientStop, Image as _, ImageFormat, InterpolationMode, LineJoin,
+    RenderContext as _, StrokeStyle,
+};
+use theo::{BlendMode, Display, ExtendMode, FillRule, GradientInterpolation, RenderContext};
+
+/// Offscreen surfaces keep their alpha channel.
+#[test]
+fn translucent_clear() {
+    let frame = render(1, 1, |ctx| ctx.clear(None, Color::TRANSPARENT));
+    assert_eq!(frame.pixels, [0, 0, 0, 0]);
+}
+
+/// A surface with a background is cleared to it without drawing anything.
+#[test]
+fn background() {
+    let mut display = Display::offscreen();
+    let mut surface = display.make_offscreen_surface(1, 1).unwrap();
+    surface.set_background(Color::BLUE);
+
+    let mut ctx = RenderContext::new(&mut display, &mut surface, 1, 1).unwrap();
+    assert_eq!(ctx.finish_to_buffer().unwrap(), [0, 0, 255, 255]);
+}
+
+/// Without auto-clear, only the damaged area is cleared to the background.
+#[test]
+fn clear_background() {
+    let mut display = Display::offscreen();
+    let mut surface = display.make_offscreen_surface(2, 1).unwrap();
+    surface.set_background(Color::WHITE);
+    surface.set_auto_clear(false);
+
+    let mut ctx = RenderContext::new(&mut display, &mut surface, 2, 1).unwrap();
+    ctx.clear(None, Color::BLACK);
+    ctx.clear_background(Rect::new(1.0, 0.0, 2.0, 1.0));
+    let pixels = ctx.finish_to_buffer().unwrap();
+    assert_eq!(pixels, [0, 0, 0, 255, 255, 255, 255, 255]);
+}
+
+/// Render contexts start scaled by the scale factor of their surface.
+#[test]
+fn scale_factor() {
+    let mut display = Display::offscreen();
+    let mut surface = display.make_offscreen_surface(2, 2).unwrap();
+    surface.set_scale_factor(2.0);
+
+    // One logical pixel covers the whole surface.
+    let mut ctx = RenderContext::new(&mut display, &mut surface, 2, 2).unwrap();
+    ctx.fill(Rect::new(0.0, 0.0, 1.0, 1.0), &Color::WHITE);
+    let pixels = ctx.finish_to_buffer().unwrap();
+    assert!(pixels.chunks(4).all(|pixel| pixel[3] == 255));
+}
+
+/// Miter limits follow SVG, and the other joins never reach as far as a miter.
+#[test]
+fn line_joins() {
+    // A sharp corner at (15, 15), whose miter reaches up to about y = 9.6.
+    let mut path = BezPath::new();
+    path.move_to((5.0, 40.0));
+    path.line_to((15.0, 15.0));
+    path.line_to((25.0, 40.0));
+
+    let joins = [
+        (LineJoin::Miter { limit: 10.0 }, 255),
+        (LineJoin::Miter { limit: 2.0 }, 0),
+        (LineJoin::Bevel, 0),
+        (LineJoin::Round, 0),
+    ];
+
+    for (join, expected) in joins {
+        let style = StrokeStyle::new().line_join(join);
+        let frame = render(30, 45, |ctx| {
+            ctx.stroke_styled(&path, &Color::WHITE, 4.0, &style)
+        });
+
+        // Only the unclipped miter covers the pixel above the corner.
+        assert_eq!(frame.alpha(15, 12), expected, "{join:?}");
+    }
+}
+
+/// The dash offset moves the pattern, and is wrapped around to the length of the pattern.
+#[test]
+fn dashes() {
+    let dashed = |offset: f64| {
+        let style = StrokeStyle::new()
+            .dash_pattern(&[10.0, 5.0])
+            .dash_offset(offset);
+        render(40, 3, |ctx| {
+            ctx.stroke_styled(
+                Line::new((0.0, 1.5), (40.0, 1.5)),
+                &Color::WHITE,
+                3.0,
+                &style,
+            )
+        })
+    };
+
+    // The dashes cover 0..7, 12..22 and 27..37.
+    let frame = dashed(3.0);
+    let alphas = [3, 9, 17, 24, 32].map(|x| frame.alpha(x, 1));
+    assert_eq!(alphas, [255, 0, 255, 0, 255]);
+
+    // After a billion full patterns, the dashes are in the same place.
+    assert_eq!(dashed(3.0 + 15.0 * 1e9).pixels, frame.pixels);
+}
+
+/// The nonzero rule fills the center of a star, and the even-odd rule leaves a hole there.
+#[test]
+fn fill_rules() {
+    let star = star(Point::new(20.0, 20.0));
+
+    for (even_odd, expected) in [(false, 255), (true, 0)] {
+        let frame = render(40, 40, |ctx| {
+            if even_odd {
+                ctx.fill_even_odd(&star, &Color::WHITE);
+            } else {
+                ctx.fill(&star, &Color::WHITE);
+            }
+        });
+
+        assert_eq!(frame.alpha(20, 5), 255, "even-odd: {even_odd}");
+        assert_eq!(frame.alpha(20, 20), expected, "even-odd: {even_odd}");
+    }
+}
+
+/// Elements streamed into `fill_path_elements` are filled like a path.
+#[test]
+fn fill_path_elements() {
+    // A circle made of ten thousand line segments.
+    let point = |i: usize| {
+        let angle = std::f64::consts::TAU * i as f64 / 10_000.0;
+        Point::new(20.0 + 15.0 * angle.cos(), 20.0 + 15.0 * angle.sin())
+    };
+    let elements = std::iter::once(PathEl::MoveTo(point(0)))
+        .chain((1..10_000).map(|i| PathEl::LineTo(point(i))))
+        .chain(std::iter::once(PathEl::ClosePath));
+
+    let frame = render(40, 40, |ctx| {
+        ctx.fill_path_elements(elements, &Color::WHITE)
+    });
+    assert_eq!(frame.alpha(20, 20), 255);
+    assert_eq!(frame.alpha(2, 2), 0);
+}
+
+/// Clipping to a path uses the nonzero rule, and `clip_even_odd` the even-odd rule.
+#[test]
+fn clip_rules() {
+    // A square that winds around its center twice.
+    let mut square = BezPath::new();
+    for _ in 0..2 {
+        square.move_to((5.0, 5.0));
+        square.line_to((35.0, 5.0));
+        square.line_to((35.0, 35.0));
+        square.line_to((5.0, 35.0));
+        square.close_path();
+    }
+
+    for (even_odd, expected) in [(false, 255), (true, 0)] {
+        let frame = render(40, 40, |ctx| {
+            if even_odd {
+                ctx.clip_even_odd(&square);
+            } else {
+                ctx.clip(&square);
+            }
+            ctx.fill(Rect::new(0.0, 0.0, 40.0, 40.0), &Color::WHITE);
+        });
+
+        assert_eq!(frame.alpha(20, 20), expected, "even-odd: {even_odd}");
+        assert_eq!(frame.alpha(2, 2), 0, "even-odd: {even_odd}");
+    }
+}
+
+/// Subpaths with different fill rules are filled in one pass, without a seam between them.
+#[test]
+fn mixed_fill_rules() {
+    // A rectangle, next to one with a hole, sharing an edge in the middle of a pixel.
+    let mut path = Rect::new(0.0, 0.0, 10.5, 20.0).to_path(0.1);
+    path.extend(Rect::new(10.5, 0.0, 30.0, 20.0).path_elements(0.1));
+    path.extend(Rect::new(20.0, 5.0, 25.0, 15.0).path_elements(0.1));
+    path.extend(star(Point::new(60.0, 20.0)));
+    let rules = [
+        FillRule::NonZero,
+        FillRule::EvenOdd,
+        FillRule::EvenOdd,
+        FillRule::EvenOdd,
+    ];
+
+    let frame = render(80, 40, |ctx| {
+        ctx.fill_with_rules(&path, &Color::WHITE, &rules)
+    });
+    assert_eq!(frame.alpha(10, 10), 255);
+    assert_eq!(frame.alpha(15, 10), 255);
+    assert_eq!(frame.alpha(22, 10), 0);
+    assert_eq!(frame.alpha(60, 5), 255);
+    assert_eq!(frame.alpha(60, 20), 0);
+}
+
+/// Antialiasing can be turned off for solid fills, including curves.
+#[test]
+fn antialias_off() {
+    let frame = render(20, 20, |ctx| {
+        ctx.set_antialias(false);
+        ctx.fill(Rect::new(0.4, 0.4, 9.6, 9.6), &Color::WHITE);
+        ctx.fill(Circle::new((15.0, 15.0), 4.3), &Color::WHITE);
+    });
+
+    // Every pixel is either fully covered or not covered at all.
+    assert!(frame.pixels.chunks(4).all(|px| px[3] == 0 || px[3] == 255));
+    assert_eq!(frame.alpha(0, 0), 255);
+    assert_eq!(frame.alpha(9, 9), 255);
+    assert_eq!(frame.alpha(10, 10), 0);
+}
+
+/// A hairline stays one pixel wide at any scale.
+#[test]
+fn hairline() {
+    let frame = render(20, 20, |ctx| {
+        // A line through the middle of the pixels in row 10, drawn at a tenth of the size.
+        ctx.transform(Affine::scale(0.1));
+        ctx.stroke_hairline(Line::new((0.0, 105.0), (200.0, 105.0)), &Color::WHITE);
+    });
+    let alphas = [9, 10, 11].map(|y| frame.alpha(5, y));
+    assert_eq!(alphas, [0, 255, 0]);
+}
+
+/// The clip is transformed like everything else, and `with_clip` removes it afterwards.
+#[test]
+fn clip_transformed() {
+    let frame = render(20, 1, |ctx| {
+        ctx.with_clip(Rect::new(0.0, 0.0, 5.0, 1.0), |ctx| {
+            ctx.fill(Rect::new(0.0, 0.0, 20.0, 1.0), &Color::RED);
+            Ok(())
+        })
+        .unwrap();
+
+        ctx.transform(Affine::translate((10.0, 0.0)));
+        ctx.clip(Rect::new(0.0, 0.0, 5.0, 1.0));
+        ctx.fill(Rect::new(-10.0, 0.0, 10.0, 1.0), &Color::BLUE);
+    });
+
+    assert_eq!(frame.pixel(4, 0), [255, 0, 0, 255]);
+    let alphas = [5, 10, 14, 15].map(|x| frame.alpha(x, 0));
+    assert_eq!(alphas, [0, 255, 255, 0]);
+    assert_eq!(frame.pixel(10, 0), [0, 0, 255, 255]);
+}
+
+/// The global alpha fades shapes and images, and is restored with the state.
+#[test]
+fn global_alpha() {
+    let frame = render(3, 1, |ctx| {
+        let red = ctx
+            .make_image(1, 1, &[255, 0, 0, 255], ImageFormat::RgbaSeparate)
+            .unwrap();
+
+        ctx.save().unwrap();
+        ctx.set_global_alpha(0.5);
+        ctx.fill(Rect::new(0.0, 0.0, 1.0, 1.0), &Color::RED);
+        let dst = Rect::new(1.0, 0.0, 2.0, 1.0);
+        ctx.draw_image(&red, dst, InterpolationMode::Bilinear);
+        ctx.restore().unwrap();
+        ctx.fill(Rect::new(2.0, 0.0, 3.0, 1.0), &Color::RED);
+    });
+
+    assert_eq!([0, 1, 2].map(|x| frame.alpha(x, 0)), [128, 128, 255]);
+}
+
+/// Interpolating gradients in linear RGB keeps the middle brighter.
+#[test]
+fn gradient_interpolation() {
+    let gradient = FixedLinearGradient {
+        start: (0.0, 0.0).into(),
+        end: (64.0, 0.0).into(),
+        stops: vec![
+            GradientStop {
+                pos: 0.0,
+                color: Color::rgb8(255, 0, 0),
+            },
+            GradientStop {
+                pos: 1.0,
+                color: Color::rgb8(0, 255, 0),
+            },
+        ],
+    };
+
+    let green = |interpolation| {
+        let frame = render(64, 1, |ctx| {
+            ctx.set_gradient_interpolation(interpolation);
+            let brush = ctx.gradient(gradient.clone()).unwrap();
+            ctx.fill(Rect::new(0.0, 0.0, 64.0, 1.0), &brush);
+        });
+        frame.pixel(32, 0)[1]
+    };
+
+    assert!(green(GradientInterpolation::Srgb) < 150);
+    assert!(green(GradientInterpolation::LinearRgb) > 170);
+}
+
+/// Extended gradients repeat past their ends.
+#[test]
+fn extended_gradient() {
+    let frame = render(8, 1, |ctx| {
+        let brush = ctx
+            .extended_gradient(stripes(2.0), ExtendMode::Repeat)
+            .unwrap();
+        ctx.fill(Rect::new(0.0, 0.0, 8.0, 1.0), &brush);
+    });
+
+    let reds = (0..8).map(|x| frame.pixel(x, 0)[0]).collect::<Vec<_>>();
+    assert_eq!(reds, [255, 0, 255, 0, 255, 0, 255, 0]);
+}
+
+/// Conic gradients sweep clockwise from their start angle.
+#[test]
+fn conic_gradient() {
+    let frame = render(4, 4, |ctx| {
+        let brush = ctx.conic_gradient((2.0, 2.0), 0.0, stripes(1.0).stops);
+        ctx.fill(Rect::new(0.0, 0.0, 4.0, 4.0), &brush);
+    });
+
+    // Going clockwise from the right, the bottom half is red and the top half is blue.
+    assert_eq!(frame.pixel(1, 3), [255, 0, 0, 255]);
+    assert_eq!(frame.pixel(1, 0), [0, 0, 255, 255]);
+}
+
+/// Image brushes repeat, and follow the fill rule and the opacity.
+#[test]
+fn image_brush() {
+    let frame = render(4, 4, |ctx| {
+        let tile = ctx
+            .make_image(2, 2, &[0, 255, 255, 0], ImageFormat::Grayscale)
+            .unwrap();
+        let brush = ctx.image_brush(&tile, ExtendMode::Repeat);
+        ctx.fill(Rect::new(0.0, 0.0, 4.0, 4.0), &brush);
+    });
+    let row = (0..4).map(|x| frame.pixel(x, 0)[0]).collect::<Vec<_>>();
+    assert_eq!(row, [0, 255, 0, 255]);
+
+    let frame = render(8, 8, |ctx| {
+        ctx.clear(None, Color::BLACK);
+        let white = ctx
+            .make_image(1, 1, &[255], ImageFormat::Grayscale)
+            .unwrap();
+        let brush = ctx.image_brush(&white, ExtendMode::Repeat);
+
+        // A square with a hole in the middle.
+        let mut path = Rect::new(0.0, 0.0, 8.0, 8.0).to_path(0.1);
+        path.extend(Rect::new(2.0, 2.0, 6.0, 6.0).path_elements(0.1));
+        ctx.push_opacity(0.5);
+        ctx.fill_even_odd(path, &brush);
+        ctx.pop_opacity().unwrap();
+    });
+    assert!((127..=128).contains(&frame.pixel(0, 0)[0]));
+    assert_eq!(frame.pixel(4, 4)[0], 0);
+}
+
+/// Premultiplied and straight alpha images look the same.
+#[test]
+fn image_formats() {
+    let images = [
+        (&[128, 0, 0, 128], ImageFormat::RgbaPremul),
+        (&[255, 0, 0, 128], ImageFormat::RgbaSeparate),
+    ];
+
+    for (data, format) in images {
+        let frame = render(1, 1, |ctx| {
+            ctx.clear(None, Color::WHITE);
+            let image = ctx.make_image(1, 1, data, format).unwrap();
+            let dst = Rect::new(0.0, 0.0, 1.0, 1.0);
+            ctx.draw_image(&image, dst, InterpolationMode::NearestNeighbor);
+        });
+        assert_eq!(frame.pixel(0, 0), [255, 127, 127, 255], "{format:?}");
+    }
+}
+
+/// Nearest neighbor sampling keeps scaled images crisp.
+#[test]
+fn nearest_neighbor() {
+    let frame = render(200, 200, |ctx| {
+        // A 2x2 checkerboard, scaled up a hundred times.
+        let checkerboard = [255, 0, 0, 255];
+        let image = ctx
+            .make_image(2, 2, &checkerboard, ImageFormat::Grayscale)
+            .unwrap();
+        let dst = Rect::new(0.0, 0.0, 200.0, 200.0);
+        ctx.draw_image(&image, dst, InterpolationMode::NearestNeighbor);
+    });
+
+    // Every pixel is either black or white, even where the squares meet.
+    assert!(frame.pixels.chunks(4).all(|px| px[0] == 0 || px[0] == 255));
+    let reds = [(99, 99), (100, 99), (99, 100), (100, 100)].map(|(x, y)| frame.pixel(x, y)[0]);
+    assert_eq!(reds, [255, 0, 0, 255]);
+}
+
+/// Images drawn much smaller than their size are drawn from a smaller copy.
+#[test]
+fn mipmapped_image() {
+    let frame = render(1, 1, |ctx| {
+        // A fine checkerboard, which averages out to gray.
+        let pixels = (0..64 * 64)
+            .map(|i| if (i % 64 + i / 64) % 2 == 0 { 0 } else { 255 })
+            .collect::<Vec<u8>>();
+        let image = ctx
+            .make_image_mipmapped(64, 64, &pixels, ImageFormat::Grayscale)
+            .unwrap();
+        let dst = Rect::new(0.0, 0.0, 1.0, 1.0);
+        ctx.draw_image(&image, dst, InterpolationMode::Bilinear);
+    });
+
+    assert!((126..=129).contains(&frame.pixel(0, 0)[0]));
+}
+
+/// The image transform is applied on top of the current transform.
+#[test]
+fn draw_image_transformed() {
+    let frame = render(1, 2, |ctx| {
+        // A red pixel next to a blue one, stood up by rotating it a quarter turn clockwise.
+        let pixels = [255, 0, 0, 255, 0, 0, 255, 255];
+        let image = ctx
+            .make_image(2, 1, &pixels, ImageFormat::RgbaSeparate)
+            .unwrap();
+        let transform = Affine::translate((1.0, 0.0)) * Affine::rotate(std::f64::consts::FRAC_PI_2);
+        let src = Rect::new(0.0, 0.0, 2.0, 1.0);
+        ctx.draw_image_transformed(&image, src, transform, InterpolationMode::NearestNeighbor);
+    });
+
+    assert_eq!(frame.pixel(0, 0), [255, 0, 0, 255]);
+    assert_eq!(frame.pixel(0, 1), [0, 0, 255, 255]);
+}
+
+/// Blurred rectangles spread out by two and a half times the radius, and keep their coverage.
+#[test]
+fn blurred_rect() {
+    let frame = render(100, 100, |ctx| {
+        ctx.blurred_rect(Rect::new(40.0, 40.0, 60.0, 60.0), 8.0, &Color::BLACK)
+    });
+    let alpha = |x, y| u32::from(frame.alpha(x, y));
+
+    // Nothing is drawn more than 20 pixels away from the rectangle.
+    for i in 0..100 {
+        for edge in [0, 19, 80, 99] {
+            assert_eq!(alpha(i, edge), 0);
+            assert_eq!(alpha(edge, i), 0);
+        }
+    }
+
+    // The blur is symmetric, and spreads the rectangle out without adding or losing coverage.
+    assert_eq!(alpha(30, 50), alpha(69, 50));
+    assert_eq!(alpha(50, 30), alpha(50, 69));
+    let energy: u32 = (0..100 * 100).map(|i| alpha(i % 100, i / 100)).sum();
+    assert!(energy.abs_diff(20 * 20 * 255) < 20 * 20 * 255 / 50);
+}
+
+/// The same shadow looks the same wherever it is drawn.
+#[test]
+fn blurred_rect_position() {
+    let frame = render(96, 32, |ctx| {
+        ctx.blurred_rect(Rect::new(8.0, 8.0, 24.0, 24.0), 4.0, &Color::BLACK);
+        ctx.blurred_rect(Rect::new(56.0, 8.0, 72.0, 24.0), 4.0, &Color::BLACK);
+    });
+
+    for y in 0..32 {
+        for x in 0..40 {
+            assert_eq!(frame.alpha(x, y), frame.alpha(x + 48, y));
+        }
+    }
+}
+
+/// The area passed to `capture_image_area` is in device pixels.
+#[test]
+fn capture_image_area() {
+    render(4, 4, |ctx| {
+        ctx.transform(Affine::scale(2.0));
+        ctx.fill(Rect::new(0.0, 0.0, 1.0, 1.0), &Color::RED);
+
+        let image = ctx
+            .capture_image_area(Rect::new(0.0, 0.0, 2.0, 2.0))
+            .unwrap();
+        assert_eq!(image.size(), Size::new(2.0, 2.0));
+    });
+}
+
+/// Warming up the renderers doesn't draw anything.
+#[test]
+fn warm_up() {
+    let frame = render(4, 4, |ctx| {
+        ctx.clear(None, Color::WHITE);
+        ctx.warm_up().unwrap();
+    });
+    assert!(frame.pixels.iter().all(|&channel| channel == 255));
+}
+
+/// Overlapping shapes under `push_opacity` are faded as a group.
+#[test]
+fn opacity_group() {
+    let frame = render(4, 1, |ctx| {
+        ctx.clear(None, Color::WHITE);
+        ctx.push_opacity(0.5);
+        ctx.fill(Rect::new(0.0, 0.0, 4.0, 1.0), &Color::BLACK);
+        ctx.fill(Rect::new(0.0, 0.0, 2.0, 1.0), &Color::BLACK);
+        ctx.pop_opacity().unwrap();
+    });
+
+    assert!((127..=128).contains(&frame.pixel(0, 0)[0]));
+    assert_eq!(frame.pixel(0, 0), frame.pixel(3, 0));
+}
+
+/// Overlapping shapes in a layer are faded as a group.
+#[test]
+fn layer_group() {
+    let frame = render(4, 4, |ctx| {
+        ctx.clear(None, Color::WHITE);
+        ctx.push_layer(0.5).unwrap();
+        ctx.fill(Rect::new(0.0, 0.0, 4.0, 4.0), &Color::BLACK);
+        ctx.fill(Rect::new(0.0, 0.0, 4.0, 4.0), &Color::BLACK);
+        ctx.pop_layer().unwrap();
+    });
+    assert!((127..=128).contains(&frame.pixel(0, 0)[0]));
+}
+
+/// Blend modes apply to the group drawn by the closure.
+#[test]
+fn blend_mode() {
+    let frame = render(4, 4, |ctx| {
+        ctx.clear(None, Color::rgb8(255, 0, 255));
+        ctx.with_blend_mode(BlendMode::Multiply, |ctx| {
+            ctx.fill(Rect::new(0.0, 0.0, 4.0, 4.0), &Color::rgb8(255, 255, 0));
+        })
+        .unwrap();
+    });
+    assert_eq!(frame.pixel(0, 0), [255, 0, 0, 255]);
+}
+
+/// Opacities and layers have to be popped in the order they were pushed.
+#[test]
+fn opacity_and_layers_nest() {
+    render(1, 1, |ctx| {
+        ctx.push_opacity(0.5);
+        ctx.push_layer(0.5).unwrap();
+        assert!(matches!(
+            ctx.pop_opacity(),
+            Err(piet::Error::StackUnbalance)
+        ));
+        ctx.pop_layer().unwrap();
+        ctx.pop_opacity().unwrap();
+    });
+}
+
+/// The pixels drawn by [`render`].
+struct Frame {
+    /// The width of the frame, in pixels.
+    width: usize,
+
+    /// The RGBA pixels, row by row from the top-left corner.
+    pixels: Vec<u8>,
+}
+
+impl Frame {
+    /// Get the pixel at `x` and `y`.
+    fn pixel(&self, x: usize, y: usize) -> [u8; 4] {
+        let start = (y * self.width + x) * 4;
+        self.pixels[start..start + 4].try_into().unwrap()
+    }
+
+    /// Get the alpha of the pixel at `x` and `y`.
+    fn alpha(&self, x: usize, y: usize) -> u8 {
+        self.pixel(x, y)[3]
+    }
+}
+
+/// Draw a frame of `width` by `height` pixels with the software rasterizer.
+fn render(width: u32, height: u32, draw: impl FnOnce(&mut RenderContext<'_, '_>)) -> Frame {
+    let mut display = Display::offscreen();
+    let mut surface = display.make_offscreen_surface(width, height).unwrap();
+    let mut ctx = RenderContext::new(&mut display, &mut surface, width, height).unwrap();
+    draw(&mut ctx);
+
+    Frame {
+        width: width as usize,
+        pixels: ctx.finish_to_buffer().unwrap(),
+    }
+}
+
+/// A pentagram around `center`, drawn by connecting every second point of a pentagon.
+fn star(center: Point) -> BezPath {
+    let mut star = BezPath::new();
+    for i in 0..5 {
+        let angle = std::f64::consts::PI * 4.0 / 5.0 * i as f64;
+        let point = center + (18.0 * angle.sin(), -18.0 * angle.cos());
+        if i == 0 {
+            star.move_to(point);
+        } else {
+            star.line_to(point);
+        }
+    }
+    star.close_path();
+    star
+}
+
+/// A linear gradient with a red and a blue stripe, `width` pixels wide in total.
+fn stripes(width: f64) -> FixedLinearGradient {
+    let stop = |pos, color| GradientStop { pos, color };
+    FixedLinearGradient {
+        start: (0.0, 0.0).into(),
+        end: (width, 0.0).into(),
+        stops: vec![
+            stop(0.0, Color::RED),
+            stop(0.5, Color::RED),
+            stop(0.5, Color::BLUE),
+            stop(1.0, Color::BLUE),
+        ],
+    }
+}